@@ -1,5 +1,5 @@
 #![allow(clippy::module_inception)]
-use std::time::Duration;
+use std::{ops::Range, time::Duration};
 
 use crossterm::event;
 use ratatui::{
@@ -12,22 +12,35 @@ use ratatui::{
 use termbg::Theme;
 
 use super::{
-    asm::assembly_line::AssemblyLine,
+    asm::{
+        assembly_line::AssemblyLine, assembly_offsets::AssemblyOffsets, code_map::CodeMap,
+        labels::Labels, xrefs::Xrefs,
+    },
+    buffers::Buffer,
     comments::Comments,
     data::Data,
+    diff::Diff,
     files::filesystem::FileSystem,
+    firmware::firmware_image::FirmwareImage,
     frame_info::{FrameInfo, InfoViewFrameInfo},
     help::HelpLine,
     info_mode::InfoMode,
+    inspector::INSPECTOR_WIDTH,
     log::{logger::Logger, NotificationLevel},
     pane::Pane,
     plugins::plugin_manager::PluginManager,
     popup::popup_state::PopupState,
     settings::{color_settings::ColorSettings, Settings},
+    structs::{overlay::StructOverlay, struct_types::StructTypes},
+    user_symbols::UserSymbols,
     widgets::logo::Logo,
 };
 
-use crate::{args::Args, get_app_context, headers::Header};
+use crate::{
+    args::Args,
+    get_app_context,
+    headers::{isa_mode::IsaModes, raw_binary::RawBinary, Header},
+};
 
 pub struct App {
     pub(super) plugin_manager: PluginManager,
@@ -37,13 +50,34 @@ pub struct App {
     pub(super) help_list: Vec<HelpLine>,
     pub(super) data: Data,
     pub(super) comments: Comments,
-    pub(super) assembly_offsets: Vec<usize>,
+    pub(super) assembly_offsets: AssemblyOffsets,
+    pub(super) xrefs: Xrefs,
+    pub(super) labels: Labels,
+    pub(super) user_symbols: UserSymbols,
+    /// The instruction set modes chosen by the user, the header also has the detected ones.
+    pub(super) isa_modes: IsaModes,
+    /// How to decode the file if it has no header.
+    pub(super) raw_binary: Option<RawBinary>,
+    /// The firmware image the data was decoded from, the file is written back in its format.
+    pub(super) firmware: Option<FirmwareImage>,
+    pub(super) struct_types: StructTypes,
+    pub(super) struct_overlay: Option<StructOverlay>,
+    pub(super) diff: Option<Diff>,
     pub(super) assembly_instructions: Vec<AssemblyLine>,
+    pub(super) assembly_window: Range<usize>,
+    /// The code found by the recursive disassembly, None for a linear sweep.
+    pub(super) code_map: Option<CodeMap>,
     pub(super) text_last_searched_string: String,
+    pub(super) search_results: Vec<Range<usize>>,
     pub(super) info_mode: InfoMode,
     pub(super) scroll: usize,
     pub(super) cursor: (u16, u16),
+    pub(super) selection_anchor: Option<usize>,
+    pub(super) clipboard: Vec<u8>,
+    pub(super) buffers: Vec<Buffer>,
+    pub(super) active_buffer: usize,
     pub(super) selected_pane: Pane,
+    pub(super) inspector: bool,
     pub(super) fullscreen: bool,
     pub(super) poll_time: Duration,
     pub(super) needs_to_exit: bool,
@@ -141,16 +175,9 @@ impl App {
             terminal,
         )?;
 
-        let filesystem = if let Some(ssh) = &args.ssh {
-            FileSystem::new_remote(&args.path, ssh, args.password.as_deref())
-                .map_err(|e| t!("errors.connect_ssh", ssh = ssh, e = e))?
-        } else {
-            FileSystem::new_local(&args.path).map_err(|e| e.to_string())?
-        };
         let screen_size = Self::get_size(terminal)?;
 
         let mut app = App {
-            filesystem,
             screen_size,
             help_list: Self::help_list(&settings.key),
             settings,
@@ -158,6 +185,22 @@ impl App {
             ..Default::default()
         };
 
+        app.filesystem = if let Some(ssh) = &args.ssh {
+            // the popup asking to trust an unknown host is shown before the file is opened
+            app.connect_remote(&args.path, ssh, args.connection_options(), terminal)
+                .map_err(|e| t!("errors.connect_ssh", ssh = ssh, e = e))?
+        } else {
+            FileSystem::new_local(&args.path).map_err(|e| e.to_string())?
+        };
+
+        app.struct_types = match StructTypes::load(args.types.as_deref(), &mut app.logger) {
+            Ok(types) => types,
+            Err(e) => {
+                app.log(NotificationLevel::Error, t!("errors.load_types", e = e));
+                StructTypes::new()
+            }
+        };
+
         let mut app_context = get_app_context!(app);
         app.plugin_manager = match PluginManager::load(args.plugins.as_deref(), &mut app_context) {
             Ok(plugins) => plugins,
@@ -169,7 +212,16 @@ impl App {
 
         if app.filesystem.is_file(app.filesystem.pwd()) {
             let path = app.filesystem.pwd().to_string();
+            app.raw_binary = args.raw_binary()?;
             app.open_file(&path, terminal).map_err(|e| e.to_string())?;
+            if let Some(diff) = &args.diff {
+                if let Err(e) = app.open_diff(diff) {
+                    app.log(
+                        NotificationLevel::Error,
+                        t!("errors.open_diff", path = diff, e = e),
+                    );
+                }
+            }
         } else {
             let dir = app.filesystem.pwd().to_string();
             Self::open_dir(&mut app.popup, &dir, &mut app.filesystem).map_err(|e| e.to_string())?;
@@ -182,6 +234,19 @@ impl App {
         &mut self,
         terminal: &mut ratatui::Terminal<B>,
     ) -> Result<(), Box<dyn std::error::Error + 'a>> {
+        // files read on demand need the visible bytes to be fetched before drawing
+        let bytes_per_row = self.blocks_per_row * self.block_size;
+        let first_visible_byte = self.scroll * bytes_per_row;
+        self.load_range(
+            first_visible_byte..first_visible_byte + bytes_per_row * self.screen_size.1 as usize,
+        );
+        self.update_assembly_window();
+        self.load_range(self.get_inspector_range());
+        if let Some(overlay) = &self.struct_overlay {
+            let range = overlay.address..overlay.address + overlay.size;
+            self.load_range(range);
+        }
+
         terminal.draw(|f| {
             let screen_size = (f.area().width, f.area().height);
             self.resize_to_size(screen_size.0, screen_size.1);
@@ -193,7 +258,7 @@ impl App {
             let status_rect = Rect::new(0, f.area().height - 1, f.area().width, 1);
             let address_rect = Rect::new(0, 0, 17, f.area().height - status_rect.height);
             let hex_editor_rect: Rect;
-            let info_view_rect: Rect;
+            let mut info_view_rect: Rect;
             if self.fullscreen {
                 hex_editor_rect = Rect::new(
                     address_rect.width,
@@ -221,6 +286,31 @@ impl App {
                     f.area().height - status_rect.height,
                 );
             }
+            let inspector_rect = if !self.inspector {
+                None
+            } else if self.fullscreen {
+                Some(info_view_rect)
+            } else {
+                let width = INSPECTOR_WIDTH.min(info_view_rect.width);
+                info_view_rect.width -= width;
+                Some(Rect::new(
+                    info_view_rect.x + info_view_rect.width,
+                    info_view_rect.y,
+                    width,
+                    info_view_rect.height,
+                ))
+            };
+            // the struct pane takes the bottom of the info view
+            let struct_view_rect = self.struct_overlay.as_ref().map(|overlay| {
+                let height = (overlay.fields.len() as u16 + 1).min(info_view_rect.height / 2);
+                info_view_rect.height -= height;
+                Rect::new(
+                    info_view_rect.x,
+                    info_view_rect.y + info_view_rect.height,
+                    info_view_rect.width,
+                    height,
+                )
+            });
 
             let scrollbar_rect = Rect::new(f.area().width - 1, 0, 1, f.area().height);
 
@@ -256,15 +346,15 @@ impl App {
                     dirty = if self.data.dirty() { " *" } else { "" }
                 );
 
-                let hex_border_style: Style;
-                let pretty_border_style: Style;
-                if self.selected_pane == Pane::Hex {
-                    hex_border_style = self.settings.color.pane_selected;
-                    pretty_border_style = self.settings.color.pane;
-                } else {
-                    hex_border_style = self.settings.color.pane;
-                    pretty_border_style = self.settings.color.pane_selected;
-                }
+                let border_style = |pane: Pane| -> Style {
+                    if self.selected_pane == pane {
+                        self.settings.color.pane_selected
+                    } else {
+                        self.settings.color.pane
+                    }
+                };
+                let hex_border_style = border_style(Pane::Hex);
+                let pretty_border_style = border_style(Pane::View);
 
                 let hex_editor_block = ratatui::widgets::Paragraph::new(hex_view).block(
                     Block::default()
@@ -280,6 +370,21 @@ impl App {
                 };
 
                 let info_view_block = match &self.info_mode {
+                    _ if self.diff.is_some() => {
+                        let diff_subview = self.get_diff_view(line_start_index, line_end_index);
+                        info_view_frame_info = InfoViewFrameInfo::DiffView;
+                        let path = self
+                            .diff
+                            .as_ref()
+                            .map(|diff| diff.path.as_str())
+                            .unwrap_or_default();
+                        ratatui::widgets::Paragraph::new(diff_subview).block(
+                            Block::default()
+                                .title(t!("app.diff_view_title", path = path))
+                                .borders(info_view_block_flags)
+                                .border_style(pretty_border_style),
+                        )
+                    }
                     InfoMode::Text => {
                         let text_subview_lines =
                             self.get_text_view(line_start_index, line_end_index);
@@ -311,15 +416,19 @@ impl App {
                             .last()
                             .map(|x| format!("{:X}", x.file_address()).len() + 1)
                             .unwrap_or(1);
+                        let selection = self.get_selection();
                         assembly_subview
                             .lines
                             .extend(assembly_subview_lines.iter().map(|x| {
                                 x.to_line(
                                     &self.settings.color,
                                     self.get_cursor_position().global_byte_index,
+                                    selection.as_ref(),
                                     &self.header,
                                     address_min_width,
                                     &self.comments,
+                                    &self.xrefs,
+                                    &self.labels,
                                 )
                             }));
                         ratatui::widgets::Paragraph::new(assembly_subview).block(
@@ -336,11 +445,56 @@ impl App {
                     match self.selected_pane {
                         Pane::Hex => f.render_widget(hex_editor_block, hex_editor_rect),
                         Pane::View => f.render_widget(info_view_block, info_view_rect),
+                        Pane::Inspector => {}
                     }
                 } else {
                     f.render_widget(hex_editor_block, hex_editor_rect);
                     f.render_widget(info_view_block, info_view_rect);
                 }
+                if let Some(inspector_rect) = inspector_rect {
+                    if !self.fullscreen || self.selected_pane == Pane::Inspector {
+                        let mut inspector_view = Text::default();
+                        inspector_view.lines.extend(
+                            self.get_inspector_view(
+                                inspector_rect.height.saturating_sub(1) as usize
+                            ),
+                        );
+                        let inspector_block = ratatui::widgets::Paragraph::new(inspector_view)
+                            .block(
+                                Block::default()
+                                    .title(t!("app.inspector_view_title"))
+                                    .borders(info_view_block_flags)
+                                    .border_style(border_style(Pane::Inspector)),
+                            );
+                        f.render_widget(inspector_block, inspector_rect);
+                    }
+                }
+                if let (Some(overlay), Some(struct_view_rect)) =
+                    (&self.struct_overlay, struct_view_rect)
+                {
+                    if !self.fullscreen || self.selected_pane == Pane::View {
+                        let address = overlay.address;
+                        let mut struct_view = Text::default();
+                        struct_view.lines.extend(overlay.to_lines(
+                            &self.settings.color,
+                            &self.data,
+                            self.get_cursor_position().global_byte_index,
+                            struct_view_rect.height.saturating_sub(1) as usize,
+                        ));
+                        let struct_view_block = ratatui::widgets::Paragraph::new(struct_view)
+                            .block(
+                                Block::default()
+                                    .title(t!(
+                                        "app.struct_view_title",
+                                        name = overlay.name,
+                                        address = address : {:#X}
+                                    ))
+                                    .borders(info_view_block_flags)
+                                    .border_style(self.settings.color.pane),
+                            );
+                        f.render_widget(struct_view_block, struct_view_rect);
+                    }
+                }
             }
             f.render_widget(status_block, status_rect);
             f.render_stateful_widget(scrollbar, scrollbar_rect, &mut scrollbar_state);
@@ -450,13 +604,30 @@ impl Default for App {
             help_list: Self::help_list(&Settings::default().key),
             data: Data::default(),
             comments: Comments::default(),
-            assembly_offsets: Vec::new(),
+            assembly_offsets: AssemblyOffsets::new(),
+            xrefs: Xrefs::new(),
+            labels: Labels::new(),
+            user_symbols: UserSymbols::new(),
+            isa_modes: IsaModes::new(),
+            raw_binary: None,
+            firmware: None,
+            struct_types: StructTypes::new(),
+            struct_overlay: None,
+            diff: None,
             assembly_instructions: Vec::new(),
+            assembly_window: 0..0,
+            code_map: None,
             text_last_searched_string: String::new(),
+            search_results: Vec::new(),
             info_mode: InfoMode::Text,
             scroll: 0,
             cursor: (0, 0),
+            selection_anchor: None,
+            clipboard: Vec::new(),
+            buffers: Vec::new(),
+            active_buffer: 0,
             selected_pane: Pane::Hex,
+            inspector: false,
             fullscreen: false,
             poll_time: Duration::from_millis(1000),
             needs_to_exit: false,
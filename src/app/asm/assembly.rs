@@ -1,17 +1,26 @@
+use std::ops::Range;
+
+use object::ReadCache;
 use ratatui::text::{Line, Span};
 
 use crate::{
     app::{
-        instruction::Instruction, log::NotificationLevel, settings::color_settings::ColorSettings,
-        App,
+        files::data_reader::DataReader, instruction::Instruction, log::NotificationLevel,
+        settings::color_settings::ColorSettings, App,
     },
     asm::assembler::assemble,
     get_app_context,
-    headers::{section::Section, Header},
+    headers::{isa_mode::Decoders, section::Section, Header},
 };
 
 use super::{
-    assembly_line::AssemblyLine, instruction_tag::InstructionTag, section_tag::SectionTag,
+    assembly_line::AssemblyLine,
+    assembly_offsets::AssemblyOffsets,
+    code_map::CodeMap,
+    instruction_tag::InstructionTag,
+    labels::Labels,
+    section_tag::SectionTag,
+    xrefs::{Xref, Xrefs},
 };
 
 impl App {
@@ -19,27 +28,30 @@ impl App {
         if filter.is_empty() {
             return Vec::new();
         }
-        let symbol_table = self.header.get_symbols();
-        if let Some(symbol_table) = symbol_table {
-            let mut symbols: Vec<(u64, String)> = symbol_table
-                .iter()
-                .filter(|(_, symbol)| symbol.contains(filter))
-                .map(|(address, symbol)| (*address, symbol.clone()))
-                .collect();
-            symbols.sort_by_key(|(_, symbol)| symbol.len());
-            symbols
-        } else {
-            Vec::new()
-        }
+        let mut symbols: Vec<(u64, String)> = self
+            .header
+            .get_symbols()
+            .into_iter()
+            .flatten()
+            .map(|(address, symbol)| (*address, symbol.clone()))
+            .chain(self.labels.iter())
+            .filter(|(_, symbol)| symbol.contains(filter))
+            .collect();
+        symbols.sort_by_key(|(_, symbol)| symbol.len());
+        symbols
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub(super) fn instruction_to_line(
         color_settings: &ColorSettings,
         instruction: &InstructionTag,
         selected: bool,
+        in_selection: bool,
         header: &Header,
         address_min_width: usize,
         comment: Option<&str>,
+        xref_count: usize,
+        labels: &Labels,
     ) -> Line<'static> {
         let symbol_table = header.get_symbols();
         let mut line = Line::default();
@@ -47,6 +59,8 @@ impl App {
             format!("{:>address_min_width$X}", instruction.file_address),
             if selected {
                 color_settings.assembly_selected
+            } else if in_selection {
+                color_settings.assembly_selection
             } else {
                 color_settings.assembly_address
             },
@@ -64,15 +78,16 @@ impl App {
         line.spans
             .push(Span::styled(mnemonic.to_string(), mnemonic_style));
         line.spans.push(Span::raw(" "));
-        line.spans.push(Span::raw(args.to_string()));
-        if let Some(symbol_table) = symbol_table {
-            if let Some(symbol) = symbol_table.get(&instruction.instruction.ip()) {
-                line.spans.push(Span::raw(" "));
-                line.spans.push(Span::styled(
-                    format!("<{symbol}>"),
-                    color_settings.assembly_symbol,
-                ));
-            }
+        line.spans.push(Span::raw(labels.substitute(args)));
+        let symbol = symbol_table
+            .and_then(|symbol_table| symbol_table.get(&instruction.instruction.ip()).cloned())
+            .or_else(|| labels.get(instruction.instruction.ip()));
+        if let Some(symbol) = symbol {
+            line.spans.push(Span::raw(" "));
+            line.spans.push(Span::styled(
+                format!("<{symbol}>"),
+                color_settings.assembly_symbol,
+            ));
         }
         if instruction.instruction.ip() == header.entry_point() {
             line.spans.push(Span::raw(" "));
@@ -85,6 +100,13 @@ impl App {
             format!(" @{:X}", instruction.instruction.ip()),
             color_settings.assembly_virtual_address,
         ));
+        if xref_count > 0 {
+            line.spans.push(Span::raw(" "));
+            line.spans.push(Span::styled(
+                t!("app.xref_count", count = xref_count),
+                color_settings.assembly_comment,
+            ));
+        }
         if let Some(comment) = comment {
             line.spans.push(Span::raw(" "));
             line.spans.push(Span::styled(
@@ -100,12 +122,15 @@ impl App {
         color_settings: &ColorSettings,
         section: &SectionTag,
         selected: bool,
+        in_selection: bool,
         address_min_width: usize,
         comment: Option<&str>,
     ) -> Line<'static> {
         let mut line = Line::default();
         let address_style = if selected {
             color_settings.assembly_selected
+        } else if in_selection {
+            color_settings.assembly_selection
         } else {
             color_settings.assembly_address
         };
@@ -132,12 +157,20 @@ impl App {
         line
     }
 
+    /// Builds the assembly view of bytes, code sections are disassembled only
+    /// inside window, the rest of their bytes are shown as a section tag.
+    /// With a code map only the instructions it contains are disassembled, the
+    /// other bytes of the code sections are shown as data.
+    /// The references made by the disassembled instructions are collected along the way.
     pub(in crate::app) fn sections_from_bytes(
         bytes: &[u8],
         header: &Header,
-    ) -> (Vec<usize>, Vec<AssemblyLine>) {
-        let mut line_offsets = vec![0; bytes.len()];
+        window: Range<usize>,
+        code_map: Option<&CodeMap>,
+    ) -> (AssemblyOffsets, Vec<AssemblyLine>, Xrefs) {
+        let mut line_offsets = AssemblyOffsets::new();
         let mut lines = Vec::new();
+        let mut xrefs = Xrefs::new();
         let mut sections = header.get_sections();
         if sections.is_empty() {
             sections.push(Section {
@@ -147,6 +180,11 @@ impl App {
                 size: bytes.len() as u64,
             });
         }
+        // sections can go past the end of the data if some bytes were deleted
+        sections.retain(|section| section.file_offset < bytes.len() as u64);
+        for section in sections.iter_mut() {
+            section.size = section.size.min(bytes.len() as u64 - section.file_offset);
+        }
 
         let mut current_byte = 0;
         for section in sections {
@@ -157,13 +195,11 @@ impl App {
                     virtual_address: 0,
                     size: section.file_offset as usize - current_byte,
                 }));
-                for _ in 0..section.file_offset as usize - current_byte {
-                    line_offsets[current_byte] = lines.len() - 1;
-                    current_byte += 1;
-                }
+                line_offsets.push(lines.len() - 1, section.file_offset as usize - current_byte);
             }
             // if there are any overlapping sections, this should fix it
             current_byte = section.file_offset as usize;
+            line_offsets.truncate(current_byte);
             match section.name.as_str() {
                 ".text" | "__text" => {
                     lines.push(AssemblyLine::SectionTag(SectionTag {
@@ -172,21 +208,45 @@ impl App {
                         virtual_address: section.virtual_address,
                         size: section.size as usize,
                     }));
-                    let (offsets, instructions) = Self::assembly_from_section(
-                        bytes,
-                        header,
-                        section.virtual_address as usize,
-                        current_byte,
-                        section.size as usize,
-                        lines.len(),
-                    );
-                    line_offsets.splice(
-                        section.file_offset as usize
-                            ..section.file_offset as usize + section.size as usize,
-                        offsets,
-                    );
+                    let section_end = current_byte + section.size as usize;
+                    let code_start = window.start.clamp(current_byte, section_end);
+                    let code_end = window.end.clamp(code_start, section_end);
+                    // the bytes before the window belong to the section tag
+                    line_offsets.push(lines.len() - 1, code_start - current_byte);
+                    let starting_ip = section.virtual_address as usize + code_start - current_byte;
+                    let (offsets, instructions, section_xrefs) = match code_map {
+                        Some(code_map) => Self::assembly_from_code_map(
+                            bytes,
+                            header,
+                            code_map,
+                            starting_ip,
+                            code_start,
+                            code_end - code_start,
+                            lines.len(),
+                        ),
+                        None => Self::assembly_from_section(
+                            bytes,
+                            header,
+                            starting_ip,
+                            code_start,
+                            code_end - code_start,
+                            lines.len(),
+                        ),
+                    };
+                    line_offsets.splice(code_start..code_start, offsets);
                     lines.extend(instructions);
-                    current_byte += section.size as usize;
+                    xrefs.extend(section_xrefs);
+                    if code_end < section_end {
+                        lines.push(AssemblyLine::SectionTag(SectionTag {
+                            name: section.name.clone(),
+                            file_address: code_end as u64,
+                            virtual_address: section.virtual_address
+                                + (code_end - current_byte) as u64,
+                            size: section_end - code_end,
+                        }));
+                        line_offsets.push(lines.len() - 1, section_end - code_end);
+                    }
+                    current_byte = section_end;
                 }
                 name => {
                     lines.push(AssemblyLine::SectionTag(SectionTag {
@@ -195,10 +255,8 @@ impl App {
                         virtual_address: section.virtual_address,
                         size: section.size as usize,
                     }));
-                    for _ in 0..section.size as usize {
-                        line_offsets[current_byte] = lines.len() - 1;
-                        current_byte += 1;
-                    }
+                    line_offsets.push(lines.len() - 1, section.size as usize);
+                    current_byte += section.size as usize;
                 }
             }
         }
@@ -209,14 +267,26 @@ impl App {
                 virtual_address: 0,
                 size: bytes.len() - current_byte,
             }));
-            let initial_current_byte = current_byte;
-            for _ in initial_current_byte..bytes.len() {
-                line_offsets[current_byte] = lines.len() - 1;
-                current_byte += 1;
-            }
+            line_offsets.push(lines.len() - 1, bytes.len() - current_byte);
         }
 
-        (line_offsets, lines)
+        (line_offsets, lines, xrefs)
+    }
+
+    /// Returns a function telling if a virtual address points inside the file,
+    /// without sections the addresses are the file offsets.
+    pub(in crate::app) fn is_mapped_address(header: &Header, len: usize) -> impl Fn(u64) -> bool {
+        let sections = header.get_sections();
+        move |address| {
+            if sections.is_empty() {
+                address < len as u64
+            } else {
+                sections.iter().any(|section| {
+                    address >= section.virtual_address
+                        && address < section.virtual_address + section.size
+                })
+            }
+        }
     }
 
     pub(in crate::app) fn assembly_from_section(
@@ -226,29 +296,124 @@ impl App {
         starting_file_address: usize,
         section_size: usize,
         starting_sections: usize,
-    ) -> (Vec<usize>, Vec<AssemblyLine>) {
-        let mut line_offsets = vec![0; section_size];
+    ) -> (AssemblyOffsets, Vec<AssemblyLine>, Xrefs) {
+        let mut line_offsets = AssemblyOffsets::new();
         let mut instructions = Vec::new();
+        let mut xrefs = Xrefs::new();
+        let is_mapped = Self::is_mapped_address(header, bytes.len());
         let mut current_byte = 0;
-        let decoder = header.get_decoder().expect(&t!("errors.create_decoder"));
-        let decoded = decoder
-            .disasm_all(
-                &bytes[starting_file_address..starting_file_address + section_size],
-                starting_ip as u64,
-            )
-            .expect(&t!("errors.disassemble"));
-        for instruction in decoded.iter() {
-            let instruction_tag = InstructionTag {
-                instruction: Instruction::new(instruction, header.get_symbols()),
-                file_address: current_byte as u64 + starting_file_address as u64,
-            };
-            instructions.push(AssemblyLine::Instruction(instruction_tag));
-            for _ in 0..instruction.len() {
-                line_offsets[current_byte] = starting_sections + instructions.len() - 1;
-                current_byte += 1;
+        let mut decoders = Decoders::new(header);
+        // each part of the section that uses a different instruction set is decoded on its own
+        let runs = header.isa_runs(starting_ip as u64..(starting_ip + section_size) as u64);
+        for (run, _) in runs {
+            let run_start = (run.start - starting_ip as u64) as usize;
+            let run_end = (run.end - starting_ip as u64) as usize;
+            let decoder = decoders.at(run.start).expect(&t!("errors.create_decoder"));
+            let decoded = decoder
+                .disasm_all(
+                    &bytes[starting_file_address + run_start..starting_file_address + run_end],
+                    run.start,
+                )
+                .expect(&t!("errors.disassemble"));
+            for instruction in decoded.iter() {
+                let instruction_tag = InstructionTag {
+                    instruction: Instruction::new(instruction, header.get_symbols()),
+                    file_address: current_byte as u64 + starting_file_address as u64,
+                };
+                for (target, kind) in Xrefs::references(decoder, instruction, &is_mapped) {
+                    xrefs.insert(
+                        target,
+                        Xref {
+                            from_virtual_address: instruction.address(),
+                            from_file_address: instruction_tag.file_address,
+                            kind,
+                        },
+                    );
+                }
+                instructions.push(AssemblyLine::Instruction(instruction_tag));
+                line_offsets.push(
+                    starting_sections + instructions.len() - 1,
+                    instruction.len(),
+                );
+                current_byte += instruction.len();
+            }
+            if current_byte < run_end {
+                // bytes at the end of a run that are too few to be decoded belong to the last line
+                line_offsets.push(
+                    (starting_sections + instructions.len()).saturating_sub(1),
+                    run_end - current_byte,
+                );
+                current_byte = run_end;
             }
         }
-        (line_offsets, instructions)
+        // trailing bytes that are too few to be decoded belong to the last line
+        line_offsets.push(
+            starting_sections + instructions.len() - 1,
+            section_size - current_byte,
+        );
+        (line_offsets, instructions, xrefs)
+    }
+
+    /// Like [App::assembly_from_section], but only the instructions in the code map
+    /// are disassembled, the bytes between them are shown as data.
+    pub(in crate::app) fn assembly_from_code_map(
+        bytes: &[u8],
+        header: &Header,
+        code_map: &CodeMap,
+        starting_ip: usize,
+        starting_file_address: usize,
+        section_size: usize,
+        starting_sections: usize,
+    ) -> (AssemblyOffsets, Vec<AssemblyLine>, Xrefs) {
+        let mut line_offsets = AssemblyOffsets::new();
+        let mut lines = Vec::new();
+        let mut xrefs = Xrefs::new();
+        let end = starting_file_address + section_size;
+        let mut current_byte = starting_file_address;
+        while current_byte < end {
+            let ip = starting_ip + current_byte - starting_file_address;
+            if code_map.instruction_len(current_byte).is_some() {
+                // consecutive instructions are disassembled together
+                let mut code_end = current_byte;
+                while let Some(len) = code_map.instruction_len(code_end) {
+                    code_end += len;
+                    if code_end >= end {
+                        break;
+                    }
+                }
+                let code_end = code_end.min(end);
+                let (offsets, instructions, code_xrefs) = Self::assembly_from_section(
+                    bytes,
+                    header,
+                    ip,
+                    current_byte,
+                    code_end - current_byte,
+                    starting_sections + lines.len(),
+                );
+                line_offsets.splice(line_offsets.len()..line_offsets.len(), offsets);
+                lines.extend(instructions);
+                xrefs.extend(code_xrefs);
+                current_byte = code_end;
+            } else {
+                let data_end = code_map
+                    .next_instruction(current_byte)
+                    .unwrap_or(end)
+                    .min(end);
+                // the lines of data are aligned to 8 bytes
+                let len = (8 - ip % 8).min(data_end - current_byte);
+                lines.push(AssemblyLine::Instruction(InstructionTag {
+                    instruction: Instruction::data(
+                        ip as u64,
+                        &bytes[current_byte..current_byte + len],
+                        header.endianness(),
+                    ),
+                    file_address: current_byte as u64,
+                }));
+                line_offsets.push(starting_sections + lines.len() - 1, len);
+                current_byte += len;
+            }
+        }
+        (line_offsets, lines, xrefs)
     }
 
     pub(in crate::app) fn bytes_from_assembly(
@@ -281,6 +446,7 @@ impl App {
                 self.get_cursor_position().global_byte_index - current_ip as usize
             };
             let offset = current_ip as usize + instruction_offset;
+            self.load_range(offset..offset + bytes.len());
             let mut bytes = bytes.to_vec();
             let mut app_context = get_app_context!(self);
             app_context.offset = offset;
@@ -293,7 +459,32 @@ impl App {
     }
 
     pub(in crate::app) fn patch(&mut self, assembly: &str) {
-        if let Some(current_instruction) = self.get_current_instruction() {
+        if let Some(selection) = self.get_selection() {
+            let virtual_address = self
+                .header
+                .physical_to_virtual_address(selection.start as u64)
+                .unwrap_or(selection.start as u64);
+            match self.bytes_from_assembly(assembly, virtual_address) {
+                Ok(bytes) if bytes.len() > selection.len() => {
+                    self.log(
+                        NotificationLevel::Error,
+                        t!(
+                            "errors.patch_exceeds_selection",
+                            patch_size = bytes.len(),
+                            selection_size = selection.len()
+                        ),
+                    );
+                }
+                Ok(bytes) => {
+                    self.selection_anchor = None;
+                    self.jump_to(selection.start, false);
+                    self.patch_bytes(&bytes, false);
+                }
+                Err(e) => {
+                    self.log(NotificationLevel::Error, &e);
+                }
+            }
+        } else if let Some(current_instruction) = self.get_current_instruction() {
             let current_virtual_address =
                 if let AssemblyLine::Instruction(instruction) = current_instruction {
                     instruction.instruction.ip()
@@ -310,6 +501,88 @@ impl App {
         }
     }
 
+    /// Rebuilds the whole assembly view, for files bigger than the disassembly window
+    /// only the code around the cursor is disassembled. With recursive disassembly
+    /// the code is found again from the entry point and the symbols.
+    pub(in crate::app) fn disassemble(&mut self) {
+        self.code_map = if self.settings.app.recursive_disassembly {
+            // the code can be reached from anywhere in the code sections
+            for section in CodeMap::code_sections(self.data.len(), &self.header) {
+                let start = section.file_offset as usize;
+                self.load_range(start..start + section.size as usize);
+            }
+            Some(CodeMap::analyze(self.data.bytes(), &self.header))
+        } else {
+            None
+        };
+        self.disassemble_window();
+    }
+
+    pub(in crate::app) fn toggle_recursive_disassembly(&mut self) {
+        self.settings.app.recursive_disassembly = !self.settings.app.recursive_disassembly;
+        self.disassemble();
+        match &self.code_map {
+            Some(code_map) => {
+                let count = code_map.len();
+                self.log(
+                    NotificationLevel::Info,
+                    t!("app.messages.recursive_disassembly_on", count = count),
+                );
+            }
+            None => self.log(
+                NotificationLevel::Info,
+                t!("app.messages.recursive_disassembly_off"),
+            ),
+        }
+    }
+
+    /// Rebuilds the assembly view around the cursor, reusing the code map.
+    fn disassemble_window(&mut self) {
+        let cursor = self.get_cursor_position().global_byte_index;
+        let window_size = self.settings.app.disassembly_window;
+        self.assembly_window = if window_size == 0 || self.data.len() <= window_size {
+            0..self.data.len()
+        } else {
+            let start =
+                (cursor.saturating_sub(window_size / 2) & !0xF).min(self.data.len() - window_size);
+            start..start + window_size
+        };
+        self.load_range(self.assembly_window.clone());
+        (
+            self.assembly_offsets,
+            self.assembly_instructions,
+            self.xrefs,
+        ) = Self::sections_from_bytes(
+            self.data.bytes(),
+            &self.header,
+            self.assembly_window.clone(),
+            self.code_map.as_ref(),
+        );
+        self.update_labels();
+    }
+
+    /// Moves the disassembly window if the cursor got too close to one of its edges.
+    pub(in crate::app) fn update_assembly_window(&mut self) {
+        let window = self.assembly_window.clone();
+        if window == (0..self.data.len()) {
+            return;
+        }
+        let margin = window.len() / 4;
+        let start = if window.start == 0 {
+            0
+        } else {
+            window.start + margin
+        };
+        let end = if window.end >= self.data.len() {
+            self.data.len()
+        } else {
+            window.end - margin
+        };
+        if !(start..end).contains(&self.get_cursor_position().global_byte_index) {
+            self.disassemble_window();
+        }
+    }
+
     pub(in crate::app) fn get_assembly_view_scroll(&self) -> usize {
         let cursor_position = self.get_cursor_position();
         let current_ip = cursor_position
@@ -342,6 +615,12 @@ impl App {
     }
 
     pub(in crate::app) fn edit_assembly(&mut self, modifyied_bytes: usize) {
+        if self.code_map.is_some() {
+            // an edit can change which bytes are reached, the code is found again
+            self.disassemble();
+            return;
+        }
+        self.update_assembly_window();
         let current_instruction = self.get_current_instruction();
         if let Some(current_instruction) = current_instruction {
             let from_byte = current_instruction.file_address() as usize;
@@ -353,21 +632,22 @@ impl App {
                         from_byte >= text_section.file_offset as usize
                             && from_byte
                                 < text_section.file_offset as usize + text_section.size as usize,
-                        text_section.file_offset as usize + text_section.size as usize,
+                        (text_section.file_offset as usize + text_section.size as usize)
+                            .min(self.data.len()),
                     )
                 } else {
                     (true, self.data.len())
                 };
+            let maximum_code_byte = maximum_code_byte.min(self.assembly_window.end);
             if !is_inside_text_section {
                 return;
             }
-            let decoder = self
-                .header
-                .get_decoder()
-                .expect(&t!("errors.create_decoder"));
-            let mut offsets = Vec::new();
+            let mut decoders = Decoders::new(&self.header);
+            let mut offsets = AssemblyOffsets::new();
             let mut instructions = Vec::new();
-            let mut to_byte = self.data.len();
+            let mut xrefs = Xrefs::new();
+            let is_mapped = Self::is_mapped_address(&self.header, self.data.len());
+            let to_byte;
 
             let from_instruction = self.assembly_offsets[from_byte];
             let mut current_byte = from_byte;
@@ -379,10 +659,14 @@ impl App {
                     break;
                 }
                 let bytes = &self.data.bytes()[current_byte..maximum_code_byte];
+                let decoder = decoders
+                    .at(virtual_address + ip_offset)
+                    .expect(&t!("errors.create_decoder"));
                 let decoded = decoder
                     .disasm_count(bytes, virtual_address + ip_offset, 1)
                     .expect(&t!("errors.disassemble"));
                 if decoded.is_empty() {
+                    to_byte = current_byte;
                     break;
                 }
                 let instruction = decoded.iter().next().unwrap();
@@ -399,11 +683,19 @@ impl App {
                     to_byte = old_instruction.file_address() as usize;
                     break;
                 }
-                instructions.push(new_assembly_line);
-                for _ in 0..instruction.len() {
-                    offsets.push(from_instruction + instructions.len() - 1);
-                    current_byte += 1;
+                for (target, kind) in Xrefs::references(decoder, instruction, &is_mapped) {
+                    xrefs.insert(
+                        target,
+                        Xref {
+                            from_virtual_address: instruction.address(),
+                            from_file_address: current_byte as u64,
+                            kind,
+                        },
+                    );
                 }
+                instructions.push(new_assembly_line);
+                offsets.push(from_instruction + instructions.len() - 1, instruction.len());
+                current_byte += instruction.len();
             }
             if from_byte == to_byte {
                 return;
@@ -412,7 +704,6 @@ impl App {
             let to_instruction = self
                 .assembly_offsets
                 .get(to_byte)
-                .cloned()
                 .unwrap_or(self.assembly_instructions.len());
 
             let mut original_instruction_count = 1;
@@ -430,9 +721,7 @@ impl App {
 
             self.assembly_offsets.splice(from_byte..to_byte, offsets);
             if delta != 0 {
-                for offset in self.assembly_offsets.iter_mut().skip(to_byte) {
-                    *offset = (*offset as isize + delta) as usize;
-                }
+                self.assembly_offsets.shift_lines(to_byte, delta);
             }
 
             for i in from_instruction..to_instruction {
@@ -464,6 +753,9 @@ impl App {
 
             self.assembly_instructions
                 .splice(from_instruction..to_instruction, instructions);
+            self.xrefs.remove_from(from_byte as u64..to_byte as u64);
+            self.xrefs.extend(xrefs);
+            self.update_labels();
         }
     }
 
@@ -471,9 +763,14 @@ impl App {
         let mut app_context = get_app_context!(self);
         match self.plugin_manager.try_parse_header(&mut app_context) {
             Some(header) => Header::CustomHeader(header),
-            None => {
+            None if self.data.missing_ranges(0..self.data.len()).is_empty() => {
                 Header::parse_header(self.data.bytes(), self.filesystem.pwd(), &self.filesystem)
             }
+            // only the parts of the file needed by the parser are fetched
+            None => {
+                let cache = ReadCache::new(DataReader::new(&mut self.data, &self.filesystem));
+                Header::parse_header(&cache, self.filesystem.pwd(), &self.filesystem)
+            }
         }
     }
 }
@@ -502,9 +799,12 @@ mod test {
         let line = al.to_line(
             &ColorSettings::get_default_dark_theme(),
             0,
+            None,
             &Header::None,
             0,
             &comments,
+            &Xrefs::new(),
+            &Labels::new(),
         );
 
         let contains_mnemonic = line.spans.iter().any(|span| span.content.contains("mov"));
@@ -553,9 +853,12 @@ mod test {
         let line = al.to_line(
             &ColorSettings::get_default_dark_theme(),
             0,
+            None,
             &Header::None,
             0,
             &comments,
+            &Xrefs::new(),
+            &Labels::new(),
         );
 
         let contains_section_name = line.spans.iter().any(|span| span.content.contains(".text"));
@@ -691,4 +994,61 @@ mod test {
             }
         }
     }
+
+    #[test]
+    fn test_disassembly_window() {
+        let mut app = App::mockup(vec![0x90; 0x1000]);
+        app.resize_to_size(80, 24);
+        assert_eq!(app.assembly_window, 0..0x1000);
+        app.settings.app.disassembly_window = 0x100;
+        app.disassemble();
+        assert_eq!(app.assembly_window, 0..0x100);
+        // .text tag, the nops in the window and the tag for the rest of .text
+        assert_eq!(app.assembly_instructions.len(), 0x102);
+        assert_eq!(app.assembly_offsets.len(), 0x1000);
+        assert_eq!(app.assembly_offsets[0x200], 0x101);
+
+        app.jump_to(0x800, false);
+        app.update_assembly_window();
+        assert!(app.assembly_window.contains(&0x800));
+        assert_eq!(app.assembly_window.len(), 0x100);
+        match app.get_current_instruction() {
+            Some(AssemblyLine::Instruction(instruction)) => {
+                assert_eq!(instruction.file_address, 0x800)
+            }
+            _ => panic!("The cursor should be on an instruction"),
+        }
+        assert_eq!(app.assembly_offsets.len(), 0x1000);
+    }
+
+    #[test]
+    fn test_recursive_disassembly() {
+        // jmp over 4 bytes of data, then nop; ret; and more data after the ret
+        let data = vec![0xEB, 0x04, 0xDE, 0xAD, 0xBE, 0xEF, 0x90, 0xC3, 0xFF, 0xFF];
+        let mut app = App::mockup(data);
+        app.toggle_recursive_disassembly();
+        assert!(app.settings.app.recursive_disassembly);
+        let lines = app
+            .assembly_instructions
+            .iter()
+            .filter_map(|line| match line {
+                AssemblyLine::Instruction(instruction) => Some(instruction.instruction.to_string()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(lines.len(), 5);
+        assert!(lines[0].starts_with("jmp"));
+        assert_eq!(lines[1], "db 0xde, 0xad, 0xbe, 0xef");
+        assert!(lines[2].starts_with("nop"));
+        assert!(lines[3].starts_with("ret"));
+        assert_eq!(lines[4], "db 0xff, 0xff");
+        assert_eq!(app.assembly_offsets.len(), 10);
+
+        app.toggle_recursive_disassembly();
+        assert!(app.code_map.is_none());
+        assert!(app.assembly_instructions.iter().all(|line| match line {
+            AssemblyLine::Instruction(instruction) => instruction.instruction.mnemonic() != "db",
+            _ => true,
+        }));
+    }
 }
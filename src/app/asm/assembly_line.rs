@@ -1,3 +1,5 @@
+use std::ops::Range;
+
 use ratatui::text::Line;
 
 use crate::{
@@ -5,7 +7,9 @@ use crate::{
     headers::Header,
 };
 
-use super::{instruction_tag::InstructionTag, section_tag::SectionTag};
+use super::{
+    instruction_tag::InstructionTag, labels::Labels, section_tag::SectionTag, xrefs::Xrefs,
+};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum AssemblyLine {
@@ -42,14 +46,22 @@ impl AssemblyLine {
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn to_line(
         &self,
         color_settings: &ColorSettings,
         current_byte_index: usize,
+        selection: Option<&Range<usize>>,
         header: &Header,
         address_min_width: usize,
         comments: &Comments,
+        xrefs: &Xrefs,
+        labels: &Labels,
     ) -> Line<'_> {
+        let in_selection = selection.is_some_and(|selection| {
+            (self.file_address() as usize) < selection.end
+                && self.file_address() as usize + self.len().max(1) > selection.start
+        });
         match self {
             AssemblyLine::Instruction(instruction) => {
                 let selected = current_byte_index >= instruction.file_address as usize
@@ -59,9 +71,12 @@ impl AssemblyLine {
                     color_settings,
                     instruction,
                     selected,
+                    in_selection,
                     header,
                     address_min_width,
                     comments.get(&instruction.file_address).map(|s| s.as_str()),
+                    xrefs.get(instruction.instruction.ip()).len(),
+                    labels,
                 )
             }
             AssemblyLine::SectionTag(section) => {
@@ -71,6 +86,7 @@ impl AssemblyLine {
                     color_settings,
                     section,
                     selected,
+                    in_selection,
                     address_min_width,
                     comments.get(&section.file_address).map(|s| s.as_str()),
                 )
@@ -0,0 +1,154 @@
+use std::ops::{Index, Range};
+
+/// Maps every byte of the file to the index of the assembly line that contains it.
+///
+/// Consecutive bytes belonging to the same line are stored as a single run,
+/// so the memory used depends on the number of lines and not on the file size.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AssemblyOffsets {
+    starts: Vec<usize>,
+    lines: Vec<usize>,
+    len: usize,
+}
+
+impl AssemblyOffsets {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Maps the next count bytes to line.
+    pub fn push(&mut self, line: usize, count: usize) {
+        if count == 0 {
+            return;
+        }
+        if self.lines.last() != Some(&line) {
+            self.starts.push(self.len);
+            self.lines.push(line);
+        }
+        self.len += count;
+    }
+
+    /// Forgets the mapping of every byte from len onwards.
+    pub fn truncate(&mut self, len: usize) {
+        if len >= self.len {
+            return;
+        }
+        let runs = self.starts.partition_point(|start| *start < len);
+        self.starts.truncate(runs);
+        self.lines.truncate(runs);
+        self.len = len;
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn get(&self, byte: usize) -> Option<usize> {
+        if byte >= self.len {
+            None
+        } else {
+            Some(self.lines[self.run_of(byte)])
+        }
+    }
+
+    fn run_of(&self, byte: usize) -> usize {
+        self.starts.partition_point(|start| *start <= byte) - 1
+    }
+
+    /// Makes sure a run starts at byte, returns the index of that run.
+    fn split_at(&mut self, byte: usize) -> usize {
+        if byte >= self.len {
+            return self.starts.len();
+        }
+        let run = self.run_of(byte);
+        if self.starts[run] == byte {
+            run
+        } else {
+            self.starts.insert(run + 1, byte);
+            self.lines.insert(run + 1, self.lines[run]);
+            run + 1
+        }
+    }
+
+    /// Replaces the mapping of the bytes in range with the one in offsets.
+    /// The bytes after the range are shifted if the lengths differ.
+    pub fn splice(&mut self, range: Range<usize>, offsets: AssemblyOffsets) {
+        let first = self.split_at(range.start);
+        let last = self.split_at(range.end);
+        let delta = offsets.len as isize - range.len() as isize;
+        for start in self.starts.iter_mut().skip(last) {
+            *start = (*start as isize + delta) as usize;
+        }
+        self.starts.splice(
+            first..last,
+            offsets.starts.iter().map(|start| start + range.start),
+        );
+        self.lines.splice(first..last, offsets.lines);
+        self.len = (self.len as isize + delta) as usize;
+    }
+
+    /// Adds delta to the line of every byte from byte onwards.
+    pub fn shift_lines(&mut self, byte: usize, delta: isize) {
+        let first = self.split_at(byte);
+        for line in self.lines.iter_mut().skip(first) {
+            *line = (*line as isize + delta) as usize;
+        }
+    }
+}
+
+impl Index<usize> for AssemblyOffsets {
+    type Output = usize;
+
+    fn index(&self, byte: usize) -> &Self::Output {
+        if byte >= self.len {
+            panic!(
+                "index out of bounds: the len is {} but the index is {}",
+                self.len, byte
+            );
+        }
+        &self.lines[self.run_of(byte)]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_assembly_offsets() {
+        let mut offsets = AssemblyOffsets::new();
+        offsets.push(0, 3);
+        offsets.push(0, 1);
+        offsets.push(1, 2);
+        offsets.push(2, 0);
+        offsets.push(3, 4);
+        assert_eq!(offsets.len(), 10);
+        assert_eq!(
+            (0..10).map(|i| offsets[i]).collect::<Vec<_>>(),
+            vec![0, 0, 0, 0, 1, 1, 3, 3, 3, 3]
+        );
+        assert_eq!(offsets.get(10), None);
+
+        let mut new = AssemblyOffsets::new();
+        new.push(1, 1);
+        new.push(2, 1);
+        new.push(3, 1);
+        offsets.splice(3..5, new);
+        offsets.shift_lines(6, 1);
+        assert_eq!(
+            (0..offsets.len()).map(|i| offsets[i]).collect::<Vec<_>>(),
+            vec![0, 0, 0, 1, 2, 3, 2, 4, 4, 4, 4]
+        );
+
+        offsets.truncate(4);
+        offsets.push(5, 2);
+        assert_eq!(
+            (0..offsets.len()).map(|i| offsets[i]).collect::<Vec<_>>(),
+            vec![0, 0, 0, 1, 5, 5]
+        );
+    }
+}
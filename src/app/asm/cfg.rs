@@ -0,0 +1,663 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use ratatui::{
+    style::Style,
+    text::{Line, Span},
+};
+
+use crate::{
+    app::{
+        info_mode::InfoMode, instruction::Instruction, log::NotificationLevel,
+        popup::popup_state::PopupState, settings::color_settings::ColorSettings, App,
+    },
+    headers::{isa_mode::Decoders, Header},
+};
+
+use super::{
+    assembly_line::AssemblyLine,
+    code_map::CodeMap,
+    labels::Labels,
+    xrefs::{XrefKind, Xrefs},
+};
+
+/// The most instructions that are disassembled for a single function.
+const MAX_INSTRUCTIONS: usize = 0x4000;
+/// The longest line of text inside a block, longer instructions are cut.
+const MAX_BLOCK_WIDTH: usize = 56;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EdgeKind {
+    /// An unconditional jump.
+    Jump,
+    /// A conditional branch, when the condition holds.
+    Taken,
+    /// The block after a conditional branch, when the condition does not hold.
+    NotTaken,
+    /// The block after one that ends without a branch.
+    Fallthrough,
+}
+
+/// A sequence of instructions that is always executed from the first to the last.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BasicBlock {
+    pub file_address: u64,
+    pub instructions: Vec<Instruction>,
+    /// The index of each block that can be executed right after this one.
+    pub successors: Vec<(usize, EdgeKind)>,
+}
+
+impl BasicBlock {
+    pub fn virtual_address(&self) -> u64 {
+        self.instructions[0].ip()
+    }
+
+    pub fn contains(&self, virtual_address: u64) -> bool {
+        let last = &self.instructions[self.instructions.len() - 1];
+        virtual_address >= self.virtual_address() && virtual_address < last.ip() + last.len() as u64
+    }
+}
+
+struct DecodedInstruction {
+    file_address: u64,
+    instruction: Instruction,
+    /// The targets of the jump or branch made by the instruction.
+    targets: Vec<u64>,
+    ends_flow: bool,
+}
+
+/// The basic blocks of a function sorted by address, found by following its
+/// jumps and branches from the start, the calls are not followed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ControlFlowGraph {
+    pub name: String,
+    pub blocks: Vec<BasicBlock>,
+}
+
+impl ControlFlowGraph {
+    pub fn build(bytes: &[u8], header: &Header, start: u64, name: String) -> Self {
+        let mut ret = Self {
+            name,
+            blocks: Vec::new(),
+        };
+        let code_sections = CodeMap::code_sections(bytes.len(), header);
+        let mut decoders = Decoders::new(header);
+
+        let mut decoded = BTreeMap::new();
+        let mut leaders = BTreeSet::from([start]);
+        let mut to_visit = vec![start];
+        while let Some(mut virtual_address) = to_visit.pop() {
+            // the targets and the end of flow of a branch waiting for its delay slot
+            let mut delayed_branch = None;
+            while decoded.len() < MAX_INSTRUCTIONS && !decoded.contains_key(&virtual_address) {
+                let Some((file_address, section_end)) =
+                    CodeMap::to_file_address(&code_sections, virtual_address)
+                else {
+                    break;
+                };
+                let Some(decoder) = decoders.at(virtual_address) else {
+                    break;
+                };
+                let Ok(instructions) =
+                    decoder.disasm_count(&bytes[file_address..section_end], virtual_address, 1)
+                else {
+                    break;
+                };
+                let Some(instruction) = instructions.iter().next() else {
+                    break;
+                };
+                if instruction.mnemonic() == Some(".byte") {
+                    break;
+                }
+                let targets = Xrefs::references(decoder, instruction, |_| true)
+                    .into_iter()
+                    .filter(|(_, kind)| *kind == XrefKind::Jump)
+                    .map(|(target, _)| target)
+                    .collect::<Vec<_>>();
+                let ends_flow = CodeMap::ends_flow(decoder, instruction);
+                let next = virtual_address + instruction.len() as u64;
+                leaders.extend(&targets);
+                to_visit.extend(&targets);
+                // a branch takes effect after the instruction in its delay slot
+                let (targets, ends_flow) = match delayed_branch.take() {
+                    Some(branch) => branch,
+                    None if (ends_flow || !targets.is_empty())
+                        && CodeMap::has_delay_slot(decoder, instruction) =>
+                    {
+                        delayed_branch = Some((targets, ends_flow));
+                        (Vec::new(), false)
+                    }
+                    None => (targets, ends_flow),
+                };
+                if !targets.is_empty() && !ends_flow {
+                    leaders.insert(next);
+                }
+                decoded.insert(
+                    virtual_address,
+                    DecodedInstruction {
+                        file_address: file_address as u64,
+                        instruction: Instruction::new(instruction, header.get_symbols()),
+                        targets,
+                        ends_flow,
+                    },
+                );
+                if ends_flow {
+                    break;
+                }
+                virtual_address = next;
+            }
+        }
+
+        // a block ends before a leader, after a branch or where the code is not contiguous
+        let mut block_at = BTreeMap::new();
+        let mut previous: Option<&DecodedInstruction> = None;
+        for (virtual_address, current) in decoded.iter() {
+            let starts_block = match previous {
+                Some(previous) => {
+                    leaders.contains(virtual_address)
+                        || previous.ends_flow
+                        || previous.instruction.ip() + previous.instruction.len() as u64
+                            != *virtual_address
+                }
+                None => true,
+            };
+            if starts_block {
+                block_at.insert(*virtual_address, ret.blocks.len());
+                ret.blocks.push(BasicBlock {
+                    file_address: current.file_address,
+                    instructions: Vec::new(),
+                    successors: Vec::new(),
+                });
+            }
+            if let Some(block) = ret.blocks.last_mut() {
+                block.instructions.push(current.instruction.clone());
+            }
+            previous = Some(current);
+        }
+
+        for block in ret.blocks.iter_mut() {
+            let last = &block.instructions[block.instructions.len() - 1];
+            let current = &decoded[&last.ip()];
+            let kind = if current.ends_flow {
+                EdgeKind::Jump
+            } else {
+                EdgeKind::Taken
+            };
+            for target in current.targets.iter() {
+                if let Some(index) = block_at.get(target) {
+                    block.successors.push((*index, kind));
+                }
+            }
+            if !current.ends_flow {
+                let next = last.ip() + last.len() as u64;
+                if let Some(index) = block_at.get(&next) {
+                    let kind = if current.targets.is_empty() {
+                        EdgeKind::Fallthrough
+                    } else {
+                        EdgeKind::NotTaken
+                    };
+                    block.successors.push((*index, kind));
+                }
+            }
+        }
+        ret
+    }
+
+    /// Returns the index of the block containing virtual_address.
+    pub fn block_at(&self, virtual_address: u64) -> Option<usize> {
+        self.blocks
+            .iter()
+            .position(|block| block.contains(virtual_address))
+    }
+
+    /// Returns the block reached by the branch of a block, or the next one if it has no branch.
+    pub fn follow(&self, index: usize) -> Option<usize> {
+        let successors = &self.blocks.get(index)?.successors;
+        successors
+            .iter()
+            .find(|(_, kind)| matches!(kind, EdgeKind::Jump | EdgeKind::Taken))
+            .or(successors.first())
+            .map(|(successor, _)| *successor)
+    }
+
+    /// Returns the first block that can be executed right before a block.
+    pub fn predecessor(&self, index: usize) -> Option<usize> {
+        self.blocks.iter().position(|block| {
+            block
+                .successors
+                .iter()
+                .any(|(successor, _)| *successor == index)
+        })
+    }
+
+    /// Draws the blocks one under the other: the edges to the next block are arrows
+    /// between them, the other edges run on the right of the blocks.
+    /// Returns the lines and the first line of each block.
+    pub fn to_lines(
+        &self,
+        color_settings: &ColorSettings,
+        labels: &Labels,
+        selected: usize,
+    ) -> (Vec<Line<'static>>, Vec<usize>) {
+        let texts = self
+            .blocks
+            .iter()
+            .enumerate()
+            .map(|(i, block)| {
+                let title = if i == 0 {
+                    self.name.clone()
+                } else {
+                    labels
+                        .get(block.virtual_address())
+                        .unwrap_or_else(|| format!("{:X}", block.virtual_address()))
+                };
+                let instructions = block
+                    .instructions
+                    .iter()
+                    .map(|instruction| {
+                        (
+                            format!("{:X}", instruction.ip()),
+                            format!(
+                                "{} {}",
+                                instruction.mnemonic(),
+                                labels.substitute(instruction.operands())
+                            ),
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                (title, instructions)
+            })
+            .collect::<Vec<_>>();
+        let inner_width = texts
+            .iter()
+            .flat_map(|(title, instructions)| {
+                instructions
+                    .iter()
+                    .map(|(address, text)| address.len() + 1 + text.chars().count())
+                    .chain([title.chars().count() + 2])
+            })
+            .max()
+            .unwrap_or_default()
+            .min(MAX_BLOCK_WIDTH);
+        let right = inner_width + 3;
+
+        let mut block_rows = Vec::new();
+        let mut row = 0;
+        for block in self.blocks.iter() {
+            block_rows.push(row);
+            row += block.instructions.len() + 3;
+        }
+        let mut canvas = Canvas::new(row.saturating_sub(1));
+
+        for (i, (title, instructions)) in texts.iter().enumerate() {
+            let top = block_rows[i];
+            let bottom = top + instructions.len() + 1;
+            let (border_style, title_style, address_style, text_style) = if i == selected {
+                (
+                    color_settings.assembly_selected,
+                    color_settings.assembly_selected,
+                    color_settings.assembly_selected,
+                    color_settings.assembly_selected,
+                )
+            } else {
+                (
+                    color_settings.menu_text,
+                    color_settings.assembly_symbol,
+                    color_settings.assembly_address,
+                    color_settings.assembly_default,
+                )
+            };
+            for column in 0..=right {
+                let (top_corner, bottom_corner) = match column {
+                    0 => (RIGHT | DOWN, RIGHT | UP),
+                    c if c == right => (LEFT | DOWN, LEFT | UP),
+                    _ => (LEFT | RIGHT, LEFT | RIGHT),
+                };
+                canvas.lines(top, column, top_corner, border_style);
+                canvas.lines(bottom, column, bottom_corner, border_style);
+            }
+            canvas.text(top, 2, &format!(" {title} "), inner_width, title_style);
+            for (j, (address, text)) in instructions.iter().enumerate() {
+                let row = top + 1 + j;
+                canvas.lines(row, 0, UP | DOWN, border_style);
+                canvas.text(row, 1, " ", 1, text_style);
+                canvas.text(row, 2, address, inner_width, address_style);
+                canvas.text(
+                    row,
+                    2 + address.len(),
+                    &format!(" {text}"),
+                    inner_width - address.len(),
+                    text_style,
+                );
+                canvas.lines(row, right, UP | DOWN, border_style);
+            }
+        }
+
+        let edge_style = |kind: EdgeKind| match kind {
+            EdgeKind::Taken => color_settings.yes,
+            EdgeKind::NotTaken => color_settings.no,
+            EdgeKind::Jump | EdgeKind::Fallthrough => color_settings.menu_text,
+        };
+        // the shortest edges get the lanes closest to the blocks
+        let mut edges = Vec::new();
+        for (i, block) in self.blocks.iter().enumerate() {
+            for (successor, kind) in block.successors.iter() {
+                let from = block_rows[i] + block.instructions.len();
+                if *successor == i + 1 && matches!(kind, EdgeKind::NotTaken | EdgeKind::Fallthrough)
+                {
+                    canvas.text(from + 2, right / 2, "▼", 1, edge_style(*kind));
+                } else {
+                    edges.push((from, block_rows[*successor], *kind));
+                }
+            }
+        }
+        edges.sort_by_key(|(from, to, _)| from.abs_diff(*to));
+        let mut lanes: Vec<Vec<(usize, usize)>> = Vec::new();
+        for (from, to, kind) in edges {
+            let span = (from.min(to), from.max(to));
+            let lane = match lanes
+                .iter()
+                .position(|lane| lane.iter().all(|used| used.1 < span.0 || used.0 > span.1))
+            {
+                Some(lane) => lane,
+                None => {
+                    lanes.push(Vec::new());
+                    lanes.len() - 1
+                }
+            };
+            lanes[lane].push(span);
+            let column = right + 3 + 2 * lane;
+            let style = edge_style(kind);
+            let (from_turn, to_turn) = if to > from { (DOWN, UP) } else { (UP, DOWN) };
+            canvas.lines(from, right, RIGHT, style);
+            for c in right + 1..column {
+                canvas.lines(from, c, LEFT | RIGHT, style);
+            }
+            canvas.lines(from, column, LEFT | from_turn, style);
+            for r in span.0 + 1..span.1 {
+                canvas.lines(r, column, UP | DOWN, style);
+            }
+            canvas.lines(to, column, LEFT | to_turn, style);
+            for c in right + 2..column {
+                canvas.lines(to, c, LEFT | RIGHT, style);
+            }
+            canvas.text(to, right + 1, "◄", 1, style);
+        }
+
+        (canvas.into_lines(), block_rows)
+    }
+}
+
+const UP: u8 = 1;
+const DOWN: u8 = 2;
+const LEFT: u8 = 4;
+const RIGHT: u8 = 8;
+
+#[derive(Clone, Copy)]
+enum Cell {
+    Empty,
+    Char(char),
+    /// The directions of the lines that leave the center of the cell.
+    Lines(u8),
+}
+
+/// A grid of characters where the lines drawn on the same cell are joined.
+struct Canvas {
+    rows: Vec<Vec<(Cell, Style)>>,
+}
+
+impl Canvas {
+    fn new(height: usize) -> Self {
+        Self {
+            rows: vec![Vec::new(); height],
+        }
+    }
+
+    fn cell(&mut self, row: usize, column: usize) -> &mut (Cell, Style) {
+        let row = &mut self.rows[row];
+        if row.len() <= column {
+            row.resize(column + 1, (Cell::Empty, Style::default()));
+        }
+        &mut row[column]
+    }
+
+    fn lines(&mut self, row: usize, column: usize, directions: u8, style: Style) {
+        let cell = self.cell(row, column);
+        let directions = match cell.0 {
+            Cell::Lines(previous) => previous | directions,
+            _ => directions,
+        };
+        *cell = (Cell::Lines(directions), style);
+    }
+
+    fn text(&mut self, row: usize, column: usize, text: &str, max_len: usize, style: Style) {
+        for (i, c) in text.chars().take(max_len).enumerate() {
+            *self.cell(row, column + i) = (Cell::Char(c), style);
+        }
+    }
+
+    fn into_lines(self) -> Vec<Line<'static>> {
+        self.rows
+            .into_iter()
+            .map(|row| {
+                let mut spans: Vec<(String, Style)> = Vec::new();
+                for (cell, style) in row {
+                    let c = match cell {
+                        Cell::Empty => ' ',
+                        Cell::Char(c) => c,
+                        Cell::Lines(directions) => Self::line_char(directions),
+                    };
+                    match spans.last_mut() {
+                        Some((text, last_style)) if *last_style == style => text.push(c),
+                        _ => spans.push((c.to_string(), style)),
+                    }
+                }
+                Line::from(
+                    spans
+                        .into_iter()
+                        .map(|(text, style)| Span::styled(text, style))
+                        .collect::<Vec<_>>(),
+                )
+                .left_aligned()
+            })
+            .collect()
+    }
+
+    fn line_char(directions: u8) -> char {
+        match directions {
+            d if d == LEFT | RIGHT || d == LEFT || d == RIGHT => '─',
+            d if d == DOWN | RIGHT => '┌',
+            d if d == DOWN | LEFT => '┐',
+            d if d == UP | RIGHT => '└',
+            d if d == UP | LEFT => '┘',
+            d if d == UP | DOWN | RIGHT => '├',
+            d if d == UP | DOWN | LEFT => '┤',
+            d if d == LEFT | RIGHT | DOWN => '┬',
+            d if d == LEFT | RIGHT | UP => '┴',
+            d if d == UP | DOWN | LEFT | RIGHT => '┼',
+            0 => ' ',
+            _ => '│',
+        }
+    }
+}
+
+impl App {
+    /// Returns the closest symbol, called address or entry point at or before virtual_address.
+    fn function_start(&self, virtual_address: u64) -> Option<u64> {
+        let symbol = self.header.get_symbols().and_then(|symbols| {
+            symbols
+                .keys()
+                .filter(|address| **address <= virtual_address)
+                .max()
+                .copied()
+        });
+        let entry_point = Some(self.header.entry_point()).filter(|entry| *entry <= virtual_address);
+        symbol
+            .max(entry_point)
+            .max(self.labels.function_start(virtual_address))
+    }
+
+    fn build_cfg(&self, start: u64) -> ControlFlowGraph {
+        let name = self
+            .header
+            .get_symbols()
+            .and_then(|symbols| symbols.get(&start).cloned())
+            .or_else(|| self.labels.get(start))
+            .unwrap_or_else(|| format!("{start:X}"));
+        ControlFlowGraph::build(self.data.bytes(), &self.header, start, name)
+    }
+
+    pub(in crate::app) fn request_popup_cfg(&mut self) {
+        let Some(AssemblyLine::Instruction(instruction)) = self.get_current_instruction() else {
+            let address = self.get_cursor_position().global_byte_index;
+            self.log(
+                NotificationLevel::Error,
+                t!("errors.no_cfg", address = address : {:#X}),
+            );
+            return;
+        };
+        let virtual_address = instruction.instruction.ip();
+        // the branches can reach anywhere in the code sections
+        for section in CodeMap::code_sections(self.data.len(), &self.header) {
+            let start = section.file_offset as usize;
+            self.load_range(start..start + section.size as usize);
+        }
+        let mut graph = self.build_cfg(
+            self.function_start(virtual_address)
+                .unwrap_or(virtual_address),
+        );
+        if graph.block_at(virtual_address).is_none() {
+            // the closest function does not reach the cursor, the graph starts there
+            graph = self.build_cfg(virtual_address);
+        }
+        match graph.block_at(virtual_address) {
+            Some(selected) => self.popup = Some(PopupState::Cfg { graph, selected }),
+            None => self.log(
+                NotificationLevel::Error,
+                t!("errors.no_cfg", address = virtual_address : {:#X}),
+            ),
+        }
+    }
+
+    pub(in crate::app) fn jump_to_block(&mut self, block: &BasicBlock) {
+        self.info_mode = InfoMode::Assembly;
+        self.jump_to(block.file_address as usize, false);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use object::{Architecture, Endianness};
+
+    use crate::headers::{bitness::Bitness, custom_header::CustomHeader, isa_mode::IsaModes};
+
+    use super::*;
+
+    // 0: test edi, edi; 2: je 8; 4: xor eax, eax; 6: jmp 0xd; 8: mov eax, 1; d: ret
+    const FUNCTION: [u8; 14] = [
+        0x85, 0xFF, 0x74, 0x04, 0x31, 0xC0, 0xEB, 0x05, 0xB8, 0x01, 0x00, 0x00, 0x00, 0xC3,
+    ];
+
+    #[test]
+    fn test_build_cfg() {
+        let graph = ControlFlowGraph::build(&FUNCTION, &Header::None, 0, "f".to_string());
+        assert_eq!(
+            graph
+                .blocks
+                .iter()
+                .map(|block| (block.file_address, block.instructions.len()))
+                .collect::<Vec<_>>(),
+            vec![(0, 2), (4, 2), (8, 1), (0xd, 1)]
+        );
+        assert_eq!(
+            graph.blocks[0].successors,
+            vec![(2, EdgeKind::Taken), (1, EdgeKind::NotTaken)]
+        );
+        assert_eq!(graph.blocks[1].successors, vec![(3, EdgeKind::Jump)]);
+        assert_eq!(graph.blocks[2].successors, vec![(3, EdgeKind::Fallthrough)]);
+        assert!(graph.blocks[3].successors.is_empty());
+        assert_eq!(graph.block_at(9), Some(2));
+        assert_eq!(graph.block_at(0xe), None);
+        assert_eq!(graph.follow(0), Some(2));
+        assert_eq!(graph.follow(2), Some(3));
+        assert_eq!(graph.predecessor(3), Some(1));
+        assert_eq!(graph.predecessor(0), None);
+    }
+
+    #[test]
+    fn test_build_cfg_delay_slots() {
+        // 0: beqz $a0, 0x10; 4: nop; 8: jr $ra; c: nop; 10: jr $ra; 14: nop
+        let bytes = [
+            0x03, 0x00, 0x80, 0x10, 0, 0, 0, 0, 0x08, 0x00, 0xE0, 0x03, 0, 0, 0, 0, 0x08, 0x00,
+            0xE0, 0x03, 0, 0, 0, 0,
+        ];
+        let header = Header::CustomHeader(CustomHeader {
+            bitness: Bitness::Bit32,
+            entry: 0,
+            endianness: Endianness::Little,
+            architecture: Architecture::Mips,
+            sections: Vec::new(),
+            symbols: HashMap::new(),
+            symbols_by_name: HashMap::new(),
+            isa_modes: IsaModes::new(),
+        });
+        let graph = ControlFlowGraph::build(&bytes, &header, 0, "f".to_string());
+        assert_eq!(
+            graph
+                .blocks
+                .iter()
+                .map(|block| (block.file_address, block.instructions.len()))
+                .collect::<Vec<_>>(),
+            vec![(0, 2), (8, 2), (0x10, 2)]
+        );
+        assert_eq!(
+            graph.blocks[0].successors,
+            vec![(2, EdgeKind::Taken), (1, EdgeKind::NotTaken)]
+        );
+        assert!(graph.blocks[1].successors.is_empty());
+        assert!(graph.blocks[2].successors.is_empty());
+    }
+
+    #[test]
+    fn test_cfg_lines() {
+        let graph = ControlFlowGraph::build(&FUNCTION, &Header::None, 0, "f".to_string());
+        let (lines, block_rows) =
+            graph.to_lines(&ColorSettings::get_default_dark_theme(), &Labels::new(), 0);
+        assert_eq!(block_rows, vec![0, 5, 10, 14]);
+        assert_eq!(lines.len(), 17);
+        let text = lines
+            .iter()
+            .map(|line| {
+                line.spans
+                    .iter()
+                    .map(|span| span.content.as_ref())
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>();
+        assert!(text[0].starts_with("┌─ f ─"));
+        assert!(text[2].contains("2 je 8"));
+        assert!(text[2].contains("├"));
+        // the taken branch and the jump need a lane, the other edges are arrows
+        assert_eq!(text.iter().filter(|line| line.contains('◄')).count(), 2);
+        assert_eq!(text.iter().filter(|line| line.contains('▼')).count(), 2);
+        assert!(text[4].contains('▼'));
+        assert!(text[10].contains('◄'));
+        assert!(text[14].contains('◄'));
+    }
+
+    #[test]
+    fn test_request_popup_cfg() {
+        let mut app = App::mockup(FUNCTION.to_vec());
+        app.resize_to_size(80, 24);
+        app.jump_to(9, false);
+        app.request_popup_cfg();
+        let Some(PopupState::Cfg { graph, selected }) = app.popup.clone() else {
+            panic!("The control flow graph popup should be open");
+        };
+        assert_eq!(graph.blocks.len(), 4);
+        assert_eq!(selected, 2);
+        app.jump_to_block(&graph.blocks[1]);
+        assert_eq!(app.get_cursor_position().global_byte_index, 4);
+    }
+}
@@ -0,0 +1,284 @@
+use std::collections::BTreeMap;
+
+use capstone::{arch::ArchDetail, Capstone, Insn, InsnGroupId, InsnGroupIdInt, InsnGroupType};
+
+use crate::headers::{isa_mode::Decoders, section::Section, Header};
+
+use super::xrefs::{XrefKind, Xrefs};
+
+/// The mnemonics of the unconditional jumps and of the instructions that stop the
+/// execution on each architecture. The returns are found through the instruction
+/// groups, and the calls through a register, like blr on Aarch64, are not here.
+const X86_FLOW_ENDING_MNEMONICS: &[&str] = &["jmp", "ljmp", "hlt", "ud2"];
+const ARM_FLOW_ENDING_MNEMONICS: &[&str] = &["b", "b.w", "bx"];
+const ARM64_FLOW_ENDING_MNEMONICS: &[&str] = &["b", "br", "eret"];
+const MIPS_FLOW_ENDING_MNEMONICS: &[&str] = &[
+    "j",
+    "jr",
+    "b",
+    "bc",
+    "jic",
+    "jrc",
+    "b16",
+    "bc16",
+    "jr16",
+    "jrc16",
+    "jraddiusp",
+    "eret",
+    "deret",
+];
+const PPC_FLOW_ENDING_MNEMONICS: &[&str] = &["b", "ba", "bctr", "blr"];
+const RISCV_FLOW_ENDING_MNEMONICS: &[&str] = &["j", "jr", "c.j", "c.jr", "ret", "mret", "sret"];
+const SPARC_FLOW_ENDING_MNEMONICS: &[&str] = &["b", "ba", "b,a", "ba,a", "jmp", "ret", "retl"];
+const SYSZ_FLOW_ENDING_MNEMONICS: &[&str] = &["j", "jg", "b", "br"];
+
+/// The instructions reached by following the control flow of the code sections
+/// from the entry point and the symbols, the bytes of the code sections that
+/// are never reached are data.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CodeMap {
+    /// The length of each instruction, indexed by its file address.
+    instructions: BTreeMap<usize, usize>,
+}
+
+impl CodeMap {
+    /// Disassembles by recursive descent, the jumps and calls with a known target are
+    /// followed, the execution is assumed to continue after a call.
+    pub fn analyze(bytes: &[u8], header: &Header) -> Self {
+        let mut ret = Self::default();
+        let code_sections = Self::code_sections(bytes.len(), header);
+        let mut decoders = Decoders::new(header);
+        let mut to_visit = vec![header.entry_point()];
+        if let Some(symbols) = header.get_symbols() {
+            to_visit.extend(symbols.keys());
+        }
+        while let Some(mut virtual_address) = to_visit.pop() {
+            let Some((mut file_address, section_end)) =
+                Self::to_file_address(&code_sections, virtual_address)
+            else {
+                continue;
+            };
+            let mut in_delay_slot = false;
+            while file_address < section_end && !ret.instructions.contains_key(&file_address) {
+                let Some(decoder) = decoders.at(virtual_address) else {
+                    break;
+                };
+                let Ok(decoded) =
+                    decoder.disasm_count(&bytes[file_address..section_end], virtual_address, 1)
+                else {
+                    break;
+                };
+                let Some(instruction) = decoded.iter().next() else {
+                    break;
+                };
+                // with skipdata the bytes that can not be decoded are returned as .byte
+                if instruction.mnemonic() == Some(".byte") {
+                    break;
+                }
+                ret.instructions.insert(file_address, instruction.len());
+                for (target, kind) in Xrefs::references(decoder, instruction, |_| true) {
+                    if kind != XrefKind::Data {
+                        to_visit.push(target);
+                    }
+                }
+                if in_delay_slot {
+                    break;
+                }
+                if Self::ends_flow(decoder, instruction) {
+                    // the instruction in the delay slot is executed before the jump
+                    if !Self::has_delay_slot(decoder, instruction) {
+                        break;
+                    }
+                    in_delay_slot = true;
+                }
+                file_address += instruction.len();
+                virtual_address += instruction.len() as u64;
+            }
+        }
+        ret
+    }
+
+    /// Returns the sections that are disassembled, the whole file if there are no sections.
+    pub fn code_sections(len: usize, header: &Header) -> Vec<Section> {
+        let sections = header.get_sections();
+        if sections.is_empty() {
+            return vec![Section {
+                name: ".text".to_string(),
+                virtual_address: 0,
+                file_offset: 0,
+                size: len as u64,
+            }];
+        }
+        sections
+            .into_iter()
+            .filter(|section| section.name == ".text" || section.name == "__text")
+            .filter(|section| section.file_offset < len as u64)
+            .map(|mut section| {
+                section.size = section.size.min(len as u64 - section.file_offset);
+                section
+            })
+            .collect()
+    }
+
+    /// Returns the file address of virtual_address and the end of its code section.
+    pub(super) fn to_file_address(
+        code_sections: &[Section],
+        virtual_address: u64,
+    ) -> Option<(usize, usize)> {
+        code_sections.iter().find_map(|section| {
+            (virtual_address >= section.virtual_address
+                && virtual_address < section.virtual_address + section.size)
+                .then(|| {
+                    let offset = section.file_offset + virtual_address - section.virtual_address;
+                    (
+                        offset as usize,
+                        (section.file_offset + section.size) as usize,
+                    )
+                })
+        })
+    }
+
+    /// Checks if the execution never continues with the instruction after this one,
+    /// or after its delay slot if it has one.
+    pub(super) fn ends_flow(decoder: &Capstone, instruction: &Insn) -> bool {
+        let Ok(detail) = decoder.insn_detail(instruction) else {
+            return false;
+        };
+        let in_group = |group| {
+            detail
+                .groups()
+                .contains(&InsnGroupId(group as InsnGroupIdInt))
+        };
+        if in_group(InsnGroupType::CS_GRP_CALL) {
+            return false;
+        }
+        if in_group(InsnGroupType::CS_GRP_RET) || in_group(InsnGroupType::CS_GRP_IRET) {
+            return true;
+        }
+        let mnemonics = match detail.arch_detail() {
+            ArchDetail::X86Detail(_) => X86_FLOW_ENDING_MNEMONICS,
+            ArchDetail::ArmDetail(_) => ARM_FLOW_ENDING_MNEMONICS,
+            ArchDetail::Arm64Detail(_) => ARM64_FLOW_ENDING_MNEMONICS,
+            ArchDetail::MipsDetail(_) => MIPS_FLOW_ENDING_MNEMONICS,
+            ArchDetail::PpcDetail(_) => PPC_FLOW_ENDING_MNEMONICS,
+            ArchDetail::RiscVDetail(_) => RISCV_FLOW_ENDING_MNEMONICS,
+            ArchDetail::SparcDetail(_) => SPARC_FLOW_ENDING_MNEMONICS,
+            ArchDetail::SysZDetail(_) => SYSZ_FLOW_ENDING_MNEMONICS,
+            _ => &[],
+        };
+        instruction
+            .mnemonic()
+            .is_some_and(|mnemonic| mnemonics.contains(&mnemonic))
+    }
+
+    /// Checks if the instruction after this branch is executed before the branch is
+    /// taken, as on Mips and Sparc. The compact branches of Mips and the annulled
+    /// unconditional branches of Sparc have no delay slot.
+    pub(super) fn has_delay_slot(decoder: &Capstone, instruction: &Insn) -> bool {
+        let Ok(detail) = decoder.insn_detail(instruction) else {
+            return false;
+        };
+        let is_branch = [
+            InsnGroupType::CS_GRP_JUMP,
+            InsnGroupType::CS_GRP_CALL,
+            InsnGroupType::CS_GRP_RET,
+            InsnGroupType::CS_GRP_BRANCH_RELATIVE,
+        ]
+        .into_iter()
+        .any(|group| {
+            detail
+                .groups()
+                .contains(&InsnGroupId(group as InsnGroupIdInt))
+        });
+        let mnemonic = instruction.mnemonic().unwrap_or_default();
+        is_branch
+            && match detail.arch_detail() {
+                ArchDetail::MipsDetail(_) => {
+                    !mnemonic.trim_end_matches("16").ends_with('c') && mnemonic != "jraddiusp"
+                }
+                ArchDetail::SparcDetail(_) => !matches!(mnemonic, "b,a" | "ba,a"),
+                _ => false,
+            }
+    }
+
+    /// Returns the length of the instruction starting at file_address, if it was reached.
+    pub fn instruction_len(&self, file_address: usize) -> Option<usize> {
+        self.instructions.get(&file_address).copied()
+    }
+
+    /// Returns the file address of the first instruction at or after file_address.
+    pub fn next_instruction(&self, file_address: usize) -> Option<usize> {
+        self.instructions
+            .range(file_address..)
+            .next()
+            .map(|(address, _)| *address)
+    }
+
+    /// Returns the number of instructions.
+    pub fn len(&self) -> usize {
+        self.instructions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.instructions.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use object::{Architecture, Endianness};
+
+    use crate::headers::{bitness::Bitness, custom_header::CustomHeader, isa_mode::IsaModes};
+
+    use super::*;
+
+    #[test]
+    fn test_code_map() {
+        // jmp over 4 bytes of data, then nop; ret; and more data after the ret
+        let bytes = vec![0xEB, 0x04, 0xDE, 0xAD, 0xBE, 0xEF, 0x90, 0xC3, 0xFF, 0xFF];
+        let code_map = CodeMap::analyze(&bytes, &Header::None);
+        assert_eq!(code_map.len(), 3);
+        assert_eq!(code_map.instruction_len(0), Some(2));
+        assert_eq!(code_map.instruction_len(2), None);
+        assert_eq!(code_map.next_instruction(2), Some(6));
+        assert_eq!(code_map.instruction_len(7), Some(1));
+        assert_eq!(code_map.next_instruction(8), None);
+    }
+
+    fn raw_header(architecture: Architecture, bitness: Bitness) -> Header {
+        Header::CustomHeader(CustomHeader {
+            bitness,
+            entry: 0,
+            endianness: Endianness::Little,
+            architecture,
+            sections: Vec::new(),
+            symbols: HashMap::new(),
+            symbols_by_name: HashMap::new(),
+            isa_modes: IsaModes::new(),
+        })
+    }
+
+    #[test]
+    fn test_code_map_flow_end() {
+        // blr x1 is a call, then nop; ret; and an unreached nop
+        let bytes = vec![
+            0x20, 0x00, 0x3F, 0xD6, 0x1F, 0x20, 0x03, 0xD5, 0xC0, 0x03, 0x5F, 0xD6, 0x1F, 0x20,
+            0x03, 0xD5,
+        ];
+        let header = raw_header(Architecture::Aarch64, Bitness::Bit64);
+        let code_map = CodeMap::analyze(&bytes, &header);
+        assert_eq!(code_map.len(), 3);
+        assert_eq!(code_map.instruction_len(8), Some(4));
+        assert_eq!(code_map.instruction_len(12), None);
+
+        // jr $ra; nop in its delay slot; and an unreached nop
+        let bytes = vec![0x08, 0x00, 0xE0, 0x03, 0, 0, 0, 0, 0, 0, 0, 0];
+        let header = raw_header(Architecture::Mips, Bitness::Bit32);
+        let code_map = CodeMap::analyze(&bytes, &header);
+        assert_eq!(code_map.len(), 2);
+        assert_eq!(code_map.instruction_len(4), Some(4));
+        assert_eq!(code_map.instruction_len(8), None);
+    }
+}
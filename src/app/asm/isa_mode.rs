@@ -0,0 +1,133 @@
+use std::ops::Range;
+
+use object::Architecture;
+
+use crate::{
+    app::{log::NotificationLevel, popup::popup_state::PopupState, App},
+    headers::isa_mode::IsaMode,
+};
+
+impl App {
+    /// Returns the virtual addresses of the selection, or of the section under the cursor
+    /// if nothing is selected. Without sections the addresses are the file offsets.
+    fn isa_mode_range(&self) -> Range<u64> {
+        if let Some(selection) = self.get_selection() {
+            let start = self
+                .header
+                .physical_to_virtual_address(selection.start as u64)
+                .unwrap_or(selection.start as u64);
+            return start..start + selection.len() as u64;
+        }
+        let cursor = self.get_cursor_position().global_byte_index as u64;
+        self.header
+            .get_sections()
+            .into_iter()
+            .find(|section| {
+                cursor >= section.file_offset && cursor < section.file_offset + section.size
+            })
+            .map(|section| section.virtual_address..section.virtual_address + section.size)
+            .unwrap_or(0..self.data.len() as u64)
+    }
+
+    pub(in crate::app) fn set_isa_mode(&mut self, range: Range<u64>, mode: IsaMode) {
+        self.isa_modes.set(range.clone(), mode);
+        self.header.set_isa_mode(range.clone(), mode);
+        self.selection_anchor = None;
+        self.log(
+            NotificationLevel::Info,
+            t!(
+                "app.messages.isa_mode_set",
+                mode = mode,
+                start = range.start : {:#X},
+                end = range.end : {:#X}
+            ),
+        );
+        self.disassemble();
+    }
+
+    pub(in crate::app) fn request_popup_isa_mode(&mut self) {
+        // without a known architecture the code is decoded as x86_64
+        let architecture = match self.header.architecture() {
+            Architecture::Unknown => Architecture::X86_64,
+            architecture => architecture,
+        };
+        let modes = IsaMode::available(architecture);
+        if modes.len() < 2 {
+            self.log(
+                NotificationLevel::Warning,
+                t!(
+                    "app.messages.no_isa_modes",
+                    architecture = format!("{architecture:?}")
+                ),
+            );
+            return;
+        }
+        let range = self.isa_mode_range();
+        let current = self.header.isa_mode_at(range.start);
+        let selected = modes.iter().position(|mode| *mode == current).unwrap_or(0);
+        self.popup = Some(PopupState::IsaMode {
+            range,
+            modes,
+            selected,
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{app::asm::assembly_line::AssemblyLine, headers::Header};
+
+    use super::*;
+
+    #[test]
+    fn test_isa_mode() {
+        // mov eax, 1 in 64-bit mode, mov ax, 1; add [bx + si], al in 16-bit mode
+        let data = vec![0xb8, 0x01, 0x00, 0x00, 0x00];
+        let mut app = App::mockup(data);
+        app.resize_to_size(80, 24);
+        let instructions = |app: &App| {
+            app.assembly_instructions
+                .iter()
+                .filter_map(|line| match line {
+                    AssemblyLine::Instruction(i) => Some(i.instruction.to_string()),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(instructions(&app).len(), 1);
+
+        app.request_popup_isa_mode();
+        let Some(PopupState::IsaMode {
+            range,
+            modes,
+            selected,
+        }) = app.popup.clone()
+        else {
+            panic!("The instruction set popup was not opened.");
+        };
+        assert_eq!(range, 0..5);
+        assert_eq!(modes, vec![IsaMode::Default, IsaMode::X86_16]);
+        assert_eq!(selected, 0);
+
+        app.set_isa_mode(range, IsaMode::X86_16);
+        assert_eq!(instructions(&app).len(), 2);
+        // the header holding the modes does not make up an architecture
+        assert_eq!(app.header.architecture(), Architecture::Unknown);
+        app.request_popup_isa_mode();
+        assert!(matches!(app.popup, Some(PopupState::IsaMode { .. })));
+        app.popup = None;
+        assert_eq!(
+            app.bytes_from_assembly("mov ax, 1", 0),
+            Ok(vec![0xb8, 0x01, 0x00])
+        );
+
+        let tmp = tempfile::NamedTempFile::new().unwrap();
+        let path = tmp.path().to_str().unwrap().to_string();
+        app.save_comments(Some(path.clone()));
+        app.isa_modes = Default::default();
+        app.header = Header::None;
+        app.load_comments(Some(path));
+        assert_eq!(app.header.isa_mode_at(2), IsaMode::X86_16);
+        assert_eq!(app.header.isa_mode_at(5), IsaMode::Default);
+    }
+}
@@ -0,0 +1,260 @@
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{Deserialize, Serialize};
+
+use crate::app::{log::NotificationLevel, popup::popup_state::PopupState, App};
+
+use super::{assembly_line::AssemblyLine, xrefs::XrefKind, xrefs::Xrefs};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LabelKind {
+    /// The start of a function, called or with a recognizable prologue.
+    Sub,
+    /// The target of a jump.
+    Loc,
+}
+
+/// Names for the code addresses that have no symbol, indexed by virtual address.
+/// The automatic ones are found again at every disassembly, only the names
+/// given by the user are saved next to the comments.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Labels {
+    names: HashMap<u64, String>,
+    #[serde(skip)]
+    auto: BTreeMap<u64, LabelKind>,
+    #[serde(skip)]
+    dirty: bool,
+}
+
+impl Labels {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Finds the labels of the disassembled code, addresses that already have
+    /// a symbol and branches pointing outside the file are skipped.
+    pub fn update(
+        &mut self,
+        xrefs: &Xrefs,
+        lines: &[AssemblyLine],
+        symbols: Option<&HashMap<u64, String>>,
+        is_mapped: impl Fn(u64) -> bool,
+    ) {
+        self.auto.clear();
+        for (target, refs) in xrefs.iter() {
+            if refs.iter().any(|xref| xref.kind == XrefKind::Call) {
+                self.auto.insert(target, LabelKind::Sub);
+            } else if refs.iter().any(|xref| xref.kind == XrefKind::Jump) {
+                self.auto.insert(target, LabelKind::Loc);
+            }
+        }
+        let instructions = lines
+            .iter()
+            .filter_map(|line| match line {
+                AssemblyLine::Instruction(instruction) => Some(&instruction.instruction),
+                AssemblyLine::SectionTag(_) => None,
+            })
+            .collect::<Vec<_>>();
+        for (i, instruction) in instructions.iter().enumerate() {
+            let next = instructions.get(i + 1).map(|next| next.to_string());
+            if Self::is_prologue(&instruction.to_string(), next.as_deref()) {
+                self.auto.insert(instruction.ip(), LabelKind::Sub);
+            }
+        }
+        self.auto.retain(|address, _| {
+            is_mapped(*address) && !symbols.is_some_and(|symbols| symbols.contains_key(address))
+        });
+    }
+
+    fn is_prologue(instruction: &str, next: Option<&str>) -> bool {
+        let instruction = instruction.trim();
+        match instruction {
+            "endbr64" | "endbr32" => true,
+            "push rbp" => next == Some("mov rbp, rsp"),
+            "push ebp" => next == Some("mov ebp, esp"),
+            _ => {
+                // arm64 frame record and arm register save including the link register
+                instruction.starts_with("stp x29, x30, [sp, #-")
+                    || (instruction.starts_with("push {") && instruction.ends_with("lr}"))
+            }
+        }
+    }
+
+    /// Returns the name given by the user, or the automatic one.
+    pub fn get(&self, address: u64) -> Option<String> {
+        if let Some(name) = self.names.get(&address) {
+            return Some(name.clone());
+        }
+        self.auto.get(&address).map(|kind| match kind {
+            LabelKind::Sub => format!("sub_{address:X}"),
+            LabelKind::Loc => format!("loc_{address:X}"),
+        })
+    }
+
+    /// Returns the closest address at or before address that starts a function.
+    pub fn function_start(&self, address: u64) -> Option<u64> {
+        self.auto
+            .range(..=address)
+            .rev()
+            .find(|(_, kind)| **kind == LabelKind::Sub)
+            .map(|(address, _)| *address)
+    }
+
+    pub fn contains(&self, address: u64) -> bool {
+        self.names.contains_key(&address) || self.auto.contains_key(&address)
+    }
+
+    /// Gives a new name to a label, an empty name restores the automatic one.
+    pub fn rename(&mut self, address: u64, name: &str) {
+        if name.is_empty() {
+            self.names.remove(&address);
+        } else {
+            self.names.insert(address, name.to_string());
+        }
+        self.dirty = true;
+    }
+
+    /// Replaces the addresses in the operands with their labels, like the
+    /// disassembler does with the symbols.
+    pub fn substitute(&self, operands: &str) -> String {
+        operands
+            .split(", ")
+            .map(|operand| {
+                operand
+                    .strip_prefix("0x")
+                    .or_else(|| operand.strip_prefix("#0x"))
+                    .and_then(|address| u64::from_str_radix(address, 16).ok())
+                    .and_then(|address| self.get(address))
+                    .unwrap_or_else(|| operand.to_string())
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    pub fn address_of(&self, name: &str) -> Option<u64> {
+        self.iter()
+            .find(|(_, label)| label == name)
+            .map(|(address, _)| address)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (u64, String)> + '_ {
+        self.names
+            .keys()
+            .chain(self.auto.keys().filter(|a| !self.names.contains_key(a)))
+            .filter_map(|address| self.get(*address).map(|name| (*address, name)))
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    pub fn reset_dirty(&mut self) {
+        self.dirty = false;
+    }
+}
+
+impl App {
+    pub(in crate::app) fn update_labels(&mut self) {
+        let is_mapped = Self::is_mapped_address(&self.header, self.data.len());
+        self.labels.update(
+            &self.xrefs,
+            &self.assembly_instructions,
+            self.header.get_symbols(),
+            is_mapped,
+        );
+    }
+
+    pub(in crate::app) fn rename_label(&mut self, name: &str) {
+        let Some(address) = self
+            .get_current_instruction()
+            .map(|instruction| instruction.virtual_address())
+        else {
+            return;
+        };
+        if !self.labels.contains(address) {
+            self.log(
+                NotificationLevel::Error,
+                t!("errors.no_label", address = address : {:#X}),
+            );
+            return;
+        }
+        let name = name.trim();
+        if name.contains(char::is_whitespace) {
+            self.log(
+                NotificationLevel::Error,
+                t!("errors.invalid_label", label = name),
+            );
+            return;
+        }
+        self.labels.rename(address, name);
+    }
+
+    pub(in crate::app) fn request_popup_rename_label(&mut self) {
+        let label = self
+            .get_current_instruction()
+            .and_then(|instruction| self.labels.get(instruction.virtual_address()));
+        match label {
+            Some(label) => {
+                self.popup = Some(PopupState::RenameLabel {
+                    cursor: label.len(),
+                    name: label,
+                });
+            }
+            None => {
+                let address = self
+                    .get_current_instruction()
+                    .map(|instruction| instruction.virtual_address())
+                    .unwrap_or_default();
+                self.log(
+                    NotificationLevel::Error,
+                    t!("errors.no_label", address = address : {:#X}),
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_labels() {
+        // 0: call 0xa; 5: jmp 0xb; 7: nop; 8: nop; 9: nop; a: ret; b: push rbp; c: mov rbp, rsp; f: ret
+        let data = vec![
+            0xe8, 0x05, 0x00, 0x00, 0x00, 0xeb, 0x04, 0x90, 0x90, 0x90, 0xc3, 0x55, 0x48, 0x89,
+            0xe5, 0xc3,
+        ];
+        let mut app = App::mockup(data);
+        app.resize_to_size(80, 24);
+        assert_eq!(app.labels.get(0xa), Some("sub_A".to_string()));
+        assert_eq!(app.labels.get(0xb), Some("sub_B".to_string()));
+        assert_eq!(app.labels.get(0x7), None);
+        app.jump_to(0x5, false);
+        app.patch_bytes(&[0xeb, 0x00], true);
+        assert_eq!(app.labels.get(0x7), Some("loc_7".to_string()));
+
+        app.jump_to(0xa, false);
+        app.rename_label("helper");
+        assert_eq!(app.labels.get(0xa), Some("helper".to_string()));
+        assert_eq!(app.labels.address_of("helper"), Some(0xa));
+        assert!(app
+            .find_symbols("help")
+            .contains(&(0xa, "helper".to_string())));
+        app.jump_to(0x8, false);
+        app.rename_label("not_a_label");
+        assert_eq!(app.labels.get(0x8), None);
+
+        let tmp = tempfile::NamedTempFile::new().unwrap();
+        let path = tmp.path().to_str().unwrap().to_string();
+        app.save_comments(Some(path.clone()));
+        app.labels = Labels::new();
+        app.load_comments(Some(path));
+        assert_eq!(app.labels.get(0xa), Some("helper".to_string()));
+        assert_eq!(app.labels.get(0xb), Some("sub_B".to_string()));
+        app.jump_to(0xa, false);
+        app.rename_label("");
+        assert_eq!(app.labels.get(0xa), Some("sub_A".to_string()));
+    }
+}
@@ -1,4 +1,10 @@
 pub mod assembly;
 pub mod assembly_line;
+pub mod assembly_offsets;
+pub mod cfg;
+pub mod code_map;
 pub mod instruction_tag;
+pub mod isa_mode;
+pub mod labels;
 pub mod section_tag;
+pub mod xrefs;
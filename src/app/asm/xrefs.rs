@@ -0,0 +1,355 @@
+use std::{collections::BTreeMap, ops::Range};
+
+use capstone::{
+    arch::{
+        arm::ArmOperandType, arm64::Arm64OperandType, x86::X86OperandType, x86::X86Reg, ArchOperand,
+    },
+    Capstone, Insn, InsnGroupId, InsnGroupIdInt, InsnGroupType, RegId, RegIdInt,
+};
+
+use crate::{
+    app::{instruction::Instruction, log::NotificationLevel, popup::popup_state::PopupState, App},
+    headers::isa_mode::Decoders,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum XrefKind {
+    Call,
+    Jump,
+    Data,
+}
+
+/// A reference from an instruction to a virtual address.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Xref {
+    pub from_virtual_address: u64,
+    pub from_file_address: u64,
+    pub kind: XrefKind,
+}
+
+/// Incoming references of every address referenced by the disassembled code,
+/// indexed by the virtual address of the target.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Xrefs {
+    refs: BTreeMap<u64, Vec<Xref>>,
+}
+
+impl Xrefs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the references to target, sorted by the file address of the instruction.
+    pub fn get(&self, target: u64) -> &[Xref] {
+        self.refs.get(&target).map(|v| v.as_slice()).unwrap_or(&[])
+    }
+
+    pub fn insert(&mut self, target: u64, xref: Xref) {
+        let refs = self.refs.entry(target).or_default();
+        let index = refs.partition_point(|r| r.from_file_address < xref.from_file_address);
+        refs.insert(index, xref);
+    }
+
+    pub fn extend(&mut self, other: Xrefs) {
+        for (target, refs) in other.refs {
+            for xref in refs {
+                self.insert(target, xref);
+            }
+        }
+    }
+
+    /// Removes the references made by the instructions inside the file range.
+    pub fn remove_from(&mut self, range: Range<u64>) {
+        self.refs.retain(|_, refs| {
+            refs.retain(|r| !range.contains(&r.from_file_address));
+            !refs.is_empty()
+        });
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (u64, &[Xref])> {
+        self.refs
+            .iter()
+            .map(|(target, refs)| (*target, refs.as_slice()))
+    }
+
+    /// Returns the number of references.
+    pub fn len(&self) -> usize {
+        self.refs.values().map(|refs| refs.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.refs.is_empty()
+    }
+
+    /// Returns the addresses referenced by an instruction, the decoder must have
+    /// the details turned on. Branches are always recorded, other immediates
+    /// and memory operands only if is_mapped says they point inside the file.
+    pub fn references(
+        decoder: &Capstone,
+        instruction: &Insn,
+        is_mapped: impl Fn(u64) -> bool,
+    ) -> Vec<(u64, XrefKind)> {
+        let Ok(detail) = decoder.insn_detail(instruction) else {
+            return Vec::new();
+        };
+        let in_group = |group| {
+            detail
+                .groups()
+                .contains(&InsnGroupId(group as InsnGroupIdInt))
+        };
+        let kind = if in_group(InsnGroupType::CS_GRP_CALL) {
+            XrefKind::Call
+        } else if in_group(InsnGroupType::CS_GRP_JUMP)
+            || in_group(InsnGroupType::CS_GRP_BRANCH_RELATIVE)
+        {
+            XrefKind::Jump
+        } else {
+            XrefKind::Data
+        };
+        let next_instruction = instruction.address() + instruction.len() as u64;
+        let mut ret = Vec::new();
+        for operand in detail.arch_detail().operands() {
+            let target = match operand {
+                ArchOperand::X86Operand(operand) => match operand.op_type {
+                    X86OperandType::Imm(imm) => Some(imm as u64),
+                    X86OperandType::Mem(mem)
+                        if mem.base() == RegId(X86Reg::X86_REG_RIP as RegIdInt)
+                            && mem.index() == RegId(X86Reg::X86_REG_INVALID as RegIdInt) =>
+                    {
+                        Some(next_instruction.wrapping_add(mem.disp() as u64))
+                    }
+                    _ => None,
+                },
+                ArchOperand::Arm64Operand(operand) => match operand.op_type {
+                    Arm64OperandType::Imm(imm) => Some(imm as u64),
+                    _ => None,
+                },
+                ArchOperand::ArmOperand(operand) => match operand.op_type {
+                    ArmOperandType::Imm(imm) => Some(imm as u32 as u64),
+                    _ => None,
+                },
+                ArchOperand::MipsOperand(capstone::arch::mips::MipsOperand::Imm(imm))
+                | ArchOperand::PpcOperand(capstone::arch::ppc::PpcOperand::Imm(imm))
+                | ArchOperand::RiscVOperand(capstone::arch::riscv::RiscVOperand::Imm(imm)) => {
+                    Some(imm as u64)
+                }
+                _ => None,
+            };
+            if let Some(target) = target {
+                if kind != XrefKind::Data || is_mapped(target) {
+                    ret.push((target, kind));
+                }
+            }
+        }
+        ret
+    }
+}
+
+impl XrefKind {
+    pub fn name(&self) -> String {
+        match self {
+            XrefKind::Call => t!("app.xref_call").to_string(),
+            XrefKind::Jump => t!("app.xref_jump").to_string(),
+            XrefKind::Data => t!("app.xref_data").to_string(),
+        }
+    }
+}
+
+impl App {
+    /// Parses the target of an xref search: a file address like `0x1000`, a virtual
+    /// address like `v0x401000` or a symbol. An empty target is the current instruction.
+    pub(in crate::app) fn parse_xref_target(&self, target: &str) -> Result<u64, String> {
+        let target = target.trim();
+        if target.is_empty() {
+            return self
+                .get_current_instruction()
+                .map(|instruction| instruction.virtual_address())
+                .ok_or_else(|| t!("errors.invalid_address", address = target).to_string());
+        }
+        if let Some(address) = target.strip_prefix("0x") {
+            let address = u64::from_str_radix(address, 16)
+                .map_err(|_| t!("errors.invalid_address", address = target).to_string())?;
+            Ok(self
+                .header
+                .physical_to_virtual_address(address)
+                .unwrap_or(address))
+        } else if let Some(address) = target.strip_prefix("v0x") {
+            u64::from_str_radix(address, 16)
+                .map_err(|_| t!("errors.invalid_virtual_address", address = target).to_string())
+        } else {
+            self.header
+                .symbol_to_address(target)
+                .ok_or_else(|| t!("errors.symbol_not_found", symbol = target).to_string())
+        }
+    }
+
+    /// Lists the references to target together with the instruction making each of them.
+    pub(in crate::app) fn find_xrefs(&mut self, target: &str) -> Vec<(Xref, String)> {
+        let target = match self.parse_xref_target(target) {
+            Ok(target) => target,
+            Err(e) => {
+                self.log(NotificationLevel::Error, e);
+                return Vec::new();
+            }
+        };
+        // the index only covers the disassembly window, callers can be anywhere in the file
+        let full_xrefs;
+        let xrefs = if self.assembly_window == (0..self.data.len()) {
+            &self.xrefs
+        } else {
+            self.load_range(0..self.data.len());
+            (_, _, full_xrefs) = Self::sections_from_bytes(
+                self.data.bytes(),
+                &self.header,
+                0..self.data.len(),
+                self.code_map.as_ref(),
+            );
+            &full_xrefs
+        };
+        let mut decoders = Decoders::new(&self.header);
+        let ret = xrefs
+            .get(target)
+            .iter()
+            .map(|xref| {
+                let bytes = &self.data.bytes()[xref.from_file_address as usize..];
+                let text = decoders
+                    .at(xref.from_virtual_address)
+                    .and_then(|decoder| {
+                        decoder
+                            .disasm_count(bytes, xref.from_virtual_address, 1)
+                            .ok()
+                    })
+                    .and_then(|decoded| {
+                        decoded.iter().next().map(|instruction| {
+                            Instruction::new(instruction, self.header.get_symbols())
+                                .to_string()
+                                .trim()
+                                .to_string()
+                        })
+                    })
+                    .unwrap_or_default();
+                (xref.clone(), text)
+            })
+            .collect::<Vec<_>>();
+        if ret.is_empty() {
+            self.log(
+                NotificationLevel::Warning,
+                t!("app.messages.no_xrefs", address = target : {:#X}),
+            );
+        }
+        ret
+    }
+
+    pub(in crate::app) fn jump_to_xref(&mut self, xref: &Xref) {
+        let address = xref.from_virtual_address;
+        self.log(
+            NotificationLevel::Debug,
+            t!("app.messages.jump_to_xref", address = address : {:#X}),
+        );
+        self.jump_to(xref.from_file_address as usize, false);
+    }
+
+    pub(in crate::app) fn request_popup_xrefs(&mut self) {
+        let target = self
+            .get_current_instruction()
+            .map(|instruction| format!("v0x{:X}", instruction.virtual_address()))
+            .unwrap_or_default();
+        let results = self.find_xrefs(&target);
+        let cursor = target.len();
+        self.popup = Some(PopupState::Xrefs {
+            target,
+            cursor,
+            results,
+            scroll: 0,
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use capstone::arch::{self, BuildsCapstone};
+
+    use super::*;
+
+    #[test]
+    fn test_references() {
+        let mut decoder = Capstone::new()
+            .x86()
+            .mode(arch::x86::ArchMode::Mode64)
+            .build()
+            .unwrap();
+        decoder.set_detail(true).unwrap();
+        // call 0x1010; jmp 0x1000; lea rax, [rip + 0x10]; mov eax, 1
+        let bytes = [
+            0xe8, 0x0b, 0x00, 0x00, 0x00, 0xeb, 0xf9, 0x48, 0x8d, 0x05, 0x10, 0x00, 0x00, 0x00,
+            0xb8, 0x01, 0x00, 0x00, 0x00,
+        ];
+        let decoded = decoder.disasm_all(&bytes, 0x1000).unwrap();
+        let references = decoded
+            .iter()
+            .map(|i| Xrefs::references(&decoder, i, |address| address >= 0x1000))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            references,
+            vec![
+                vec![(0x1010, XrefKind::Call)],
+                vec![(0x1000, XrefKind::Jump)],
+                vec![(0x101e, XrefKind::Data)],
+                vec![],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_xrefs() {
+        let xref = |from: u64, kind| Xref {
+            from_virtual_address: from + 0x1000,
+            from_file_address: from,
+            kind,
+        };
+        let mut xrefs = Xrefs::new();
+        xrefs.insert(0x10, xref(0x8, XrefKind::Jump));
+        xrefs.insert(0x10, xref(0x4, XrefKind::Call));
+        xrefs.insert(0x20, xref(0x8, XrefKind::Data));
+        assert_eq!(
+            xrefs.get(0x10),
+            &[xref(0x4, XrefKind::Call), xref(0x8, XrefKind::Jump)]
+        );
+        assert_eq!(xrefs.len(), 3);
+        xrefs.remove_from(0x6..0x10);
+        assert_eq!(xrefs.get(0x10), &[xref(0x4, XrefKind::Call)]);
+        assert!(xrefs.get(0x20).is_empty());
+        assert_eq!(xrefs.len(), 1);
+    }
+
+    #[test]
+    fn test_find_xrefs() {
+        // 0: call 0xa; 5: jmp 0xa; 7: nop; 8: jmp 0; a: ret
+        let data = vec![
+            0xe8, 0x05, 0x00, 0x00, 0x00, 0xeb, 0x03, 0x90, 0xeb, 0xf6, 0xc3,
+        ];
+        let mut app = App::mockup(data);
+        app.resize_to_size(80, 24);
+        assert_eq!(app.xrefs.len(), 3);
+        let results = app.find_xrefs("0xa");
+        assert_eq!(
+            results
+                .iter()
+                .map(|(xref, text)| (xref.from_file_address, xref.kind, text.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                (0, XrefKind::Call, "call 0xa"),
+                (5, XrefKind::Jump, "jmp 0xa")
+            ]
+        );
+        app.jump_to(0xa, false);
+        assert_eq!(app.find_xrefs("").len(), 2);
+
+        // patching the jump removes its reference
+        app.jump_to(5, false);
+        app.patch_bytes(&[0x90, 0x90], true);
+        assert_eq!(app.find_xrefs("0xa").len(), 1);
+        assert_eq!(app.xrefs.get(0).len(), 1);
+        assert!(app.find_xrefs("0x7").is_empty());
+    }
+}
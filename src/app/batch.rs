@@ -0,0 +1,483 @@
+use std::io::Read;
+
+use termbg::Theme;
+
+use crate::{
+    args::{Args, Command},
+    get_app_context,
+};
+
+use super::{
+    data::Data,
+    files::filesystem::FileSystem,
+    log::{logger::Logger, NotificationLevel},
+    plugins::script::run_script,
+    settings::Settings,
+    structs::struct_types::StructTypes,
+    App,
+};
+
+/// What a line of a patch script writes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PatchCommand {
+    /// Assembled at the virtual address of the target.
+    Assembly(String),
+    Hex(Vec<u8>),
+    Text(Vec<u8>),
+}
+
+/// A line of a patch script, written like `<command> <target>: <payload>` where
+/// the command is `asm`, `hex` or `text` and the target is a file address (`0x1000`),
+/// a virtual address (`v0x401000`) or a symbol.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PatchLine {
+    pub line: usize,
+    pub target: String,
+    pub command: PatchCommand,
+}
+
+/// Parses a patch script, the empty lines and the ones starting with `#` are skipped.
+pub fn parse_patch_script(source: &str) -> Result<Vec<PatchLine>, String> {
+    let mut ret = Vec::new();
+    for (i, line) in source.lines().enumerate() {
+        let line_number = i + 1;
+        let trimmed = line.trim_start();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        let error =
+            |e: String| t!("errors.patch_script_line", line = line_number, e = e).to_string();
+        let (command, rest) = trimmed
+            .split_once(char::is_whitespace)
+            .unwrap_or((trimmed, ""));
+        let Some((target, payload)) = rest.split_once(':') else {
+            return Err(error(t!("errors.patch_missing_payload").to_string()));
+        };
+        let command = match command {
+            "asm" => PatchCommand::Assembly(payload.trim().to_string()),
+            "hex" => PatchCommand::Hex(parse_hex(payload).map_err(error)?),
+            // only the space after the colon is removed, the text may start with spaces
+            "text" => PatchCommand::Text(
+                unescape(payload.strip_prefix(' ').unwrap_or(payload)).map_err(error)?,
+            ),
+            _ => {
+                return Err(error(
+                    t!("errors.unknown_patch_command", command = command).to_string(),
+                ))
+            }
+        };
+        ret.push(PatchLine {
+            line: line_number,
+            target: target.trim().to_string(),
+            command,
+        });
+    }
+    Ok(ret)
+}
+
+fn parse_hex(payload: &str) -> Result<Vec<u8>, String> {
+    let digits = payload
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>();
+    if digits.is_empty() || !digits.is_ascii() || digits.len() % 2 != 0 {
+        return Err(t!("errors.invalid_hex", hex = payload.trim()).to_string());
+    }
+    (0..digits.len() / 2)
+        .map(|i| {
+            u8::from_str_radix(&digits[i * 2..i * 2 + 2], 16)
+                .map_err(|_| t!("errors.invalid_hex", hex = payload.trim()).to_string())
+        })
+        .collect()
+}
+
+/// Encodes the text as UTF-8, expanding `\n`, `\r`, `\t`, `\0`, `\\` and `\xNN`.
+fn unescape(text: &str) -> Result<Vec<u8>, String> {
+    let mut ret = Vec::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut buffer = [0; 4];
+            ret.extend_from_slice(c.encode_utf8(&mut buffer).as_bytes());
+            continue;
+        }
+        let byte = match chars.next() {
+            Some('n') => b'\n',
+            Some('r') => b'\r',
+            Some('t') => b'\t',
+            Some('0') => 0,
+            Some('\\') => b'\\',
+            Some('x') => {
+                let digits = chars.by_ref().take(2).collect::<String>();
+                u8::from_str_radix(&digits, 16)
+                    .ok()
+                    .filter(|_| digits.len() == 2)
+                    .ok_or_else(|| {
+                        t!("errors.invalid_escape", escape = format!("\\x{digits}")).to_string()
+                    })?
+            }
+            other => {
+                return Err(t!(
+                    "errors.invalid_escape",
+                    escape = format!("\\{}", other.map(String::from).unwrap_or_default())
+                )
+                .to_string())
+            }
+        };
+        ret.push(byte);
+    }
+    Ok(ret)
+}
+
+impl App {
+    /// Opens the file without a terminal, the header is parsed but the file is not
+    /// disassembled and the plugins are not loaded.
+    pub fn new_headless(args: &Args, path: &str) -> Result<Self, String> {
+        let mut logger = Logger::default();
+        let settings = match Settings::load_or_create(args.config.as_deref(), Theme::Dark) {
+            Ok(settings) => settings,
+            Err(e) => {
+                logger.log(NotificationLevel::Error, t!("errors.load_settings", e = e));
+                Settings::default()
+            }
+        };
+        settings.app.locale.apply();
+        logger.change_limit(settings.app.log_limit);
+        logger.change_verbosity(settings.app.log_level);
+
+        let filesystem = if let Some(ssh) = &args.ssh {
+            FileSystem::new_remote(path, ssh, &args.connection_options())
+                .map_err(|e| t!("errors.connect_ssh", ssh = ssh, e = e))?
+        } else {
+            FileSystem::new_local(path).map_err(|e| e.to_string())?
+        };
+        if !filesystem.is_file(filesystem.pwd()) {
+            return Err(t!("errors.not_a_file", path = path).to_string());
+        }
+
+        let mut app = App {
+            filesystem,
+            settings,
+            logger,
+            ..Default::default()
+        };
+        app.struct_types = match StructTypes::load(args.types.as_deref(), &mut app.logger) {
+            Ok(types) => types,
+            Err(e) => {
+                app.log(NotificationLevel::Error, t!("errors.load_types", e = e));
+                StructTypes::new()
+            }
+        };
+        app.data = Data::with_storage(
+            app.open_storage().map_err(|e| e.to_string())?,
+            app.settings.app.history_limit,
+        );
+        app.raw_binary = args.raw_binary()?;
+        app.header = app.parse_header();
+        app.load_comments(None);
+        Ok(app)
+    }
+
+    /// Returns the file address and the virtual address of a patch target.
+    fn patch_target(&self, target: &str) -> Result<(usize, u64), String> {
+        if let Some(address) = target.strip_prefix("0x") {
+            let address = u64::from_str_radix(address, 16)
+                .map_err(|_| t!("errors.invalid_address", address = target).to_string())?;
+            let virtual_address = self
+                .header
+                .physical_to_virtual_address(address)
+                .unwrap_or(address);
+            return Ok((address as usize, virtual_address));
+        }
+        if target.is_empty() {
+            return Err(t!("errors.invalid_address", address = target).to_string());
+        }
+        let virtual_address = self.parse_xref_target(target)?;
+        let address = self
+            .virtual_to_file_address(virtual_address)
+            .ok_or_else(|| {
+                t!("errors.virtual_address_not_found", address = virtual_address : {:#X})
+                    .to_string()
+            })?;
+        Ok((address as usize, virtual_address))
+    }
+
+    fn apply_patch_line(&mut self, line: &PatchLine) -> Result<(), String> {
+        let (address, virtual_address) = self.patch_target(&line.target)?;
+        let bytes = match &line.command {
+            PatchCommand::Assembly(assembly) => {
+                self.bytes_from_assembly(assembly, virtual_address)?
+            }
+            PatchCommand::Hex(bytes) | PatchCommand::Text(bytes) => bytes.clone(),
+        };
+        if bytes.is_empty() {
+            return Ok(());
+        }
+        // a huge address would overflow the end of the patch
+        if !matches!(address.checked_add(bytes.len()), Some(end) if end <= self.data.len()) {
+            let size = bytes.len();
+            return Err(
+                t!("errors.patch_out_of_bounds", size = size, address = address : {:#X})
+                    .to_string(),
+            );
+        }
+        self.load_range(address..address + bytes.len());
+        self.data.push_change(address, bytes);
+        Ok(())
+    }
+
+    /// Applies every line of the script, stopping at the first one that fails.
+    pub fn apply_patch_script(&mut self, source: &str) -> Result<(), String> {
+        for line in parse_patch_script(source)? {
+            self.apply_patch_line(&line)
+                .map_err(|e| t!("errors.patch_script_line", line = line.line, e = e).to_string())?;
+        }
+        Ok(())
+    }
+
+    /// Runs a Lua script with the same context given to the plugins.
+    pub fn run_lua_script(&mut self, source: &str, name: &str) -> Result<(), String> {
+        let mut app_context = get_app_context!(self);
+        run_script(source, name, &mut app_context)
+            .map_err(|e| t!("errors.run_script", e = e).to_string())
+    }
+
+    /// Prints the log to stderr, used when there is no UI to show it.
+    pub fn print_log(&self) {
+        for line in self.logger.iter() {
+            eprintln!("{} {}", line.level, line.message);
+        }
+    }
+}
+
+fn read_script(script: &std::path::Path) -> Result<String, String> {
+    let mut source = String::new();
+    let result = if script.as_os_str() == "-" {
+        std::io::stdin().read_to_string(&mut source).map(|_| ())
+    } else {
+        std::fs::read_to_string(script).map(|text| source = text)
+    };
+    result.map_err(|e| {
+        t!("errors.read_script", path = script.to_string_lossy(), e = e).to_string()
+    })?;
+    Ok(source)
+}
+
+/// Saves the file, to output if given.
+fn save(app: &mut App, output: Option<&str>) -> Result<(), String> {
+    match output {
+        Some(output) => app.save_file_as(output),
+        None => app.save_file(),
+    }
+    .map_err(|e| t!("errors.save_file", e = e).to_string())
+}
+
+/// Runs the subcommand without the UI, the log is printed to stderr.
+pub fn run_headless(args: Args) -> Result<(), String> {
+    match &args.command {
+        Some(Command::Patch {
+            file,
+            script,
+            output,
+        }) => {
+            let source = read_script(script)?;
+            let mut app = App::new_headless(&args, file)?;
+            let result = app
+                .apply_patch_script(&source)
+                .and_then(|_| save(&mut app, output.as_deref()));
+            app.print_log();
+            result
+        }
+        Some(Command::Script {
+            file,
+            script,
+            output,
+        }) => {
+            let source = read_script(script)?;
+            let mut app = App::new_headless(&args, file)?;
+            // the script can read any byte and expects the current instruction to be available
+            app.load_range(0..app.data.len());
+            app.disassemble();
+            let result = app
+                .run_lua_script(&source, &script.to_string_lossy())
+                .and_then(|_| {
+                    // the file is written only if the script changed it
+                    if output.is_some() || app.data.dirty() {
+                        save(&mut app, output.as_deref())
+                    } else {
+                        Ok(())
+                    }
+                });
+            app.print_log();
+            result
+        }
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::app::storage::{Storage, PAGE_SIZE};
+
+    use super::*;
+
+    #[test]
+    fn test_parse_patch_script() {
+        let lines = parse_patch_script(
+            "# a comment\n\
+             \n\
+             hex 0x10: 90 90\n\
+             text main:  hi\\x21\\0\n\
+             asm v0x401000: nop; ret",
+        )
+        .unwrap();
+        assert_eq!(
+            lines,
+            vec![
+                PatchLine {
+                    line: 3,
+                    target: "0x10".to_string(),
+                    command: PatchCommand::Hex(vec![0x90, 0x90])
+                },
+                PatchLine {
+                    line: 4,
+                    target: "main".to_string(),
+                    command: PatchCommand::Text(b" hi!\0".to_vec())
+                },
+                PatchLine {
+                    line: 5,
+                    target: "v0x401000".to_string(),
+                    command: PatchCommand::Assembly("nop; ret".to_string())
+                },
+            ]
+        );
+        assert!(parse_patch_script("hex 0x10: 9").is_err());
+        assert!(parse_patch_script("hex 0x10 90").is_err());
+        assert!(parse_patch_script("poke 0x10: 90").is_err());
+        assert!(parse_patch_script("text 0x10: \\q").is_err());
+    }
+
+    #[test]
+    fn test_apply_patch_script() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        std::io::Write::write_all(&mut file, &[0; 0x10]).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let config = dir.path().join("settings.json");
+        let args = <Args as clap::Parser>::parse_from([
+            "hex-patch".as_ref(),
+            "--config".as_ref(),
+            config.as_os_str(),
+            "--types".as_ref(),
+            dir.path().as_os_str(),
+        ]);
+        let path = file.path().to_string_lossy().to_string();
+        let mut app = App::new_headless(&args, &path).unwrap();
+        app.apply_patch_script("hex 0x2: AA BB\ntext 0x8: ok")
+            .unwrap();
+        assert_eq!(&app.data.bytes()[..0xa], b"\0\0\xAA\xBB\0\0\0\0ok");
+
+        assert!(app.apply_patch_script("hex 0xf: AA BB").is_err());
+        assert!(app.apply_patch_script("hex missing: AA").is_err());
+        assert!(app
+            .apply_patch_script("hex 0xFFFFFFFFFFFFFFFF: AA")
+            .is_err());
+        assert_eq!(app.data.bytes()[0xf], 0);
+
+        app.save_file().unwrap();
+        assert_eq!(&std::fs::read(file.path()).unwrap()[2..4], &[0xaa, 0xbb]);
+    }
+
+    #[test]
+    fn test_run_lua_script() {
+        let mut app = App::mockup(vec![0; 0x10]);
+        app.run_lua_script(
+            "function run(context)\n\
+                 if context.data:get(0) ~= 0 then error('not zero') end\n\
+                 context.data:set(1, 0x41)\n\
+                 context.log(1, 'patched')\n\
+             end",
+            "test.lua",
+        )
+        .unwrap();
+        assert_eq!(app.data.bytes()[1], 0x41);
+        assert!(app.data.dirty());
+        assert!(app.logger.iter().any(|line| line.message == "patched"));
+
+        assert!(app
+            .run_lua_script(
+                "function run(context) error('invalid file') end",
+                "test.lua"
+            )
+            .is_err());
+        assert!(app.run_lua_script("x = 1", "test.lua").is_err());
+    }
+
+    #[test]
+    fn test_run_headless_script() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("file.bin");
+        let script = dir.path().join("script.lua");
+        let config = dir.path().join("settings.json");
+        let run = |source: &str| {
+            std::fs::write(&script, source).unwrap();
+            run_headless(<Args as clap::Parser>::parse_from([
+                "hex-patch".as_ref(),
+                "--config".as_ref(),
+                config.as_os_str(),
+                "--types".as_ref(),
+                dir.path().as_os_str(),
+                "script".as_ref(),
+                file.as_os_str(),
+                script.as_os_str(),
+            ]))
+        };
+        std::fs::write(&file, [0; 4]).unwrap();
+        let modified = std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1);
+        std::fs::File::options()
+            .write(true)
+            .open(&file)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+
+        // a script that only reads the file does not write it
+        run("function run(context) context.data:get(0) end").unwrap();
+        let metadata = std::fs::metadata(&file).unwrap();
+        assert_eq!(metadata.modified().unwrap(), modified);
+        assert_eq!(std::fs::read(&file).unwrap(), [0; 4]);
+
+        run("function run(context) context.data:set(1, 0x41) end").unwrap();
+        assert_eq!(std::fs::read(&file).unwrap(), [0, 0x41, 0, 0]);
+
+        // a script that fails after changing the file does not save it
+        assert!(run("function run(context)\n\
+                 context.data:set(2, 0x42)\n\
+                 error('invalid file')\n\
+             end")
+        .is_err());
+        assert_eq!(std::fs::read(&file).unwrap(), [0, 0x41, 0, 0]);
+    }
+
+    #[test]
+    #[allow(clippy::single_range_in_vec_init)]
+    fn test_run_lua_script_fetches_pages() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        let bytes = (0..PAGE_SIZE * 3)
+            .map(|i| (i / PAGE_SIZE) as u8)
+            .collect::<Vec<u8>>();
+        std::io::Write::write_all(&mut file, &bytes).unwrap();
+        let mut app = App::mockup(Vec::new());
+        app.filesystem = FileSystem::new_local(&file.path().to_string_lossy()).unwrap();
+        app.data = Data::with_storage(Storage::paged(bytes.len()).unwrap(), 0);
+        app.run_lua_script(
+            "function run(context)\n\
+                 if context.data:get(0x1001) ~= 1 then error('not fetched') end\n\
+                 context.data:set(0x2000, context.data:get(0x2001) + 1)\n\
+             end",
+            "test.lua",
+        )
+        .unwrap();
+        assert_eq!(app.data.bytes()[0x2000], 3);
+        assert_eq!(app.data.missing_ranges(0..bytes.len()), [0..PAGE_SIZE]);
+    }
+}
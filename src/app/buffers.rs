@@ -0,0 +1,346 @@
+use std::{error::Error, mem, ops::Range};
+
+use ratatui::{backend::Backend, Terminal};
+
+use crate::headers::{isa_mode::IsaModes, raw_binary::RawBinary, Header};
+
+use super::{
+    asm::{
+        assembly_line::AssemblyLine, assembly_offsets::AssemblyOffsets, code_map::CodeMap,
+        labels::Labels, xrefs::Xrefs,
+    },
+    comments::Comments,
+    data::Data,
+    diff::Diff,
+    firmware::firmware_image::FirmwareImage,
+    info_mode::InfoMode,
+    log::NotificationLevel,
+    popup::{popup_state::PopupState, simple_choice::SimpleChoice},
+    structs::overlay::StructOverlay,
+    user_symbols::UserSymbols,
+    App,
+};
+
+/// The state of an open file. The file in the foreground keeps its state in the
+/// fields of [App], its slot in the list of buffers is left empty until it is
+/// moved to the background.
+pub struct Buffer {
+    pub(super) path: String,
+    pub(super) header: Header,
+    pub(super) data: Data,
+    pub(super) comments: Comments,
+    pub(super) assembly_offsets: AssemblyOffsets,
+    pub(super) xrefs: Xrefs,
+    pub(super) labels: Labels,
+    pub(super) user_symbols: UserSymbols,
+    pub(super) isa_modes: IsaModes,
+    pub(super) raw_binary: Option<RawBinary>,
+    pub(super) firmware: Option<FirmwareImage>,
+    pub(super) struct_overlay: Option<StructOverlay>,
+    pub(super) diff: Option<Diff>,
+    pub(super) assembly_instructions: Vec<AssemblyLine>,
+    pub(super) assembly_window: Range<usize>,
+    pub(super) code_map: Option<CodeMap>,
+    pub(super) search_results: Vec<Range<usize>>,
+    pub(super) info_mode: InfoMode,
+    pub(super) scroll: usize,
+    pub(super) cursor: (u16, u16),
+    pub(super) selection_anchor: Option<usize>,
+    pub(super) blocks_per_row: usize,
+}
+
+impl Default for Buffer {
+    fn default() -> Self {
+        Buffer {
+            path: String::new(),
+            header: Header::None,
+            data: Data::default(),
+            comments: Comments::default(),
+            assembly_offsets: AssemblyOffsets::new(),
+            xrefs: Xrefs::new(),
+            labels: Labels::new(),
+            user_symbols: UserSymbols::new(),
+            isa_modes: IsaModes::new(),
+            raw_binary: None,
+            firmware: None,
+            struct_overlay: None,
+            diff: None,
+            assembly_instructions: Vec::new(),
+            assembly_window: 0..0,
+            code_map: None,
+            search_results: Vec::new(),
+            info_mode: InfoMode::Text,
+            scroll: 0,
+            cursor: (0, 0),
+            selection_anchor: None,
+            blocks_per_row: 1,
+        }
+    }
+}
+
+impl App {
+    /// Exchanges the state of the file in the foreground with the one in buffer.
+    fn swap_buffer(&mut self, buffer: &mut Buffer) {
+        let path = self.filesystem.pwd().to_string();
+        self.filesystem.cd(&buffer.path);
+        buffer.path = path;
+        mem::swap(&mut self.header, &mut buffer.header);
+        mem::swap(&mut self.data, &mut buffer.data);
+        mem::swap(&mut self.comments, &mut buffer.comments);
+        mem::swap(&mut self.assembly_offsets, &mut buffer.assembly_offsets);
+        mem::swap(&mut self.xrefs, &mut buffer.xrefs);
+        mem::swap(&mut self.labels, &mut buffer.labels);
+        mem::swap(&mut self.user_symbols, &mut buffer.user_symbols);
+        mem::swap(&mut self.isa_modes, &mut buffer.isa_modes);
+        mem::swap(&mut self.raw_binary, &mut buffer.raw_binary);
+        mem::swap(&mut self.firmware, &mut buffer.firmware);
+        mem::swap(&mut self.struct_overlay, &mut buffer.struct_overlay);
+        mem::swap(&mut self.diff, &mut buffer.diff);
+        mem::swap(
+            &mut self.assembly_instructions,
+            &mut buffer.assembly_instructions,
+        );
+        mem::swap(&mut self.assembly_window, &mut buffer.assembly_window);
+        mem::swap(&mut self.code_map, &mut buffer.code_map);
+        mem::swap(&mut self.search_results, &mut buffer.search_results);
+        mem::swap(&mut self.info_mode, &mut buffer.info_mode);
+        mem::swap(&mut self.scroll, &mut buffer.scroll);
+        mem::swap(&mut self.cursor, &mut buffer.cursor);
+        mem::swap(&mut self.selection_anchor, &mut buffer.selection_anchor);
+        mem::swap(&mut self.blocks_per_row, &mut buffer.blocks_per_row);
+    }
+
+    fn swap_buffer_at(&mut self, index: usize) {
+        let mut buffer = mem::take(&mut self.buffers[index]);
+        self.swap_buffer(&mut buffer);
+        self.buffers[index] = buffer;
+    }
+
+    /// Returns the path and whether the data was modified for each open file.
+    pub(in crate::app) fn buffer_list(&self) -> Vec<(&str, bool)> {
+        self.buffers
+            .iter()
+            .enumerate()
+            .map(|(i, buffer)| {
+                if i == self.active_buffer {
+                    (self.filesystem.pwd(), self.data.dirty())
+                } else {
+                    (buffer.path.as_str(), buffer.data.dirty())
+                }
+            })
+            .collect()
+    }
+
+    pub(in crate::app) fn buffer_index(&self, path: &str) -> Option<usize> {
+        self.buffer_list()
+            .iter()
+            .position(|(buffer_path, _)| *buffer_path == path)
+    }
+
+    pub(in crate::app) fn any_buffer_dirty(&self) -> bool {
+        // the slot of the buffer in the foreground is empty and never dirty
+        self.data.dirty() || self.buffers.iter().any(|buffer| buffer.data.dirty())
+    }
+
+    /// Opens a file in a new buffer, the current one is moved to the background.
+    /// If the file is already open its buffer is brought to the foreground,
+    /// unless it is the current one, in that case it is read again.
+    pub(in crate::app) fn open_file<B: Backend>(
+        &mut self,
+        path: &str,
+        terminal: &mut Terminal<B>,
+    ) -> Result<(), Box<dyn Error>> {
+        let previous = self.active_buffer;
+        let new_buffer = match self.buffer_index(path) {
+            Some(index) if index != self.active_buffer => {
+                self.select_buffer(index);
+                return Ok(());
+            }
+            Some(_) => false,
+            None => {
+                if !self.buffers.is_empty() {
+                    self.swap_buffer_at(self.active_buffer);
+                }
+                self.buffers.push(Buffer::default());
+                self.active_buffer = self.buffers.len() - 1;
+                true
+            }
+        };
+        let ret = self.load_file(path, terminal);
+        if ret.is_err() && new_buffer {
+            self.buffers.pop();
+            if !self.buffers.is_empty() {
+                self.active_buffer = previous;
+                self.swap_buffer_at(previous);
+                self.buffers[previous] = Buffer::default();
+                self.resize_to_size(self.screen_size.0, self.screen_size.1);
+            }
+        }
+        ret
+    }
+
+    /// Brings the buffer at index to the foreground.
+    pub(in crate::app) fn switch_buffer(&mut self, index: usize) {
+        if index == self.active_buffer || index >= self.buffers.len() {
+            return;
+        }
+        self.swap_buffer_at(self.active_buffer);
+        self.swap_buffer_at(index);
+        self.active_buffer = index;
+        // the screen may have been resized while the buffer was in the background
+        self.resize_to_size(self.screen_size.0, self.screen_size.1);
+    }
+
+    pub(in crate::app) fn select_buffer(&mut self, index: usize) {
+        self.switch_buffer(index);
+        let path = self.filesystem.pwd().to_string();
+        self.log(
+            NotificationLevel::Info,
+            t!(
+                "app.messages.buffer_switched",
+                index = self.active_buffer + 1,
+                count = self.buffers.len(),
+                path = path
+            ),
+        );
+    }
+
+    /// Switches to the next buffer if direction is positive, to the previous one otherwise.
+    pub(in crate::app) fn switch_to_near_buffer(&mut self, direction: isize) {
+        if self.buffers.len() < 2 {
+            self.log(
+                NotificationLevel::Warning,
+                t!("app.messages.no_other_buffer"),
+            );
+            return;
+        }
+        let count = self.buffers.len() as isize;
+        let index = (self.active_buffer as isize + direction).rem_euclid(count);
+        self.select_buffer(index as usize);
+    }
+
+    /// Closes the buffer in the foreground, discarding its changes. If it was the
+    /// last one the open popup is shown.
+    pub(in crate::app) fn close_buffer(&mut self) -> Result<(), Box<dyn Error>> {
+        if self.buffers.is_empty() {
+            return Ok(());
+        }
+        let path = self.filesystem.pwd().to_string();
+        let index = self.active_buffer;
+        if self.buffers.len() > 1 {
+            self.switch_buffer(if index + 1 < self.buffers.len() {
+                index + 1
+            } else {
+                index - 1
+            });
+            self.buffers.remove(index);
+            if self.active_buffer > index {
+                self.active_buffer -= 1;
+            }
+        } else {
+            let dir = self.get_current_dir();
+            self.buffers.clear();
+            self.active_buffer = 0;
+            self.swap_buffer(&mut Buffer::default());
+            self.filesystem.cd(&dir);
+            Self::open_dir(&mut self.popup, &dir, &mut self.filesystem)?;
+        }
+        self.log(
+            NotificationLevel::Info,
+            t!("app.messages.buffer_closed", path = path),
+        );
+        Ok(())
+    }
+
+    /// Saves every modified buffer, the one in the foreground stays the same.
+    pub(in crate::app) fn save_all_buffers(&mut self) -> Result<(), Box<dyn Error>> {
+        let active = self.active_buffer;
+        let mut ret = Ok(());
+        for index in 0..self.buffers.len() {
+            if index != active && self.buffers[index].data.dirty() {
+                self.switch_buffer(index);
+                ret = self.save_file();
+                if ret.is_err() {
+                    break;
+                }
+            }
+        }
+        self.switch_buffer(active);
+        ret?;
+        if self.data.dirty() {
+            self.save_file()?;
+        }
+        Ok(())
+    }
+
+    pub(in crate::app) fn request_close_buffer(&mut self) -> Result<(), Box<dyn Error>> {
+        if self.data.dirty() {
+            self.popup = Some(PopupState::CloseDirtySave(SimpleChoice::Cancel));
+            Ok(())
+        } else {
+            self.close_buffer()
+        }
+    }
+
+    pub(in crate::app) fn request_popup_buffers(&mut self) {
+        self.popup = Some(PopupState::Buffers(self.active_buffer));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ratatui::{backend::TestBackend, Terminal};
+
+    use super::*;
+
+    #[test]
+    fn test_buffers() {
+        let dir = tempfile::tempdir().unwrap();
+        let first = dir.path().join("first.bin").to_string_lossy().to_string();
+        let second = dir.path().join("second.bin").to_string_lossy().to_string();
+        std::fs::write(&first, [1; 0x40]).unwrap();
+        std::fs::write(&second, [2; 0x40]).unwrap();
+        let mut terminal = Terminal::new(TestBackend::new(80, 25)).unwrap();
+        let mut app = App::default();
+        app.open_file(&first, &mut terminal).unwrap();
+        app.jump_to(0x10, false);
+        app.data.push_change(0x10, vec![0xAA]);
+
+        app.open_file(&second, &mut terminal).unwrap();
+        assert_eq!(app.buffers.len(), 2);
+        assert_eq!(app.active_buffer, 1);
+        assert_eq!(app.data.bytes()[0x10], 2);
+        assert_eq!(app.get_cursor_position().global_byte_index, 0);
+        assert!(!app.data.dirty());
+        assert!(app.any_buffer_dirty());
+
+        // opening a file that is already open switches to its buffer
+        app.open_file(&first, &mut terminal).unwrap();
+        assert_eq!(app.buffers.len(), 2);
+        assert_eq!(app.active_buffer, 0);
+        assert_eq!(app.filesystem.pwd(), first);
+        assert_eq!(app.get_cursor_position().global_byte_index, 0x10);
+        app.undo();
+        assert_eq!(app.data.bytes()[0x10], 1);
+        app.redo();
+
+        // the clipboard is shared between buffers
+        app.selection_anchor = Some(0x10);
+        app.copy_selection();
+        app.switch_to_near_buffer(1);
+        assert_eq!(app.filesystem.pwd(), second);
+        app.jump_to(0x20, false);
+        app.paste();
+        assert_eq!(app.data.bytes()[0x20], 0xAA);
+
+        app.close_buffer().unwrap();
+        assert_eq!(app.buffers.len(), 1);
+        assert_eq!(app.active_buffer, 0);
+        assert_eq!(app.filesystem.pwd(), first);
+        assert_eq!(app.data.bytes()[0x10], 0xAA);
+
+        app.close_buffer().unwrap();
+        assert!(app.buffers.is_empty());
+        assert!(matches!(app.popup, Some(PopupState::Open { .. })));
+    }
+}
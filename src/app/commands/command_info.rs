@@ -36,8 +36,42 @@ impl CommandInfo {
             CommandInfo::new("patch", t!("app.commands.patch")),
             CommandInfo::new("jump", t!("app.commands.jump")),
             CommandInfo::new("view", t!("app.commands.view")),
+            CommandInfo::new("recursive", t!("app.commands.recursive")),
+            CommandInfo::new("isa", t!("app.commands.isa")),
+            CommandInfo::new("raw", t!("app.commands.raw")),
             CommandInfo::new("undo", t!("app.commands.undo")),
             CommandInfo::new("redo", t!("app.commands.redo")),
+            CommandInfo::new("checkpoint", t!("app.commands.checkpoint")),
+            CommandInfo::new("undotree", t!("app.commands.undotree")),
+            CommandInfo::new("select", t!("app.commands.select")),
+            CommandInfo::new("copy", t!("app.commands.copy")),
+            CommandInfo::new("paste", t!("app.commands.paste")),
+            CommandInfo::new("fill", t!("app.commands.fill")),
+            CommandInfo::new("delete", t!("app.commands.delete")),
+            CommandInfo::new("export", t!("app.commands.export")),
+            CommandInfo::new("pexport", t!("app.commands.pexport")),
+            CommandInfo::new("pimport", t!("app.commands.pimport")),
+            CommandInfo::new("fpat", t!("app.commands.fpat")),
+            CommandInfo::new("fasm", t!("app.commands.fasm")),
+            CommandInfo::new("xrefs", t!("app.commands.xrefs")),
+            CommandInfo::new("cfg", t!("app.commands.cfg")),
+            CommandInfo::new("rename", t!("app.commands.rename")),
+            CommandInfo::new("symbol", t!("app.commands.symbol")),
+            CommandInfo::new("symdel", t!("app.commands.symdel")),
+            CommandInfo::new("inspector", t!("app.commands.inspector")),
+            CommandInfo::new("struct", t!("app.commands.struct")),
+            CommandInfo::new("unstruct", t!("app.commands.unstruct")),
+            CommandInfo::new("field", t!("app.commands.field")),
+            CommandInfo::new("nmatch", t!("app.commands.nmatch")),
+            CommandInfo::new("pmatch", t!("app.commands.pmatch")),
+            CommandInfo::new("diff", t!("app.commands.diff")),
+            CommandInfo::new("nodiff", t!("app.commands.nodiff")),
+            CommandInfo::new("ndiff", t!("app.commands.ndiff")),
+            CommandInfo::new("pdiff", t!("app.commands.pdiff")),
+            CommandInfo::new("buffers", t!("app.commands.buffers")),
+            CommandInfo::new("bnext", t!("app.commands.bnext")),
+            CommandInfo::new("bprev", t!("app.commands.bprev")),
+            CommandInfo::new("bclose", t!("app.commands.bclose")),
         ]
     }
 
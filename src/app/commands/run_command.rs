@@ -88,12 +88,114 @@ impl App {
             "view" => {
                 self.request_view_change();
             }
+            "recursive" => {
+                self.toggle_recursive_disassembly();
+            }
+            "isa" => {
+                self.request_popup_isa_mode();
+            }
+            "raw" => {
+                self.request_popup_raw_binary();
+            }
             "undo" => {
                 self.undo();
             }
             "redo" => {
                 self.redo();
             }
+            "checkpoint" => {
+                self.request_popup_checkpoint();
+            }
+            "undotree" => {
+                self.request_popup_undo_tree();
+            }
+            "select" => {
+                self.toggle_selection();
+            }
+            "copy" => {
+                self.copy_selection();
+            }
+            "paste" => {
+                self.paste();
+            }
+            "fill" => {
+                self.request_popup_fill();
+            }
+            "delete" => {
+                self.delete_selection();
+            }
+            "export" => {
+                self.request_popup_export();
+            }
+            "pexport" => {
+                self.request_popup_export_patch();
+            }
+            "pimport" => {
+                self.request_popup_import_patch();
+            }
+            "fpat" => {
+                self.request_popup_find_pattern();
+            }
+            "fasm" => {
+                self.request_popup_find_assembly();
+            }
+            "xrefs" => {
+                self.request_popup_xrefs();
+            }
+            "cfg" => {
+                self.request_popup_cfg();
+            }
+            "rename" => {
+                self.request_popup_rename_label();
+            }
+            "symbol" => {
+                self.request_popup_edit_symbol();
+            }
+            "symdel" => {
+                self.delete_symbol();
+            }
+            "inspector" => {
+                self.toggle_inspector();
+            }
+            "struct" => {
+                self.request_popup_apply_struct();
+            }
+            "unstruct" => {
+                self.remove_struct();
+            }
+            "field" => {
+                self.request_popup_edit_field();
+            }
+            "nmatch" => {
+                self.jump_to_near_search_result(1);
+            }
+            "pmatch" => {
+                self.jump_to_near_search_result(-1);
+            }
+            "diff" => {
+                self.request_popup_diff();
+            }
+            "nodiff" => {
+                self.close_diff();
+            }
+            "ndiff" => {
+                self.jump_to_near_difference(1);
+            }
+            "pdiff" => {
+                self.jump_to_near_difference(-1);
+            }
+            "buffers" => {
+                self.request_popup_buffers();
+            }
+            "bnext" => {
+                self.switch_to_near_buffer(1);
+            }
+            "bprev" => {
+                self.switch_to_near_buffer(-1);
+            }
+            "bclose" => {
+                self.request_close_buffer()?;
+            }
             any_other_command => {
                 let mut app_context = get_app_context!(self);
                 self.plugin_manager
@@ -110,9 +212,7 @@ impl App {
                     NotificationLevel::Debug,
                     t!("app.messages.saving_and_quitting"),
                 );
-                if self.data.dirty() {
-                    self.save_file()?;
-                }
+                self.save_all_buffers()?;
                 self.needs_to_exit = true;
             }
             Some(false) => {
@@ -124,7 +224,7 @@ impl App {
             }
             None => {
                 self.log(NotificationLevel::Debug, t!("app.messages.quitting"));
-                if self.data.dirty() {
+                if self.any_buffer_dirty() {
                     self.log(
                         NotificationLevel::Warning,
                         t!("app.messages.unsaved_changes"),
@@ -138,7 +238,7 @@ impl App {
     }
 
     pub(in crate::app) fn request_quit(&mut self) {
-        if self.data.dirty() {
+        if self.any_buffer_dirty() {
             self.popup = Some(PopupState::QuitDirtySave(SimpleChoice::Cancel));
         } else {
             self.needs_to_exit = true;
@@ -152,7 +252,7 @@ impl App {
     }
 
     pub(in crate::app) fn request_save_and_quit(&mut self) {
-        if self.data.dirty() {
+        if self.any_buffer_dirty() {
             self.popup = Some(PopupState::SaveAndQuit(BinaryChoice::No));
         } else {
             self.needs_to_exit = true;
@@ -264,6 +364,10 @@ impl App {
 
     pub(in crate::app) fn undo(&mut self) {
         if let Some(change) = self.data.undo().cloned() {
+            if change.is_resize() {
+                self.data_resize_undone(&change);
+                return;
+            }
             let instruction_offset = self.get_instruction_at(change.offset()).file_address();
             let instruction_offset = change
                 .offset()
@@ -280,6 +384,10 @@ impl App {
 
     pub(in crate::app) fn redo(&mut self) {
         if let Some(change) = self.data.redo().cloned() {
+            if change.is_resize() {
+                self.data_resized(change.offset(), change.old_len(), change.new_len());
+                return;
+            }
             let instruction_offset = self.get_instruction_at(change.offset()).file_address();
             let instruction_offset = change
                 .offset()
@@ -2,9 +2,15 @@ use std::collections::{hash_map::Iter, HashMap};
 
 use serde::{Deserialize, Serialize};
 
-use super::{log::NotificationLevel, App};
+use crate::headers::{isa_mode::IsaModes, raw_binary::RawBinary};
+
+use super::{
+    asm::labels::Labels, history::saved_history::SavedHistory, log::NotificationLevel,
+    user_symbols::UserSymbols, App,
+};
 
 #[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
 pub struct Comments {
     comments: HashMap<u64, String>,
     #[serde(skip)]
@@ -57,6 +63,29 @@ impl Comments {
         self.comments.iter().map(|(a, s)| (*a, s.clone())).collect()
     }
 
+    /// Moves the comments after a change that replaced old_len bytes with new_len bytes
+    /// starting from offset. Comments on bytes that were removed are deleted.
+    pub fn rebase(&mut self, offset: u64, old_len: u64, new_len: u64) {
+        if old_len == new_len {
+            return;
+        }
+        let removed_start = offset + new_len.min(old_len);
+        let old_end = offset + old_len;
+        let comments = std::mem::take(&mut self.comments);
+        self.comments = comments
+            .into_iter()
+            .filter(|(address, _)| *address < removed_start || *address >= old_end)
+            .map(|(address, comment)| {
+                if address >= old_end {
+                    (address + new_len - old_len, comment)
+                } else {
+                    (address, comment)
+                }
+            })
+            .collect();
+        self.dirty = true;
+    }
+
     pub fn check_max_address(&mut self, max_address: u64) {
         let mut comments_removed = false;
         self.comments.retain(|address, _| {
@@ -73,6 +102,22 @@ impl Comments {
     }
 }
 
+/// Contents of the .hp-data.json file kept next to the opened file.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct HpData {
+    comments: Comments,
+    #[serde(default)]
+    labels: Labels,
+    #[serde(default)]
+    symbols: UserSymbols,
+    #[serde(default)]
+    isa_modes: IsaModes,
+    #[serde(default)]
+    raw_binary: Option<RawBinary>,
+    #[serde(default)]
+    history: Option<SavedHistory>,
+}
+
 impl App {
     pub(super) fn edit_comment(&mut self, comment: &str) {
         let address = self.get_cursor_position().global_byte_index as u64;
@@ -102,27 +147,118 @@ impl App {
         path.to_string() + ".hp-data.json"
     }
 
+    /// Saves the comments, the renamed labels, the symbols and the instruction set modes
+    /// edited by the user, and the description of a file without a header.
     /// If comments_path is None, it will use the default path calculated by get_comments_path.
     pub(super) fn save_comments(&mut self, comments_path: Option<String>) {
-        if self.comments.is_dirty() {
-            let comments_str = serde_json::to_string_pretty(&self.comments).unwrap();
+        if self.comments.is_dirty()
+            || self.labels.is_dirty()
+            || self.user_symbols.is_dirty()
+            || self.isa_modes.is_dirty()
+        {
             let comments_path = comments_path.unwrap_or(self.get_comments_path());
-            if let Err(e) = self.filesystem.create(&comments_path) {
+            // the saved history must match the file on disk, unsaved changes are left out
+            let history = if self.data.dirty() {
+                self.read_hp_data(&comments_path)
+                    .and_then(|data| data.history)
+            } else {
+                self.saved_history()
+            };
+            let data = HpData {
+                comments: self.comments.clone(),
+                labels: self.labels.clone(),
+                symbols: self.user_symbols.clone(),
+                isa_modes: self.isa_modes.clone(),
+                raw_binary: self.raw_binary.clone(),
+                history,
+            };
+            if self.write_hp_data(&comments_path, &data) {
+                self.log(NotificationLevel::Info, t!("app.messages.comments_saved"));
+                self.comments.reset_dirty();
+                self.labels.reset_dirty();
+                self.user_symbols.reset_dirty();
+                self.isa_modes.reset_dirty();
+            }
+        }
+    }
+
+    /// Saves the undo history in the project file, this must be called after the file is saved.
+    pub(super) fn save_history(&mut self) {
+        let comments_path = self.get_comments_path();
+        let history = self.saved_history();
+        let data = match self.read_hp_data(&comments_path) {
+            Some(data) => data,
+            // no need to create the project file for an empty history
+            None if history.is_none() => return,
+            None => HpData::default(),
+        };
+        if data.history != history {
+            self.write_hp_data(&comments_path, &HpData { history, ..data });
+        }
+    }
+
+    /// Remembers the description of a file without a header in the project file.
+    pub(super) fn save_raw_binary(&mut self, comments_path: &str) {
+        let data = self.read_hp_data(comments_path).unwrap_or_default();
+        if data.raw_binary != self.raw_binary {
+            let raw_binary = self.raw_binary.clone();
+            self.write_hp_data(comments_path, &HpData { raw_binary, ..data });
+        }
+    }
+
+    /// Returns None if there is nothing to undo or redo, or if the file is read on demand
+    /// and its hash cannot be computed.
+    fn saved_history(&self) -> Option<SavedHistory> {
+        let history = self.data.history();
+        if history.is_empty() || !self.data.missing_ranges(0..self.data.len()).is_empty() {
+            None
+        } else {
+            Some(SavedHistory::new(history.clone(), self.data.bytes()))
+        }
+    }
+
+    fn read_hp_data(&self, comments_path: &str) -> Option<HpData> {
+        let comments_data = self.filesystem.read(comments_path).ok()?;
+        serde_json::from_slice::<HpData>(&comments_data).ok()
+    }
+
+    /// Returns false and logs the error if the file could not be written.
+    fn write_hp_data(&mut self, comments_path: &str, data: &HpData) -> bool {
+        let comments_str = serde_json::to_string_pretty(data).unwrap();
+        if let Err(e) = self.filesystem.create(comments_path) {
+            self.log(
+                NotificationLevel::Error,
+                t!("errors.create_comments", e = e),
+            );
+            return false;
+        }
+        if let Err(e) = self
+            .filesystem
+            .write(comments_path, comments_str.as_bytes())
+        {
+            self.log(NotificationLevel::Error, t!("errors.write_comments", e = e));
+            return false;
+        }
+        true
+    }
+
+    /// Restores the undo history saved in the project file if the file did not change since then.
+    fn restore_history(&mut self, history: SavedHistory) {
+        if !self.data.missing_ranges(0..self.data.len()).is_empty() {
+            return;
+        }
+        match history.restore(self.data.bytes()) {
+            Some(history) => {
+                let count = history.len();
+                self.data.restore_history(history);
                 self.log(
-                    NotificationLevel::Error,
-                    t!("errors.create_comments", e = e),
+                    NotificationLevel::Info,
+                    t!("app.messages.history_restored", count = count),
                 );
-                return;
             }
-            if let Err(e) = self
-                .filesystem
-                .write(&comments_path, comments_str.as_bytes())
-            {
-                self.log(NotificationLevel::Error, t!("errors.write_comments", e = e));
-                return;
+            None => {
+                self.log(NotificationLevel::Warning, t!("app.messages.history_stale"));
             }
-            self.log(NotificationLevel::Info, t!("app.messages.comments_saved"));
-            self.comments.reset_dirty();
         }
     }
 
@@ -130,21 +266,47 @@ impl App {
     pub(super) fn load_comments(&mut self, comments_path: Option<String>) {
         let comments_path = comments_path.unwrap_or(self.get_comments_path());
         match self.filesystem.read(&comments_path) {
-            Ok(comments_data) => match serde_json::from_slice::<Comments>(&comments_data) {
-                Ok(comments) => {
-                    self.comments = comments;
+            Ok(comments_data) => match serde_json::from_slice::<HpData>(&comments_data) {
+                Ok(data) => {
+                    // the description given on the command line replaces the saved one
+                    if self.raw_binary.is_none() {
+                        self.raw_binary.clone_from(&data.raw_binary);
+                    }
+                    self.apply_raw_binary();
+                    if self.raw_binary != data.raw_binary {
+                        self.save_raw_binary(&comments_path);
+                    }
+                    self.comments = data.comments;
+                    self.labels = data.labels;
+                    self.user_symbols = data.symbols;
+                    self.user_symbols.apply(&mut self.header);
+                    self.isa_modes = data.isa_modes;
+                    self.isa_modes.apply(&mut self.header);
+                    self.update_labels();
                     self.comments
                         .check_max_address(self.data.bytes().len() as u64);
                     self.log(NotificationLevel::Info, t!("app.messages.comments_loaded"));
+                    if let Some(history) = data.history {
+                        self.restore_history(history);
+                    }
                 }
                 Err(e) => {
                     self.log(NotificationLevel::Error, t!("errors.parse_comments", e = e));
+                    self.apply_raw_binary();
                 }
             },
             Err(e) => {
                 // This is in debug because the file may not exist.
                 self.log(NotificationLevel::Debug, t!("errors.read_comments", e = e));
                 self.comments = Comments::new();
+                self.labels = Labels::new();
+                self.user_symbols = UserSymbols::new();
+                self.isa_modes = IsaModes::new();
+                self.apply_raw_binary();
+                if self.raw_binary.is_some() {
+                    self.save_raw_binary(&comments_path);
+                }
+                self.update_labels();
             }
         }
     }
@@ -182,6 +344,22 @@ mod tests {
         assert_eq!(comments.get(&0x3000), None);
     }
 
+    #[test]
+    fn rebase() {
+        let mut comments = Comments::new();
+        comments.insert(0x10, "comment_1".to_string());
+        comments.insert(0x20, "comment_2".to_string());
+        comments.insert(0x30, "comment_3".to_string());
+        comments.rebase(0x20, 0, 4);
+        assert_eq!(comments.get(&0x10), Some(&"comment_1".to_string()));
+        assert_eq!(comments.get(&0x24), Some(&"comment_2".to_string()));
+        assert_eq!(comments.get(&0x34), Some(&"comment_3".to_string()));
+        comments.rebase(0x20, 8, 0);
+        assert_eq!(comments.len(), 2);
+        assert_eq!(comments.get(&0x10), Some(&"comment_1".to_string()));
+        assert_eq!(comments.get(&0x2C), Some(&"comment_3".to_string()));
+    }
+
     #[test]
     fn save_and_load() {
         let mut app = App::mockup(vec![0x90; 0x100]);
@@ -201,6 +379,40 @@ mod tests {
         assert_eq!(app.comments.get(&0x30), Some(&"comment_3".to_string()));
     }
 
+    #[test]
+    fn save_and_restore_history() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("file.bin").to_string_lossy().to_string();
+        std::fs::write(&file, [0; 0x20]).unwrap();
+        let mut terminal =
+            ratatui::Terminal::new(ratatui::backend::TestBackend::new(80, 24)).unwrap();
+        let mut app = App::default();
+        app.open_file(&file, &mut terminal).unwrap();
+        app.data.push_change(4, vec![1]);
+        app.data.push_change(8, vec![2]);
+        app.undo();
+        app.save_file().unwrap();
+
+        let mut app = App::default();
+        app.open_file(&file, &mut terminal).unwrap();
+        assert_eq!(app.data.history().len(), 2);
+        app.redo();
+        assert_eq!(app.data.bytes()[8], 2);
+        app.undo();
+        app.undo();
+        assert_eq!(app.data.bytes(), &[0; 0x20]);
+        assert!(app.data.dirty());
+
+        std::fs::write(&file, [1; 0x20]).unwrap();
+        let mut app = App::default();
+        app.open_file(&file, &mut terminal).unwrap();
+        assert!(app.data.history().is_empty());
+        assert_eq!(
+            app.logger.get_notification_level(),
+            NotificationLevel::Warning
+        );
+    }
+
     #[test]
     fn test_plugin() {
         let source = "
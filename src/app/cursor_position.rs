@@ -226,7 +226,11 @@ impl App {
                     t!("errors.invalid_virtual_address", address = symbol),
                 );
             }
-        } else if let Some(address) = self.header.symbol_to_address(symbol) {
+        } else if let Some(address) = self
+            .header
+            .symbol_to_address(symbol)
+            .or_else(|| self.labels.address_of(symbol))
+        {
             self.log(
                 NotificationLevel::Debug,
                 t!("app.messages.jump_to_symbol", symbol = symbol, address = address : {:#X}),
@@ -299,10 +303,20 @@ impl App {
         }
     }
 
+    /// Translates a virtual address to a file address, without sections
+    /// the virtual addresses are the file offsets.
+    pub(super) fn virtual_to_file_address(&self, address: u64) -> Option<u64> {
+        self.header
+            .virtual_to_physical_address(address)
+            .or_else(|| {
+                (self.header.get_sections().is_empty() && address < self.data.len() as u64)
+                    .then_some(address)
+            })
+    }
+
     pub(super) fn jump_to(&mut self, mut address: usize, is_virtual: bool) {
         if is_virtual {
-            if let Some(physical_address) = self.header.virtual_to_physical_address(address as u64)
-            {
+            if let Some(physical_address) = self.virtual_to_file_address(address as u64) {
                 address = physical_address as usize;
             } else {
                 self.log(
@@ -327,6 +341,8 @@ impl App {
     pub(super) fn move_cursor_in_selected_panel(&mut self, dx: isize, dy: isize) {
         match self.selected_pane {
             Pane::Hex => self.move_cursor(dx, dy, false),
+            Pane::Inspector => self.move_cursor(dx * 2, dy, false),
+            Pane::View if self.diff.is_some() => self.move_cursor(dx * 2, dy, false),
             Pane::View => match self.info_mode {
                 InfoMode::Text => self.move_cursor(dx * 2, dy, false),
                 InfoMode::Assembly => self.move_cursor_to_near_instruction(dy),
@@ -430,7 +446,8 @@ impl App {
     pub(super) fn switch_selected_pane(&mut self) {
         match self.selected_pane {
             Pane::Hex => self.selected_pane = Pane::View,
-            Pane::View => self.selected_pane = Pane::Hex,
+            Pane::View if self.inspector => self.selected_pane = Pane::Inspector,
+            Pane::View | Pane::Inspector => self.selected_pane = Pane::Hex,
         }
     }
 }
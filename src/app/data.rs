@@ -1,14 +1,24 @@
-use super::history::{change::Change, History};
+use std::ops::Range;
 
-#[derive(Debug, Clone, Default)]
+use super::{
+    files::filesystem::FileSystem,
+    history::{change::Change, History},
+    storage::Storage,
+};
+
+#[derive(Debug, Default)]
 pub struct Data {
-    bytes: Vec<u8>,
+    bytes: Storage,
     history: History,
     dirty: bool,
 }
 
 impl Data {
     pub fn new(bytes: Vec<u8>, history_limit: usize) -> Self {
+        Self::with_storage(bytes.into(), history_limit)
+    }
+
+    pub fn with_storage(bytes: Storage, history_limit: usize) -> Self {
         Self {
             bytes,
             history: History::with_limit(history_limit),
@@ -17,14 +27,14 @@ impl Data {
     }
 
     pub fn get(&self, i: usize) -> Option<u8> {
-        self.bytes.get(i).copied()
+        self.bytes.as_slice().get(i).copied()
     }
 
     pub fn set(&mut self, i: usize, byte: u8) -> Result<(), mlua::Error> {
-        match self.bytes.get_mut(i) {
+        match self.get(i) {
             Some(b) => {
-                self.history.push(Change::new(i, &[*b], &[byte]));
-                *b = byte;
+                self.history.push(Change::new(i, &[b], &[byte]));
+                self.bytes.as_mut_slice(i..i + 1)[0] = byte;
                 self.dirty = true;
                 Ok(())
             }
@@ -33,15 +43,45 @@ impl Data {
     }
 
     pub fn bytes(&self) -> &[u8] {
+        self.bytes.as_slice()
+    }
+
+    pub fn storage(&self) -> &Storage {
         &self.bytes
     }
 
+    /// Returns the ranges of bytes in range that have not been fetched yet.
+    pub fn missing_ranges(&self, range: Range<usize>) -> Vec<Range<usize>> {
+        self.bytes.missing_ranges(range)
+    }
+
+    /// Stores bytes fetched from the file, this is not a change and is not pushed to the history.
+    pub fn load(&mut self, offset: usize, bytes: &[u8]) {
+        self.bytes.load(offset, bytes);
+    }
+
+    /// Fetches the bytes in range that have not been read from the open file of
+    /// filesystem yet. This does nothing unless the file is being read on demand.
+    pub fn fetch(&mut self, range: Range<usize>, filesystem: &FileSystem) -> Result<(), String> {
+        for missing in self.missing_ranges(range) {
+            let bytes = filesystem
+                .read_range(filesystem.pwd(), missing.start as u64, missing.len())
+                .map_err(|e| {
+                    let address = missing.start;
+                    t!("errors.read_range", address = address : {:#X}, e = e).to_string()
+                })?;
+            self.load(missing.start, &bytes);
+        }
+        Ok(())
+    }
+
     pub fn dirty(&self) -> bool {
         self.dirty
     }
 
     pub fn reset_dirty(&mut self) {
         self.dirty = false;
+        self.bytes.clear_dirty_pages();
     }
 
     /// Pushes a change to the history and updates the data.
@@ -59,32 +99,110 @@ impl Data {
             );
         }
         new.truncate(self.bytes.len().checked_sub(offset).unwrap());
-        let old = &self.bytes[offset..offset + new.len()];
+        let old = &self.bytes.as_slice()[offset..offset + new.len()];
         if old == new.as_slice() {
             return 0;
         }
         self.history.push(Change::new(offset, old, &new));
-        self.bytes[offset..offset + new.len()].copy_from_slice(&new);
+        self.bytes.splice(offset..offset + new.len(), &new);
         self.dirty = true;
         new.len()
     }
 
+    /// Inserts bytes at the given offset, shifting the rest of the data.
+    /// Returns the number of bytes inserted.
+    /// Panics if the offset is greater than the length of the data.
+    pub fn insert(&mut self, offset: usize, new: Vec<u8>) -> usize {
+        if offset > self.bytes.len() {
+            panic!(
+                "{}",
+                t!(
+                    "panic.data_offset_out_of_bounds",
+                    offset = offset,
+                    data_len = self.bytes.len()
+                )
+            );
+        }
+        if new.is_empty() {
+            return 0;
+        }
+        self.history.push(Change::new(offset, &[], &new));
+        let len = new.len();
+        self.bytes.splice(offset..offset, &new);
+        self.dirty = true;
+        len
+    }
+
+    /// Deletes up to count bytes starting from the given offset, shifting the rest of the data.
+    /// Returns the number of bytes deleted.
+    /// Panics if the offset is out of bounds.
+    pub fn delete(&mut self, offset: usize, count: usize) -> usize {
+        if offset >= self.bytes.len() {
+            panic!(
+                "{}",
+                t!(
+                    "panic.data_offset_out_of_bounds",
+                    offset = offset,
+                    data_len = self.bytes.len()
+                )
+            );
+        }
+        let end = offset + count.min(self.bytes.len() - offset);
+        if end == offset {
+            return 0;
+        }
+        let old = self.bytes.as_slice()[offset..end].to_vec();
+        self.bytes.splice(offset..end, &[]);
+        self.history.push(Change::new(offset, &old, &[]));
+        self.dirty = true;
+        old.len()
+    }
+
     /// Undo the last change.
     /// Returns the change that was undone, if any.
     pub fn undo(&mut self) -> Option<&Change> {
-        self.history.undo(&mut self.bytes)
+        let change = self.history.undo(&mut self.bytes);
+        self.dirty |= change.is_some();
+        change
     }
 
     /// Redo the last change.
     /// Returns the change that was redone, if any.
     pub fn redo(&mut self) -> Option<&Change> {
-        self.history.redo(&mut self.bytes)
+        let change = self.history.redo(&mut self.bytes);
+        self.dirty |= change.is_some();
+        change
     }
 
     pub fn clear_history(&mut self) {
         self.history.clear();
     }
 
+    pub fn history(&self) -> &History {
+        &self.history
+    }
+
+    /// Returns the last change that was made or redone, see [History::current_change_mut].
+    pub fn last_change_mut(&mut self) -> Option<&mut Change> {
+        self.history.current_change_mut()
+    }
+
+    /// Names the current state of the history, an empty name removes the checkpoint.
+    pub fn set_checkpoint(&mut self, name: &str) {
+        self.history.set_checkpoint(name);
+    }
+
+    /// Makes the next redo apply the change with the given id, see [History::select_redo].
+    pub fn select_redo(&mut self, id: usize) {
+        self.history.select_redo(id);
+    }
+
+    /// Replaces the history with one read from the project file, keeping the current limit.
+    pub fn restore_history(&mut self, mut history: History) {
+        history.change_limit(self.history.limit());
+        self.history = history;
+    }
+
     pub fn len(&self) -> usize {
         self.bytes.len()
     }
@@ -128,6 +246,36 @@ mod tests {
         data.push_change(5, vec![9, 8, 7]);
     }
 
+    #[test]
+    fn test_data_insert_delete() {
+        let mut data = Data::new(vec![0, 1, 2, 3, 4], 0);
+        assert_eq!(data.insert(2, vec![9, 8]), 2);
+        assert_eq!(data.bytes(), &[0, 1, 9, 8, 2, 3, 4]);
+        assert_eq!(data.insert(7, vec![7]), 1);
+        assert_eq!(data.bytes(), &[0, 1, 9, 8, 2, 3, 4, 7]);
+        assert_eq!(data.insert(0, vec![]), 0);
+        assert_eq!(data.delete(1, 3), 3);
+        assert_eq!(data.bytes(), &[0, 2, 3, 4, 7]);
+        assert_eq!(data.delete(3, 10), 2);
+        assert_eq!(data.bytes(), &[0, 2, 3]);
+        data.undo();
+        assert_eq!(data.bytes(), &[0, 2, 3, 4, 7]);
+        data.undo();
+        assert_eq!(data.bytes(), &[0, 1, 9, 8, 2, 3, 4, 7]);
+        data.undo();
+        assert_eq!(data.bytes(), &[0, 1, 9, 8, 2, 3, 4]);
+        data.redo();
+        data.redo();
+        assert_eq!(data.bytes(), &[0, 2, 3, 4, 7]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_data_delete_out_of_bounds() {
+        let mut data = Data::new(vec![0, 1, 2, 3, 4], 0);
+        data.delete(5, 1);
+    }
+
     #[test]
     fn test_data_undo_redo() {
         let mut data = Data::new(vec![0, 1, 2, 3, 4], 0);
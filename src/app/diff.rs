@@ -0,0 +1,520 @@
+use std::{error::Error, ops::Range};
+
+use ratatui::text::{Line, Span, Text};
+
+use crate::headers::Header;
+
+use super::{
+    log::NotificationLevel, popup::popup_state::PopupState,
+    settings::color_settings::ColorSettings, App,
+};
+
+/// A region of the open file compared with a region of the other file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffRegion {
+    pub offset: usize,
+    pub size: usize,
+    pub other_offset: usize,
+    pub other_size: usize,
+}
+
+impl DiffRegion {
+    fn contains(&self, offset: usize, other_offset: usize) -> bool {
+        (self.offset..self.offset + self.size).contains(&offset)
+            && (self.other_offset..self.other_offset + self.other_size).contains(&other_offset)
+    }
+}
+
+/// Another file compared byte by byte with the open one.
+#[derive(Debug, Clone)]
+pub struct Diff {
+    pub path: String,
+    pub bytes: Vec<u8>,
+    /// Sorted by offset, they never overlap.
+    pub regions: Vec<DiffRegion>,
+}
+
+impl Diff {
+    /// If both files are executables the sections and symbols with the same name
+    /// are compared with each other, otherwise the files are compared from the start.
+    pub fn new(
+        path: &str,
+        bytes: Vec<u8>,
+        len: usize,
+        header: &Header,
+        other_header: &Header,
+    ) -> Self {
+        let mut regions = Self::align(header, other_header);
+        regions.retain(|region| region.offset < len);
+        for region in regions.iter_mut() {
+            region.size = region.size.min(len - region.offset);
+            region.other_size = region
+                .other_size
+                .min(bytes.len().saturating_sub(region.other_offset));
+        }
+        if regions.is_empty() {
+            regions.push(DiffRegion {
+                offset: 0,
+                size: len.max(bytes.len()),
+                other_offset: 0,
+                other_size: bytes.len(),
+            });
+        }
+        Self {
+            path: path.to_string(),
+            bytes,
+            regions,
+        }
+    }
+
+    /// Pairs the sections with the same name, and inside them the symbols with the same name.
+    /// The headers before the first section are compared with each other.
+    pub fn align(header: &Header, other_header: &Header) -> Vec<DiffRegion> {
+        let other_sections = other_header.get_sections();
+        let mut sections = Vec::new();
+        for section in header.get_sections() {
+            if let Some(other) = other_sections.iter().find(|s| s.name == section.name) {
+                sections.push(DiffRegion {
+                    offset: section.file_offset as usize,
+                    size: section.size as usize,
+                    other_offset: other.file_offset as usize,
+                    other_size: other.size as usize,
+                });
+            }
+        }
+        if sections.is_empty() {
+            return sections;
+        }
+
+        let mut regions = Vec::new();
+        if let Some(symbols) = header.get_symbols() {
+            for (address, name) in symbols {
+                let offset = header.virtual_to_physical_address(*address);
+                let other_offset = other_header
+                    .symbol_to_address(name)
+                    .and_then(|address| other_header.virtual_to_physical_address(address));
+                let (Some(offset), Some(other_offset)) = (offset, other_offset) else {
+                    continue;
+                };
+                let (offset, other_offset) = (offset as usize, other_offset as usize);
+                // a symbol can only move inside the section it belongs to
+                if let Some(section) = sections.iter().find(|s| s.contains(offset, other_offset)) {
+                    regions.push(DiffRegion {
+                        offset,
+                        size: section.offset + section.size - offset,
+                        other_offset,
+                        other_size: section.other_offset + section.other_size - other_offset,
+                    });
+                }
+            }
+        }
+        // the symbols are sorted first so that they win over a section starting at the same offset
+        regions.sort_by_key(|region| (region.offset, region.other_offset));
+        let first_section = sections.iter().map(|s| s.offset).min().unwrap_or_default();
+        let other_first_section = sections
+            .iter()
+            .map(|s| s.other_offset)
+            .min()
+            .unwrap_or_default();
+        regions.extend(sections);
+        regions.push(DiffRegion {
+            offset: 0,
+            size: first_section,
+            other_offset: 0,
+            other_size: other_first_section,
+        });
+        regions.retain(|region| region.size > 0);
+        regions.sort_by_key(|region| region.offset);
+        regions.dedup_by_key(|region| region.offset);
+        for i in 1..regions.len() {
+            let next = regions[i].offset;
+            let region = &mut regions[i - 1];
+            region.size = region.size.min(next - region.offset);
+        }
+        regions
+    }
+
+    /// Moves the regions after old_len bytes at offset were replaced with new_len bytes.
+    /// A region containing the replaced bytes is split around them, the new bytes are
+    /// compared with nothing so they are always different.
+    pub fn rebase(&mut self, offset: usize, old_len: usize, new_len: usize) {
+        let old_end = offset + old_len;
+        let mut regions = Vec::with_capacity(self.regions.len() + 2);
+        let mut inserted = new_len == 0;
+        for region in self.regions.drain(..) {
+            let end = region.offset + region.size;
+            if end <= offset {
+                regions.push(region);
+            } else if region.offset >= old_end && (old_len > 0 || region.offset > offset) {
+                regions.push(DiffRegion {
+                    offset: region.offset - old_len + new_len,
+                    ..region
+                });
+            } else {
+                if region.offset < offset {
+                    regions.push(DiffRegion {
+                        size: offset - region.offset,
+                        other_size: region.other_size.min(offset - region.offset),
+                        ..region
+                    });
+                }
+                if !inserted {
+                    regions.push(DiffRegion {
+                        offset,
+                        size: new_len,
+                        other_offset: region.other_offset,
+                        other_size: 0,
+                    });
+                    inserted = true;
+                }
+                if old_end < end {
+                    let skipped = old_end - region.offset;
+                    regions.push(DiffRegion {
+                        offset: offset + new_len,
+                        size: end - old_end,
+                        other_offset: region.other_offset + skipped,
+                        other_size: region.other_size.saturating_sub(skipped),
+                    });
+                }
+            }
+        }
+        self.regions = regions;
+    }
+
+    /// Returns the region containing offset and the offset in the other file compared with it.
+    fn locate(&self, offset: usize) -> Option<(&DiffRegion, usize)> {
+        let index = self
+            .regions
+            .partition_point(|region| region.offset <= offset)
+            .checked_sub(1)?;
+        let region = &self.regions[index];
+        let delta = offset - region.offset;
+        (delta < region.size).then_some((region, delta))
+    }
+
+    /// The byte compared with the one at offset, None if the offset is not in any
+    /// region or if the region of the other file is shorter.
+    pub fn other_byte(&self, offset: usize) -> Option<u8> {
+        let (region, delta) = self.locate(offset)?;
+        if delta < region.other_size {
+            self.bytes.get(region.other_offset + delta).copied()
+        } else {
+            None
+        }
+    }
+
+    /// The bytes outside of the compared regions are never different.
+    pub fn differs(&self, bytes: &[u8], offset: usize) -> bool {
+        self.locate(offset).is_some() && self.other_byte(offset) != bytes.get(offset).copied()
+    }
+
+    /// Returns the ranges of contiguous different bytes in bytes.
+    pub fn differences(&self, bytes: &[u8]) -> Vec<Range<usize>> {
+        let mut ret: Vec<Range<usize>> = Vec::new();
+        for region in self.regions.iter() {
+            let end = (region.offset + region.size).min(bytes.len());
+            for offset in region.offset..end {
+                if self.differs(bytes, offset) {
+                    match ret.last_mut() {
+                        Some(range) if range.end == offset => range.end += 1,
+                        _ => ret.push(offset..offset + 1),
+                    }
+                }
+            }
+        }
+        ret
+    }
+}
+
+impl App {
+    /// Reads another file from the same filesystem and shows it next to the open one.
+    pub(in crate::app) fn open_diff(&mut self, path: &str) -> Result<(), Box<dyn Error>> {
+        let bytes = self.filesystem.read(path)?;
+        let other_header = Header::parse_header(bytes.as_slice(), path, &self.filesystem);
+        // the differences are searched in the whole file, even if it is read on demand
+        self.load_range(0..self.data.len());
+        let diff = Diff::new(path, bytes, self.data.len(), &self.header, &other_header);
+        let count = diff.differences(self.data.bytes()).len();
+        self.log(
+            NotificationLevel::Info,
+            t!("app.messages.diff_opened", path = path, count = count),
+        );
+        self.diff = Some(diff);
+        self.resize_to_size(self.screen_size.0, self.screen_size.1);
+        Ok(())
+    }
+
+    pub(in crate::app) fn close_diff(&mut self) {
+        self.diff = None;
+        self.resize_to_size(self.screen_size.0, self.screen_size.1);
+    }
+
+    /// Jumps to the next group of different bytes after the cursor if direction is positive,
+    /// or to the previous one before the cursor if it is negative, wrapping around.
+    pub(in crate::app) fn jump_to_near_difference(&mut self, direction: isize) {
+        let Some(diff) = &self.diff else {
+            self.log(NotificationLevel::Warning, t!("app.messages.no_diff"));
+            return;
+        };
+        self.load_range(0..self.data.len());
+        let differences = diff.differences(self.data.bytes());
+        if differences.is_empty() {
+            self.log(NotificationLevel::Info, t!("app.messages.no_differences"));
+            return;
+        }
+        let cursor = self.get_cursor_position().global_byte_index;
+        let index = if direction >= 0 {
+            let next = differences.partition_point(|range| range.start <= cursor);
+            if next == differences.len() {
+                0
+            } else {
+                next
+            }
+        } else {
+            let previous = differences.partition_point(|range| range.start < cursor);
+            previous.checked_sub(1).unwrap_or(differences.len() - 1)
+        };
+        let address = differences[index].start;
+        self.log(
+            NotificationLevel::Info,
+            t!(
+                "app.messages.jump_to_difference",
+                index = index + 1,
+                count = differences.len(),
+                address = address : {:#X}
+            ),
+        );
+        self.jump_to(address, false);
+    }
+
+    pub(in crate::app) fn request_popup_diff(&mut self) {
+        let path = self
+            .diff
+            .as_ref()
+            .map(|diff| diff.path.clone())
+            .unwrap_or_default();
+        let cursor = path.len();
+        self.popup = Some(PopupState::Diff { path, cursor });
+    }
+
+    /// For every byte from start, whether it differs from the compared file.
+    /// Empty if no file is compared. The bytes that have not been fetched yet
+    /// are not compared, they are loaded with the visible rows before drawing.
+    pub(super) fn get_differences(&self, start: usize, end: usize) -> Vec<bool> {
+        let Some(diff) = &self.diff else {
+            return Vec::new();
+        };
+        let missing = self.data.missing_ranges(start..end);
+        (start..end)
+            .map(|offset| {
+                !missing.iter().any(|range| range.contains(&offset))
+                    && diff.differs(self.data.bytes(), offset)
+            })
+            .collect()
+    }
+
+    fn bytes_to_styled_diff(
+        color_settings: &ColorSettings,
+        bytes: &[Option<u8>],
+        differences: &[bool],
+        block_size: usize,
+        blocks_per_row: usize,
+        selected_byte_index: usize,
+    ) -> Text<'static> {
+        let mut ret = Text::default();
+        let mut current_line = Line::default();
+        for (byte_index, b) in bytes.iter().enumerate() {
+            let (hex, mut style) = match b {
+                Some(b) => (
+                    Self::u8_to_hex(*b).iter().collect::<String>(),
+                    Self::get_style_for_byte(color_settings, *b),
+                ),
+                None => ("--".to_string(), color_settings.placeholder),
+            };
+            if differences[byte_index] {
+                style = color_settings.hex_difference;
+            }
+            if byte_index == selected_byte_index {
+                style = color_settings.hex_selected;
+            }
+            current_line.spans.push(Span::styled(hex, style));
+            let mut spacing_string = " ".to_string();
+            if (byte_index + 1) % block_size == 0 {
+                spacing_string.push(' ');
+            }
+            current_line
+                .spans
+                .push(Span::styled(spacing_string, color_settings.hex_default));
+            if (byte_index + 1) % (block_size * blocks_per_row) == 0 {
+                ret.lines.push(std::mem::take(&mut current_line));
+            }
+        }
+        if !current_line.spans.is_empty() {
+            ret.lines.push(current_line);
+        }
+        ret
+    }
+
+    /// The bytes of the compared file next to the rows of the hex view.
+    /// start_row is included, end_row is excluded
+    pub(super) fn get_diff_view(&self, start_row: usize, end_row: usize) -> Text<'static> {
+        let Some(diff) = &self.diff else {
+            return Text::default();
+        };
+        let start_byte = start_row * self.blocks_per_row * self.block_size;
+        let end_byte = end_row * self.blocks_per_row * self.block_size;
+        let end_byte = std::cmp::min(end_byte, self.data.len());
+        let bytes = (start_byte..end_byte)
+            .map(|offset| diff.other_byte(offset))
+            .collect::<Vec<_>>();
+        let selected_byte_index = self
+            .get_cursor_position()
+            .global_byte_index
+            .saturating_sub(start_byte);
+        Self::bytes_to_styled_diff(
+            &self.settings.color,
+            &bytes,
+            &self.get_differences(start_byte, end_byte),
+            self.block_size,
+            self.blocks_per_row,
+            selected_byte_index,
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use object::{Architecture, Endianness};
+
+    use crate::headers::{
+        bitness::Bitness, custom_header::CustomHeader, isa_mode::IsaModes, section::Section,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_diff_without_headers() {
+        let diff = Diff::new("b", vec![0, 1, 9, 3], 5, &Header::None, &Header::None);
+        assert_eq!(diff.other_byte(2), Some(9));
+        assert_eq!(diff.other_byte(4), None);
+        assert_eq!(diff.differences(&[0, 1, 2, 3, 4]), vec![2..3, 4..5]);
+        assert!(diff.differences(&[0, 1, 9, 3]).is_empty());
+    }
+
+    #[test]
+    fn test_diff_align_sections_and_symbols() {
+        let header = |sections: Vec<Section>, symbols: &[(u64, &str)]| {
+            let mut header = Header::CustomHeader(CustomHeader {
+                bitness: Bitness::Bit64,
+                entry: 0,
+                endianness: Endianness::Little,
+                architecture: Architecture::X86_64,
+                sections,
+                symbols: HashMap::new(),
+                symbols_by_name: HashMap::new(),
+                isa_modes: IsaModes::new(),
+            });
+            for (address, name) in symbols {
+                header.set_symbol(*address, name);
+            }
+            header
+        };
+        let section = |name: &str, file_offset: u64, size: u64| Section {
+            name: name.to_string(),
+            virtual_address: 0x1000 + file_offset,
+            file_offset,
+            size,
+        };
+        let header_a = header(
+            vec![section(".text", 4, 8), section(".data", 12, 4)],
+            &[(0x1008, "f")],
+        );
+        let header_b = header(
+            vec![section(".text", 2, 10), section(".data", 12, 4)],
+            &[(0x100A, "f")],
+        );
+        let regions = Diff::align(&header_a, &header_b);
+        assert_eq!(
+            regions,
+            vec![
+                DiffRegion {
+                    offset: 0,
+                    size: 4,
+                    other_offset: 0,
+                    other_size: 2
+                },
+                DiffRegion {
+                    offset: 4,
+                    size: 4,
+                    other_offset: 2,
+                    other_size: 10
+                },
+                DiffRegion {
+                    offset: 8,
+                    size: 4,
+                    other_offset: 10,
+                    other_size: 2
+                },
+                DiffRegion {
+                    offset: 12,
+                    size: 4,
+                    other_offset: 12,
+                    other_size: 4
+                },
+            ]
+        );
+        let diff = Diff::new("b", (0..16).collect(), 16, &header_a, &header_b);
+        assert_eq!(diff.other_byte(8), Some(10));
+        assert_eq!(diff.other_byte(9), Some(11));
+        assert_eq!(diff.other_byte(10), None);
+        assert_eq!(diff.other_byte(13), Some(13));
+    }
+
+    #[test]
+    #[allow(clippy::single_range_in_vec_init)]
+    fn test_diff_rebase() {
+        let mut diff = Diff::new("b", (0..8).collect(), 8, &Header::None, &Header::None);
+        diff.rebase(3, 0, 2);
+        let bytes = [0, 1, 2, 0xAA, 0xBB, 3, 4, 5, 6, 7];
+        assert_eq!(diff.differences(&bytes), vec![3..5]);
+        assert_eq!(diff.other_byte(5), Some(3));
+
+        // deleting the inserted bytes compares the rest of the file as before
+        diff.rebase(3, 2, 0);
+        assert!(diff.differences(&(0..8).collect::<Vec<u8>>()).is_empty());
+        diff.rebase(0, 1, 0);
+        assert!(diff.differences(&(1..8).collect::<Vec<u8>>()).is_empty());
+        assert_eq!(diff.other_byte(0), Some(1));
+    }
+
+    #[test]
+    fn test_jump_to_near_difference() {
+        let dir = tempfile::tempdir().unwrap();
+        let other = dir.path().join("other.bin").to_string_lossy().to_string();
+        std::fs::write(&other, [0, 1, 2, 9, 4, 5, 9, 9]).unwrap();
+        let mut app = App::mockup((0..8).collect());
+        app.resize_to_size(80, 24);
+        app.open_diff(&other).unwrap();
+        app.jump_to_near_difference(1);
+        assert_eq!(app.get_cursor_position().global_byte_index, 3);
+        app.jump_to_near_difference(1);
+        assert_eq!(app.get_cursor_position().global_byte_index, 6);
+        app.jump_to_near_difference(1);
+        assert_eq!(app.get_cursor_position().global_byte_index, 3);
+        app.jump_to_near_difference(-1);
+        assert_eq!(app.get_cursor_position().global_byte_index, 6);
+
+        // the compared bytes follow an insertion
+        app.jump_to(0, false);
+        app.insert_bytes(&[0xFF]);
+        app.jump_to_near_difference(1);
+        assert_eq!(app.get_cursor_position().global_byte_index, 4);
+        app.undo();
+        app.jump_to_near_difference(1);
+        assert_eq!(app.get_cursor_position().global_byte_index, 3);
+        app.close_diff();
+        assert!(app.diff.is_none());
+    }
+}
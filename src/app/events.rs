@@ -75,8 +75,30 @@ impl App {
                     self.request_popup_find_text();
                 } else if event == self.settings.key.find_symbol {
                     self.request_popup_find_symbol();
+                } else if event == self.settings.key.find_pattern {
+                    self.request_popup_find_pattern();
+                } else if event == self.settings.key.find_assembly {
+                    self.request_popup_find_assembly();
+                } else if event == self.settings.key.xrefs {
+                    self.request_popup_xrefs();
+                } else if event == self.settings.key.next_match {
+                    self.jump_to_near_search_result(1);
+                } else if event == self.settings.key.previous_match {
+                    self.jump_to_near_search_result(-1);
+                } else if event == self.settings.key.next_difference {
+                    self.jump_to_near_difference(1);
+                } else if event == self.settings.key.previous_difference {
+                    self.jump_to_near_difference(-1);
                 } else if event == self.settings.key.edit_comment {
                     self.request_popup_edit_comment();
+                } else if event == self.settings.key.rename_label {
+                    self.request_popup_rename_label();
+                } else if event == self.settings.key.edit_symbol {
+                    self.request_popup_edit_symbol();
+                } else if event == self.settings.key.apply_struct {
+                    self.request_popup_apply_struct();
+                } else if event == self.settings.key.edit_field {
+                    self.request_popup_edit_field();
                 } else if event == self.settings.key.find_comment {
                     self.request_popup_find_comment();
                 } else if event == self.settings.key.patch_text {
@@ -91,8 +113,22 @@ impl App {
                     self.undo();
                 } else if event == self.settings.key.redo {
                     self.redo();
+                } else if event == self.settings.key.insert_byte {
+                    self.insert_bytes(&[0]);
+                } else if event == self.settings.key.delete_byte {
+                    self.delete_selection();
+                } else if event == self.settings.key.select {
+                    self.toggle_selection();
+                } else if event == self.settings.key.copy {
+                    self.copy_selection();
+                } else if event == self.settings.key.paste {
+                    self.paste();
+                } else if event == self.settings.key.close_popup {
+                    self.selection_anchor = None;
                 } else if event == self.settings.key.change_selected_pane {
                     self.switch_selected_pane();
+                } else if event == self.settings.key.inspector {
+                    self.toggle_inspector();
                 } else if event == self.settings.key.fullscreen {
                     self.switch_fullscreen();
                 } else if let KeyCode::Char(c) = event.code {
@@ -287,6 +323,66 @@ impl App {
                     *symbols = self.find_symbols(filter);
                 }
             }
+            Some(PopupState::FindPattern {
+                pattern,
+                cursor,
+                results,
+                scroll: _scroll,
+            }) => {
+                let old_pattern = pattern.clone();
+                Self::handle_string_edit(
+                    pattern,
+                    cursor,
+                    &event,
+                    None,
+                    None,
+                    false,
+                    &self.settings.key,
+                )?;
+                if old_pattern != *pattern {
+                    results.clear();
+                }
+            }
+            Some(PopupState::FindAssembly {
+                pattern,
+                cursor,
+                results,
+                scroll: _scroll,
+            }) => {
+                let old_pattern = pattern.clone();
+                Self::handle_string_edit(
+                    pattern,
+                    cursor,
+                    &event,
+                    None,
+                    None,
+                    false,
+                    &self.settings.key,
+                )?;
+                if old_pattern != *pattern {
+                    results.clear();
+                }
+            }
+            Some(PopupState::Xrefs {
+                target,
+                cursor,
+                results,
+                scroll: _scroll,
+            }) => {
+                let old_target = target.clone();
+                Self::handle_string_edit(
+                    target,
+                    cursor,
+                    &event,
+                    None,
+                    None,
+                    false,
+                    &self.settings.key,
+                )?;
+                if old_target != *target {
+                    results.clear();
+                }
+            }
             Some(PopupState::EditComment { comment, cursor }) => {
                 Self::handle_string_edit(
                     comment,
@@ -298,6 +394,61 @@ impl App {
                     &self.settings.key,
                 )?;
             }
+            Some(PopupState::RenameLabel { name, cursor }) => {
+                Self::handle_string_edit(
+                    name,
+                    cursor,
+                    &event,
+                    None,
+                    None,
+                    false,
+                    &self.settings.key,
+                )?;
+            }
+            Some(PopupState::Checkpoint { name, cursor }) => {
+                Self::handle_string_edit(
+                    name,
+                    cursor,
+                    &event,
+                    None,
+                    None,
+                    false,
+                    &self.settings.key,
+                )?;
+            }
+            Some(PopupState::EditSymbol { name, cursor }) => {
+                Self::handle_string_edit(
+                    name,
+                    cursor,
+                    &event,
+                    None,
+                    None,
+                    false,
+                    &self.settings.key,
+                )?;
+            }
+            Some(PopupState::ApplyStruct { name, cursor }) => {
+                Self::handle_string_edit(
+                    name,
+                    cursor,
+                    &event,
+                    None,
+                    None,
+                    false,
+                    &self.settings.key,
+                )?;
+            }
+            Some(PopupState::EditField { value, cursor, .. }) => {
+                Self::handle_string_edit(
+                    value,
+                    cursor,
+                    &event,
+                    None,
+                    None,
+                    false,
+                    &self.settings.key,
+                )?;
+            }
             Some(PopupState::FindComment {
                 filter,
                 comments,
@@ -343,7 +494,13 @@ impl App {
                     true,
                     &self.settings.key,
                 )?;
-                if let Some(current_instruction) = self.get_current_instruction() {
+                if let Some(selection) = self.get_selection() {
+                    let virtual_address = self
+                        .header
+                        .physical_to_virtual_address(selection.start as u64)
+                        .unwrap_or(selection.start as u64);
+                    *preview = self.bytes_from_assembly(assembly, virtual_address);
+                } else if let Some(current_instruction) = self.get_current_instruction() {
                     *preview =
                         self.bytes_from_assembly(assembly, current_instruction.virtual_address());
                 }
@@ -362,6 +519,19 @@ impl App {
                     &self.settings.key,
                 )?;
             }
+            Some(PopupState::Passphrase {
+                passphrase, cursor, ..
+            }) => {
+                Self::handle_string_edit(
+                    passphrase,
+                    cursor,
+                    &event,
+                    None,
+                    None,
+                    false,
+                    &self.settings.key,
+                )?;
+            }
             Some(PopupState::SaveAs { path, cursor }) => {
                 Self::handle_string_edit(
                     path,
@@ -373,6 +543,72 @@ impl App {
                     &self.settings.key,
                 )?;
             }
+            Some(PopupState::Fill { pattern, cursor }) => {
+                Self::handle_string_edit(
+                    pattern,
+                    cursor,
+                    &event,
+                    None,
+                    None,
+                    false,
+                    &self.settings.key,
+                )?;
+            }
+            Some(PopupState::RawBinary { text, cursor }) => {
+                Self::handle_string_edit(
+                    text,
+                    cursor,
+                    &event,
+                    None,
+                    None,
+                    false,
+                    &self.settings.key,
+                )?;
+            }
+            Some(PopupState::Export { path, cursor }) => {
+                Self::handle_string_edit(
+                    path,
+                    cursor,
+                    &event,
+                    None,
+                    None,
+                    false,
+                    &self.settings.key,
+                )?;
+            }
+            Some(PopupState::ExportPatch { path, cursor }) => {
+                Self::handle_string_edit(
+                    path,
+                    cursor,
+                    &event,
+                    None,
+                    None,
+                    false,
+                    &self.settings.key,
+                )?;
+            }
+            Some(PopupState::ImportPatch { path, cursor }) => {
+                Self::handle_string_edit(
+                    path,
+                    cursor,
+                    &event,
+                    None,
+                    None,
+                    false,
+                    &self.settings.key,
+                )?;
+            }
+            Some(PopupState::Diff { path, cursor }) => {
+                Self::handle_string_edit(
+                    path,
+                    cursor,
+                    &event,
+                    None,
+                    None,
+                    false,
+                    &self.settings.key,
+                )?;
+            }
             _ => {}
         }
 
@@ -380,22 +616,38 @@ impl App {
             event::Event::Key(event) if event.kind == event::KeyEventKind::Press => {
                 if event == self.settings.key.right {
                     match &mut popup {
-                        Some(PopupState::Save(choice)) | Some(PopupState::SaveAndQuit(choice)) => {
+                        Some(PopupState::Save(choice))
+                        | Some(PopupState::SaveAndQuit(choice))
+                        | Some(PopupState::HostKey { choice, .. }) => {
                             *choice = choice.next();
                         }
-                        Some(PopupState::QuitDirtySave(choice)) => {
+                        Some(PopupState::QuitDirtySave(choice))
+                        | Some(PopupState::CloseDirtySave(choice)) => {
                             *choice = choice.next();
                         }
+                        Some(PopupState::Cfg { graph, selected }) => {
+                            if let Some(next) = graph.follow(*selected) {
+                                *selected = next;
+                            }
+                        }
                         _ => {}
                     }
                 } else if event == self.settings.key.left {
                     match &mut popup {
-                        Some(PopupState::Save(choice)) | Some(PopupState::SaveAndQuit(choice)) => {
+                        Some(PopupState::Save(choice))
+                        | Some(PopupState::SaveAndQuit(choice))
+                        | Some(PopupState::HostKey { choice, .. }) => {
                             *choice = choice.previous();
                         }
-                        Some(PopupState::QuitDirtySave(choice)) => {
+                        Some(PopupState::QuitDirtySave(choice))
+                        | Some(PopupState::CloseDirtySave(choice)) => {
                             *choice = choice.previous();
                         }
+                        Some(PopupState::Cfg { graph, selected }) => {
+                            if let Some(previous) = graph.predecessor(*selected) {
+                                *selected = previous;
+                            }
+                        }
                         _ => {}
                     }
                 } else if event == self.settings.key.confirm {
@@ -443,6 +695,52 @@ impl App {
                             self.jump_to_fuzzy_symbol(filter, symbols, *scroll);
                             popup = None;
                         }
+                        Some(PopupState::FindPattern {
+                            pattern,
+                            cursor: _cursor,
+                            results,
+                            scroll,
+                        }) => {
+                            // the first confirm searches, the next one jumps to the selected result
+                            if results.is_empty() {
+                                *results = self.find_pattern(pattern);
+                                *scroll = 0;
+                            } else {
+                                self.search_results.clone_from(results);
+                                self.jump_to_search_result(*scroll);
+                                popup = None;
+                            }
+                        }
+                        Some(PopupState::FindAssembly {
+                            pattern,
+                            cursor: _cursor,
+                            results,
+                            scroll,
+                        }) => {
+                            // the first confirm searches, the next one jumps to the selected result
+                            if results.is_empty() {
+                                *results = self.find_assembly(pattern);
+                                *scroll = 0;
+                            } else {
+                                self.jump_to_assembly_match(results, *scroll);
+                                popup = None;
+                            }
+                        }
+                        Some(PopupState::Xrefs {
+                            target,
+                            cursor: _cursor,
+                            results,
+                            scroll,
+                        }) => {
+                            // the first confirm searches, the next one jumps to the selected result
+                            if results.is_empty() {
+                                *results = self.find_xrefs(target);
+                                *scroll = 0;
+                            } else if let Some((xref, _)) = results.get(*scroll) {
+                                self.jump_to_xref(xref);
+                                popup = None;
+                            }
+                        }
                         Some(PopupState::Log(_)) => {
                             popup = None;
                         }
@@ -475,6 +773,49 @@ impl App {
                             self.edit_comment(comment);
                             popup = None;
                         }
+                        Some(PopupState::RenameLabel { name, cursor: _ }) => {
+                            self.rename_label(name);
+                            popup = None;
+                        }
+                        Some(PopupState::Checkpoint { name, cursor: _ }) => {
+                            self.set_checkpoint(name);
+                            popup = None;
+                        }
+                        Some(PopupState::Cfg { graph, selected }) => {
+                            self.jump_to_block(&graph.blocks[*selected]);
+                            popup = None;
+                        }
+                        Some(PopupState::IsaMode {
+                            range,
+                            modes,
+                            selected,
+                        }) => {
+                            self.set_isa_mode(range.clone(), modes[*selected]);
+                            popup = None;
+                        }
+                        Some(PopupState::UndoTree(scroll)) => {
+                            let id = self.data.history().tree().get(*scroll).map(|line| line.id);
+                            if let Some(id) = id {
+                                self.jump_to_history_state(id);
+                            }
+                            popup = None;
+                        }
+                        Some(PopupState::EditSymbol { name, cursor: _ }) => {
+                            self.edit_symbol(name);
+                            popup = None;
+                        }
+                        Some(PopupState::ApplyStruct { name, cursor: _ }) => {
+                            self.apply_struct(name);
+                            popup = None;
+                        }
+                        Some(PopupState::EditField {
+                            field,
+                            value,
+                            cursor: _,
+                        }) => {
+                            self.edit_field(*field, value);
+                            popup = None;
+                        }
                         Some(PopupState::FindComment {
                             filter,
                             cursor: _cursor,
@@ -488,6 +829,38 @@ impl App {
                             self.save_file_as(path)?;
                             popup = None;
                         }
+                        Some(PopupState::Fill { pattern, cursor: _ }) => {
+                            self.fill_selection(pattern);
+                            popup = None;
+                        }
+                        Some(PopupState::Export { path, cursor: _ }) => {
+                            self.export_selection(path)?;
+                            popup = None;
+                        }
+                        Some(PopupState::RawBinary { text, cursor: _ }) => {
+                            self.set_raw_binary(text);
+                            popup = None;
+                        }
+                        Some(PopupState::ExportPatch { path, cursor: _ }) => {
+                            self.export_patch(path)?;
+                            popup = None;
+                        }
+                        Some(PopupState::ImportPatch { path, cursor: _ }) => {
+                            self.import_patch(path)?;
+                            popup = None;
+                        }
+                        Some(PopupState::Diff { path, cursor: _ }) => {
+                            if path.is_empty() {
+                                self.close_diff();
+                            } else {
+                                self.open_diff(path)?;
+                            }
+                            popup = None;
+                        }
+                        Some(PopupState::Passphrase { .. }) => {
+                            // the passphrase is read by the connection that asked for it
+                            popup = None;
+                        }
                         Some(PopupState::Save(choice)) => {
                             if *choice == BinaryChoice::Yes {
                                 self.save_file()?;
@@ -496,7 +869,15 @@ impl App {
                         }
                         Some(PopupState::SaveAndQuit(choice)) => {
                             if *choice == BinaryChoice::Yes {
-                                self.save_file()?;
+                                self.save_all_buffers()?;
+                                self.needs_to_exit = true;
+                            }
+                            popup = None;
+                        }
+                        Some(PopupState::HostKey { host_key, choice }) => {
+                            if *choice == BinaryChoice::Yes {
+                                self.accept_host_key(host_key);
+                            } else {
                                 self.needs_to_exit = true;
                             }
                             popup = None;
@@ -504,7 +885,7 @@ impl App {
                         Some(PopupState::QuitDirtySave(choice)) => {
                             match choice {
                                 SimpleChoice::Yes => {
-                                    self.save_file()?;
+                                    self.save_all_buffers()?;
                                     self.needs_to_exit = true;
                                 }
                                 SimpleChoice::No => {
@@ -514,6 +895,25 @@ impl App {
                             }
                             popup = None;
                         }
+                        Some(PopupState::CloseDirtySave(choice)) => {
+                            self.popup = None;
+                            match choice {
+                                SimpleChoice::Yes => {
+                                    self.save_file()?;
+                                    self.close_buffer()?;
+                                }
+                                SimpleChoice::No => {
+                                    self.close_buffer()?;
+                                }
+                                SimpleChoice::Cancel => {}
+                            }
+                            // closing the last buffer opens a popup to choose another file
+                            popup.clone_from(&self.popup);
+                        }
+                        Some(PopupState::Buffers(scroll)) => {
+                            self.select_buffer(*scroll);
+                            popup = None;
+                        }
                         Some(PopupState::Help(_)) => {
                             popup = None;
                         }
@@ -558,6 +958,30 @@ impl App {
                                 Self::handle_popup_scroll(scroll, symbols.len(), None, 1);
                             }
                         }
+                        Some(PopupState::FindPattern {
+                            pattern: _pattern,
+                            cursor: _cursor,
+                            results,
+                            scroll,
+                        }) => {
+                            Self::handle_popup_scroll(scroll, results.len(), None, 1);
+                        }
+                        Some(PopupState::FindAssembly {
+                            pattern: _pattern,
+                            cursor: _cursor,
+                            results,
+                            scroll,
+                        }) => {
+                            Self::handle_popup_scroll(scroll, results.len(), None, 1);
+                        }
+                        Some(PopupState::Xrefs {
+                            target: _target,
+                            cursor: _cursor,
+                            results,
+                            scroll,
+                        }) => {
+                            Self::handle_popup_scroll(scroll, results.len(), None, 1);
+                        }
                         Some(PopupState::FindComment {
                             filter: _filter,
                             comments,
@@ -578,6 +1002,25 @@ impl App {
                                 -1,
                             );
                         }
+                        Some(PopupState::Buffers(scroll)) => {
+                            Self::handle_popup_scroll(scroll, self.buffers.len(), None, 1);
+                        }
+                        Some(PopupState::UndoTree(scroll)) => {
+                            Self::handle_popup_scroll(
+                                scroll,
+                                self.data.history().len() + 1,
+                                None,
+                                1,
+                            );
+                        }
+                        Some(PopupState::Cfg { graph, selected }) => {
+                            *selected = (*selected + 1).min(graph.blocks.len() - 1);
+                        }
+                        Some(PopupState::IsaMode {
+                            modes, selected, ..
+                        }) => {
+                            *selected = (*selected + 1).min(modes.len() - 1);
+                        }
                         Some(PopupState::Help(scroll)) => {
                             Self::handle_popup_scroll(
                                 scroll,
@@ -615,6 +1058,30 @@ impl App {
                         }) => {
                             Self::handle_popup_scroll(scroll, symbols.len(), None, -1);
                         }
+                        Some(PopupState::FindPattern {
+                            pattern: _pattern,
+                            cursor: _cursor,
+                            results,
+                            scroll,
+                        }) => {
+                            Self::handle_popup_scroll(scroll, results.len(), None, -1);
+                        }
+                        Some(PopupState::FindAssembly {
+                            pattern: _pattern,
+                            cursor: _cursor,
+                            results,
+                            scroll,
+                        }) => {
+                            Self::handle_popup_scroll(scroll, results.len(), None, -1);
+                        }
+                        Some(PopupState::Xrefs {
+                            target: _target,
+                            cursor: _cursor,
+                            results,
+                            scroll,
+                        }) => {
+                            Self::handle_popup_scroll(scroll, results.len(), None, -1);
+                        }
                         Some(PopupState::FindComment {
                             filter: _filter,
                             comments,
@@ -631,6 +1098,21 @@ impl App {
                                 1,
                             );
                         }
+                        Some(PopupState::Buffers(scroll)) => {
+                            Self::handle_popup_scroll(scroll, self.buffers.len(), None, -1);
+                        }
+                        Some(PopupState::UndoTree(scroll)) => {
+                            Self::handle_popup_scroll(
+                                scroll,
+                                self.data.history().len() + 1,
+                                None,
+                                -1,
+                            );
+                        }
+                        Some(PopupState::Cfg { selected, .. })
+                        | Some(PopupState::IsaMode { selected, .. }) => {
+                            *selected = selected.saturating_sub(1);
+                        }
                         Some(PopupState::Help(scroll)) => {
                             Self::handle_popup_scroll(
                                 scroll,
@@ -690,6 +1172,30 @@ impl App {
                             }) => {
                                 *scroll = 0;
                             }
+                            Some(PopupState::FindPattern {
+                                pattern: _,
+                                cursor: _,
+                                results: _,
+                                scroll,
+                            }) => {
+                                *scroll = 0;
+                            }
+                            Some(PopupState::FindAssembly {
+                                pattern: _,
+                                cursor: _,
+                                results: _,
+                                scroll,
+                            }) => {
+                                *scroll = 0;
+                            }
+                            Some(PopupState::Xrefs {
+                                target: _,
+                                cursor: _,
+                                results: _,
+                                scroll,
+                            }) => {
+                                *scroll = 0;
+                            }
                             _ => {}
                         }
                     }
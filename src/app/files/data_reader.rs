@@ -0,0 +1,80 @@
+use std::io::{Read, Seek, SeekFrom};
+
+use crate::app::data::Data;
+
+use super::filesystem::FileSystem;
+
+/// Reads the data of a file that is fetched on demand, keeping the fetched pages.
+pub struct DataReader<'a> {
+    data: &'a mut Data,
+    filesystem: &'a FileSystem,
+    position: u64,
+}
+
+impl<'a> DataReader<'a> {
+    pub fn new(data: &'a mut Data, filesystem: &'a FileSystem) -> Self {
+        Self {
+            data,
+            filesystem,
+            position: 0,
+        }
+    }
+}
+
+impl Read for DataReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let start = (self.position as usize).min(self.data.len());
+        let end = (start + buf.len()).min(self.data.len());
+        self.data
+            .fetch(start..end, self.filesystem)
+            .map_err(std::io::Error::other)?;
+        buf[..end - start].copy_from_slice(&self.data.bytes()[start..end]);
+        self.position += (end - start) as u64;
+        Ok(end - start)
+    }
+}
+
+impl Seek for DataReader<'_> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => (self.data.len() as u64).checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+        };
+        match position {
+            Some(position) => {
+                self.position = position;
+                Ok(position)
+            }
+            None => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "invalid seek to a negative position",
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::single_range_in_vec_init)]
+mod test {
+    use std::io::Write;
+
+    use crate::app::storage::{Storage, PAGE_SIZE};
+
+    use super::*;
+
+    #[test]
+    fn test_data_reader() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        let bytes = (0..PAGE_SIZE * 3).map(|i| i as u8).collect::<Vec<u8>>();
+        file.write_all(&bytes).unwrap();
+        let filesystem = FileSystem::new_local(&file.path().to_string_lossy()).unwrap();
+        let mut data = Data::with_storage(Storage::paged(bytes.len()).unwrap(), 0);
+        let mut reader = DataReader::new(&mut data, &filesystem);
+        reader.seek(SeekFrom::End(-2)).unwrap();
+        let mut buf = [0; 4];
+        assert_eq!(reader.read(&mut buf).unwrap(), 2);
+        assert_eq!(&buf[..2], &bytes[PAGE_SIZE * 3 - 2..]);
+        assert_eq!(data.missing_ranges(0..bytes.len()), [0..PAGE_SIZE * 2]);
+    }
+}
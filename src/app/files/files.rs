@@ -1,12 +1,12 @@
 #![allow(clippy::module_inception)]
-use std::error::Error;
+use std::{error::Error, ops::Range};
 
 use ratatui::{backend::Backend, Terminal};
 
 use crate::{
     app::{
-        data::Data, info_mode::InfoMode, log::NotificationLevel, popup::popup_state::PopupState,
-        App,
+        data::Data, firmware::firmware_image::FirmwareFormat, info_mode::InfoMode,
+        log::NotificationLevel, popup::popup_state::PopupState, storage::Storage, App,
     },
     get_app_context,
     headers::Header,
@@ -117,7 +117,7 @@ impl App {
     }
 
     pub fn log_header_info(&mut self) {
-        if self.header != Header::None {
+        if !self.header.is_none() {
             match &self.header {
                 Header::GenericHeader(header) => self.log(
                     NotificationLevel::Info,
@@ -161,7 +161,8 @@ impl App {
         );
     }
 
-    pub(in crate::app) fn open_file<B: Backend>(
+    /// Reads the file at path into the state of the buffer in the foreground.
+    pub(in crate::app) fn load_file<B: Backend>(
         &mut self,
         path: &str,
         terminal: &mut Terminal<B>,
@@ -175,6 +176,9 @@ impl App {
         self.info_mode = InfoMode::Text;
         self.scroll = 0;
         self.cursor = (0, 0);
+        self.selection_anchor = None;
+        self.search_results.clear();
+        self.diff = None;
 
         self.screen_size = Self::get_size(terminal)?;
         self.block_size = 8;
@@ -184,6 +188,8 @@ impl App {
             self.screen_size.0,
             self.fullscreen,
             self.selected_pane,
+            self.inspector,
+            self.diff.is_some(),
         );
 
         Self::print_loading_status(
@@ -191,12 +197,7 @@ impl App {
             &t!("app.messages.opening_path", path = path),
             terminal,
         )?;
-        self.data = Data::new(
-            self.filesystem.read(self.filesystem.pwd())?,
-            self.settings.app.history_limit,
-        );
-
-        self.load_comments(None);
+        self.data = Data::with_storage(self.open_storage()?, self.settings.app.history_limit);
 
         Self::print_loading_status(
             &self.settings.color,
@@ -205,6 +206,8 @@ impl App {
         )?;
 
         self.header = self.parse_header();
+        // the symbols saved with the comments are merged into the header
+        self.load_comments(None);
 
         Self::print_loading_status(
             &self.settings.color,
@@ -212,8 +215,7 @@ impl App {
             terminal,
         )?;
 
-        (self.assembly_offsets, self.assembly_instructions) =
-            Self::sections_from_bytes(self.data.bytes(), &self.header);
+        self.disassemble();
 
         Self::print_loading_status(
             &self.settings.color,
@@ -227,23 +229,92 @@ impl App {
         Ok(())
     }
 
+    /// Files smaller than the large file threshold are read entirely, bigger ones are
+    /// memory mapped if local or fetched a page at a time if remote.
+    /// Firmware images are always read entirely and decoded.
+    pub(in crate::app) fn open_storage(&mut self) -> Result<Storage, Box<dyn Error>> {
+        self.firmware = None;
+        let path = self.filesystem.pwd();
+        if let Some(format) = FirmwareFormat::from_path(path) {
+            let file = self.filesystem.read(path)?;
+            return Ok(Storage::from(self.decode_firmware(format, file)));
+        }
+        let threshold = self.settings.app.large_file_threshold;
+        let size = self.filesystem.file_size(path)?;
+        if threshold == 0 || size < threshold as u64 {
+            return Ok(Storage::from(self.filesystem.read(path)?));
+        }
+        match &self.filesystem {
+            FileSystem::Local { .. } => Ok(Storage::map_file(path)?),
+            FileSystem::Remote { .. } => Ok(Storage::paged(size as usize)?),
+        }
+    }
+
+    /// Fetches the bytes in range that have not been read from the file yet.
+    /// This does nothing unless the file is being read on demand.
+    pub(in crate::app) fn load_range(&mut self, range: Range<usize>) {
+        if let Err(e) = self.data.fetch(range, &self.filesystem) {
+            self.log(NotificationLevel::Error, e);
+        }
+    }
+
     pub(in crate::app) fn save_file_as(&mut self, path: &str) -> Result<(), Box<dyn Error>> {
         if let Some(parent) = path::parent(path) {
             self.filesystem.mkdirs(parent)?;
         };
 
+        self.load_range(0..self.data.len());
         self.filesystem.create(path)?;
         self.filesystem.cd(&self.filesystem.canonicalize(path)?);
-        self.save_file()?;
-        Ok(())
+        // a firmware image is converted to the format of the new extension, if any
+        self.firmware = self.firmware.take().and_then(|mut image| {
+            image.convert(FirmwareFormat::from_path(path)?);
+            Some(image)
+        });
+        self.write_file(true)
     }
 
     pub(in crate::app) fn save_file(&mut self) -> Result<(), Box<dyn Error>> {
+        self.write_file(false)
+    }
+
+    /// Writes the data to the open file, only the modified pages are written
+    /// if the file is big and its size did not change, unless whole_file is true.
+    /// A firmware image is encoded again as a whole.
+    fn write_file(&mut self, whole_file: bool) -> Result<(), Box<dyn Error>> {
         let mut app_context = get_app_context!(self);
         self.plugin_manager.on_save(&mut app_context);
-        self.filesystem
-            .write(self.filesystem.pwd(), self.data.bytes())?;
+        let mut dropped_metadata = 0;
+        match (&self.firmware, self.data.storage().dirty_ranges()) {
+            (Some(image), _) => {
+                dropped_metadata = image.dropped_metadata(self.data.bytes());
+                self.filesystem
+                    .write(self.filesystem.pwd(), &image.encode(self.data.bytes()))?
+            }
+            (None, Some(ranges)) if !whole_file => {
+                for range in ranges {
+                    self.filesystem.write_range(
+                        self.filesystem.pwd(),
+                        range.start as u64,
+                        &self.data.bytes()[range],
+                    )?;
+                }
+            }
+            _ => self
+                .filesystem
+                .write(self.filesystem.pwd(), self.data.bytes())?,
+        }
         self.data.reset_dirty();
+        self.save_history();
+        if dropped_metadata > 0 {
+            self.log(
+                NotificationLevel::Warning,
+                t!(
+                    "app.messages.firmware_metadata_dropped",
+                    count = dropped_metadata
+                ),
+            );
+        }
         match &self.filesystem {
             FileSystem::Local { path } => {
                 self.log(
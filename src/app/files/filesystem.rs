@@ -1,6 +1,11 @@
-use std::{error::Error, path::Path};
+use std::{
+    error::Error,
+    fs::OpenOptions,
+    io::{Read, Seek, SeekFrom, Write},
+    path::Path,
+};
 
-use crate::app::ssh::connection::Connection;
+use crate::app::ssh::connection::{Connection, ConnectionOptions};
 
 use super::path;
 
@@ -26,9 +31,9 @@ impl FileSystem {
     pub fn new_remote(
         path: &str,
         connection_str: &str,
-        password: Option<&str>,
+        options: &ConnectionOptions,
     ) -> Result<Self, Box<dyn Error>> {
-        let connection = Connection::new(connection_str, password)?;
+        let connection = Connection::new(connection_str, options)?;
         Ok(Self::Remote {
             path: connection.canonicalize(path)?,
             connection,
@@ -84,6 +89,45 @@ impl FileSystem {
         }
     }
 
+    pub fn file_size(&self, path: &str) -> Result<u64, Box<dyn Error>> {
+        match self {
+            Self::Local { .. } => Ok(std::fs::metadata(path)?.len()),
+            Self::Remote { connection, .. } => connection.file_size(path),
+        }
+    }
+
+    /// Reads len bytes starting from offset without reading the rest of the file.
+    pub fn read_range(
+        &self,
+        path: &str,
+        offset: u64,
+        len: usize,
+    ) -> Result<Vec<u8>, Box<dyn Error>> {
+        match self {
+            Self::Local { .. } => {
+                let mut file = std::fs::File::open(path)?;
+                file.seek(SeekFrom::Start(offset))?;
+                let mut data = vec![0; len];
+                file.read_exact(&mut data)?;
+                Ok(data)
+            }
+            Self::Remote { connection, .. } => connection.read_range(path, offset, len),
+        }
+    }
+
+    /// Overwrites the bytes starting from offset, the rest of the file is left untouched.
+    pub fn write_range(&self, path: &str, offset: u64, data: &[u8]) -> Result<(), Box<dyn Error>> {
+        match self {
+            Self::Local { .. } => {
+                let mut file = OpenOptions::new().write(true).open(path)?;
+                file.seek(SeekFrom::Start(offset))?;
+                file.write_all(data)?;
+                Ok(())
+            }
+            Self::Remote { connection, .. } => connection.write_range(path, offset, data),
+        }
+    }
+
     pub fn mkdirs(&self, path: &str) -> Result<(), Box<dyn Error>> {
         match self {
             Self::Local { .. } => std::fs::create_dir_all(path)?,
@@ -1,3 +1,4 @@
+pub mod data_reader;
 mod files;
 pub mod filesystem;
 pub mod path;
@@ -0,0 +1,629 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Display,
+};
+
+use object::{Architecture, Endianness};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app::{history::change::Change, log::NotificationLevel, App},
+    headers::{
+        bitness::Bitness,
+        custom_header::CustomHeader,
+        isa_mode::{IsaMode, IsaModes},
+        raw_binary::RawBinary,
+        section::Section,
+        Header,
+    },
+};
+
+use super::{intel_hex, srec, uf2};
+
+/// The formats of the firmware images that are decoded to the bytes they hold,
+/// chosen by the extension of the file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FirmwareFormat {
+    IntelHex,
+    SRecord,
+    Uf2,
+}
+
+impl FirmwareFormat {
+    pub fn from_path(path: &str) -> Option<Self> {
+        let extension = path
+            .rsplit_once('.')
+            .map(|(_, extension)| extension.to_lowercase())
+            .unwrap_or_default();
+        match extension.as_str() {
+            "hex" | "ihex" | "ihx" => Some(FirmwareFormat::IntelHex),
+            "srec" | "s19" | "s28" | "s37" | "mot" => Some(FirmwareFormat::SRecord),
+            "uf2" => Some(FirmwareFormat::Uf2),
+            _ => None,
+        }
+    }
+
+    /// Decodes a file, returning the image and the bytes of its segments.
+    pub fn decode(&self, file: &[u8]) -> Result<(FirmwareImage, Vec<u8>), String> {
+        match self {
+            FirmwareFormat::IntelHex => intel_hex::decode(file),
+            FirmwareFormat::SRecord => srec::decode(file),
+            FirmwareFormat::Uf2 => uf2::decode(file),
+        }
+    }
+
+    /// The number of data bytes in each record of a new image.
+    fn default_record_size(&self) -> usize {
+        match self {
+            FirmwareFormat::IntelHex | FirmwareFormat::SRecord => 16,
+            FirmwareFormat::Uf2 => 256,
+        }
+    }
+}
+
+impl Display for FirmwareFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            FirmwareFormat::IntelHex => "Intel HEX",
+            FirmwareFormat::SRecord => "S-record",
+            FirmwareFormat::Uf2 => "UF2",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// The data of a record, or of a UF2 block, loaded at address.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record {
+    pub address: u64,
+    pub bytes: Vec<u8>,
+    /// The UF2 family id, the boards the block is meant for.
+    pub family_id: Option<u32>,
+}
+
+/// Contiguous addresses of the image, with the offset of their bytes in the decoded data.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Segment {
+    pub address: u64,
+    pub file_offset: usize,
+    pub size: usize,
+    pub family_id: Option<u32>,
+}
+
+/// The flags of a UF2 block and the bytes after its payload, its extension tags or
+/// the checksum of its data, with the payload they were written with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockMetadata {
+    pub flags: u32,
+    pub payload: Vec<u8>,
+    pub tail: Vec<u8>,
+}
+
+impl Segment {
+    pub fn end(&self) -> u64 {
+        self.address + self.size as u64
+    }
+}
+
+/// A firmware image decoded to the bytes of its segments, one after the other in
+/// address order. The other fields are kept to write the file back in the same shape.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FirmwareImage {
+    pub format: FirmwareFormat,
+    pub segments: Vec<Segment>,
+    /// The start address written in the file, if any.
+    pub entry: Option<u64>,
+    /// The largest number of data bytes in a record or block.
+    pub record_size: usize,
+    /// The smallest number of bytes of the addresses of the S-records.
+    pub address_bytes: usize,
+    /// The data of the S-record header, usually the name of the image.
+    pub header_record: Vec<u8>,
+    /// The segment and the offset of the start segment address record of an Intel
+    /// HEX file, written back as long as they point at the entry.
+    pub start_segment: Option<(u16, u16)>,
+    /// The UF2 blocks that are not meant for the main flash, written back unchanged.
+    pub other_blocks: Vec<Vec<u8>>,
+    /// The UF2 blocks with more than a family id, by address.
+    pub block_metadata: BTreeMap<u64, BlockMetadata>,
+}
+
+impl FirmwareImage {
+    /// Sorts the records by address and joins the contiguous ones into segments,
+    /// the overlapping records overwrite each other.
+    pub fn from_records(format: FirmwareFormat, mut records: Vec<Record>) -> (Self, Vec<u8>) {
+        records.sort_by_key(|record| record.address);
+        let mut segments: Vec<Segment> = Vec::new();
+        let mut data = Vec::new();
+        let mut record_size = 0;
+        for record in records {
+            record_size = record_size.max(record.bytes.len());
+            if record.bytes.is_empty() {
+                continue;
+            }
+            match segments.last_mut() {
+                Some(last)
+                    if last.family_id == record.family_id && record.address <= last.end() =>
+                {
+                    let start = last.file_offset + (record.address - last.address) as usize;
+                    let end = start + record.bytes.len();
+                    if end > data.len() {
+                        data.resize(end, 0);
+                    }
+                    data[start..end].copy_from_slice(&record.bytes);
+                    last.size = last.size.max(end - last.file_offset);
+                }
+                _ => {
+                    segments.push(Segment {
+                        address: record.address,
+                        file_offset: data.len(),
+                        size: record.bytes.len(),
+                        family_id: record.family_id,
+                    });
+                    data.extend_from_slice(&record.bytes);
+                }
+            }
+        }
+        if record_size == 0 {
+            record_size = format.default_record_size();
+        }
+        let image = Self {
+            format,
+            segments,
+            entry: None,
+            record_size,
+            address_bytes: 2,
+            header_record: Vec::new(),
+            start_segment: None,
+            other_blocks: Vec::new(),
+            block_metadata: BTreeMap::new(),
+        };
+        (image, data)
+    }
+
+    /// Updates the segments after old_len bytes at offset were replaced with new_len
+    /// bytes. The removed bytes are taken from the segments that held them, the inserted
+    /// ones are added to the segment that holds offset, or that ends at it, so that the
+    /// other segments keep their addresses.
+    pub fn rebase(&mut self, offset: usize, old_len: usize, new_len: usize) {
+        let removed_end = offset + old_len;
+        for segment in self.segments.iter_mut() {
+            let start = segment.file_offset;
+            let end = start + segment.size;
+            let removed_before = removed_end.min(start).saturating_sub(offset);
+            let removed_inside = removed_end.min(end).saturating_sub(offset.max(start));
+            if offset <= start {
+                // the bytes removed from the start of the segment take their addresses
+                segment.address += removed_inside as u64;
+            }
+            segment.file_offset -= removed_before;
+            segment.size -= removed_inside;
+        }
+        self.segments.retain(|segment| segment.size > 0);
+        if new_len == 0 {
+            return;
+        }
+        let growing = self.segments.iter().position(|segment| {
+            segment.file_offset <= offset && offset <= segment.file_offset + segment.size
+        });
+        for (i, segment) in self.segments.iter_mut().enumerate() {
+            if Some(i) == growing {
+                segment.size += new_len;
+            } else if segment.file_offset >= offset {
+                segment.file_offset += new_len;
+            }
+        }
+    }
+
+    /// Splits data into records of at most record_size bytes, aligned to multiples of
+    /// record_size and never crossing a 64KiB boundary.
+    pub fn records(&self, data: &[u8], record_size: usize) -> Vec<Record> {
+        let record_size = record_size.max(1) as u64;
+        let mut ret = Vec::new();
+        for segment in self.segments.iter() {
+            let start = segment.file_offset.min(data.len());
+            let end = (segment.file_offset + segment.size).min(data.len());
+            let mut offset = start;
+            while offset < end {
+                let address = segment.address + (offset - start) as u64;
+                let boundary =
+                    ((address / record_size + 1) * record_size).min(((address >> 16) + 1) << 16);
+                let len = ((boundary - address) as usize).min(end - offset);
+                ret.push(Record {
+                    address,
+                    bytes: data[offset..offset + len].to_vec(),
+                    family_id: segment.family_id,
+                });
+                offset += len;
+            }
+        }
+        ret
+    }
+
+    /// Writes the image back in its format, with the checksums of the changed data.
+    pub fn encode(&self, data: &[u8]) -> Vec<u8> {
+        match self.format {
+            FirmwareFormat::IntelHex => intel_hex::encode(self, data),
+            FirmwareFormat::SRecord => srec::encode(self, data),
+            FirmwareFormat::Uf2 => uf2::encode(self, data),
+        }
+    }
+
+    /// Counts the UF2 blocks that are not written back as they were, with their
+    /// flags, checksum and extension tags, because they were moved, resized,
+    /// or the image was converted to another format.
+    pub fn dropped_metadata(&self, data: &[u8]) -> usize {
+        match self.format {
+            FirmwareFormat::Uf2 => uf2::dropped_metadata(self, data),
+            _ => self.other_blocks.len() + self.block_metadata.len(),
+        }
+    }
+
+    /// Changes the format the image is written in.
+    pub fn convert(&mut self, format: FirmwareFormat) {
+        if self.format != format {
+            self.format = format;
+            self.record_size = format.default_record_size();
+        }
+    }
+
+    /// Each segment is a code section.
+    pub fn sections(&self) -> Vec<Section> {
+        self.segments
+            .iter()
+            .map(|segment| Section {
+                name: ".text".to_string(),
+                virtual_address: segment.address,
+                file_offset: segment.file_offset as u64,
+                size: segment.size as u64,
+            })
+            .collect()
+    }
+
+    /// Builds the header of the image, the architecture is the one of the raw binary
+    /// description, or the one of the UF2 family, x86_64 if neither is known.
+    /// The entry point is the start address of the file, or the one of the description,
+    /// if they are inside a segment, otherwise the start of the first segment.
+    pub fn to_header(&self, raw_binary: Option<&RawBinary>) -> Result<Header, String> {
+        let family = self
+            .segments
+            .iter()
+            .find_map(|segment| segment.family_id.and_then(family_architecture));
+        let mut header = match (raw_binary, family) {
+            (Some(raw_binary), _) => raw_binary.to_header(0)?,
+            (None, Some((architecture, _))) => Self::empty_header(architecture, Bitness::Bit32),
+            (None, None) => Self::empty_header(Architecture::X86_64, Bitness::Bit64),
+        };
+        let inside = |address: &u64| {
+            self.segments
+                .iter()
+                .any(|segment| (segment.address..segment.end()).contains(address))
+        };
+        let entry = self
+            .entry
+            .filter(inside)
+            .or(raw_binary.map(|raw_binary| raw_binary.entry).filter(inside))
+            .or(self.segments.first().map(|segment| segment.address))
+            .unwrap_or_default();
+        let architecture = header.architecture();
+        if let Header::CustomHeader(custom) = &mut header {
+            custom.entry = entry;
+            custom.sections = self.sections();
+        }
+        // the boards of a family run only one instruction set
+        for segment in self.segments.iter() {
+            if let Some((segment_architecture, mode)) =
+                segment.family_id.and_then(family_architecture)
+            {
+                if segment_architecture == architecture && mode != IsaMode::Default {
+                    header.set_isa_mode(segment.address..segment.end(), mode);
+                }
+            }
+        }
+        Ok(header)
+    }
+
+    fn empty_header(architecture: Architecture, bitness: Bitness) -> Header {
+        Header::CustomHeader(CustomHeader {
+            bitness,
+            entry: 0,
+            endianness: Endianness::Little,
+            architecture,
+            sections: Vec::new(),
+            symbols: HashMap::new(),
+            symbols_by_name: HashMap::new(),
+            isa_modes: IsaModes::new(),
+        })
+    }
+}
+
+/// The architecture and the instruction set of the boards of some UF2 families.
+fn family_architecture(family_id: u32) -> Option<(Architecture, IsaMode)> {
+    match family_id {
+        // RP2040, RP2350 Arm, SAMD21, SAMD51, nRF52840, STM32F1, STM32F4
+        0xE48BFF56 | 0xE48BFF59 | 0x68ED2B88 | 0x55114460 | 0xADA52840 | 0x5EE21072
+        | 0x57755A57 => Some((Architecture::Arm, IsaMode::Thumb)),
+        // RP2350 RISC-V, ESP32-C3
+        0xE48BFF5A | 0xD42BA06C => Some((Architecture::Riscv32, IsaMode::Default)),
+        _ => None,
+    }
+}
+
+/// Parses the pairs of hex digits of a text record.
+pub(super) fn hex_bytes(text: &str) -> Option<Vec<u8>> {
+    if text.len() % 2 != 0 || !text.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&text[i..i + 2], 16).ok())
+        .collect()
+}
+
+impl App {
+    /// Decodes a firmware image, the file is opened as raw bytes if it is not valid.
+    pub(in crate::app) fn decode_firmware(
+        &mut self,
+        format: FirmwareFormat,
+        file: Vec<u8>,
+    ) -> Vec<u8> {
+        match format.decode(&file) {
+            Ok((image, data)) => {
+                self.log(
+                    NotificationLevel::Info,
+                    t!(
+                        "app.messages.firmware_decoded",
+                        count = image.segments.len(),
+                        format = format
+                    ),
+                );
+                self.firmware = Some(image);
+                data
+            }
+            Err(e) => {
+                self.log(
+                    NotificationLevel::Warning,
+                    t!("app.messages.firmware_not_decoded", format = format, e = e),
+                );
+                file
+            }
+        }
+    }
+
+    /// Moves the segments of the firmware image after old_len bytes at offset were
+    /// replaced with new_len bytes, the layout before the last change is recorded
+    /// in it for its undo.
+    pub(in crate::app) fn rebase_firmware(
+        &mut self,
+        offset: usize,
+        old_len: usize,
+        new_len: usize,
+    ) {
+        let Some(image) = &mut self.firmware else {
+            return;
+        };
+        if let Some(change) = self.data.last_change_mut() {
+            change.set_segments(image.segments.clone());
+        }
+        let sections = image.sections();
+        image.rebase(offset, old_len, new_len);
+        self.firmware_segments_changed(sections);
+    }
+
+    /// Puts back the segments of the firmware image as they were before change.
+    pub(in crate::app) fn restore_firmware(&mut self, change: &Change) {
+        let Some(image) = &mut self.firmware else {
+            return;
+        };
+        let sections = image.sections();
+        match change.segments() {
+            Some(segments) => image.segments = segments.to_vec(),
+            // a history saved without the layout of the segments
+            None => image.rebase(change.offset(), change.new_len(), change.old_len()),
+        }
+        self.firmware_segments_changed(sections);
+    }
+
+    /// The sections of the header built from the segments follow them, the ones of
+    /// a header made by a plugin are left alone.
+    fn firmware_segments_changed(&mut self, old_sections: Vec<Section>) {
+        let Some(image) = &self.firmware else {
+            return;
+        };
+        if let Header::CustomHeader(header) = &mut self.header {
+            if header.sections == old_sections {
+                header.sections = image.sections();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_firmware_image() {
+        assert_eq!(
+            FirmwareFormat::from_path("a/b.HEX"),
+            Some(FirmwareFormat::IntelHex)
+        );
+        assert_eq!(
+            FirmwareFormat::from_path("b.s19"),
+            Some(FirmwareFormat::SRecord)
+        );
+        assert_eq!(
+            FirmwareFormat::from_path("b.uf2"),
+            Some(FirmwareFormat::Uf2)
+        );
+        assert_eq!(FirmwareFormat::from_path("b.bin"), None);
+
+        let record = |address, bytes: &[u8]| Record {
+            address,
+            bytes: bytes.to_vec(),
+            family_id: None,
+        };
+        let (mut image, data) = FirmwareImage::from_records(
+            FirmwareFormat::IntelHex,
+            vec![
+                record(0x2000, &[5, 6]),
+                record(0x1004, &[3, 4]),
+                record(0x1000, &[1, 2, 0, 0]),
+                record(0x1002, &[9]),
+            ],
+        );
+        assert_eq!(data, vec![1, 2, 9, 0, 3, 4, 5, 6]);
+        assert_eq!(image.record_size, 4);
+        assert_eq!(
+            image.segments,
+            vec![
+                Segment {
+                    address: 0x1000,
+                    file_offset: 0,
+                    size: 6,
+                    family_id: None,
+                },
+                Segment {
+                    address: 0x2000,
+                    file_offset: 6,
+                    size: 2,
+                    family_id: None,
+                },
+            ]
+        );
+        assert_eq!(
+            image.records(&data, 4),
+            vec![
+                record(0x1000, &[1, 2, 9, 0]),
+                record(0x1004, &[3, 4]),
+                record(0x2000, &[5, 6]),
+            ]
+        );
+        // the bytes inserted in a segment move the later ones without changing
+        // their addresses, the deleted ones take their addresses with them
+        let mut rebased = image.clone();
+        rebased.rebase(6, 0, 1);
+        assert_eq!(rebased.segments[0].size, 7);
+        assert_eq!(
+            rebased.records(&[1, 2, 9, 0, 3, 4, 7, 5, 6], 4)[2],
+            record(0x2000, &[5, 6])
+        );
+        rebased.rebase(4, 3, 0);
+        assert_eq!(rebased.segments[0].size, 4);
+        assert_eq!(rebased.segments[1].file_offset, 4);
+        rebased.rebase(3, 2, 0);
+        assert_eq!(rebased.segments[1].address, 0x2001);
+        assert_eq!(rebased.segments[1].file_offset, 3);
+        rebased.rebase(0, 4, 0);
+        assert_eq!(rebased.segments.len(), 0);
+
+        image.entry = Some(0x1004);
+        let header = image.to_header(None).unwrap();
+        assert_eq!(header.architecture(), Architecture::X86_64);
+        assert_eq!(header.entry_point(), 0x1004);
+        assert_eq!(header.virtual_to_physical_address(0x2001), Some(7));
+        let raw_binary = RawBinary::new("arm", None, None, 0, Some(0x2000)).unwrap();
+        image.entry = Some(0x3000);
+        let header = image.to_header(Some(&raw_binary)).unwrap();
+        assert_eq!(header.architecture(), Architecture::Arm);
+        assert_eq!(header.entry_point(), 0x2000);
+        assert_eq!(header.isa_mode_at(0x1000), IsaMode::Default);
+
+        image.segments[0].family_id = Some(0xE48BFF56);
+        let header = image.to_header(None).unwrap();
+        assert_eq!(header.architecture(), Architecture::Arm);
+        assert_eq!(header.isa_mode_at(0x1000), IsaMode::Thumb);
+        assert_eq!(header.isa_mode_at(0x2000), IsaMode::Default);
+    }
+
+    #[test]
+    fn test_firmware_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name).to_string_lossy().to_string();
+        // 4 nops at 0x1000
+        std::fs::write(path("firmware.hex"), ":0410000090909090AC\n:00000001FF\n").unwrap();
+        let mut terminal =
+            ratatui::Terminal::new(ratatui::backend::TestBackend::new(80, 24)).unwrap();
+        let mut app = App::default();
+        app.open_file(&path("firmware.hex"), &mut terminal).unwrap();
+        assert_eq!(app.data.bytes(), &[0x90; 4]);
+        assert_eq!(app.header.get_sections()[0].virtual_address, 0x1000);
+        assert_eq!(app.header.entry_point(), 0x1000);
+
+        let ret = app.bytes_from_assembly("ret", 0x1000).unwrap();
+        app.data.push_change(0, ret);
+        app.save_file().unwrap();
+        assert_eq!(
+            std::fs::read_to_string(path("firmware.hex")).unwrap(),
+            ":04100000C390909079\n:00000001FF\n"
+        );
+
+        app.save_file_as(&path("firmware.s19")).unwrap();
+        let file = std::fs::read(path("firmware.s19")).unwrap();
+        let (image, data) = FirmwareFormat::SRecord.decode(&file).unwrap();
+        assert_eq!(data, vec![0xC3, 0x90, 0x90, 0x90]);
+        assert_eq!(image.segments[0].address, 0x1000);
+
+        // nops at 0x1000 and a ret at 0x2000
+        std::fs::write(
+            path("segments.hex"),
+            ":0410000090909090AC\n:01200000C31C\n:00000001FF\n",
+        )
+        .unwrap();
+        app.open_file(&path("segments.hex"), &mut terminal).unwrap();
+        app.insert_bytes(&[0xCC]);
+        assert_eq!(app.header.get_sections()[1].file_offset, 5);
+        assert_eq!(app.header.get_sections()[1].virtual_address, 0x2000);
+        app.save_file().unwrap();
+        let file = std::fs::read(path("segments.hex")).unwrap();
+        let (image, data) = FirmwareFormat::IntelHex.decode(&file).unwrap();
+        assert_eq!(data, vec![0xCC, 0x90, 0x90, 0x90, 0x90, 0xC3]);
+        assert_eq!(image.segments[0].size, 5);
+        assert_eq!(image.segments[1].address, 0x2000);
+
+        std::fs::write(path("broken.hex"), "not a hex file").unwrap();
+        app.open_file(&path("broken.hex"), &mut terminal).unwrap();
+        assert_eq!(app.firmware, None);
+        assert_eq!(app.data.bytes(), b"not a hex file");
+    }
+
+    #[test]
+    fn test_firmware_undo() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut terminal =
+            ratatui::Terminal::new(ratatui::backend::TestBackend::new(80, 24)).unwrap();
+        let mut app = App::default();
+        // nops at 0x1000, then a nop and a ret at 0x2000
+        for (name, file) in [
+            (
+                "undo.hex",
+                ":0410000090909090AC\n:0220000090C38B\n:00000001FF\n",
+            ),
+            (
+                "undo.s19",
+                "S0030000FC\nS107100090909090A8\nS105200090C387\nS5030002FA\nS9030000FC\n",
+            ),
+        ] {
+            let path = dir.path().join(name).to_string_lossy().to_string();
+            std::fs::write(&path, file).unwrap();
+            app.open_file(&path, &mut terminal).unwrap();
+
+            // the first byte of the second segment, then the whole segment
+            for count in [1, 2] {
+                app.jump_to(4, false);
+                app.delete_bytes(count);
+                assert_eq!(app.firmware.as_ref().unwrap().segments.len(), 3 - count);
+                app.undo();
+                assert_eq!(app.header.get_sections()[1].virtual_address, 0x2000);
+                app.save_file().unwrap();
+                assert_eq!(std::fs::read_to_string(&path).unwrap(), file);
+            }
+
+            // the redo after the undo moves the segment again
+            app.redo();
+            app.undo();
+            app.save_file().unwrap();
+            assert_eq!(std::fs::read_to_string(&path).unwrap(), file);
+        }
+    }
+}
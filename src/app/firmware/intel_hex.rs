@@ -0,0 +1,179 @@
+use std::fmt::Write;
+
+use super::firmware_image::{hex_bytes, FirmwareFormat, FirmwareImage, Record};
+
+const DATA: u8 = 0x00;
+const END_OF_FILE: u8 = 0x01;
+const EXTENDED_SEGMENT_ADDRESS: u8 = 0x02;
+const START_SEGMENT_ADDRESS: u8 = 0x03;
+const EXTENDED_LINEAR_ADDRESS: u8 = 0x04;
+const START_LINEAR_ADDRESS: u8 = 0x05;
+const MAX_RECORD: usize = 0xFF;
+
+/// Decodes the records of an Intel HEX file, the checksum of each one is checked.
+pub fn decode(file: &[u8]) -> Result<(FirmwareImage, Vec<u8>), String> {
+    let format = FirmwareFormat::IntelHex;
+    let mut records = Vec::new();
+    let mut base = 0;
+    let mut entry = None;
+    let mut start_segment = None;
+    for (i, line) in String::from_utf8_lossy(file).lines().enumerate() {
+        let line_number = i + 1;
+        let invalid = || {
+            t!(
+                "errors.invalid_firmware_record",
+                format = format,
+                record = line_number
+            )
+            .to_string()
+        };
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let bytes = line
+            .strip_prefix(':')
+            .and_then(hex_bytes)
+            .ok_or_else(invalid)?;
+        if bytes.len() < 5 || bytes.len() != bytes[0] as usize + 5 {
+            return Err(invalid());
+        }
+        if bytes.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte)) != 0 {
+            return Err(t!(
+                "errors.firmware_checksum",
+                format = format,
+                record = line_number
+            )
+            .to_string());
+        }
+        let address = u16::from_be_bytes([bytes[1], bytes[2]]) as u64;
+        let data = &bytes[4..bytes.len() - 1];
+        match (bytes[3], data.len()) {
+            (DATA, _) => records.push(Record {
+                address: base + address,
+                bytes: data.to_vec(),
+                family_id: None,
+            }),
+            (END_OF_FILE, _) => break,
+            (EXTENDED_SEGMENT_ADDRESS, 2) => {
+                base = (u16::from_be_bytes([data[0], data[1]]) as u64) << 4;
+            }
+            (START_SEGMENT_ADDRESS, 4) => {
+                let segment = u16::from_be_bytes([data[0], data[1]]);
+                let offset = u16::from_be_bytes([data[2], data[3]]);
+                entry = Some(((segment as u64) << 4) + offset as u64);
+                start_segment = Some((segment, offset));
+            }
+            (EXTENDED_LINEAR_ADDRESS, 2) => {
+                base = (u16::from_be_bytes([data[0], data[1]]) as u64) << 16;
+            }
+            (START_LINEAR_ADDRESS, 4) => {
+                entry = Some(u32::from_be_bytes([data[0], data[1], data[2], data[3]]) as u64);
+                start_segment = None;
+            }
+            _ => return Err(invalid()),
+        }
+    }
+    let (mut image, data) = FirmwareImage::from_records(format, records);
+    image.entry = entry;
+    image.start_segment = start_segment;
+    Ok((image, data))
+}
+
+/// Writes the data records of the image, preceded by an extended linear address
+/// record whenever the upper 16 bits of the address change. The entry point is
+/// written as a start linear address record, or as the start segment address
+/// record of the original file if it still points at it.
+pub fn encode(image: &FirmwareImage, data: &[u8]) -> Vec<u8> {
+    let mut ret = String::new();
+    let mut base = 0;
+    for record in image.records(data, image.record_size.min(MAX_RECORD)) {
+        let upper = record.address >> 16;
+        if upper != base {
+            write_record(
+                &mut ret,
+                0,
+                EXTENDED_LINEAR_ADDRESS,
+                &(upper as u16).to_be_bytes(),
+            );
+            base = upper;
+        }
+        write_record(&mut ret, record.address as u16, DATA, &record.bytes);
+    }
+    match (image.entry, image.start_segment) {
+        (Some(entry), Some((segment, offset)))
+            if ((segment as u64) << 4) + offset as u64 == entry =>
+        {
+            let mut bytes = segment.to_be_bytes().to_vec();
+            bytes.extend_from_slice(&offset.to_be_bytes());
+            write_record(&mut ret, 0, START_SEGMENT_ADDRESS, &bytes);
+        }
+        (Some(entry), _) => write_record(
+            &mut ret,
+            0,
+            START_LINEAR_ADDRESS,
+            &(entry as u32).to_be_bytes(),
+        ),
+        (None, _) => {}
+    }
+    write_record(&mut ret, 0, END_OF_FILE, &[]);
+    ret.into_bytes()
+}
+
+/// The checksum is the two's complement of the sum of the other bytes of the record.
+fn write_record(ret: &mut String, address: u16, kind: u8, data: &[u8]) {
+    let mut bytes = vec![data.len() as u8];
+    bytes.extend_from_slice(&address.to_be_bytes());
+    bytes.push(kind);
+    bytes.extend_from_slice(data);
+    let checksum = bytes
+        .iter()
+        .fold(0u8, |sum, byte| sum.wrapping_add(*byte))
+        .wrapping_neg();
+    bytes.push(checksum);
+    ret.push(':');
+    for byte in bytes {
+        let _ = write!(ret, "{byte:02X}");
+    }
+    ret.push('\n');
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_intel_hex() {
+        let file = b":020000040800F2\n\
+            :0400000001020304F2\n\
+            :02001000AABB89\n\
+            :0400000508000101ED\n\
+            :00000001FF\n";
+        let (image, mut data) = decode(file).unwrap();
+        assert_eq!(data, vec![1, 2, 3, 4, 0xAA, 0xBB]);
+        assert_eq!(image.segments.len(), 2);
+        assert_eq!(image.segments[1].address, 0x08000010);
+        assert_eq!(image.entry, Some(0x08000101));
+        assert_eq!(encode(&image, &data), file.to_vec());
+
+        data[0] = 0xFF;
+        let encoded = String::from_utf8(encode(&image, &data)).unwrap();
+        assert_eq!(encoded.lines().nth(1), Some(":04000000FF020304F4"));
+        assert_eq!(decode(encoded.as_bytes()).unwrap().1, data);
+
+        // a segment base of 0x1000 puts the record at 0x10000
+        let (image, _) = decode(b":020000021000EC\n:01000000AA55\n").unwrap();
+        assert_eq!(image.segments[0].address, 0x10000);
+
+        // the start segment address record is kept
+        let file = b":01000000906F\n:0400000310000100E8\n:00000001FF\n";
+        let (image, data) = decode(file).unwrap();
+        assert_eq!(image.entry, Some(0x10100));
+        assert_eq!(encode(&image, &data), file.to_vec());
+
+        assert!(decode(b":0400000001020304F3\n").is_err());
+        assert!(decode(b":04000000010203F2\n").is_err());
+        assert!(decode(b"0400000001020304F2\n").is_err());
+        assert!(decode(b":0000000AF6\n").is_err());
+    }
+}
@@ -0,0 +1,4 @@
+pub mod firmware_image;
+pub mod intel_hex;
+pub mod srec;
+pub mod uf2;
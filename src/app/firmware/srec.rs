@@ -0,0 +1,177 @@
+use std::fmt::Write;
+
+use super::firmware_image::{hex_bytes, FirmwareFormat, FirmwareImage, Record};
+
+/// The count byte limits a record to 255 bytes, including the address and the checksum.
+const MAX_DATA: usize = 0xFF - 4 - 1;
+
+/// Decodes the records of a Motorola S-record file, the checksum of each one is checked.
+/// The record counts are ignored.
+pub fn decode(file: &[u8]) -> Result<(FirmwareImage, Vec<u8>), String> {
+    let format = FirmwareFormat::SRecord;
+    let mut records = Vec::new();
+    let mut entry = None;
+    let mut header_record = Vec::new();
+    let mut data_address_bytes = 2;
+    for (i, line) in String::from_utf8_lossy(file).lines().enumerate() {
+        let line_number = i + 1;
+        let invalid = || {
+            t!(
+                "errors.invalid_firmware_record",
+                format = format,
+                record = line_number
+            )
+            .to_string()
+        };
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let (kind, bytes) = line
+            .strip_prefix(['S', 's'])
+            .filter(|record| record.is_char_boundary(1))
+            .map(|record| record.split_at(1))
+            .ok_or_else(invalid)?;
+        let bytes = hex_bytes(bytes).ok_or_else(invalid)?;
+        let address_bytes = match kind {
+            "0" | "1" | "5" | "9" => 2,
+            "2" | "6" | "8" => 3,
+            "3" | "7" => 4,
+            _ => return Err(invalid()),
+        };
+        if bytes.len() < address_bytes + 2 || bytes.len() != bytes[0] as usize + 1 {
+            return Err(invalid());
+        }
+        if bytes.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte)) != 0xFF {
+            return Err(t!(
+                "errors.firmware_checksum",
+                format = format,
+                record = line_number
+            )
+            .to_string());
+        }
+        let address = bytes[1..=address_bytes]
+            .iter()
+            .fold(0u64, |address, byte| address << 8 | *byte as u64);
+        let data = &bytes[address_bytes + 1..bytes.len() - 1];
+        match kind {
+            "0" => header_record = data.to_vec(),
+            "1" | "2" | "3" => {
+                data_address_bytes = data_address_bytes.max(address_bytes);
+                records.push(Record {
+                    address,
+                    bytes: data.to_vec(),
+                    family_id: None,
+                });
+            }
+            "7" | "8" | "9" => entry = Some(address),
+            _ => {}
+        }
+    }
+    let (mut image, data) = FirmwareImage::from_records(format, records);
+    image.entry = entry;
+    image.address_bytes = data_address_bytes;
+    image.header_record = header_record;
+    Ok((image, data))
+}
+
+/// Writes the header, the data records, their count and the termination record with
+/// the entry point. The addresses are as wide as the ones of the original file, or
+/// wider if the data moved past them.
+pub fn encode(image: &FirmwareImage, data: &[u8]) -> Vec<u8> {
+    let records = image.records(data, image.record_size.min(MAX_DATA));
+    let last_address = records
+        .iter()
+        .map(|record| record.address + record.bytes.len() as u64 - 1)
+        .chain(image.entry)
+        .max()
+        .unwrap_or_default();
+    let address_bytes = match last_address {
+        0..=0xFFFF => image.address_bytes,
+        0x10000..=0xFFFFFF => image.address_bytes.max(3),
+        _ => 4,
+    }
+    .clamp(2, 4);
+
+    let mut ret = String::new();
+    let header_record = &image.header_record[..image.header_record.len().min(MAX_DATA)];
+    write_record(&mut ret, 0, 0, 2, header_record);
+    for record in records.iter() {
+        write_record(
+            &mut ret,
+            address_bytes as u8 - 1,
+            record.address,
+            address_bytes,
+            &record.bytes,
+        );
+    }
+    match records.len() {
+        count @ 0..=0xFFFF => write_record(&mut ret, 5, count as u64, 2, &[]),
+        count => write_record(&mut ret, 6, count as u64, 3, &[]),
+    }
+    write_record(
+        &mut ret,
+        11 - address_bytes as u8,
+        image.entry.unwrap_or_default(),
+        address_bytes,
+        &[],
+    );
+    ret.into_bytes()
+}
+
+/// The checksum is the ones' complement of the sum of the count, address and data bytes.
+fn write_record(ret: &mut String, kind: u8, address: u64, address_bytes: usize, data: &[u8]) {
+    let mut bytes = vec![(address_bytes + data.len() + 1) as u8];
+    bytes.extend_from_slice(&address.to_be_bytes()[8 - address_bytes..]);
+    bytes.extend_from_slice(data);
+    let checksum = !bytes.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+    bytes.push(checksum);
+    let _ = write!(ret, "S{kind}");
+    for byte in bytes {
+        let _ = write!(ret, "{byte:02X}");
+    }
+    ret.push('\n');
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_srec() {
+        let file = b"S00600004844521B\n\
+            S107010001020304ED\n\
+            S5030001FB\n\
+            S9030100FB\n";
+        let (mut image, mut data) = decode(file).unwrap();
+        assert_eq!(data, vec![1, 2, 3, 4]);
+        assert_eq!(image.segments[0].address, 0x100);
+        assert_eq!(image.entry, Some(0x100));
+        assert_eq!(image.header_record, b"HDR");
+        assert_eq!(encode(&image, &data), file.to_vec());
+
+        // the data moved past 16-bit addresses is written with S2 records
+        data.extend_from_slice(&[0; 0x10000]);
+        image.rebase(4, 0, 0x10000);
+        let encoded = String::from_utf8(encode(&image, &data)).unwrap();
+        assert!(encoded
+            .lines()
+            .nth(1)
+            .unwrap()
+            .starts_with("S2080001000102030"));
+        assert!(encoded.lines().last().unwrap().starts_with("S804000100"));
+        assert_eq!(decode(encoded.as_bytes()).unwrap().1, data);
+
+        let (image, data) = decode(b"S30900001000AABBCCDDD8\nS70500001000EA\n").unwrap();
+        assert_eq!(image.address_bytes, 4);
+        assert_eq!(
+            encode(&image, &data),
+            b"S0030000FC\nS30900001000AABBCCDDD8\nS5030001FB\nS70500001000EA\n".to_vec()
+        );
+
+        assert!(decode(b"S107010001020304EC\n").is_err());
+        assert!(decode(b"S10701000102030\n").is_err());
+        assert!(decode(b"S4030000FC\n").is_err());
+        assert!(decode(b"X107010001020304ED\n").is_err());
+    }
+}
@@ -0,0 +1,257 @@
+use std::collections::BTreeMap;
+
+use super::firmware_image::{BlockMetadata, FirmwareFormat, FirmwareImage, Record};
+
+const BLOCK_SIZE: usize = 512;
+const DATA_OFFSET: usize = 32;
+const MAX_PAYLOAD: usize = 476;
+const MAGIC_START0: u32 = 0x0A324655;
+const MAGIC_START1: u32 = 0x9E5D5157;
+const MAGIC_END: u32 = 0x0AB16F30;
+const FLAG_NOT_MAIN_FLASH: u32 = 0x00000001;
+const FLAG_FAMILY_ID: u32 = 0x00002000;
+const FLAG_MD5: u32 = 0x00004000;
+/// The address, the length and the MD5 of the region checked by FLAG_MD5.
+const MD5_OFFSET: usize = DATA_OFFSET + MAX_PAYLOAD - 24;
+
+fn word(block: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        block[offset],
+        block[offset + 1],
+        block[offset + 2],
+        block[offset + 3],
+    ])
+}
+
+/// Decodes the blocks of a UF2 file, the blocks that are not meant for the main
+/// flash are kept aside, and so are the flags and the tags of the others.
+pub fn decode(file: &[u8]) -> Result<(FirmwareImage, Vec<u8>), String> {
+    let format = FirmwareFormat::Uf2;
+    let mut records = Vec::new();
+    let mut other_blocks = Vec::new();
+    let mut block_metadata = BTreeMap::new();
+    if file.len() % BLOCK_SIZE != 0 {
+        let record = file.len() / BLOCK_SIZE + 1;
+        return Err(t!(
+            "errors.invalid_firmware_record",
+            format = format,
+            record = record
+        )
+        .into());
+    }
+    for (i, block) in file.chunks_exact(BLOCK_SIZE).enumerate() {
+        let payload_size = word(block, 16) as usize;
+        if word(block, 0) != MAGIC_START0
+            || word(block, 4) != MAGIC_START1
+            || word(block, BLOCK_SIZE - 4) != MAGIC_END
+            || payload_size > MAX_PAYLOAD
+        {
+            let record = i + 1;
+            return Err(t!(
+                "errors.invalid_firmware_record",
+                format = format,
+                record = record
+            )
+            .into());
+        }
+        let flags = word(block, 8);
+        if flags & FLAG_NOT_MAIN_FLASH != 0 {
+            other_blocks.push(block.to_vec());
+            continue;
+        }
+        let address = word(block, 12) as u64;
+        let bytes = block[DATA_OFFSET..DATA_OFFSET + payload_size].to_vec();
+        if flags & !FLAG_FAMILY_ID != 0 {
+            block_metadata.insert(
+                address,
+                BlockMetadata {
+                    flags,
+                    payload: bytes.clone(),
+                    tail: block[DATA_OFFSET + payload_size..BLOCK_SIZE - 4].to_vec(),
+                },
+            );
+        }
+        records.push(Record {
+            address,
+            bytes,
+            family_id: (flags & FLAG_FAMILY_ID != 0).then(|| word(block, 28)),
+        });
+    }
+    let (mut image, data) = FirmwareImage::from_records(format, records);
+    image.other_blocks = other_blocks;
+    image.block_metadata = block_metadata;
+    Ok((image, data))
+}
+
+/// Returns the flags and the tags of the original block written at the address of
+/// record with as many bytes, the checksum of a changed block is not valid anymore.
+fn kept_metadata<'a>(image: &'a FirmwareImage, record: &Record) -> Option<&'a BlockMetadata> {
+    image
+        .block_metadata
+        .get(&record.address)
+        .filter(|metadata| metadata.payload.len() == record.bytes.len())
+}
+
+/// Counts the blocks with flags or tags that are not written back as they were.
+pub fn dropped_metadata(image: &FirmwareImage, data: &[u8]) -> usize {
+    let records = image.records(data, image.record_size.min(MAX_PAYLOAD));
+    let kept = records
+        .iter()
+        .filter_map(|record| {
+            kept_metadata(image, record).filter(|metadata| {
+                metadata.flags & FLAG_MD5 == 0 || metadata.payload == record.bytes
+            })
+        })
+        .count();
+    image.block_metadata.len() - kept
+}
+
+/// Writes a block for each record, numbered again from 0, followed by the blocks
+/// that are not meant for the main flash. The flags and the tags of the original
+/// blocks are kept while they have the same address and size.
+pub fn encode(image: &FirmwareImage, data: &[u8]) -> Vec<u8> {
+    let records = image.records(data, image.record_size.min(MAX_PAYLOAD));
+    let count = records.len() + image.other_blocks.len();
+    let mut ret = Vec::with_capacity(count * BLOCK_SIZE);
+    for (i, record) in records.iter().enumerate() {
+        let mut block = [0u8; BLOCK_SIZE];
+        let mut flags = match record.family_id {
+            Some(_) => FLAG_FAMILY_ID,
+            None => 0,
+        };
+        if let Some(metadata) = kept_metadata(image, record) {
+            flags = metadata.flags;
+            block[DATA_OFFSET + record.bytes.len()..BLOCK_SIZE - 4].copy_from_slice(&metadata.tail);
+            if flags & FLAG_MD5 != 0 && metadata.payload != record.bytes {
+                flags &= !FLAG_MD5;
+                block[MD5_OFFSET..DATA_OFFSET + MAX_PAYLOAD].fill(0);
+            }
+        }
+        let words = [
+            MAGIC_START0,
+            MAGIC_START1,
+            flags,
+            record.address as u32,
+            record.bytes.len() as u32,
+            i as u32,
+            count as u32,
+            record.family_id.unwrap_or_default(),
+        ];
+        for (j, word) in words.iter().enumerate() {
+            block[j * 4..j * 4 + 4].copy_from_slice(&word.to_le_bytes());
+        }
+        block[DATA_OFFSET..DATA_OFFSET + record.bytes.len()].copy_from_slice(&record.bytes);
+        block[BLOCK_SIZE - 4..].copy_from_slice(&MAGIC_END.to_le_bytes());
+        ret.extend_from_slice(&block);
+    }
+    for (i, block) in image.other_blocks.iter().enumerate() {
+        let mut block = block.clone();
+        block[20..24].copy_from_slice(&((records.len() + i) as u32).to_le_bytes());
+        block[24..28].copy_from_slice(&(count as u32).to_le_bytes());
+        ret.extend_from_slice(&block);
+    }
+    ret
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn block(flags: u32, address: u32, payload: &[u8], family_id: u32) -> Vec<u8> {
+        let mut ret = vec![0; BLOCK_SIZE];
+        for (i, word) in [
+            MAGIC_START0,
+            MAGIC_START1,
+            flags,
+            address,
+            payload.len() as u32,
+            0,
+            2,
+            family_id,
+        ]
+        .iter()
+        .enumerate()
+        {
+            ret[i * 4..i * 4 + 4].copy_from_slice(&word.to_le_bytes());
+        }
+        ret[DATA_OFFSET..DATA_OFFSET + payload.len()].copy_from_slice(payload);
+        ret[BLOCK_SIZE - 4..].copy_from_slice(&MAGIC_END.to_le_bytes());
+        ret
+    }
+
+    #[test]
+    fn test_uf2() {
+        let mut file = block(FLAG_FAMILY_ID, 0x10000000, &[1; 256], 0xE48BFF56);
+        file.extend(block(FLAG_NOT_MAIN_FLASH, 0, b"comment", 0));
+        let (mut image, mut data) = decode(&file).unwrap();
+        assert_eq!(data, vec![1; 256]);
+        assert_eq!(image.record_size, 256);
+        assert_eq!(image.segments[0].address, 0x10000000);
+        assert_eq!(image.segments[0].family_id, Some(0xE48BFF56));
+
+        data[0] = 2;
+        data.extend_from_slice(&[3; 16]);
+        image.rebase(256, 0, 16);
+        let encoded = encode(&image, &data);
+        assert_eq!(encoded.len(), 3 * BLOCK_SIZE);
+        assert_eq!(word(&encoded, 8), FLAG_FAMILY_ID);
+        assert_eq!(word(&encoded, 24), 3);
+        assert_eq!(word(&encoded, BLOCK_SIZE + 12), 0x10000100);
+        assert_eq!(word(&encoded, BLOCK_SIZE + 16), 16);
+        assert_eq!(word(&encoded, BLOCK_SIZE + 20), 1);
+        // the block that is not meant for the main flash is kept
+        assert_eq!(word(&encoded, 2 * BLOCK_SIZE + 8), FLAG_NOT_MAIN_FLASH);
+        assert_eq!(word(&encoded, 2 * BLOCK_SIZE + 20), 2);
+        assert_eq!(&encoded[2 * BLOCK_SIZE + DATA_OFFSET..][..7], b"comment");
+        assert_eq!(decode(&encoded).unwrap().1, data);
+        assert_eq!(image.dropped_metadata(&data), 0);
+
+        assert!(decode(&file[..BLOCK_SIZE - 1]).is_err());
+        file[BLOCK_SIZE - 1] = 0;
+        assert!(decode(&file).is_err());
+    }
+
+    #[test]
+    fn test_uf2_metadata() {
+        const FLAG_EXTENSION_TAGS: u32 = 0x00008000;
+        let flags = FLAG_FAMILY_ID | FLAG_MD5 | FLAG_EXTENSION_TAGS;
+        let mut file = block(flags, 0x2000, &[1; 256], 0xE48BFF56);
+        // a version tag after the payload, and the checksum of the block
+        file[DATA_OFFSET + 256..][..8].copy_from_slice(b"\x08\x00\x00\x9f1.0\x00");
+        file[MD5_OFFSET..][..8].copy_from_slice(&[0x00, 0x20, 0, 0, 0, 1, 0, 0]);
+        file[MD5_OFFSET + 8..][..16].fill(0xAB);
+        file.extend(block(
+            FLAG_FAMILY_ID | FLAG_MD5,
+            0x2100,
+            &[2; 256],
+            0xE48BFF56,
+        ));
+        file[BLOCK_SIZE + 20] = 1;
+        let (mut image, mut data) = decode(&file).unwrap();
+        assert_eq!(image.block_metadata.len(), 2);
+        assert_eq!(encode(&image, &data), file);
+
+        // the checksum of a changed block is dropped, its tags are kept
+        data[0] = 3;
+        let encoded = encode(&image, &data);
+        assert_eq!(word(&encoded, 8), FLAG_FAMILY_ID | FLAG_EXTENSION_TAGS);
+        assert_eq!(
+            &encoded[DATA_OFFSET + 256..][..8],
+            &file[DATA_OFFSET + 256..][..8]
+        );
+        assert!(encoded[MD5_OFFSET..DATA_OFFSET + MAX_PAYLOAD]
+            .iter()
+            .all(|byte| *byte == 0));
+        assert_eq!(image.dropped_metadata(&data), 1);
+
+        // a resized block loses its flags
+        data.truncate(256 + 128);
+        image.rebase(256 + 128, 128, 0);
+        let encoded = encode(&image, &data);
+        assert_eq!(word(&encoded, BLOCK_SIZE + 8), FLAG_FAMILY_ID);
+        assert_eq!(image.dropped_metadata(&data), 2);
+
+        image.convert(FirmwareFormat::IntelHex);
+        assert_eq!(image.dropped_metadata(&data), 2);
+    }
+}
@@ -17,5 +17,6 @@ pub struct FrameInfo {
 #[derive(Debug, Clone, Copy)]
 pub enum InfoViewFrameInfo {
     TextView,
+    DiffView,
     AssemblyView { scroll: usize },
 }
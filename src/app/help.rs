@@ -237,6 +237,74 @@ impl App {
                 &Self::key_event_to_string(key_settings.redo),
                 &t!("app.help.redo"),
             ),
+            HelpLine::new(
+                &Self::key_event_to_string(key_settings.insert_byte),
+                &t!("app.help.insert_byte"),
+            ),
+            HelpLine::new(
+                &Self::key_event_to_string(key_settings.delete_byte),
+                &t!("app.help.delete_byte"),
+            ),
+            HelpLine::new(
+                &Self::key_event_to_string(key_settings.select),
+                &t!("app.help.select"),
+            ),
+            HelpLine::new(
+                &Self::key_event_to_string(key_settings.copy),
+                &t!("app.help.copy"),
+            ),
+            HelpLine::new(
+                &Self::key_event_to_string(key_settings.paste),
+                &t!("app.help.paste"),
+            ),
+            HelpLine::new(
+                &Self::key_event_to_string(key_settings.find_pattern),
+                &t!("app.help.find_pattern"),
+            ),
+            HelpLine::new(
+                &Self::key_event_to_string(key_settings.find_assembly),
+                &t!("app.help.find_assembly"),
+            ),
+            HelpLine::new(
+                &Self::key_event_to_string(key_settings.xrefs),
+                &t!("app.help.xrefs"),
+            ),
+            HelpLine::new(
+                &Self::key_event_to_string(key_settings.rename_label),
+                &t!("app.help.rename_label"),
+            ),
+            HelpLine::new(
+                &Self::key_event_to_string(key_settings.edit_symbol),
+                &t!("app.help.edit_symbol"),
+            ),
+            HelpLine::new(
+                &Self::key_event_to_string(key_settings.inspector),
+                &t!("app.help.inspector"),
+            ),
+            HelpLine::new(
+                &Self::key_event_to_string(key_settings.apply_struct),
+                &t!("app.help.apply_struct"),
+            ),
+            HelpLine::new(
+                &Self::key_event_to_string(key_settings.edit_field),
+                &t!("app.help.edit_field"),
+            ),
+            HelpLine::new(
+                &Self::key_event_to_string(key_settings.next_match),
+                &t!("app.help.next_match"),
+            ),
+            HelpLine::new(
+                &Self::key_event_to_string(key_settings.previous_match),
+                &t!("app.help.previous_match"),
+            ),
+            HelpLine::new(
+                &Self::key_event_to_string(key_settings.next_difference),
+                &t!("app.help.next_difference"),
+            ),
+            HelpLine::new(
+                &Self::key_event_to_string(key_settings.previous_difference),
+                &t!("app.help.previous_difference"),
+            ),
             HelpLine::new(
                 &Self::key_event_to_string(key_settings.help),
                 &t!("app.help.help"),
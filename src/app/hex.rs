@@ -1,12 +1,12 @@
-use std::error::Error;
+use std::{error::Error, ops::Range};
 
 use ratatui::text::{Line, Span, Text};
 
 use crate::get_app_context;
 
 use super::{
-    asm::assembly_line::AssemblyLine, info_mode::InfoMode, pane::Pane,
-    settings::color_settings::ColorSettings, App,
+    asm::assembly_line::AssemblyLine, history::change::Change, info_mode::InfoMode,
+    inspector::INSPECTOR_WIDTH, pane::Pane, settings::color_settings::ColorSettings, App,
 };
 
 pub(super) struct InstructionInfo {
@@ -16,6 +16,7 @@ pub(super) struct InstructionInfo {
 }
 
 impl App {
+    #[allow(clippy::too_many_arguments)]
     pub(super) fn bytes_to_styled_hex(
         color_settings: &ColorSettings,
         bytes: &[u8],
@@ -24,6 +25,8 @@ impl App {
         selected_byte_index: usize,
         high_byte: bool,
         instruction_info: Option<InstructionInfo>,
+        selection: Option<Range<usize>>,
+        differences: &[bool],
     ) -> Text<'static> {
         let mut ret = Text::default();
         ret.lines
@@ -60,6 +63,19 @@ impl App {
                 }
             }
 
+            if differences.get(byte_index as usize) == Some(&true) {
+                style = color_settings.hex_difference;
+            }
+
+            if let Some(selection) = &selection {
+                if selection.contains(&(byte_index as usize)) {
+                    if byte_index as usize + 1 != selection.end {
+                        space_style = color_settings.hex_selection;
+                    }
+                    style = color_settings.hex_selection;
+                }
+            }
+
             let span = Span::styled(
                 hex_high,
                 if byte_index == selected_byte_index as isize && high_byte {
@@ -107,8 +123,14 @@ impl App {
     }
 
     pub(super) fn resize_to_size(&mut self, width: u16, height: u16) {
-        let blocks_per_row: usize =
-            Self::calc_blocks_per_row(self.block_size, width, self.fullscreen, self.selected_pane);
+        let blocks_per_row: usize = Self::calc_blocks_per_row(
+            self.block_size,
+            width,
+            self.fullscreen,
+            self.selected_pane,
+            self.inspector,
+            self.diff.is_some(),
+        );
         if (width, height) != self.screen_size || blocks_per_row != self.blocks_per_row {
             self.screen_size = (width, height);
             self.resize(blocks_per_row);
@@ -127,13 +149,23 @@ impl App {
         width: u16,
         fullscreen: bool,
         selected_pane: Pane,
+        inspector: bool,
+        diff: bool,
     ) -> usize {
         let block_characters_hex = block_size * 3 + 1;
-        let block_characters_text = block_size * 2 + 1;
-        let available_width = width.saturating_sub(18 + 2 + 2);
+        // the compared file is shown as hex in place of the text view
+        let block_characters_text = if diff {
+            block_characters_hex
+        } else {
+            block_size * 2 + 1
+        };
+        let mut available_width = width.saturating_sub(18 + 2 + 2);
+        if inspector && !fullscreen {
+            available_width = available_width.saturating_sub(INSPECTOR_WIDTH);
+        }
         let complessive_chars_per_block = if fullscreen {
             match selected_pane {
-                Pane::Hex => block_characters_hex,
+                Pane::Hex | Pane::Inspector => block_characters_hex,
                 Pane::View => block_characters_text,
             }
         } else {
@@ -155,6 +187,9 @@ impl App {
     pub(super) fn edit_data(&mut self, mut value: char) -> Result<(), Box<dyn Error>> {
         value = value.to_uppercase().next().unwrap();
 
+        if self.data.is_empty() {
+            return Ok(());
+        }
         if value.is_ascii_hexdigit() {
             let cursor_position = self.get_cursor_position();
 
@@ -182,6 +217,74 @@ impl App {
         Ok(())
     }
 
+    pub(super) fn insert_bytes(&mut self, bytes: &[u8]) {
+        let offset = if self.data.is_empty() {
+            0
+        } else {
+            self.get_cursor_position().global_byte_index
+        };
+        let mut new_bytes = bytes.to_vec();
+        let mut app_context = get_app_context!(self);
+        app_context.offset = offset;
+        self.plugin_manager
+            .on_edit(&mut new_bytes, &mut app_context);
+
+        // shifting the data requires all of it to be in memory
+        self.load_range(0..self.data.len());
+        let inserted_bytes = self.data.insert(offset, new_bytes);
+        if inserted_bytes > 0 {
+            self.data_resized(offset, 0, inserted_bytes);
+        }
+    }
+
+    pub(super) fn delete_bytes(&mut self, count: usize) {
+        if self.data.is_empty() {
+            return;
+        }
+        let offset = self.get_cursor_position().global_byte_index;
+        self.load_range(0..self.data.len());
+        let deleted_bytes = self.data.delete(offset, count);
+        if deleted_bytes > 0 {
+            self.data_resized(offset, deleted_bytes, 0);
+        }
+    }
+
+    /// Updates everything that depends on the file layout after old_len bytes
+    /// at offset were replaced with new_len bytes.
+    pub(super) fn data_resized(&mut self, offset: usize, old_len: usize, new_len: usize) {
+        self.rebase_firmware(offset, old_len, new_len);
+        self.layout_changed(offset, old_len, new_len);
+    }
+
+    /// Same as data_resized after the undo of change.
+    pub(super) fn data_resize_undone(&mut self, change: &Change) {
+        self.restore_firmware(change);
+        self.layout_changed(change.offset(), change.new_len(), change.old_len());
+    }
+
+    /// The comments, the struct overlay, the search results and the regions compared
+    /// with another file are attached to file offsets and follow the bytes. The user
+    /// symbols, the labels and the instruction set modes are attached to virtual
+    /// addresses, like the symbols of the header that are not moved by a resize, so
+    /// they stay at their address.
+    fn layout_changed(&mut self, offset: usize, old_len: usize, new_len: usize) {
+        self.comments
+            .rebase(offset as u64, old_len as u64, new_len as u64);
+        if self
+            .struct_overlay
+            .as_mut()
+            .is_some_and(|overlay| !overlay.rebase(offset, old_len, new_len))
+        {
+            self.struct_overlay = None;
+        }
+        if let Some(diff) = &mut self.diff {
+            diff.rebase(offset, old_len, new_len);
+        }
+        self.rebase_search_results(offset, old_len, new_len);
+        self.disassemble();
+        self.jump_to(offset, false);
+    }
+
     /// start_row is included, end_row is excluded
     pub(super) fn get_hex_view(&self, start_row: usize, end_row: usize) -> Text<'static> {
         let start_byte = start_row * self.blocks_per_row * self.block_size;
@@ -220,6 +323,9 @@ impl App {
             selected_byte_index,
             high_byte,
             instruction_info,
+            self.get_selection()
+                .map(|s| s.start.saturating_sub(start_byte)..s.end.saturating_sub(start_byte)),
+            &self.get_differences(start_byte, end_byte),
         )
     }
 }
@@ -260,4 +366,37 @@ mod test {
         assert_eq!(App::u8_to_hex(0xF0), ['F', '0']);
         assert_eq!(App::u8_to_hex(0xFF), ['F', 'F']);
     }
+
+    #[test]
+    fn test_insert_delete_bytes() {
+        let mut app = App::mockup(vec![0x90; 4]);
+        app.resize_to_size(80, 24);
+        app.comments.insert(2, "comment".to_string());
+        app.move_cursor(2, 0, false);
+        app.insert_bytes(&[0xcc, 0xcc]);
+        assert_eq!(app.data.bytes(), &[0x90, 0xcc, 0xcc, 0x90, 0x90, 0x90]);
+        assert_eq!(app.comments.get(&4), Some(&"comment".to_string()));
+        assert_eq!(app.assembly_offsets.len(), 6);
+        assert_eq!(app.get_cursor_position().global_byte_index, 1);
+
+        app.delete_bytes(3);
+        assert_eq!(app.data.bytes(), &[0x90, 0x90, 0x90]);
+        assert_eq!(app.comments.get(&1), Some(&"comment".to_string()));
+        assert_eq!(app.assembly_offsets.len(), 3);
+
+        app.undo();
+        assert_eq!(app.data.bytes(), &[0x90, 0xcc, 0xcc, 0x90, 0x90, 0x90]);
+        assert_eq!(app.assembly_offsets.len(), 6);
+        app.move_cursor_to_end();
+        app.delete_bytes(1);
+        assert_eq!(app.data.bytes(), &[0x90, 0xcc, 0xcc, 0x90, 0x90]);
+        assert_eq!(app.get_cursor_position().global_byte_index, 4);
+
+        // editing a file whose bytes were all deleted does nothing
+        app.move_cursor_to_start();
+        app.delete_bytes(5);
+        assert!(app.data.is_empty());
+        app.edit_data('a').unwrap();
+        assert!(app.data.is_empty());
+    }
 }
@@ -1,19 +1,27 @@
-#[derive(Debug, Clone, PartialEq, Eq)]
+use serde::{Deserialize, Serialize};
+
+use crate::app::{firmware::firmware_image::Segment, storage::Storage};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Change {
     offset: usize,
     old: Vec<u8>,
     new: Vec<u8>,
+    /// The segments of the firmware image before a resize, they are restored by its undo
+    /// since a segment that was deleted whole can not be told apart from its neighbours.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    segments: Option<Vec<Segment>>,
 }
 
 impl Change {
+    /// Old and new can have different lengths, in that case the change
+    /// shifts the rest of the data (insertion or deletion).
     pub fn new(offset: usize, old: &[u8], new: &[u8]) -> Self {
-        if old.len() != new.len() {
-            panic!("{}", t!("panic.data_length_mismatch"));
-        }
         Self {
             offset,
             old: old.to_vec(),
             new: new.to_vec(),
+            segments: None,
         }
     }
 
@@ -29,17 +37,62 @@ impl Change {
         self.old.is_empty()
     }
 
-    pub fn apply(&self, data: &mut Vec<u8>) {
-        data.splice(
-            self.offset..self.offset + self.old.len(),
-            self.new.iter().cloned(),
-        );
+    pub fn old_bytes(&self) -> &[u8] {
+        &self.old
+    }
+
+    pub fn new_bytes(&self) -> &[u8] {
+        &self.new
+    }
+
+    pub fn old_len(&self) -> usize {
+        self.old.len()
+    }
+
+    pub fn new_len(&self) -> usize {
+        self.new.len()
+    }
+
+    pub fn segments(&self) -> Option<&[Segment]> {
+        self.segments.as_deref()
     }
 
-    pub fn revert(&self, data: &mut Vec<u8>) {
-        data.splice(
-            self.offset..self.offset + self.new.len(),
-            self.old.iter().cloned(),
-        );
+    pub fn set_segments(&mut self, segments: Vec<Segment>) {
+        self.segments = Some(segments);
+    }
+
+    /// Returns true if the change modifies the length of the data.
+    pub fn is_resize(&self) -> bool {
+        self.old.len() != self.new.len()
+    }
+
+    pub fn apply(&self, data: &mut Storage) {
+        data.splice(self.offset..self.offset + self.old.len(), &self.new);
+    }
+
+    pub fn revert(&self, data: &mut Storage) {
+        data.splice(self.offset..self.offset + self.new.len(), &self.old);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_change_resize() {
+        let mut data = Storage::from(vec![0, 1, 2, 3]);
+        let insertion = Change::new(2, &[], &[9, 9]);
+        assert!(insertion.is_resize());
+        insertion.apply(&mut data);
+        assert_eq!(data.as_slice(), &[0, 1, 9, 9, 2, 3]);
+        insertion.revert(&mut data);
+        assert_eq!(data.as_slice(), &[0, 1, 2, 3]);
+
+        let deletion = Change::new(1, &[1, 2], &[]);
+        deletion.apply(&mut data);
+        assert_eq!(data.as_slice(), &[0, 3]);
+        deletion.revert(&mut data);
+        assert_eq!(data.as_slice(), &[0, 1, 2, 3]);
     }
 }
@@ -0,0 +1,123 @@
+use crate::app::{log::NotificationLevel, popup::popup_state::PopupState, App};
+
+use super::{change::Change, history::ROOT};
+
+/// The number of bytes shown for each side of a change in the undo tree.
+const PREVIEW_BYTES: usize = 8;
+
+/// Describes the bytes touched by a change, e.g. `1F0..1F2: 74 0E -> 90 90`.
+pub fn change_preview(change: &Change) -> String {
+    let bytes_to_string = |bytes: &[u8]| {
+        let mut ret = bytes
+            .iter()
+            .take(PREVIEW_BYTES)
+            .map(|byte| format!("{byte:02X}"))
+            .collect::<Vec<String>>()
+            .join(" ");
+        if bytes.len() > PREVIEW_BYTES {
+            ret.push_str(" …");
+        }
+        ret
+    };
+    let end = change.offset() + change.old_len().max(change.new_len());
+    format!(
+        "{:X}..{:X}: {} -> {}",
+        change.offset(),
+        end,
+        bytes_to_string(change.old_bytes()),
+        bytes_to_string(change.new_bytes())
+    )
+}
+
+impl App {
+    pub(in crate::app) fn set_checkpoint(&mut self, name: &str) {
+        self.data.set_checkpoint(name);
+        if name.is_empty() {
+            self.log(
+                NotificationLevel::Info,
+                t!("app.messages.checkpoint_removed"),
+            );
+        } else {
+            self.log(
+                NotificationLevel::Info,
+                t!("app.messages.checkpoint_set", name = name),
+            );
+        }
+    }
+
+    /// Undoes and redoes the changes needed to reach the state with the given id,
+    /// it can be in any branch of the undo tree.
+    pub(in crate::app) fn jump_to_history_state(&mut self, id: usize) {
+        let Some((undo, redo)) = self.data.history().path_to(id) else {
+            return;
+        };
+        for _ in 0..undo {
+            self.undo();
+        }
+        for id in redo {
+            self.data.select_redo(id);
+            self.redo();
+        }
+        let name = match self.data.history().checkpoint(id) {
+            Some(name) => name.to_string(),
+            None if id == ROOT => t!("app.messages.history_root").to_string(),
+            None => format!("#{id}"),
+        };
+        self.log(
+            NotificationLevel::Info,
+            t!("app.messages.jump_to_history_state", name = name),
+        );
+    }
+
+    pub(in crate::app) fn request_popup_checkpoint(&mut self) {
+        let history = self.data.history();
+        let name = history
+            .checkpoint(history.current())
+            .unwrap_or_default()
+            .to_string();
+        let cursor = name.chars().count();
+        self.popup = Some(PopupState::Checkpoint { name, cursor });
+    }
+
+    pub(in crate::app) fn request_popup_undo_tree(&mut self) {
+        let history = self.data.history();
+        let current = history.current();
+        let selected = history
+            .tree()
+            .iter()
+            .position(|line| line.id == current)
+            .unwrap_or_default();
+        self.popup = Some(PopupState::UndoTree(selected));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_jump_to_history_state() {
+        let mut app = App::mockup(vec![0; 0x20]);
+        app.data.push_change(0x10, vec![0x90, 0x90]);
+        app.set_checkpoint("nop");
+        app.data.push_change(0x10, vec![0xCC]);
+        app.undo();
+        app.undo();
+        app.data.push_change(4, vec![1]);
+        assert_eq!(app.data.bytes()[0x10], 0);
+
+        let tree = app.data.history().tree();
+        assert_eq!(tree.len(), 4);
+        assert_eq!(tree[1].checkpoint, Some("nop"));
+        assert_eq!(change_preview(tree[2].change.unwrap()), "10..11: 90 -> CC");
+        assert_eq!(tree[3].depth, 1);
+
+        app.jump_to_history_state(2);
+        assert_eq!(app.data.bytes()[0x10..0x12], [0xCC, 0x90]);
+        assert_eq!(app.data.bytes()[4], 0);
+        app.jump_to_history_state(ROOT);
+        assert_eq!(app.data.bytes(), &[0; 0x20]);
+        app.redo();
+        assert_eq!(app.data.bytes()[0x10..0x12], [0x90, 0x90]);
+    }
+}
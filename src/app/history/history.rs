@@ -1,13 +1,55 @@
 #![allow(clippy::module_inception)]
 
-use std::collections::VecDeque;
+use std::collections::{BTreeMap, HashSet};
+
+use serde::{Deserialize, Serialize};
 
 use super::change::Change;
-#[derive(Debug, Clone, Default)]
+use crate::app::storage::Storage;
+
+/// The id of the state of the data before any change, the other ids are
+/// given to the changes in the order they are made.
+pub const ROOT: usize = 0;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HistoryNode {
+    parent: usize,
+    change: Change,
+}
+
+impl HistoryNode {
+    pub fn parent(&self) -> usize {
+        self.parent
+    }
+
+    pub fn change(&self) -> &Change {
+        &self.change
+    }
+}
+
+/// A line of the undo tree, as returned by [History::tree].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistoryTreeLine<'a> {
+    pub id: usize,
+    /// The number of branches this state is nested in.
+    pub depth: usize,
+    /// None for the root.
+    pub change: Option<&'a Change>,
+    pub checkpoint: Option<&'a str>,
+}
+
+/// The changes are kept in a tree, making a change after an undo starts a new
+/// branch instead of dropping the changes that were undone.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct History {
+    #[serde(skip)]
     limit: usize,
-    changes: VecDeque<Change>,
+    nodes: BTreeMap<usize, HistoryNode>,
+    /// The child of each state that is applied by a redo, the last one that was undone.
+    redo_children: BTreeMap<usize, usize>,
+    checkpoints: BTreeMap<usize, String>,
     current: usize,
+    next_id: usize,
 }
 
 impl History {
@@ -15,61 +57,220 @@ impl History {
     pub fn with_limit(limit: usize) -> Self {
         Self {
             limit,
-            changes: VecDeque::with_capacity(limit),
-            current: 0,
+            nodes: BTreeMap::new(),
+            redo_children: BTreeMap::new(),
+            checkpoints: BTreeMap::new(),
+            current: ROOT,
+            next_id: ROOT + 1,
         }
     }
 
     pub fn push(&mut self, change: Change) {
-        self.changes.truncate(self.current);
-        if self.changes.len() >= self.limit && self.limit > 0 {
-            self.changes.remove(0);
-            self.current = self.current.saturating_sub(1);
-        }
-        self.changes.push_back(change);
-        self.current += 1;
+        let id = self.next_id.max(ROOT + 1);
+        self.next_id = id + 1;
+        self.nodes.insert(
+            id,
+            HistoryNode {
+                parent: self.current,
+                change,
+            },
+        );
+        self.redo_children.insert(self.current, id);
+        self.current = id;
+        self.prune();
     }
 
     /// Undo the last change.
     /// Returns the change that was undone, if any.
-    pub fn undo(&mut self, data: &mut Vec<u8>) -> Option<&Change> {
-        if self.current == 0 {
-            None
-        } else {
-            self.current -= 1;
-            self.changes[self.current].revert(data);
-            Some(&self.changes[self.current])
-        }
+    pub fn undo(&mut self, data: &mut Storage) -> Option<&Change> {
+        let id = self.current;
+        let node = self.nodes.get(&id)?;
+        node.change.revert(data);
+        self.redo_children.insert(node.parent, id);
+        self.current = node.parent;
+        Some(&node.change)
     }
 
-    /// Redo the last change.
+    /// Redo the last change that was undone from the current state.
     /// Returns the change that was redone, if any.
-    pub fn redo(&mut self, data: &mut Vec<u8>) -> Option<&Change> {
-        if self.current == self.changes.len() {
-            None
-        } else {
-            self.changes[self.current].apply(data);
-            self.current += 1;
-            Some(&self.changes[self.current - 1])
+    pub fn redo(&mut self, data: &mut Storage) -> Option<&Change> {
+        let id = *self.redo_children.get(&self.current)?;
+        let node = self.nodes.get(&id)?;
+        node.change.apply(data);
+        self.current = id;
+        Some(&node.change)
+    }
+
+    /// Makes the next redo from the parent of id apply id.
+    pub fn select_redo(&mut self, id: usize) {
+        if let Some(node) = self.nodes.get(&id) {
+            self.redo_children.insert(node.parent, id);
         }
     }
 
-    pub fn change_limit(&mut self, limit: usize) {
-        self.limit = limit;
-        if self.changes.len() > limit && limit > 0 {
-            self.changes.drain(0..self.changes.len() - limit);
-            self.current = limit;
+    /// Returns the number of undos needed to reach the closest common ancestor of
+    /// the current state and target, then the states to redo from there to target.
+    pub fn path_to(&self, target: usize) -> Option<(usize, Vec<usize>)> {
+        if target != ROOT && !self.nodes.contains_key(&target) {
+            return None;
+        }
+        let current_ancestors = self.ancestors(self.current);
+        let mut redo = Vec::new();
+        let mut id = target;
+        while !current_ancestors.contains(&id) {
+            redo.push(id);
+            id = self.nodes[&id].parent;
+        }
+        redo.reverse();
+        let undo = current_ancestors.iter().position(|a| *a == id).unwrap();
+        Some((undo, redo))
+    }
+
+    /// Returns id and all the states before it, up to the root.
+    fn ancestors(&self, mut id: usize) -> Vec<usize> {
+        let mut ret = vec![id];
+        while let Some(node) = self.nodes.get(&id) {
+            id = node.parent;
+            ret.push(id);
+        }
+        ret
+    }
+
+    fn children(&self, id: usize) -> impl Iterator<Item = usize> + '_ {
+        self.nodes
+            .iter()
+            .filter(move |(_, node)| node.parent == id)
+            .map(|(child, _)| *child)
+    }
+
+    /// Removes the oldest changes until the limit is respected. Only the leaves outside of
+    /// the path to the current state can be removed, or the first change of that path if
+    /// the root has no other branch.
+    fn prune(&mut self) {
+        if self.limit == 0 {
+            return;
+        }
+        while self.nodes.len() > self.limit {
+            let path: HashSet<usize> = self.ancestors(self.current).into_iter().collect();
+            let parents: HashSet<usize> = self.nodes.values().map(|node| node.parent).collect();
+            let only_child = self.children(ROOT).count() == 1;
+            let oldest = self.nodes.iter().find(|(id, node)| {
+                if path.contains(id) {
+                    node.parent == ROOT && only_child
+                } else {
+                    !parents.contains(id)
+                }
+            });
+            let Some((&id, _)) = oldest else {
+                break;
+            };
+            self.remove(id, path.contains(&id));
+        }
+    }
+
+    /// Removes a leaf, or the only child of the root if into_root is true, in that
+    /// case the state after its change becomes the root.
+    fn remove(&mut self, id: usize, into_root: bool) {
+        let Some(node) = self.nodes.remove(&id) else {
+            return;
+        };
+        if self.redo_children.get(&node.parent) == Some(&id) {
+            self.redo_children.remove(&node.parent);
         }
-        if let Some(additional) = limit.checked_sub(self.changes.capacity()) {
-            self.changes.reserve(additional);
+        let checkpoint = self.checkpoints.remove(&id);
+        let redo_child = self.redo_children.remove(&id);
+        if into_root {
+            let children: Vec<usize> = self.children(id).collect();
+            for child in children {
+                self.nodes.get_mut(&child).unwrap().parent = ROOT;
+            }
+            if let Some(child) = redo_child {
+                self.redo_children.insert(ROOT, child);
+            }
+            self.checkpoints.remove(&ROOT);
+            if let Some(checkpoint) = checkpoint {
+                self.checkpoints.insert(ROOT, checkpoint);
+            }
+            if self.current == id {
+                self.current = ROOT;
+            }
+        }
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// Returns the id of the current state.
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    /// Returns the change that led to the current state, None at the root.
+    pub fn current_change_mut(&mut self) -> Option<&mut Change> {
+        self.nodes
+            .get_mut(&self.current)
+            .map(|node| &mut node.change)
+    }
+
+    /// Returns the number of changes, in every branch.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn get(&self, id: usize) -> Option<&HistoryNode> {
+        self.nodes.get(&id)
+    }
+
+    /// Names the current state, an empty name removes the checkpoint.
+    pub fn set_checkpoint(&mut self, name: &str) {
+        if name.is_empty() {
+            self.checkpoints.remove(&self.current);
         } else {
-            self.changes.shrink_to_fit();
+            self.checkpoints.insert(self.current, name.to_string());
         }
     }
 
+    pub fn checkpoint(&self, id: usize) -> Option<&str> {
+        self.checkpoints.get(&id).map(|name| name.as_str())
+    }
+
+    /// Returns every state starting from the root, each followed by the states that
+    /// come after it, the older branches first.
+    pub fn tree(&self) -> Vec<HistoryTreeLine<'_>> {
+        let mut ret = Vec::with_capacity(self.nodes.len() + 1);
+        let mut stack = vec![(ROOT, 0)];
+        while let Some((id, depth)) = stack.pop() {
+            ret.push(HistoryTreeLine {
+                id,
+                depth,
+                change: self.nodes.get(&id).map(|node| &node.change),
+                checkpoint: self.checkpoint(id),
+            });
+            let children: Vec<usize> = self.children(id).collect();
+            // the oldest child continues the branch, the others open new ones
+            for (i, child) in children.into_iter().enumerate().rev() {
+                stack.push((child, if i == 0 { depth } else { depth + 1 }));
+            }
+        }
+        ret
+    }
+
+    pub fn change_limit(&mut self, limit: usize) {
+        self.limit = limit;
+        self.prune();
+    }
+
     pub fn clear(&mut self) {
-        self.changes.clear();
-        self.current = 0;
+        self.nodes.clear();
+        self.redo_children.clear();
+        self.checkpoints.clear();
+        self.current = ROOT;
+        self.next_id = ROOT + 1;
     }
 }
 
@@ -81,22 +282,21 @@ mod test {
     fn test_history_with_limit() {
         let mut history = History::with_limit(2);
         assert_eq!(history.limit, 2);
-        assert_eq!(history.changes.capacity(), 2);
 
         history.push(Change::new(0, &[0], &[1]));
         assert_eq!(history.current, 1);
         history.push(Change::new(0, &[1], &[0]));
         assert_eq!(history.current, 2);
         history.push(Change::new(0, &[0], &[1]));
-        assert_eq!(history.current, 2);
-        assert_eq!(history.changes.len(), 2);
+        assert_eq!(history.current, 3);
+        assert_eq!(history.nodes.len(), 2);
 
-        history.undo(&mut vec![0]);
-        assert_eq!(history.current, 1);
-        history.undo(&mut vec![0]);
-        assert_eq!(history.current, 0);
-        assert!(history.undo(&mut vec![0]).is_none());
-        assert_eq!(history.current, 0);
+        history.undo(&mut Storage::from(vec![0]));
+        assert_eq!(history.current, 2);
+        history.undo(&mut Storage::from(vec![0]));
+        assert_eq!(history.current, ROOT);
+        assert!(history.undo(&mut Storage::from(vec![0])).is_none());
+        assert_eq!(history.current, ROOT);
     }
 
     #[test]
@@ -105,12 +305,69 @@ mod test {
         history.push(Change::new(0, &[0], &[1]));
         history.push(Change::new(0, &[1], &[2]));
         history.push(Change::new(0, &[2], &[3]));
-        assert_eq!(history.changes.len(), 2);
-        assert_eq!(history.current, 2);
+        assert_eq!(history.nodes.len(), 2);
+        assert_eq!(history.current, 3);
 
         history.change_limit(1);
-        assert_eq!(history.changes.len(), 1);
-        assert_eq!(history.current, 1);
-        assert_eq!(history.changes[0], Change::new(0, &[2], &[3]));
+        assert_eq!(history.nodes.len(), 1);
+        assert_eq!(history.current, 3);
+        assert_eq!(history.nodes[&3].change, Change::new(0, &[2], &[3]));
+    }
+
+    #[test]
+    fn test_history_change_limit_with_undone_changes() {
+        let mut history = History::with_limit(0);
+        let mut data = Storage::from(vec![0]);
+        for i in 0..4 {
+            let change = Change::new(0, &[i], &[i + 1]);
+            change.apply(&mut data);
+            history.push(change);
+        }
+        history.undo(&mut data);
+        history.undo(&mut data);
+        history.undo(&mut data);
+
+        history.change_limit(2);
+        assert_eq!(history.current, ROOT);
+        assert_eq!(history.nodes.len(), 2);
+        assert_eq!(history.nodes[&2].change, Change::new(0, &[1], &[2]));
+        assert_eq!(history.nodes[&3].change, Change::new(0, &[2], &[3]));
+        history.redo(&mut data);
+        history.redo(&mut data);
+        assert_eq!(data.as_slice(), &[3]);
+    }
+
+    #[test]
+    fn test_history_branches() {
+        let mut history = History::with_limit(0);
+        let mut data = Storage::from(vec![0, 0]);
+        fn push(history: &mut History, data: &mut Storage, change: Change) {
+            change.apply(data);
+            history.push(change);
+        }
+        push(&mut history, &mut data, Change::new(0, &[0], &[1]));
+        history.set_checkpoint("first");
+        push(&mut history, &mut data, Change::new(1, &[0], &[2]));
+        history.undo(&mut data);
+        // a new change after an undo keeps the one that was undone in another branch
+        push(&mut history, &mut data, Change::new(1, &[0], &[3]));
+        assert_eq!(data.as_slice(), &[1, 3]);
+        assert_eq!(history.len(), 3);
+
+        let tree = history.tree();
+        let ids: Vec<(usize, usize)> = tree.iter().map(|line| (line.id, line.depth)).collect();
+        assert_eq!(ids, vec![(ROOT, 0), (1, 0), (2, 0), (3, 1)]);
+        assert_eq!(tree[1].checkpoint, Some("first"));
+
+        let (undo, redo) = history.path_to(2).unwrap();
+        assert_eq!((undo, redo.clone()), (1, vec![2]));
+        history.undo(&mut data);
+        for id in redo {
+            history.select_redo(id);
+            history.redo(&mut data);
+        }
+        assert_eq!(data.as_slice(), &[1, 2]);
+        assert_eq!(history.path_to(ROOT), Some((2, vec![])));
+        assert_eq!(history.path_to(42), None);
     }
 }
@@ -2,3 +2,5 @@ pub mod history;
 pub use history::History;
 
 pub mod change;
+pub mod checkpoints;
+pub mod saved_history;
@@ -0,0 +1,58 @@
+use serde::{Deserialize, Serialize};
+
+use crate::app::patches::bps::crc32;
+
+use super::History;
+
+/// The undo history saved in the project file, it can be restored only if the
+/// file on disk is the same it was when the history was saved.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SavedHistory {
+    len: usize,
+    crc32: u32,
+    history: History,
+}
+
+impl SavedHistory {
+    /// bytes must be the contents of the file as it is on disk.
+    pub fn new(history: History, bytes: &[u8]) -> Self {
+        Self {
+            len: bytes.len(),
+            crc32: crc32(bytes),
+            history,
+        }
+    }
+
+    /// Returns the history if it was saved for a file with the same contents as bytes.
+    pub fn restore(self, bytes: &[u8]) -> Option<History> {
+        if self.len == bytes.len() && self.crc32 == crc32(bytes) {
+            Some(self.history)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::app::{history::change::Change, storage::Storage};
+
+    use super::*;
+
+    #[test]
+    fn test_saved_history() {
+        let mut data = Storage::from(vec![0, 1, 2]);
+        let mut history = History::with_limit(0);
+        let change = Change::new(1, &[1], &[9]);
+        change.apply(&mut data);
+        history.push(change);
+        let saved = SavedHistory::new(history.clone(), data.as_slice());
+        let json = serde_json::to_string(&saved).unwrap();
+        let saved: SavedHistory = serde_json::from_str(&json).unwrap();
+
+        assert!(saved.clone().restore(&[0, 1, 2]).is_none());
+        let mut restored = saved.restore(data.as_slice()).unwrap();
+        restored.undo(&mut data);
+        assert_eq!(data.as_slice(), &[0, 1, 2]);
+    }
+}
@@ -0,0 +1,318 @@
+use std::ops::Range;
+
+use object::Endianness;
+use ratatui::text::{Line, Span};
+
+use crate::headers::Header;
+
+use super::{asm::labels::Labels, pane::Pane, structs::field_type::Primitive, App};
+
+/// The width of the inspector pane, borders included.
+pub(super) const INSPECTOR_WIDTH: u16 = 48;
+/// The number of bytes decoded by the text previews.
+const PREVIEW_LEN: usize = 16;
+const LABEL_WIDTH: usize = 9;
+
+/// Decodes bytes, starting at the cursor, as every type shown in the inspector.
+/// The values that need more bytes than available are shown as `-`.
+pub fn inspect(bytes: &[u8], header: &Header, labels: &Labels) -> Vec<(String, String)> {
+    let mut ret = Vec::new();
+    for size in [1, 2, 4, 8] {
+        for signed in [false, true] {
+            for endianness in [Endianness::Little, Endianness::Big] {
+                if size == 1 && endianness == Endianness::Big {
+                    continue;
+                }
+                let sign = if signed { 'i' } else { 'u' };
+                let label = if size == 1 {
+                    format!("{sign}8")
+                } else {
+                    format!("{sign}{} {}", size * 8, endianness_name(endianness))
+                };
+                let primitive = Primitive::Int {
+                    size,
+                    signed,
+                    endianness,
+                };
+                ret.push((label, decode(bytes, size, |b| primitive.editable_value(b))));
+            }
+        }
+    }
+    for size in [4, 8] {
+        for endianness in [Endianness::Little, Endianness::Big] {
+            let primitive = Primitive::Float { size, endianness };
+            ret.push((
+                format!("f{} {}", size * 8, endianness_name(endianness)),
+                decode(bytes, size, |b| primitive.editable_value(b)),
+            ));
+        }
+    }
+    ret.push((
+        "uleb128".to_string(),
+        read_uleb128(bytes)
+            .map(|(value, len)| format!("{value} ({len}B)"))
+            .unwrap_or_else(|| "-".to_string()),
+    ));
+    let endianness = header.endianness();
+    ret.push((
+        "time32".to_string(),
+        decode(bytes, 4, |b| {
+            format_unix_time(read_uint(b, endianness) as u32 as i64)
+        }),
+    ));
+    ret.push((
+        "time64".to_string(),
+        decode(bytes, 8, |b| {
+            format_unix_time(read_uint(b, endianness) as i64)
+        }),
+    ));
+    ret.push(("GUID".to_string(), decode(bytes, 16, format_guid)));
+    let pointer_size = header.bitness() as usize / 8;
+    ret.push((
+        "pointer".to_string(),
+        decode(bytes, pointer_size, |b| {
+            let pointer = read_uint(b, endianness);
+            match pointer_symbol(pointer, header, labels) {
+                Some(symbol) => format!("{pointer:#X} -> {symbol}"),
+                None => format!("{pointer:#X}"),
+            }
+        }),
+    ));
+    let preview = &bytes[..bytes.len().min(PREVIEW_LEN)];
+    ret.push((
+        "UTF-8".to_string(),
+        printable(&String::from_utf8_lossy(preview)),
+    ));
+    for endianness in [Endianness::Little, Endianness::Big] {
+        let units = preview
+            .chunks_exact(2)
+            .map(|unit| read_uint(unit, endianness) as u16);
+        let text = char::decode_utf16(units)
+            .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+            .collect::<String>();
+        ret.push((
+            format!("UTF-16 {}", endianness_name(endianness)),
+            printable(&text),
+        ));
+    }
+    ret
+}
+
+fn endianness_name(endianness: Endianness) -> &'static str {
+    match endianness {
+        Endianness::Little => "le",
+        Endianness::Big => "be",
+    }
+}
+
+fn decode(bytes: &[u8], size: usize, f: impl Fn(&[u8]) -> String) -> String {
+    match bytes.get(..size) {
+        Some(bytes) if size > 0 => f(bytes),
+        _ => "-".to_string(),
+    }
+}
+
+fn read_uint(bytes: &[u8], endianness: Endianness) -> u64 {
+    let mut buffer = [0u8; 8];
+    match endianness {
+        Endianness::Little => {
+            buffer[..bytes.len()].copy_from_slice(bytes);
+            u64::from_le_bytes(buffer)
+        }
+        Endianness::Big => {
+            buffer[8 - bytes.len()..].copy_from_slice(bytes);
+            u64::from_be_bytes(buffer)
+        }
+    }
+}
+
+/// Returns the value and the number of bytes it takes, None if it is truncated or too big.
+fn read_uleb128(bytes: &[u8]) -> Option<(u64, usize)> {
+    let mut value = 0u64;
+    for (i, byte) in bytes.iter().enumerate().take(10) {
+        let bits = (*byte & 0x7f) as u64;
+        if i == 9 && bits > 1 {
+            return None;
+        }
+        value |= bits << (7 * i);
+        if byte & 0x80 == 0 {
+            return Some((value, i + 1));
+        }
+    }
+    None
+}
+
+/// Formats seconds from the unix epoch as an UTC date.
+fn format_unix_time(timestamp: i64) -> String {
+    let days = timestamp.div_euclid(86400);
+    let seconds = timestamp.rem_euclid(86400);
+    // days to civil date, from http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    if !(0..=9999).contains(&year) {
+        return "-".to_string();
+    }
+    format!(
+        "{year:04}-{month:02}-{day:02} {:02}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+/// Formats a GUID with the first three groups in little endian, like Windows does.
+fn format_guid(bytes: &[u8]) -> String {
+    format!(
+        "{:08X}-{:04X}-{:04X}-{:02X}{:02X}-{}",
+        read_uint(&bytes[0..4], Endianness::Little),
+        read_uint(&bytes[4..6], Endianness::Little),
+        read_uint(&bytes[6..8], Endianness::Little),
+        bytes[8],
+        bytes[9],
+        bytes[10..16]
+            .iter()
+            .map(|b| format!("{b:02X}"))
+            .collect::<String>()
+    )
+}
+
+/// Finds the symbol a pointer refers to, the pointer is read as a virtual address
+/// and then as a file address.
+fn pointer_symbol(pointer: u64, header: &Header, labels: &Labels) -> Option<String> {
+    let symbol_at = |address: u64| {
+        header
+            .get_symbols()
+            .and_then(|symbols| symbols.get(&address).cloned())
+            .or_else(|| labels.get(address))
+    };
+    symbol_at(pointer).or_else(|| {
+        header
+            .physical_to_virtual_address(pointer)
+            .filter(|address| *address != pointer)
+            .and_then(symbol_at)
+    })
+}
+
+fn printable(text: &str) -> String {
+    text.chars()
+        .map(|c| if c.is_control() { '.' } else { c })
+        .collect()
+}
+
+impl App {
+    pub(super) fn toggle_inspector(&mut self) {
+        self.inspector = !self.inspector;
+        if !self.inspector && self.selected_pane == Pane::Inspector {
+            self.switch_selected_pane();
+        }
+    }
+
+    /// The bytes decoded by the inspector, they must be loaded before it is drawn.
+    pub(super) fn get_inspector_range(&self) -> Range<usize> {
+        let cursor = self.get_cursor_position().global_byte_index;
+        cursor.min(self.data.len())..self.data.len().min(cursor + PREVIEW_LEN)
+    }
+
+    pub(super) fn get_inspector_view(&self, height: usize) -> Vec<Line<'static>> {
+        let range = self.get_inspector_range();
+        // the bytes that could not be fetched are not decoded as zeros
+        let bytes: &[u8] = if self.data.missing_ranges(range.clone()).is_empty() {
+            &self.data.bytes()[range]
+        } else {
+            &[]
+        };
+        inspect(bytes, &self.header, &self.labels)
+            .into_iter()
+            .take(height)
+            .map(|(label, value)| {
+                let mut line = Line::default();
+                line.spans.push(Span::styled(
+                    format!("{label:<LABEL_WIDTH$}"),
+                    self.settings.color.assembly_symbol,
+                ));
+                line.spans
+                    .push(Span::styled(value, self.settings.color.assembly_default));
+                line
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_inspect() {
+        let bytes = [
+            0x78, 0x56, 0x34, 0x12, 0x00, 0x00, 0x00, 0x80, 0x41, 0x00, 0x42, 0x00, 0x00, 0x00,
+            0x00, 0x00,
+        ];
+        let values = inspect(&bytes, &Header::None, &Labels::new());
+        let get = |label: &str| {
+            values
+                .iter()
+                .find(|(l, _)| l == label)
+                .map(|(_, v)| v.as_str())
+                .unwrap()
+        };
+        assert_eq!(get("u8"), "120");
+        assert_eq!(get("u16 le"), "22136");
+        assert_eq!(get("u16 be"), "30806");
+        assert_eq!(get("i32 le"), "305419896");
+        assert_eq!(get("i64 le"), "-9223372036549355912");
+        assert_eq!(get("uleb128"), "120 (1B)");
+        assert_eq!(get("time32"), "1979-09-05 22:51:36");
+        assert_eq!(get("GUID"), "12345678-0000-8000-4100-420000000000");
+        assert_eq!(get("pointer"), "0x8000000012345678");
+        assert_eq!(get("UTF-8"), "xV4....\u{FFFD}A.B.....");
+
+        let values = inspect(&[0xff], &Header::None, &Labels::new());
+        assert_eq!(values.iter().find(|(l, _)| l == "u16 le").unwrap().1, "-");
+        assert_eq!(values.iter().find(|(l, _)| l == "uleb128").unwrap().1, "-");
+    }
+
+    #[test]
+    fn test_inspector_pointer() {
+        let mut header = Header::None;
+        header.set_symbol(0x10, "target");
+        let values = inspect(&0x10u64.to_le_bytes(), &header, &Labels::new());
+        assert_eq!(
+            values.iter().find(|(l, _)| l == "pointer").unwrap().1,
+            "0x10 -> target"
+        );
+        assert_eq!(format_unix_time(0), "1970-01-01 00:00:00");
+        assert_eq!(format_unix_time(-86400), "1969-12-31 00:00:00");
+        assert_eq!(read_uleb128(&[0xe5, 0x8e, 0x26]), Some((624485, 3)));
+    }
+
+    #[test]
+    fn test_inspector_pane() {
+        let mut app = App::mockup(vec![0x41; 0x100]);
+        let mut terminal =
+            ratatui::Terminal::new(ratatui::backend::TestBackend::new(140, 40)).unwrap();
+        app.draw(&mut terminal).unwrap();
+        let blocks_per_row = app.blocks_per_row;
+        app.toggle_inspector();
+        app.draw(&mut terminal).unwrap();
+        assert!(app.blocks_per_row < blocks_per_row);
+        let screen = format!("{:?}", terminal.backend().buffer());
+        assert!(screen.contains("u16 le"));
+        assert!(screen.contains("16705"));
+
+        app.switch_selected_pane();
+        app.switch_selected_pane();
+        assert_eq!(app.selected_pane, Pane::Inspector);
+        app.move_cursor_in_selected_panel(1, 0);
+        assert_eq!(app.get_cursor_position().global_byte_index, 1);
+        app.toggle_inspector();
+        assert_eq!(app.selected_pane, Pane::Hex);
+    }
+}
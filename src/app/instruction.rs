@@ -1,6 +1,7 @@
 use std::{collections::HashMap, fmt::Display};
 
 use capstone::Insn;
+use object::Endianness;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Instruction {
@@ -57,6 +58,34 @@ impl Instruction {
         }
     }
 
+    /// A line of bytes that are not code, shown as a `dq` if there are 8 of them
+    /// or as a `db` otherwise.
+    pub fn data(virtual_address: u64, bytes: &[u8], endianness: Endianness) -> Self {
+        let (mnemonic, operands) = match <[u8; 8]>::try_from(bytes) {
+            Ok(quad) => {
+                let value = match endianness {
+                    Endianness::Little => u64::from_le_bytes(quad),
+                    Endianness::Big => u64::from_be_bytes(quad),
+                };
+                ("dq", format!("0x{value:x}"))
+            }
+            Err(_) => (
+                "db",
+                bytes
+                    .iter()
+                    .map(|byte| format!("0x{byte:x}"))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            ),
+        };
+        Instruction {
+            mnemonic: mnemonic.to_string(),
+            operands,
+            virtual_address,
+            bytes: bytes.to_vec(),
+        }
+    }
+
     pub fn mnemonic(&self) -> &str {
         &self.mnemonic
     }
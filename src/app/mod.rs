@@ -3,25 +3,37 @@ pub use app::*;
 
 pub mod address;
 pub mod asm;
+pub mod batch;
+pub mod buffers;
 pub mod commands;
 pub mod comments;
 pub mod cursor_position;
 pub mod data;
+pub mod diff;
 pub mod events;
 pub mod files;
+pub mod firmware;
 pub mod frame_info;
 pub mod help;
 pub mod hex;
 pub mod history;
 pub mod info_mode;
+pub mod inspector;
 pub mod instruction;
 pub mod log;
 pub mod mockup;
 pub mod pane;
+pub mod patches;
 pub mod plugins;
 pub mod popup;
+pub mod raw_binary;
+pub mod search;
+pub mod selection;
 pub mod settings;
 pub mod ssh;
 pub mod status_bar;
+pub mod storage;
+pub mod structs;
 pub mod text;
+pub mod user_symbols;
 pub mod widgets;
@@ -4,6 +4,7 @@ use mlua::{FromLua, IntoLua};
 pub enum Pane {
     Hex,
     View,
+    Inspector,
 }
 
 impl IntoLua for Pane {
@@ -11,6 +12,7 @@ impl IntoLua for Pane {
         Ok(mlua::Value::String(match self {
             Pane::Hex => lua.create_string("hex").unwrap(),
             Pane::View => lua.create_string("view").unwrap(),
+            Pane::Inspector => lua.create_string("inspector").unwrap(),
         }))
     }
 }
@@ -21,6 +23,7 @@ impl FromLua for Pane {
         match value.as_str() {
             "hex" => Ok(Pane::Hex),
             "view" => Ok(Pane::View),
+            "inspector" => Ok(Pane::Inspector),
             _ => Err(mlua::Error::external("Invalid Pane")),
         }
     }
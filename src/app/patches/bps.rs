@@ -0,0 +1,216 @@
+const HEADER: &[u8] = b"BPS1";
+const SOURCE_READ: u64 = 0;
+const TARGET_READ: u64 = 1;
+const SOURCE_COPY: u64 = 2;
+const TARGET_COPY: u64 = 3;
+
+/// Creates a BPS patch turning source into target, the bytes that are equal at the
+/// same offset are read from the source, the others are stored in the patch.
+pub fn encode(source: &[u8], target: &[u8]) -> Vec<u8> {
+    let mut ret = HEADER.to_vec();
+    write_number(&mut ret, source.len() as u64);
+    write_number(&mut ret, target.len() as u64);
+    // no metadata
+    write_number(&mut ret, 0);
+    let mut start = 0;
+    while start < target.len() {
+        let same = |i: usize| source.get(i) == Some(&target[i]);
+        let kind = same(start);
+        let end = (start..target.len())
+            .find(|i| same(*i) != kind)
+            .unwrap_or(target.len());
+        let action = if kind { SOURCE_READ } else { TARGET_READ };
+        write_number(&mut ret, ((end - start - 1) as u64) << 2 | action);
+        if !kind {
+            ret.extend_from_slice(&target[start..end]);
+        }
+        start = end;
+    }
+    ret.extend_from_slice(&crc32(source).to_le_bytes());
+    ret.extend_from_slice(&crc32(target).to_le_bytes());
+    let patch_crc = crc32(&ret);
+    ret.extend_from_slice(&patch_crc.to_le_bytes());
+    ret
+}
+
+/// Applies a BPS patch to source, failing if the patch was made for a different source.
+pub fn apply(patch: &[u8], source: &[u8]) -> Result<Vec<u8>, String> {
+    let invalid = || t!("errors.invalid_patch_file", format = "BPS").to_string();
+    if patch.len() < HEADER.len() + 12 || !patch.starts_with(HEADER) {
+        return Err(invalid());
+    }
+    let (body, footer) = patch.split_at(patch.len() - 12);
+    let checksum = |i: usize| u32::from_le_bytes(footer[i * 4..i * 4 + 4].try_into().unwrap());
+    if crc32(&patch[..patch.len() - 4]) != checksum(2) {
+        return Err(invalid());
+    }
+    if crc32(source) != checksum(0) {
+        return Err(t!("errors.patch_source_mismatch").to_string());
+    }
+
+    let mut reader = Reader {
+        bytes: body,
+        offset: HEADER.len(),
+    };
+    let source_size = reader.number().ok_or_else(invalid)?;
+    let target_size = reader.number().ok_or_else(invalid)? as usize;
+    let metadata_size = reader.number().ok_or_else(invalid)? as usize;
+    if source_size != source.len() as u64 {
+        return Err(t!("errors.patch_source_mismatch").to_string());
+    }
+    reader.take(metadata_size).ok_or_else(invalid)?;
+
+    let mut target = Vec::with_capacity(target_size);
+    let mut source_offset = 0i64;
+    let mut target_offset = 0i64;
+    while reader.offset < body.len() {
+        let data = reader.number().ok_or_else(invalid)?;
+        let len = (data >> 2) as usize + 1;
+        if target.len() + len > target_size {
+            return Err(invalid());
+        }
+        match data & 3 {
+            SOURCE_READ => {
+                let start = target.len();
+                target.extend_from_slice(source.get(start..start + len).ok_or_else(invalid)?);
+            }
+            TARGET_READ => target.extend_from_slice(reader.take(len).ok_or_else(invalid)?),
+            SOURCE_COPY => {
+                source_offset += reader.signed_number().ok_or_else(invalid)?;
+                let start = usize::try_from(source_offset).map_err(|_| invalid())?;
+                target.extend_from_slice(source.get(start..start + len).ok_or_else(invalid)?);
+                source_offset += len as i64;
+            }
+            TARGET_COPY => {
+                target_offset += reader.signed_number().ok_or_else(invalid)?;
+                let start = usize::try_from(target_offset).map_err(|_| invalid())?;
+                if start >= target.len() {
+                    return Err(invalid());
+                }
+                // the copy can overlap the bytes it writes
+                for i in start..start + len {
+                    target.push(target[i]);
+                }
+                target_offset += len as i64;
+            }
+            _ => unreachable!(),
+        }
+    }
+    if target.len() != target_size || crc32(&target) != checksum(1) {
+        return Err(invalid());
+    }
+    Ok(target)
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        let bytes = self.bytes.get(self.offset..self.offset.checked_add(len)?)?;
+        self.offset += len;
+        Some(bytes)
+    }
+
+    fn number(&mut self) -> Option<u64> {
+        let mut data = 0u64;
+        let mut shift = 1u64;
+        loop {
+            let byte = *self.take(1)?.first()? as u64;
+            data = data.checked_add((byte & 0x7f).checked_mul(shift)?)?;
+            if byte & 0x80 != 0 {
+                return Some(data);
+            }
+            shift = shift.checked_shl(7)?;
+            data = data.checked_add(shift)?;
+        }
+    }
+
+    fn signed_number(&mut self) -> Option<i64> {
+        let data = self.number()?;
+        let value = (data >> 1) as i64;
+        Some(if data & 1 == 1 { -value } else { value })
+    }
+}
+
+fn write_number(bytes: &mut Vec<u8>, mut number: u64) {
+    loop {
+        let x = (number & 0x7f) as u8;
+        number >>= 7;
+        if number == 0 {
+            bytes.push(0x80 | x);
+            break;
+        }
+        bytes.push(x);
+        number -= 1;
+    }
+}
+
+/// The CRC-32 used by zip and png.
+pub(in crate::app) fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                crc >> 1 ^ 0xEDB88320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_bps() {
+        assert_eq!(crc32(b"123456789"), 0xCBF43926);
+        let mut number = Vec::new();
+        write_number(&mut number, 300);
+        assert_eq!(
+            Reader {
+                bytes: &number,
+                offset: 0
+            }
+            .number(),
+            Some(300)
+        );
+
+        let source = b"Hello, World!".to_vec();
+        let target = b"Hello, BPS!!!!!".to_vec();
+        let patch = encode(&source, &target);
+        assert_eq!(apply(&patch, &source).unwrap(), target);
+        assert!(apply(&patch, b"Hello, World?").is_err());
+        let mut corrupted = patch.clone();
+        corrupted[6] ^= 1;
+        assert!(apply(&corrupted, &source).is_err());
+
+        let shorter = encode(&source, b"Hello");
+        assert_eq!(apply(&shorter, &source).unwrap(), b"Hello");
+    }
+
+    #[test]
+    fn test_bps_copy_actions() {
+        // target "abab" from source "ab": SourceRead 2, TargetCopy 2 from 0
+        let source = b"ab";
+        let target = b"abab";
+        let mut patch = HEADER.to_vec();
+        write_number(&mut patch, 2);
+        write_number(&mut patch, 4);
+        write_number(&mut patch, 0);
+        write_number(&mut patch, 1 << 2 | SOURCE_READ);
+        write_number(&mut patch, 1 << 2 | TARGET_COPY);
+        write_number(&mut patch, 0);
+        patch.extend_from_slice(&crc32(source).to_le_bytes());
+        patch.extend_from_slice(&crc32(target).to_le_bytes());
+        let patch_crc = crc32(&patch);
+        patch.extend_from_slice(&patch_crc.to_le_bytes());
+        assert_eq!(apply(&patch, source).unwrap(), target);
+    }
+}
@@ -0,0 +1,124 @@
+use std::fmt::Write;
+
+use super::patch_format::changed_ranges;
+
+const BYTES_PER_LINE: usize = 16;
+
+/// Creates a readable diff, the sizes of the files are followed by the old bytes,
+/// prefixed by `-`, and the new bytes, prefixed by `+`, of each changed range:
+/// ```text
+/// --- original 32
+/// +++ modified 34
+/// -00000002: 00 00
+/// +00000002: AA BB
+/// +00000020: 01 02
+/// ```
+pub fn encode(source: &[u8], target: &[u8]) -> String {
+    let mut ret = format!(
+        "--- original {}\n+++ modified {}\n",
+        source.len(),
+        target.len()
+    );
+    for range in changed_ranges(source, target) {
+        for (prefix, bytes) in [('-', source), ('+', target)] {
+            let end = range.end.min(bytes.len());
+            for start in (range.start..end).step_by(BYTES_PER_LINE) {
+                let line = &bytes[start..end.min(start + BYTES_PER_LINE)];
+                let _ = write!(ret, "{prefix}{start:08X}:");
+                for byte in line {
+                    let _ = write!(ret, " {byte:02X}");
+                }
+                ret.push('\n');
+            }
+        }
+    }
+    ret
+}
+
+/// Applies a diff made by encode, the old bytes must match the ones in source.
+pub fn apply(diff: &str, source: &[u8]) -> Result<Vec<u8>, String> {
+    let invalid = |line: usize| {
+        t!(
+            "errors.patch_script_line",
+            line = line,
+            e = t!("errors.invalid_patch_file", format = "hex diff")
+        )
+        .to_string()
+    };
+    let mut target = None;
+    for (i, line) in diff.lines().enumerate() {
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(size) = line.strip_prefix("--- original ") {
+            if size.trim().parse::<usize>() != Ok(source.len()) {
+                return Err(t!("errors.patch_source_mismatch").to_string());
+            }
+            continue;
+        }
+        if let Some(size) = line.strip_prefix("+++ modified ") {
+            let size = size.trim().parse::<usize>().map_err(|_| invalid(i + 1))?;
+            let mut bytes = source.to_vec();
+            bytes.resize(size, 0);
+            target = Some(bytes);
+            continue;
+        }
+        let target = target.as_mut().ok_or_else(|| invalid(i + 1))?;
+        let (address, bytes) = line
+            .get(1..)
+            .and_then(|line| line.split_once(':'))
+            .ok_or_else(|| invalid(i + 1))?;
+        let address = usize::from_str_radix(address.trim(), 16).map_err(|_| invalid(i + 1))?;
+        let bytes = bytes
+            .split_whitespace()
+            .map(|byte| u8::from_str_radix(byte, 16))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| invalid(i + 1))?;
+        let end = address
+            .checked_add(bytes.len())
+            .ok_or_else(|| invalid(i + 1))?;
+        match line.as_bytes()[0] {
+            b'-' => {
+                if source.get(address..end) != Some(bytes.as_slice()) {
+                    return Err(t!("errors.patch_source_mismatch").to_string());
+                }
+            }
+            b'+' => {
+                target
+                    .get_mut(address..end)
+                    .ok_or_else(|| invalid(i + 1))?
+                    .copy_from_slice(&bytes);
+            }
+            _ => return Err(invalid(i + 1)),
+        }
+    }
+    target.ok_or_else(|| t!("errors.invalid_patch_file", format = "hex diff").to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_hex_diff() {
+        let source = vec![0u8; 0x20];
+        let mut target = source.clone();
+        target[2] = 0xAA;
+        target[3] = 0xBB;
+        target.extend_from_slice(&[1, 2]);
+        let diff = encode(&source, &target);
+        assert_eq!(
+            diff,
+            "--- original 32\n+++ modified 34\n\
+             -00000002: 00 00\n+00000002: AA BB\n+00000020: 01 02\n"
+        );
+        assert_eq!(apply(&diff, &source).unwrap(), target);
+        assert!(apply(&diff, &[1u8; 0x20]).is_err());
+        assert!(apply(&diff, &[0u8; 0x10]).is_err());
+
+        let diff = encode(&target, &source);
+        assert_eq!(apply(&diff, &target).unwrap(), source);
+        assert!(apply("+00000002: AA", &source).is_err());
+    }
+}
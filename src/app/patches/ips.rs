@@ -0,0 +1,123 @@
+use super::patch_format::changed_ranges;
+
+const HEADER: &[u8] = b"PATCH";
+const FOOTER: &[u8] = b"EOF";
+/// A record can't start at this offset, it would be read as the footer.
+const EOF_OFFSET: usize = 0x454F46;
+const MAX_OFFSET: usize = 0xFFFFFF;
+const MAX_RECORD: usize = 0xFFFF;
+
+/// Creates an IPS patch turning source into target, if target is shorter the
+/// truncation extension is used.
+pub fn encode(source: &[u8], target: &[u8]) -> Result<Vec<u8>, String> {
+    let mut ret = HEADER.to_vec();
+    for range in changed_ranges(source, target) {
+        let mut start = range.start;
+        let end = range.end.min(target.len());
+        while start < end {
+            if start == EOF_OFFSET {
+                // rewriting the byte before does not change it and moves the record
+                start -= 1;
+            }
+            if start > MAX_OFFSET {
+                return Err(t!("errors.ips_too_big").to_string());
+            }
+            let len = (end - start).min(MAX_RECORD);
+            ret.extend_from_slice(&(start as u32).to_be_bytes()[1..]);
+            ret.extend_from_slice(&(len as u16).to_be_bytes());
+            ret.extend_from_slice(&target[start..start + len]);
+            start += len;
+        }
+    }
+    ret.extend_from_slice(FOOTER);
+    if target.len() < source.len() {
+        if target.len() > MAX_OFFSET {
+            return Err(t!("errors.ips_too_big").to_string());
+        }
+        ret.extend_from_slice(&(target.len() as u32).to_be_bytes()[1..]);
+    }
+    Ok(ret)
+}
+
+/// Applies an IPS patch to source, the records past the end extend it with zeros.
+pub fn apply(patch: &[u8], source: &[u8]) -> Result<Vec<u8>, String> {
+    let invalid = || t!("errors.invalid_patch_file", format = "IPS").to_string();
+    let mut patch = patch
+        .strip_prefix(HEADER)
+        .ok_or_else(invalid)?
+        .iter()
+        .copied();
+    let mut read = |count: usize| -> Result<usize, String> {
+        let mut value = 0;
+        for _ in 0..count {
+            value = value << 8 | patch.next().ok_or_else(invalid)? as usize;
+        }
+        Ok(value)
+    };
+    let mut target = source.to_vec();
+    loop {
+        let offset = read(3)?;
+        if offset == EOF_OFFSET {
+            break;
+        }
+        let len = read(2)?;
+        let bytes = if len == 0 {
+            // run length encoded record
+            let len = read(2)?;
+            let byte = read(1)? as u8;
+            vec![byte; len]
+        } else {
+            (0..len)
+                .map(|_| read(1).map(|byte| byte as u8))
+                .collect::<Result<Vec<_>, _>>()?
+        };
+        if target.len() < offset + bytes.len() {
+            target.resize(offset + bytes.len(), 0);
+        }
+        target[offset..offset + bytes.len()].copy_from_slice(&bytes);
+    }
+    if let Ok(len) = read(3) {
+        target.truncate(len);
+    }
+    Ok(target)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_ips() {
+        let source = vec![0u8; 0x20];
+        let mut target = source.clone();
+        target[2] = 0xAA;
+        target[3] = 0xBB;
+        target.extend_from_slice(&[1, 2]);
+        let patch = encode(&source, &target).unwrap();
+        assert_eq!(
+            patch,
+            b"PATCH\x00\x00\x02\x00\x02\xAA\xBB\x00\x00\x20\x00\x02\x01\x02EOF"
+        );
+        assert_eq!(apply(&patch, &source).unwrap(), target);
+
+        let truncated = &source[..0x10];
+        let patch = encode(&source, truncated).unwrap();
+        assert_eq!(patch, b"PATCHEOF\x00\x00\x10");
+        assert_eq!(apply(&patch, &source).unwrap(), truncated);
+
+        let rle = b"PATCH\x00\x00\x01\x00\x00\x00\x03\xFFEOF";
+        assert_eq!(&apply(rle, &[0; 5]).unwrap(), &[0, 0xFF, 0xFF, 0xFF, 0]);
+        assert!(apply(b"PATCH\x00\x00", &source).is_err());
+        assert!(apply(b"BPS1", &source).is_err());
+    }
+
+    #[test]
+    fn test_ips_eof_offset() {
+        let source = vec![0u8; EOF_OFFSET + 2];
+        let mut target = source.clone();
+        target[EOF_OFFSET] = 1;
+        let patch = encode(&source, &target).unwrap();
+        assert_eq!(&patch[5..10], &[0x45, 0x4F, 0x45, 0x00, 0x02]);
+        assert_eq!(apply(&patch, &source).unwrap(), target);
+    }
+}
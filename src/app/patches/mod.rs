@@ -0,0 +1,4 @@
+pub mod bps;
+pub mod hex_diff;
+pub mod ips;
+pub mod patch_format;
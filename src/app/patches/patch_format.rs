@@ -0,0 +1,194 @@
+use std::{error::Error, ops::Range};
+
+use crate::app::{files::path, log::NotificationLevel, popup::popup_state::PopupState, App};
+
+use super::{bps, hex_diff, ips};
+
+/// The formats a patch can be exported to, chosen by the extension of the file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PatchFormat {
+    Ips,
+    Bps,
+    /// Any other extension, a readable diff made by [hex_diff::encode].
+    HexDiff,
+}
+
+impl PatchFormat {
+    pub fn from_path(path: &str) -> Self {
+        let extension = path
+            .rsplit_once('.')
+            .map(|(_, extension)| extension.to_lowercase())
+            .unwrap_or_default();
+        match extension.as_str() {
+            "ips" => PatchFormat::Ips,
+            "bps" => PatchFormat::Bps,
+            _ => PatchFormat::HexDiff,
+        }
+    }
+
+    /// Creates a patch turning source into target.
+    pub fn encode(&self, source: &[u8], target: &[u8]) -> Result<Vec<u8>, String> {
+        match self {
+            PatchFormat::Ips => ips::encode(source, target),
+            PatchFormat::Bps => Ok(bps::encode(source, target)),
+            PatchFormat::HexDiff => Ok(hex_diff::encode(source, target).into_bytes()),
+        }
+    }
+
+    /// Applies a patch to source, returning the patched bytes.
+    pub fn apply(&self, patch: &[u8], source: &[u8]) -> Result<Vec<u8>, String> {
+        match self {
+            PatchFormat::Ips => ips::apply(patch, source),
+            PatchFormat::Bps => bps::apply(patch, source),
+            PatchFormat::HexDiff => hex_diff::apply(&String::from_utf8_lossy(patch), source),
+        }
+    }
+}
+
+/// Returns the ranges of bytes that differ between source and target, the bytes
+/// past the end of the shorter one are all different.
+pub fn changed_ranges(source: &[u8], target: &[u8]) -> Vec<Range<usize>> {
+    let mut ret: Vec<Range<usize>> = Vec::new();
+    let common = source.len().min(target.len());
+    for i in 0..common {
+        if source[i] != target[i] {
+            match ret.last_mut() {
+                Some(range) if range.end == i => range.end += 1,
+                _ => ret.push(i..i + 1),
+            }
+        }
+    }
+    let len = source.len().max(target.len());
+    if common < len {
+        match ret.last_mut() {
+            Some(range) if range.end == common => range.end = len,
+            _ => ret.push(common..len),
+        }
+    }
+    ret
+}
+
+impl App {
+    /// Writes a patch from the file on disk to the current data, a firmware image on
+    /// disk is decoded first.
+    pub(in crate::app) fn export_patch(&mut self, path: &str) -> Result<(), Box<dyn Error>> {
+        self.load_range(0..self.data.len());
+        let mut original = self.filesystem.read(self.filesystem.pwd())?;
+        if let Some(image) = &self.firmware {
+            original = image.format.decode(&original)?.1;
+        }
+        let patch = PatchFormat::from_path(path).encode(&original, self.data.bytes())?;
+        if let Some(parent) = path::parent(path) {
+            self.filesystem.mkdirs(parent)?;
+        };
+        self.filesystem.create(path)?;
+        self.filesystem.write(path, &patch)?;
+        let count = changed_ranges(&original, self.data.bytes()).len();
+        self.log(
+            NotificationLevel::Info,
+            t!("app.messages.patch_exported", count = count, path = path),
+        );
+        Ok(())
+    }
+
+    /// Applies a patch to the current data, each changed range can be undone.
+    pub(in crate::app) fn import_patch(&mut self, path: &str) -> Result<(), Box<dyn Error>> {
+        let patch = self.filesystem.read(path)?;
+        self.load_range(0..self.data.len());
+        let target = PatchFormat::from_path(path).apply(&patch, self.data.bytes())?;
+        let count = self.apply_target(&target);
+        self.log(
+            NotificationLevel::Info,
+            t!("app.messages.patch_imported", count = count, path = path),
+        );
+        Ok(())
+    }
+
+    /// Changes the data into target, returns the number of changes pushed to the history.
+    fn apply_target(&mut self, target: &[u8]) -> usize {
+        let ranges = changed_ranges(self.data.bytes(), target);
+        let old_len = self.data.len();
+        let common = old_len.min(target.len());
+        for range in ranges.iter() {
+            let end = range.end.min(common);
+            if range.start < end {
+                self.data
+                    .push_change(range.start, target[range.start..end].to_vec());
+            }
+        }
+        if target.len() > old_len {
+            self.data.insert(old_len, target[old_len..].to_vec());
+        } else if target.len() < old_len {
+            self.data.delete(target.len(), old_len - target.len());
+        }
+        if target.len() != old_len {
+            self.data_resized(common, old_len - common, target.len() - common);
+        } else if !ranges.is_empty() {
+            self.disassemble();
+        }
+        ranges.len()
+    }
+
+    pub(in crate::app) fn request_popup_export_patch(&mut self) {
+        let path = self.filesystem.pwd().to_string() + ".ips";
+        let cursor = path.len();
+        self.popup = Some(PopupState::ExportPatch { path, cursor });
+    }
+
+    pub(in crate::app) fn request_popup_import_patch(&mut self) {
+        self.popup = Some(PopupState::ImportPatch {
+            path: String::new(),
+            cursor: 0,
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_changed_ranges() {
+        assert_eq!(
+            changed_ranges(&[0, 1, 2, 3], &[0, 9, 9, 3, 4]),
+            vec![1..3, 4..5]
+        );
+        assert_eq!(changed_ranges(&[0, 1, 2], &[0, 9]), vec![1..3]);
+        assert!(changed_ranges(&[0, 1], &[0, 1]).is_empty());
+        assert_eq!(PatchFormat::from_path("a/b.IPS"), PatchFormat::Ips);
+        assert_eq!(PatchFormat::from_path("b.bps"), PatchFormat::Bps);
+        assert_eq!(PatchFormat::from_path("b.diff"), PatchFormat::HexDiff);
+    }
+
+    #[test]
+    fn test_export_and_import_patch() {
+        // the export compares with the file on disk, that must outlive the app
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("file.bin").to_string_lossy().to_string();
+        std::fs::write(&file, [0; 0x20]).unwrap();
+        let mut terminal =
+            ratatui::Terminal::new(ratatui::backend::TestBackend::new(80, 24)).unwrap();
+        let mut app = App::default();
+        app.open_file(&file, &mut terminal).unwrap();
+        app.data.push_change(4, vec![1, 2]);
+        app.data.insert(0x20, vec![3]);
+        for extension in ["ips", "bps", "hexdiff"] {
+            let path = dir
+                .path()
+                .join(format!("patch.{extension}"))
+                .to_string_lossy()
+                .to_string();
+            app.export_patch(&path).unwrap();
+            let mut other = App::mockup(vec![0; 0x20]);
+            other.import_patch(&path).unwrap();
+            assert_eq!(other.data.bytes(), app.data.bytes());
+            other.undo();
+            other.undo();
+            assert_eq!(other.data.bytes(), &[0; 0x20]);
+        }
+        let mut other = App::mockup(vec![1; 0x20]);
+        assert!(other
+            .import_patch(&dir.path().join("patch.bps").to_string_lossy())
+            .is_err());
+    }
+}
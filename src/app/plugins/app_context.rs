@@ -1,5 +1,5 @@
 use std::{
-    ops::Deref,
+    ops::{Deref, Range},
     sync::{Arc, Mutex},
 };
 
@@ -9,10 +9,12 @@ use crate::{
     app::{
         comments::Comments,
         data::Data,
+        files::filesystem::FileSystem,
         log::{logger::Logger, NotificationLevel},
         pane::Pane,
         popup::popup_state::PopupState,
         settings::Settings,
+        structs::struct_types::StructTypes,
         App,
     },
     headers::Header,
@@ -29,6 +31,7 @@ macro_rules! get_app_context {
         $crate::app::plugins::app_context::AppContext::new(
             $app.get_cursor_position().global_byte_index,
             $app.get_current_instruction().map(|i| i.into()),
+            $app.get_selection(),
             $app.screen_size.1,
             $app.screen_size.0,
             $app.blocks_per_row,
@@ -37,6 +40,7 @@ macro_rules! get_app_context {
             &mut $app.scroll,
             &mut $app.cursor,
             &mut $app.data,
+            &$app.filesystem,
             &$app.header,
             &mut $app.settings,
             &mut $app.logger,
@@ -44,6 +48,7 @@ macro_rules! get_app_context {
             &mut $app.fullscreen,
             &mut $app.selected_pane,
             &mut $app.comments,
+            &mut $app.struct_types,
         )
     };
 }
@@ -59,10 +64,13 @@ pub struct AppContext<'app> {
     pub block_size: usize,
     pub vertical_margin: u16,
     pub data: Arc<Mutex<&'app mut Data>>,
+    /// Used to fetch the bytes of a file read on demand before the plugins read them.
+    pub filesystem: &'app FileSystem,
     pub scroll: &'app mut usize,
     pub cursor: &'app mut (u16, u16),
     pub offset: usize,
     pub current_instruction: Option<InstructionInfo>,
+    pub selection: Option<Range<usize>>,
     pub header: &'app Header,
     pub settings: &'app mut Settings,
     pub logger: &'app mut Logger,
@@ -70,6 +78,7 @@ pub struct AppContext<'app> {
     pub fullscreen: Arc<Mutex<&'app mut bool>>,
     pub selected_pane: Arc<Mutex<&'app mut Pane>>,
     pub comments: Arc<Mutex<&'app mut Comments>>,
+    pub struct_types: Arc<Mutex<&'app mut StructTypes>>,
 }
 
 impl<'app> AppContext<'app> {
@@ -77,6 +86,7 @@ impl<'app> AppContext<'app> {
     pub fn new(
         offset: usize,
         current_instruction: Option<InstructionInfo>,
+        selection: Option<Range<usize>>,
         screen_height: u16,
         screen_width: u16,
         blocks_per_row: usize,
@@ -85,6 +95,7 @@ impl<'app> AppContext<'app> {
         scroll: &'app mut usize,
         cursor: &'app mut (u16, u16),
         data: &'app mut Data,
+        filesystem: &'app FileSystem,
         header: &'app Header,
         settings: &'app mut Settings,
         logger: &'app mut Logger,
@@ -92,6 +103,7 @@ impl<'app> AppContext<'app> {
         fullscreen: &'app mut bool,
         selected_pane: &'app mut Pane,
         comments: &'app mut Comments,
+        struct_types: &'app mut StructTypes,
     ) -> Self {
         Self {
             exported_commands: Arc::new(Mutex::new(ExportedCommands::default())),
@@ -103,10 +115,12 @@ impl<'app> AppContext<'app> {
             block_size,
             vertical_margin,
             data: Arc::new(Mutex::new(data)),
+            filesystem,
             scroll,
             cursor,
             offset,
             current_instruction,
+            selection,
             header,
             settings,
             logger,
@@ -114,6 +128,7 @@ impl<'app> AppContext<'app> {
             fullscreen: Arc::new(Mutex::new(fullscreen)),
             selected_pane: Arc::new(Mutex::new(selected_pane)),
             comments: Arc::new(Mutex::new(comments)),
+            struct_types: Arc::new(Mutex::new(struct_types)),
         }
     }
 
@@ -336,7 +351,9 @@ impl<'app> AppContext<'app> {
             "get",
             scope
                 .create_function_mut(|_, (_this, index): (Table, usize)| {
-                    let data = self.data.lock().unwrap();
+                    let mut data = self.data.lock().unwrap();
+                    data.fetch(index..index + 1, self.filesystem)
+                        .map_err(mlua::Error::external)?;
                     match data.get(index) {
                         Some(byte) => Ok(byte),
                         None => Err(mlua::Error::external("Index out of bounds")),
@@ -350,6 +367,8 @@ impl<'app> AppContext<'app> {
             scope
                 .create_function_mut(|_, (_this, index, byte): (Table, usize, u8)| {
                     let mut data = self.data.lock().unwrap();
+                    data.fetch(index..index + 1, self.filesystem)
+                        .map_err(mlua::Error::external)?;
                     data.set(index, byte)
                 })
                 .unwrap(),
@@ -487,6 +506,32 @@ impl<'app> AppContext<'app> {
                     .unwrap(),
             )
             .unwrap();
+        context
+            .set(
+                "add_structs",
+                scope
+                    .create_function_mut(|_, definitions: String| {
+                        let mut struct_types = self.struct_types.lock().unwrap();
+                        struct_types
+                            .add_definitions(&definitions)
+                            .map_err(mlua::Error::external)
+                    })
+                    .unwrap(),
+            )
+            .unwrap();
+        context
+            .set(
+                "get_selection",
+                scope
+                    .create_function(|_, ()| {
+                        Ok(match &self.selection {
+                            Some(selection) => (Some(selection.start), Some(selection.end)),
+                            None => (None, None),
+                        })
+                    })
+                    .unwrap(),
+            )
+            .unwrap();
 
         context
     }
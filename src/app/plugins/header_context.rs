@@ -3,7 +3,9 @@ use std::collections::HashMap;
 use mlua::UserData;
 use object::{Architecture, Endianness};
 
-use crate::headers::{bitness::Bitness, custom_header::CustomHeader, section::Section};
+use crate::headers::{
+    bitness::Bitness, custom_header::CustomHeader, isa_mode::IsaModes, section::Section, Header,
+};
 
 #[derive(Debug, Clone, Default)]
 pub struct HeaderContext {
@@ -29,6 +31,7 @@ impl HeaderContext {
                 sections: self.sections,
                 symbols: self.symbols,
                 symbols_by_name,
+                isa_modes: IsaModes::new(),
             })
         } else {
             None
@@ -87,36 +90,8 @@ impl UserData for HeaderContext {
             if this.architecture.is_some() {
                 Err(mlua::Error::external("architecture already set"))
             } else {
-                let architecture = match architecture.as_str() {
-                    "Unknown" => Architecture::Unknown,
-                    "Aarch64" => Architecture::Aarch64,
-                    "Aarch64_Ilp32" => Architecture::Aarch64_Ilp32,
-                    "Arm" => Architecture::Arm,
-                    "Avr" => Architecture::Avr,
-                    "Bpf" => Architecture::Bpf,
-                    "Csky" => Architecture::Csky,
-                    "I386" => Architecture::I386,
-                    "X86_64" => Architecture::X86_64,
-                    "X86_64_X32" => Architecture::X86_64_X32,
-                    "Hexagon" => Architecture::Hexagon,
-                    "LoongArch64" => Architecture::LoongArch64,
-                    "Mips" => Architecture::Mips,
-                    "Mips64" => Architecture::Mips64,
-                    "Msp430" => Architecture::Msp430,
-                    "PowerPc" => Architecture::PowerPc,
-                    "PowerPc64" => Architecture::PowerPc64,
-                    "Riscv32" => Architecture::Riscv32,
-                    "Riscv64" => Architecture::Riscv64,
-                    "S390x" => Architecture::S390x,
-                    "Sbf" => Architecture::Sbf,
-                    "Sharc" => Architecture::Sharc,
-                    "Sparc" => Architecture::Sparc,
-                    "Sparc32Plus" => Architecture::Sparc32Plus,
-                    "Sparc64" => Architecture::Sparc64,
-                    "Wasm32" => Architecture::Wasm32,
-                    "Wasm64" => Architecture::Wasm64,
-                    "Xtensa" => Architecture::Xtensa,
-                    _ => return Err(mlua::Error::external("invalid architecture")),
+                let Some(architecture) = Header::architecture_from_name(&architecture) else {
+                    return Err(mlua::Error::external("invalid architecture"));
                 };
                 this.architecture = Some(architecture);
                 Ok(())
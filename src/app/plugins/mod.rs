@@ -10,4 +10,5 @@ pub mod plugin_instant;
 pub mod plugin_manager;
 pub mod popup_context;
 pub mod register_userdata;
+pub mod script;
 pub mod ui_location;
@@ -0,0 +1,38 @@
+use std::error::Error;
+
+use mlua::{Function, Lua};
+
+use super::{
+    app_context::AppContext,
+    register_userdata::{
+        register_settings, register_string, register_text, register_usize, register_vec_u8,
+    },
+};
+
+/// Runs a Lua script outside of the editor, the script must define a `run` function
+/// that receives the same context given to the plugins.
+/// Raising an error in the script makes it fail, name is used in the error messages.
+pub fn run_script(
+    source: &str,
+    name: &str,
+    app_context: &mut AppContext,
+) -> Result<(), Box<dyn Error>> {
+    let lua = Lua::new();
+    lua.load(source).set_name(format!("@{name}")).exec()?;
+
+    register_vec_u8(&lua)?;
+    register_settings(&lua)?;
+    register_text(&lua)?;
+    register_string(&lua)?;
+    register_usize(&lua)?;
+
+    let run = lua
+        .globals()
+        .get::<Function>("run")
+        .map_err(|_| t!("errors.script_without_run").to_string())?;
+    lua.scope(|scope| {
+        let context = app_context.to_lua(&lua, scope);
+        run.call::<()>(context)
+    })?;
+    Ok(())
+}
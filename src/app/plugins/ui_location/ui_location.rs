@@ -26,19 +26,20 @@ impl App {
     fn get_hex_and_text_view_byte_info(
         &self,
         relative_location: Point,
-        is_text: bool,
+        is_info_view: bool,
+        byte_size: usize,
         borders: RectBorders,
     ) -> (Option<u64>, Option<u64>, Option<u8>, Option<bool>) {
-        let (byte_size, offset_x) = if is_text {
+        let offset_x = if is_info_view {
             if borders.top || borders.right {
                 return (None, None, None, None);
             }
-            (2, relative_location.x as usize)
+            relative_location.x as usize
         } else {
             if borders.left || borders.top || borders.right {
                 return (None, None, None, None);
             }
-            (3, relative_location.x as usize - 1)
+            relative_location.x as usize - 1
         };
         let current_block = self.last_frame_info.blocks_per_row
             * (self.last_frame_info.scroll + (relative_location.y - 1) as usize)
@@ -50,6 +51,7 @@ impl App {
         if block_offset % byte_size == byte_size - 1
             || block_offset == (self.block_size * byte_size)
             || current_byte >= self.last_frame_info.file_size
+            || current_byte >= self.data.len()
         {
             (None, None, None, None)
         } else {
@@ -76,16 +78,37 @@ impl App {
                     PopupState::Run { .. } => "Run",
                     PopupState::FindText { .. } => "FindText",
                     PopupState::FindSymbol { .. } => "FindSymbol",
+                    PopupState::FindPattern { .. } => "FindPattern",
+                    PopupState::FindAssembly { .. } => "FindAssembly",
+                    PopupState::Xrefs { .. } => "Xrefs",
+                    PopupState::Cfg { .. } => "Cfg",
+                    PopupState::IsaMode { .. } => "IsaMode",
+                    PopupState::RenameLabel { .. } => "RenameLabel",
+                    PopupState::EditSymbol { .. } => "EditSymbol",
+                    PopupState::ApplyStruct { .. } => "ApplyStruct",
+                    PopupState::EditField { .. } => "EditField",
                     PopupState::Log(_) => "Log",
                     PopupState::InsertText { .. } => "InsertText",
                     PopupState::Patch { .. } => "Patch",
                     PopupState::JumpToAddress { .. } => "JumpToAddress",
                     PopupState::EditComment { .. } => "EditComment",
                     PopupState::FindComment { .. } => "FindComment",
+                    PopupState::Checkpoint { .. } => "Checkpoint",
+                    PopupState::UndoTree(_) => "UndoTree",
                     PopupState::QuitDirtySave(_) => "QuitDirtySave",
+                    PopupState::CloseDirtySave(_) => "CloseDirtySave",
                     PopupState::SaveAndQuit(_) => "SaveAndQuit",
                     PopupState::SaveAs { .. } => "SaveAs",
                     PopupState::Save(_) => "Save",
+                    PopupState::HostKey { .. } => "HostKey",
+                    PopupState::Passphrase { .. } => "Passphrase",
+                    PopupState::Fill { .. } => "Fill",
+                    PopupState::Export { .. } => "Export",
+                    PopupState::ExportPatch { .. } => "ExportPatch",
+                    PopupState::ImportPatch { .. } => "ImportPatch",
+                    PopupState::Diff { .. } => "Diff",
+                    PopupState::RawBinary { .. } => "RawBinary",
+                    PopupState::Buffers(_) => "Buffers",
                     PopupState::Help(_) => "Help",
                     PopupState::Custom { .. } => "Custom",
                 }
@@ -134,7 +157,7 @@ impl App {
             global_location.get_relative_location(self.last_frame_info.hex_view.as_ref())
         {
             let (file_address, virtual_address, byte, high) =
-                self.get_hex_and_text_view_byte_info(relative_location, false, borders);
+                self.get_hex_and_text_view_byte_info(relative_location, false, 3, borders);
 
             Some(UiLocation {
                 info: UiLocationInfo::HexView {
@@ -151,7 +174,7 @@ impl App {
             match &self.last_frame_info.info_view_frame_info {
                 InfoViewFrameInfo::TextView => {
                     let (file_address, virtual_address, byte, _high) =
-                        self.get_hex_and_text_view_byte_info(relative_location, true, borders);
+                        self.get_hex_and_text_view_byte_info(relative_location, true, 2, borders);
 
                     Some(UiLocation {
                         info: UiLocationInfo::TextView {
@@ -163,6 +186,23 @@ impl App {
                         relative_location,
                     })
                 }
+                InfoViewFrameInfo::DiffView => {
+                    let (file_address, _virtual_address, byte, _high) =
+                        self.get_hex_and_text_view_byte_info(relative_location, true, 3, borders);
+
+                    Some(UiLocation {
+                        info: UiLocationInfo::DiffView {
+                            file_address,
+                            byte,
+                            other_byte: file_address.and_then(|address| {
+                                self.diff
+                                    .as_ref()
+                                    .and_then(|diff| diff.other_byte(address as usize))
+                            }),
+                        },
+                        relative_location,
+                    })
+                }
                 InfoViewFrameInfo::AssemblyView { scroll } => {
                     let (section, file_address, virtual_address, instruction) =
                         if borders.top || borders.right {
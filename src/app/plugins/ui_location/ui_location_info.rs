@@ -17,6 +17,11 @@ pub enum UiLocationInfo {
         byte: Option<u8>,
         character: Option<char>,
     },
+    DiffView {
+        file_address: Option<u64>,
+        byte: Option<u8>,
+        other_byte: Option<u8>,
+    },
     AssemblyView {
         section: Option<String>,
         file_address: Option<u64>,
@@ -62,6 +67,16 @@ impl IntoLua for UiLocationInfo {
                 ret.set("byte", byte)?;
                 ret.set("character", character.map(|c| c.to_string()))?;
             }
+            UiLocationInfo::DiffView {
+                file_address,
+                byte,
+                other_byte,
+            } => {
+                ret.set("type", "DiffView")?;
+                ret.set("file_address", file_address)?;
+                ret.set("byte", byte)?;
+                ret.set("other_byte", other_byte)?;
+            }
             UiLocationInfo::AssemblyView {
                 section,
                 file_address,
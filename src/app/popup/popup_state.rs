@@ -1,15 +1,18 @@
-use std::error::Error;
+use std::{error::Error, ops::Range};
 
 use ratatui::text::{Line, Span, Text};
 
-use crate::get_app_context;
+use crate::{get_app_context, headers::isa_mode::IsaMode};
 
 use crate::app::{
-    asm::assembly_line::AssemblyLine,
+    asm::{assembly_line::AssemblyLine, cfg::ControlFlowGraph, xrefs::Xref},
     commands::command_info::CommandInfo,
     files::{path, path_result::PathResult},
+    history::{checkpoints::change_preview, history::HistoryTreeLine},
     plugins::popup_context::PopupContext,
+    search::AssemblyMatch,
     settings::color_settings::ColorSettings,
+    ssh::host_key::UnknownHostKey,
     App,
 };
 
@@ -41,6 +44,33 @@ pub enum PopupState {
         symbols: Vec<(u64, String)>,
         scroll: usize,
     },
+    FindPattern {
+        pattern: String,
+        cursor: usize,
+        results: Vec<Range<usize>>,
+        scroll: usize,
+    },
+    FindAssembly {
+        pattern: String,
+        cursor: usize,
+        results: Vec<AssemblyMatch>,
+        scroll: usize,
+    },
+    Xrefs {
+        target: String,
+        cursor: usize,
+        results: Vec<(Xref, String)>,
+        scroll: usize,
+    },
+    Cfg {
+        graph: ControlFlowGraph,
+        selected: usize,
+    },
+    IsaMode {
+        range: Range<u64>,
+        modes: Vec<IsaMode>,
+        selected: usize,
+    },
     Log(usize),
     InsertText {
         text: String,
@@ -65,13 +95,71 @@ pub enum PopupState {
         comments: Vec<(u64, String)>,
         scroll: usize,
     },
+    RenameLabel {
+        name: String,
+        cursor: usize,
+    },
+    EditSymbol {
+        name: String,
+        cursor: usize,
+    },
+    ApplyStruct {
+        name: String,
+        cursor: usize,
+    },
+    EditField {
+        field: usize,
+        value: String,
+        cursor: usize,
+    },
+    Checkpoint {
+        name: String,
+        cursor: usize,
+    },
+    UndoTree(usize),
     QuitDirtySave(SimpleChoice),
+    CloseDirtySave(SimpleChoice),
     SaveAndQuit(BinaryChoice),
     SaveAs {
         path: String,
         cursor: usize,
     },
     Save(BinaryChoice),
+    HostKey {
+        host_key: UnknownHostKey,
+        choice: BinaryChoice,
+    },
+    Passphrase {
+        path: String,
+        passphrase: String,
+        cursor: usize,
+        wrong: bool,
+    },
+    Fill {
+        pattern: String,
+        cursor: usize,
+    },
+    Export {
+        path: String,
+        cursor: usize,
+    },
+    ExportPatch {
+        path: String,
+        cursor: usize,
+    },
+    ImportPatch {
+        path: String,
+        cursor: usize,
+    },
+    Diff {
+        path: String,
+        cursor: usize,
+    },
+    RawBinary {
+        text: String,
+        cursor: usize,
+    },
+    Buffers(usize),
     Help(usize),
     Custom {
         plugin_index: usize,
@@ -86,8 +174,14 @@ impl App {
             Some(PopupState::Open { .. }) => screen_height - 7 - 2,
             Some(PopupState::Run { .. }) => screen_height - 6 - 2,
             Some(PopupState::FindSymbol { .. }) => screen_height - 6 - 2,
+            Some(PopupState::FindPattern { .. }) => screen_height - 6 - 2,
+            Some(PopupState::FindAssembly { .. }) => screen_height - 6 - 2,
+            Some(PopupState::Xrefs { .. }) => screen_height - 6 - 2,
             Some(PopupState::Log(_)) => screen_height - 4 - 2,
             Some(PopupState::Help(_)) => screen_height - 4 - 2,
+            Some(PopupState::Buffers(_)) => screen_height - 4 - 2,
+            Some(PopupState::UndoTree(_)) => screen_height - 4 - 2,
+            Some(PopupState::Cfg { .. }) => screen_height - 4 - 2,
             Some(PopupState::Patch { .. }) => screen_height - 6 - 2,
             Some(PopupState::InsertText { .. }) => screen_height - 5 - 2,
             Some(PopupState::FindComment { .. }) => screen_height - 6 - 2,
@@ -168,6 +262,9 @@ impl App {
     pub(in crate::app) fn resize_popup_if_needed(popup: &mut Option<PopupState>) {
         match popup {
             Some(PopupState::FindSymbol { scroll, .. })
+            | Some(PopupState::FindPattern { scroll, .. })
+            | Some(PopupState::FindAssembly { scroll, .. })
+            | Some(PopupState::Xrefs { scroll, .. })
             | Some(PopupState::Log(scroll))
             | Some(PopupState::Help(scroll)) => {
                 *scroll = 0;
@@ -789,6 +886,207 @@ impl App {
                     .lines
                     .extend(vec![editable_string.left_aligned()]);
             }
+            Some(PopupState::RenameLabel { name, cursor }) => {
+                *popup_title = t!("app.popup_titles.rename_label").into();
+                let available_width = width.saturating_sub(2);
+                *height = 3;
+                let editable_string = Self::get_line_from_string_and_cursor(
+                    &self.settings.color,
+                    name,
+                    *cursor,
+                    &t!("app.placeholders.label"),
+                    available_width,
+                    true,
+                );
+                popup_text
+                    .lines
+                    .extend(vec![editable_string.left_aligned()]);
+            }
+            Some(PopupState::Checkpoint { name, cursor }) => {
+                *popup_title = t!("app.popup_titles.checkpoint").into();
+                let available_width = width.saturating_sub(2);
+                *height = 3;
+                let editable_string = Self::get_line_from_string_and_cursor(
+                    &self.settings.color,
+                    name,
+                    *cursor,
+                    &t!("app.placeholders.checkpoint"),
+                    available_width,
+                    true,
+                );
+                popup_text
+                    .lines
+                    .extend(vec![editable_string.left_aligned()]);
+            }
+            Some(PopupState::UndoTree(scroll)) => {
+                *popup_title = t!("app.popup_titles.undo_tree").into();
+                let max_results = self.get_scrollable_popup_line_count();
+                *height = max_results + 4;
+                let history = self.data.history();
+                let tree = history.tree();
+                let mut selection = *scroll;
+                let scroll = if *scroll as isize > tree.len() as isize - (max_results as isize) / 2
+                {
+                    tree.len().saturating_sub(max_results)
+                } else if *scroll < max_results / 2 {
+                    0
+                } else {
+                    scroll.saturating_sub(max_results / 2)
+                };
+                selection = selection.saturating_sub(scroll);
+                let available_width = width.saturating_sub(2);
+                let line_to_line_lambda = |(i, line): (usize, &HistoryTreeLine)| {
+                    let marker = if line.id == history.current() {
+                        "●"
+                    } else {
+                        "○"
+                    };
+                    let (name, name_style) = match (line.checkpoint, line.change) {
+                        (Some(checkpoint), _) => {
+                            (checkpoint.to_string(), self.settings.color.assembly_symbol)
+                        }
+                        (None, Some(_)) => (
+                            format!("#{}", line.id),
+                            self.settings.color.assembly_address,
+                        ),
+                        (None, None) => (
+                            t!("app.messages.history_root").to_string(),
+                            self.settings.color.assembly_address,
+                        ),
+                    };
+                    let mut spans = vec![
+                        format!("{}{marker} ", "  ".repeat(line.depth)),
+                        name,
+                        line.change
+                            .map(|change| format!(" {}", change_preview(change)))
+                            .unwrap_or_default(),
+                    ];
+                    let mut remaining = available_width;
+                    for span in spans.iter_mut() {
+                        *span = span.chars().take(remaining).collect();
+                        remaining -= span.chars().count();
+                    }
+                    spans.push(" ".repeat(remaining));
+                    let styles = if i == selection {
+                        [self.settings.color.assembly_selected; 4]
+                    } else {
+                        [
+                            self.settings.color.menu_text,
+                            name_style,
+                            self.settings.color.assembly_default,
+                            self.settings.color.hex_default,
+                        ]
+                    };
+                    Line::from(
+                        spans
+                            .into_iter()
+                            .zip(styles)
+                            .map(|(span, style)| Span::styled(span, style))
+                            .collect::<Vec<_>>(),
+                    )
+                    .left_aligned()
+                };
+                if scroll > 0 {
+                    popup_text.lines.push(Line::from(vec![Span::styled(
+                        "▲",
+                        self.settings.color.menu_text,
+                    )]));
+                } else {
+                    popup_text.lines.push(Line::raw(""));
+                }
+                let lines = tree
+                    .iter()
+                    .skip(scroll)
+                    .take(max_results)
+                    .enumerate()
+                    .map(line_to_line_lambda)
+                    .collect::<Vec<_>>();
+                let padding = max_results.saturating_sub(lines.len());
+                popup_text.lines.extend(lines);
+                popup_text.lines.extend(vec![Line::raw(""); padding]);
+                if tree.len() as isize - scroll as isize > max_results as isize {
+                    popup_text.lines.push(Line::from(vec![Span::styled(
+                        "▼",
+                        self.settings.color.menu_text,
+                    )]));
+                } else {
+                    popup_text.lines.push(Line::raw(""));
+                }
+            }
+            Some(PopupState::EditSymbol { name, cursor }) => {
+                *popup_title = t!("app.popup_titles.edit_symbol").into();
+                let available_width = width.saturating_sub(2);
+                *height = 3;
+                let editable_string = Self::get_line_from_string_and_cursor(
+                    &self.settings.color,
+                    name,
+                    *cursor,
+                    &t!("app.placeholders.symbol"),
+                    available_width,
+                    true,
+                );
+                popup_text
+                    .lines
+                    .extend(vec![editable_string.left_aligned()]);
+            }
+            Some(PopupState::ApplyStruct { name, cursor }) => {
+                *popup_title = t!("app.popup_titles.apply_struct").into();
+                let available_width = width.saturating_sub(2);
+                let editable_string = Self::get_line_from_string_and_cursor(
+                    &self.settings.color,
+                    name,
+                    *cursor,
+                    &t!("app.placeholders.struct"),
+                    available_width,
+                    true,
+                );
+                popup_text
+                    .lines
+                    .extend(vec![editable_string.left_aligned()]);
+                // the known structs matching what was typed so far
+                let matching = self
+                    .struct_types
+                    .names()
+                    .into_iter()
+                    .filter(|s| s.contains(name.trim()))
+                    .take(8)
+                    .map(|s| Line::styled(s.to_string(), self.settings.color.assembly_symbol))
+                    .collect::<Vec<_>>();
+                if matching.is_empty() {
+                    popup_text.lines.push(Line::styled(
+                        t!("app.no_structs").to_string(),
+                        self.settings.color.placeholder,
+                    ));
+                }
+                *height = 3 + matching.len().max(1);
+                popup_text.lines.extend(matching);
+            }
+            Some(PopupState::EditField {
+                field,
+                value,
+                cursor,
+            }) => {
+                let field_name = self
+                    .struct_overlay
+                    .as_ref()
+                    .and_then(|overlay| overlay.fields.get(*field))
+                    .map(|field| format!("{} {}", field.type_name, field.name))
+                    .unwrap_or_default();
+                *popup_title = t!("app.popup_titles.edit_field", field = field_name).into();
+                let available_width = width.saturating_sub(2);
+                *height = 3;
+                let editable_string = Self::get_line_from_string_and_cursor(
+                    &self.settings.color,
+                    value,
+                    *cursor,
+                    &t!("app.placeholders.value"),
+                    available_width,
+                    true,
+                );
+                popup_text
+                    .lines
+                    .extend(vec![editable_string.left_aligned()]);
+            }
             Some(PopupState::FindComment {
                 filter,
                 comments,
@@ -914,6 +1212,366 @@ impl App {
                 ]);
                 popup_text.lines.extend(comments_as_lines);
             }
+            Some(PopupState::FindPattern {
+                pattern,
+                cursor,
+                results,
+                scroll,
+            }) => {
+                *popup_title = t!("app.popup_titles.find_pattern").into();
+                let available_width = width.saturating_sub(2);
+                let max_results = self.get_scrollable_popup_line_count();
+                *height = max_results + 2 + 4;
+                let mut selection = *scroll;
+                let scroll =
+                    if *scroll as isize > results.len() as isize - (max_results as isize) / 2 {
+                        results.len().saturating_sub(max_results)
+                    } else if *scroll < max_results / 2 {
+                        0
+                    } else {
+                        scroll.saturating_sub(max_results / 2)
+                    };
+                selection = selection.saturating_sub(scroll);
+                let editable_string = Self::get_line_from_string_and_cursor(
+                    &self.settings.color,
+                    pattern,
+                    *cursor,
+                    &t!("app.placeholders.byte_pattern"),
+                    available_width,
+                    true,
+                );
+
+                let results_as_lines = if !results.is_empty() {
+                    let result_to_line_lambda = |(i, range): (usize, &Range<usize>)| {
+                        let bytes = self.data.bytes()[range.clone()]
+                            .iter()
+                            .map(|byte| format!("{byte:02X}"))
+                            .collect::<Vec<String>>()
+                            .join(" ")
+                            .chars()
+                            .take(width.saturating_sub(19))
+                            .collect::<String>();
+                        let space_count =
+                            (width.saturating_sub(bytes.len() + 19) + 1).clamp(0, *width);
+                        let (style_bytes, style_empty, style_addr) = if i == selection {
+                            (
+                                self.settings.color.assembly_selected,
+                                self.settings.color.assembly_selected,
+                                self.settings.color.assembly_selected,
+                            )
+                        } else {
+                            (
+                                self.settings.color.hex_default,
+                                self.settings.color.hex_default,
+                                self.settings.color.assembly_address,
+                            )
+                        };
+                        Line::from(vec![
+                            Span::styled(bytes, style_bytes),
+                            Span::styled(" ".repeat(space_count), style_empty),
+                            Span::styled(format!("{:16X}", range.start), style_addr),
+                        ])
+                        .left_aligned()
+                    };
+                    let mut results_as_lines = if scroll > 0 {
+                        vec![Line::from(vec![Span::styled(
+                            "▲",
+                            self.settings.color.menu_text,
+                        )])]
+                    } else {
+                        vec![Line::raw("")]
+                    };
+                    results_as_lines.extend(
+                        results
+                            .iter()
+                            .skip(scroll)
+                            .take(max_results)
+                            .enumerate()
+                            .map(result_to_line_lambda),
+                    );
+                    if results_as_lines.len() < max_results {
+                        results_as_lines
+                            .extend(vec![Line::raw(""); max_results - results_as_lines.len()]);
+                    }
+                    if results.len() as isize - scroll as isize > max_results as isize {
+                        results_as_lines.push(Line::from(vec![Span::styled(
+                            "▼",
+                            self.settings.color.menu_text,
+                        )]));
+                    } else {
+                        results_as_lines.push(Line::raw(""));
+                    }
+                    results_as_lines
+                } else {
+                    let mut lines =
+                        vec![Line::raw(t!("app.messages.search_pattern_hint")).left_aligned()];
+                    lines.extend(vec![Line::raw(""); 7]);
+                    lines
+                };
+                popup_text.lines.extend(vec![
+                    editable_string.left_aligned(),
+                    Line::raw("─".repeat(*width)),
+                ]);
+                popup_text.lines.extend(results_as_lines);
+            }
+            Some(PopupState::FindAssembly {
+                pattern,
+                cursor,
+                results,
+                scroll,
+            }) => {
+                *popup_title = t!("app.popup_titles.find_assembly").into();
+                let available_width = width.saturating_sub(2);
+                let max_results = self.get_scrollable_popup_line_count();
+                *height = max_results + 2 + 4;
+                let mut selection = *scroll;
+                let scroll =
+                    if *scroll as isize > results.len() as isize - (max_results as isize) / 2 {
+                        results.len().saturating_sub(max_results)
+                    } else if *scroll < max_results / 2 {
+                        0
+                    } else {
+                        scroll.saturating_sub(max_results / 2)
+                    };
+                selection = selection.saturating_sub(scroll);
+                let editable_string = Self::get_line_from_string_and_cursor(
+                    &self.settings.color,
+                    pattern,
+                    *cursor,
+                    &t!("app.placeholders.assembly_pattern"),
+                    available_width,
+                    true,
+                );
+
+                let results_as_lines = if !results.is_empty() {
+                    let result_to_line_lambda = |(i, result): (usize, &AssemblyMatch)| {
+                        let text = result
+                            .text
+                            .chars()
+                            .take(width.saturating_sub(19))
+                            .collect::<String>();
+                        let space_count =
+                            (width.saturating_sub(text.chars().count() + 19) + 1).clamp(0, *width);
+                        let (style_text, style_empty, style_addr) = if i == selection {
+                            (
+                                self.settings.color.assembly_selected,
+                                self.settings.color.assembly_selected,
+                                self.settings.color.assembly_selected,
+                            )
+                        } else {
+                            (
+                                self.settings.color.assembly_default,
+                                self.settings.color.hex_default,
+                                self.settings.color.assembly_address,
+                            )
+                        };
+                        Line::from(vec![
+                            Span::styled(text, style_text),
+                            Span::styled(" ".repeat(space_count), style_empty),
+                            Span::styled(format!("{:16X}", result.virtual_address), style_addr),
+                        ])
+                        .left_aligned()
+                    };
+                    let mut results_as_lines = if scroll > 0 {
+                        vec![Line::from(vec![Span::styled(
+                            "▲",
+                            self.settings.color.menu_text,
+                        )])]
+                    } else {
+                        vec![Line::raw("")]
+                    };
+                    results_as_lines.extend(
+                        results
+                            .iter()
+                            .skip(scroll)
+                            .take(max_results)
+                            .enumerate()
+                            .map(result_to_line_lambda),
+                    );
+                    if results_as_lines.len() < max_results {
+                        results_as_lines
+                            .extend(vec![Line::raw(""); max_results - results_as_lines.len()]);
+                    }
+                    if results.len() as isize - scroll as isize > max_results as isize {
+                        results_as_lines.push(Line::from(vec![Span::styled(
+                            "▼",
+                            self.settings.color.menu_text,
+                        )]));
+                    } else {
+                        results_as_lines.push(Line::raw(""));
+                    }
+                    results_as_lines
+                } else {
+                    let mut lines =
+                        vec![Line::raw(t!("app.messages.assembly_pattern_hint")).left_aligned()];
+                    lines.extend(vec![Line::raw(""); 7]);
+                    lines
+                };
+                popup_text.lines.extend(vec![
+                    editable_string.left_aligned(),
+                    Line::raw("─".repeat(*width)),
+                ]);
+                popup_text.lines.extend(results_as_lines);
+            }
+            Some(PopupState::Xrefs {
+                target,
+                cursor,
+                results,
+                scroll,
+            }) => {
+                *popup_title = t!("app.popup_titles.xrefs").into();
+                let available_width = width.saturating_sub(2);
+                let max_results = self.get_scrollable_popup_line_count();
+                *height = max_results + 2 + 4;
+                let mut selection = *scroll;
+                let scroll =
+                    if *scroll as isize > results.len() as isize - (max_results as isize) / 2 {
+                        results.len().saturating_sub(max_results)
+                    } else if *scroll < max_results / 2 {
+                        0
+                    } else {
+                        scroll.saturating_sub(max_results / 2)
+                    };
+                selection = selection.saturating_sub(scroll);
+                let editable_string = Self::get_line_from_string_and_cursor(
+                    &self.settings.color,
+                    target,
+                    *cursor,
+                    &t!("app.placeholders.location"),
+                    available_width,
+                    true,
+                );
+
+                let results_as_lines = if !results.is_empty() {
+                    let result_to_line_lambda = |(i, (xref, text)): (usize, &(Xref, String))| {
+                        let text = format!("{:<5} {text}", xref.kind.name())
+                            .chars()
+                            .take(width.saturating_sub(19))
+                            .collect::<String>();
+                        let space_count =
+                            (width.saturating_sub(text.chars().count() + 19) + 1).clamp(0, *width);
+                        let (style_text, style_empty, style_addr) = if i == selection {
+                            (
+                                self.settings.color.assembly_selected,
+                                self.settings.color.assembly_selected,
+                                self.settings.color.assembly_selected,
+                            )
+                        } else {
+                            (
+                                self.settings.color.assembly_default,
+                                self.settings.color.hex_default,
+                                self.settings.color.assembly_address,
+                            )
+                        };
+                        Line::from(vec![
+                            Span::styled(text, style_text),
+                            Span::styled(" ".repeat(space_count), style_empty),
+                            Span::styled(format!("{:16X}", xref.from_virtual_address), style_addr),
+                        ])
+                        .left_aligned()
+                    };
+                    let mut results_as_lines = if scroll > 0 {
+                        vec![Line::from(vec![Span::styled(
+                            "▲",
+                            self.settings.color.menu_text,
+                        )])]
+                    } else {
+                        vec![Line::raw("")]
+                    };
+                    results_as_lines.extend(
+                        results
+                            .iter()
+                            .skip(scroll)
+                            .take(max_results)
+                            .enumerate()
+                            .map(result_to_line_lambda),
+                    );
+                    if results_as_lines.len() < max_results {
+                        results_as_lines
+                            .extend(vec![Line::raw(""); max_results - results_as_lines.len()]);
+                    }
+                    if results.len() as isize - scroll as isize > max_results as isize {
+                        results_as_lines.push(Line::from(vec![Span::styled(
+                            "▼",
+                            self.settings.color.menu_text,
+                        )]));
+                    } else {
+                        results_as_lines.push(Line::raw(""));
+                    }
+                    results_as_lines
+                } else {
+                    let mut lines = vec![Line::raw(t!("app.messages.xrefs_hint")).left_aligned()];
+                    lines.extend(vec![Line::raw(""); 7]);
+                    lines
+                };
+                popup_text.lines.extend(vec![
+                    editable_string.left_aligned(),
+                    Line::raw("─".repeat(*width)),
+                ]);
+                popup_text.lines.extend(results_as_lines);
+            }
+            Some(PopupState::Cfg { graph, selected }) => {
+                *popup_title = t!("app.popup_titles.cfg").into();
+                let max_lines = self.get_scrollable_popup_line_count();
+                *height = max_lines + 4;
+                let (lines, block_rows) =
+                    graph.to_lines(&self.settings.color, &self.labels, *selected);
+                let graph_width = lines.iter().map(|line| line.width()).max().unwrap_or(0);
+                *width = (*width).max(graph_width + 2);
+                // the selected block is kept in the upper part of the popup
+                let scroll = block_rows
+                    .get(*selected)
+                    .copied()
+                    .unwrap_or(0)
+                    .saturating_sub(max_lines / 4)
+                    .min(lines.len().saturating_sub(max_lines));
+                if scroll > 0 {
+                    popup_text.lines.push(Line::from(vec![Span::styled(
+                        "▲",
+                        self.settings.color.menu_text,
+                    )]));
+                } else {
+                    popup_text.lines.push(Line::raw(""));
+                }
+                let more_below = lines.len() > scroll + max_lines;
+                let visible = lines
+                    .into_iter()
+                    .skip(scroll)
+                    .take(max_lines)
+                    .collect::<Vec<_>>();
+                let padding = max_lines.saturating_sub(visible.len());
+                popup_text.lines.extend(visible);
+                popup_text.lines.extend(vec![Line::raw(""); padding]);
+                if more_below {
+                    popup_text.lines.push(Line::from(vec![Span::styled(
+                        "▼",
+                        self.settings.color.menu_text,
+                    )]));
+                } else {
+                    popup_text.lines.push(Line::raw(""));
+                }
+            }
+            Some(PopupState::IsaMode {
+                range,
+                modes,
+                selected,
+            }) => {
+                *popup_title = t!("app.popup_titles.isa_mode").into();
+                *height = modes.len() + 3;
+                popup_text
+                    .lines
+                    .push(Line::raw(format!("{:#X}..{:#X}", range.start, range.end)));
+                popup_text
+                    .lines
+                    .extend(modes.iter().enumerate().map(|(i, mode)| {
+                        let style = if i == *selected {
+                            self.settings.color.assembly_selected
+                        } else {
+                            self.settings.color.menu_text
+                        };
+                        Line::from(vec![Span::styled(mode.to_string(), style)])
+                    }));
+            }
             Some(PopupState::SaveAndQuit(choice)) => {
                 *popup_title = t!("app.popup_titles.save_and_quit").into();
                 popup_text.lines.extend(vec![
@@ -922,6 +1580,54 @@ impl App {
                     choice.to_line(&self.settings.color),
                 ]);
             }
+            Some(PopupState::HostKey { host_key, choice }) => {
+                *popup_title = t!("app.popup_titles.host_key").into();
+                *height = 6;
+                *width = 80;
+                popup_text.lines.extend(vec![
+                    Line::raw(t!(
+                        "app.messages.unknown_host_key",
+                        host = host_key.host,
+                        port = host_key.port
+                    )),
+                    Line::raw(format!(
+                        "{} {}",
+                        host_key.algorithm(),
+                        host_key.fingerprint()
+                    )),
+                    Line::raw(t!("app.messages.trust_host_key")),
+                    choice.to_line(&self.settings.color),
+                ]);
+            }
+            Some(PopupState::Passphrase {
+                path,
+                passphrase,
+                cursor,
+                wrong,
+            }) => {
+                *popup_title = t!("app.popup_titles.passphrase").into();
+                let available_width = width.saturating_sub(2);
+                *height = 4;
+                let message = if *wrong {
+                    t!("app.messages.wrong_passphrase", path = path)
+                } else {
+                    t!("app.messages.enter_passphrase", path = path)
+                };
+                // the passphrase is never shown, only its length
+                let hidden = "*".repeat(passphrase.chars().count());
+                let editable_string = Self::get_line_from_string_and_cursor(
+                    &self.settings.color,
+                    &hidden,
+                    *cursor,
+                    &t!("app.placeholders.passphrase"),
+                    available_width,
+                    true,
+                );
+                popup_text.lines.extend(vec![
+                    Line::raw(message).left_aligned(),
+                    editable_string.left_aligned(),
+                ]);
+            }
             Some(PopupState::SaveAs { path, cursor }) => {
                 *popup_title = t!("app.popup_titles.save_as").into();
                 let available_width = width.saturating_sub(2);
@@ -938,6 +1644,102 @@ impl App {
                     .lines
                     .extend(vec![editable_string.left_aligned()]);
             }
+            Some(PopupState::Fill { pattern, cursor }) => {
+                *popup_title = t!("app.popup_titles.fill").into();
+                let available_width = width.saturating_sub(2);
+                *height = 3;
+                let editable_string = Self::get_line_from_string_and_cursor(
+                    &self.settings.color,
+                    pattern,
+                    *cursor,
+                    &t!("app.placeholders.fill_pattern"),
+                    available_width,
+                    true,
+                );
+                popup_text
+                    .lines
+                    .extend(vec![editable_string.left_aligned()]);
+            }
+            Some(PopupState::RawBinary { text, cursor }) => {
+                *popup_title = t!("app.popup_titles.raw_binary").into();
+                let available_width = width.saturating_sub(2);
+                *height = 3;
+                let editable_string = Self::get_line_from_string_and_cursor(
+                    &self.settings.color,
+                    text,
+                    *cursor,
+                    &t!("app.placeholders.raw_binary"),
+                    available_width,
+                    true,
+                );
+                popup_text
+                    .lines
+                    .extend(vec![editable_string.left_aligned()]);
+            }
+            Some(PopupState::Export { path, cursor }) => {
+                *popup_title = t!("app.popup_titles.export").into();
+                let available_width = width.saturating_sub(2);
+                *height = 3;
+                let editable_string = Self::get_line_from_string_and_cursor(
+                    &self.settings.color,
+                    path,
+                    *cursor,
+                    &t!("app.placeholders.path"),
+                    available_width,
+                    true,
+                );
+                popup_text
+                    .lines
+                    .extend(vec![editable_string.left_aligned()]);
+            }
+            Some(PopupState::ExportPatch { path, cursor }) => {
+                *popup_title = t!("app.popup_titles.export_patch").into();
+                let available_width = width.saturating_sub(2);
+                *height = 3;
+                let editable_string = Self::get_line_from_string_and_cursor(
+                    &self.settings.color,
+                    path,
+                    *cursor,
+                    &t!("app.placeholders.path"),
+                    available_width,
+                    true,
+                );
+                popup_text
+                    .lines
+                    .extend(vec![editable_string.left_aligned()]);
+            }
+            Some(PopupState::ImportPatch { path, cursor }) => {
+                *popup_title = t!("app.popup_titles.import_patch").into();
+                let available_width = width.saturating_sub(2);
+                *height = 3;
+                let editable_string = Self::get_line_from_string_and_cursor(
+                    &self.settings.color,
+                    path,
+                    *cursor,
+                    &t!("app.placeholders.path"),
+                    available_width,
+                    true,
+                );
+                popup_text
+                    .lines
+                    .extend(vec![editable_string.left_aligned()]);
+            }
+            Some(PopupState::Diff { path, cursor }) => {
+                *popup_title = t!("app.popup_titles.diff").into();
+                let available_width = width.saturating_sub(2);
+                *height = 3;
+                let editable_string = Self::get_line_from_string_and_cursor(
+                    &self.settings.color,
+                    path,
+                    *cursor,
+                    &t!("app.placeholders.path"),
+                    available_width,
+                    true,
+                );
+                popup_text
+                    .lines
+                    .extend(vec![editable_string.left_aligned()]);
+            }
             Some(PopupState::Save(choice)) => {
                 *popup_title = t!("app.popup_titles.save").into();
                 popup_text.lines.extend(vec![
@@ -954,6 +1756,74 @@ impl App {
                     choice.to_line(&self.settings.color),
                 ]);
             }
+            Some(PopupState::CloseDirtySave(choice)) => {
+                *popup_title = t!("app.popup_titles.close_dirty").into();
+                popup_text.lines.extend(vec![
+                    Line::raw(t!("app.messages.file_is_dirty")),
+                    Line::raw(t!("app.messages.do_you_want_to_save_and_close")),
+                    choice.to_line(&self.settings.color),
+                ]);
+            }
+            Some(PopupState::Buffers(scroll)) => {
+                *popup_title = t!("app.popup_titles.buffers").into();
+                let max_results = self.get_scrollable_popup_line_count();
+                *height = max_results + 4;
+                let buffers = self.buffer_list();
+                let mut selection = *scroll;
+                let scroll =
+                    if *scroll as isize > buffers.len() as isize - (max_results as isize) / 2 {
+                        buffers.len().saturating_sub(max_results)
+                    } else if *scroll < max_results / 2 {
+                        0
+                    } else {
+                        scroll.saturating_sub(max_results / 2)
+                    };
+                selection = selection.saturating_sub(scroll);
+                let available_width = width.saturating_sub(2);
+                let buffer_to_line_lambda = |(i, (path, dirty)): (usize, &(&str, bool))| {
+                    let text = format!("{} {path}", if *dirty { "*" } else { " " })
+                        .chars()
+                        .take(available_width)
+                        .collect::<String>();
+                    let space_count = available_width.saturating_sub(text.chars().count());
+                    let style = if i == selection {
+                        self.settings.color.assembly_selected
+                    } else {
+                        self.settings.color.assembly_default
+                    };
+                    Line::from(vec![
+                        Span::styled(text, style),
+                        Span::styled(" ".repeat(space_count), style),
+                    ])
+                    .left_aligned()
+                };
+                if scroll > 0 {
+                    popup_text.lines.push(Line::from(vec![Span::styled(
+                        "▲",
+                        self.settings.color.menu_text,
+                    )]));
+                } else {
+                    popup_text.lines.push(Line::raw(""));
+                }
+                let lines = buffers
+                    .iter()
+                    .skip(scroll)
+                    .take(max_results)
+                    .enumerate()
+                    .map(buffer_to_line_lambda)
+                    .collect::<Vec<_>>();
+                let padding = max_results.saturating_sub(lines.len());
+                popup_text.lines.extend(lines);
+                popup_text.lines.extend(vec![Line::raw(""); padding]);
+                if buffers.len() as isize - scroll as isize > max_results as isize {
+                    popup_text.lines.push(Line::from(vec![Span::styled(
+                        "▼",
+                        self.settings.color.menu_text,
+                    )]));
+                } else {
+                    popup_text.lines.push(Line::raw(""));
+                }
+            }
             Some(PopupState::Help(scroll)) => {
                 let max_lines = self.get_scrollable_popup_line_count();
                 *height = max_lines + 4;
@@ -0,0 +1,124 @@
+use crate::headers::raw_binary::RawBinary;
+
+use super::{log::NotificationLevel, popup::popup_state::PopupState, App};
+
+impl App {
+    /// Builds the header of a file without one from the raw binary description,
+    /// or from the segments of a firmware image, this must be done before the user
+    /// symbols are merged into the header.
+    pub(super) fn apply_raw_binary(&mut self) {
+        if !self.header.is_none() {
+            if self.raw_binary.is_some() {
+                self.log(
+                    NotificationLevel::Warning,
+                    t!("app.messages.file_has_header"),
+                );
+            }
+            return;
+        }
+        let header = match (&self.firmware, &self.raw_binary) {
+            (Some(image), raw_binary) => image.to_header(raw_binary.as_ref()),
+            (None, Some(raw_binary)) => raw_binary.to_header(self.data.len()),
+            (None, None) => return,
+        };
+        match header {
+            Ok(header) => self.header = header,
+            Err(e) => self.log(NotificationLevel::Error, e),
+        }
+    }
+
+    /// Describes the file without a header as text: "architecture bitness endianness
+    /// load_address [entry]". The description is remembered in the project file.
+    /// The load address of a firmware image is the one of its segments.
+    pub(super) fn set_raw_binary(&mut self, text: &str) {
+        let raw_binary = match text.parse::<RawBinary>() {
+            Ok(raw_binary) => raw_binary,
+            Err(e) => {
+                self.log(NotificationLevel::Error, e);
+                return;
+            }
+        };
+        let header = self.parse_header();
+        if !header.is_none() {
+            self.log(
+                NotificationLevel::Warning,
+                t!("app.messages.file_has_header"),
+            );
+            return;
+        }
+        self.header = header;
+        self.raw_binary = Some(raw_binary.clone());
+        self.apply_raw_binary();
+        self.user_symbols.apply(&mut self.header);
+        self.isa_modes.apply(&mut self.header);
+        self.log(
+            NotificationLevel::Info,
+            t!(
+                "app.messages.raw_binary_set",
+                architecture = raw_binary.architecture,
+                address = raw_binary.load_address : {:#X}
+            ),
+        );
+        let comments_path = self.get_comments_path();
+        self.save_raw_binary(&comments_path);
+        self.disassemble();
+    }
+
+    pub(super) fn request_popup_raw_binary(&mut self) {
+        let text = self
+            .raw_binary
+            .as_ref()
+            .map(|raw_binary| raw_binary.to_string())
+            .unwrap_or_default();
+        self.popup = Some(PopupState::RawBinary {
+            cursor: text.len(),
+            text,
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{app::asm::assembly_line::AssemblyLine, headers::Header};
+
+    use super::*;
+
+    #[test]
+    fn test_raw_binary() {
+        // bx lr; nop in arm mode
+        let data = vec![0x1e, 0xff, 0x2f, 0xe1, 0x00, 0xf0, 0x20, 0xe3];
+        let mut app = App::mockup(data);
+        app.resize_to_size(80, 24);
+        assert_eq!(app.header, Header::None);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("raw.hp-data.json");
+        let path = path.to_str().unwrap().to_string();
+        app.raw_binary = Some("arm 32 little 0x8000".parse().unwrap());
+        app.load_comments(Some(path.clone()));
+        app.disassemble();
+        assert_eq!(app.header.architecture(), object::Architecture::Arm);
+        assert_eq!(app.header.entry_point(), 0x8000);
+        let first = app
+            .assembly_instructions
+            .iter()
+            .find_map(|line| match line {
+                AssemblyLine::Instruction(i) => Some(i.instruction.clone()),
+                _ => None,
+            });
+        let first = first.unwrap();
+        assert_eq!(first.mnemonic(), "bx");
+        assert_eq!(first.ip(), 0x8000);
+
+        // the description is remembered in the project file, the header made to hold
+        // a user symbol does not count as a header of the file
+        app.raw_binary = None;
+        app.header = Header::None;
+        app.header.set_symbol(0x8000, "start");
+        app.load_comments(Some(path));
+        assert_eq!(app.header.architecture(), object::Architecture::Arm);
+
+        app.set_raw_binary("riscv 16 little 0");
+        assert_eq!(app.header.architecture(), object::Architecture::Arm);
+    }
+}
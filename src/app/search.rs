@@ -0,0 +1,434 @@
+use std::ops::Range;
+
+use regex::bytes::Regex;
+
+use super::{
+    asm::assembly_line::AssemblyLine, log::NotificationLevel, popup::popup_state::PopupState, App,
+};
+
+/// A sequence of instructions matching an assembly pattern.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssemblyMatch {
+    pub virtual_address: u64,
+    pub file_range: Range<usize>,
+    pub text: String,
+}
+
+impl App {
+    /// Compiles a search pattern into a regex over the bytes of the file.
+    /// A pattern between slashes like `/\x48\x8B.{2}\xE8/` is used as a regex,
+    /// otherwise the pattern is a sequence of hex bytes like `48 8B ?? ?? E8`
+    /// where every `?` matches any nibble.
+    pub(super) fn parse_byte_pattern(pattern: &str) -> Result<Regex, String> {
+        let pattern = pattern.trim();
+        let regex = if let Some(regex) = pattern
+            .strip_prefix('/')
+            .and_then(|pattern| pattern.strip_suffix('/'))
+        {
+            regex.to_string()
+        } else {
+            let digits = pattern
+                .chars()
+                .filter(|c| !c.is_whitespace())
+                .collect::<Vec<char>>();
+            if digits.len() % 2 != 0 {
+                return Err(t!("errors.odd_number_of_hex_digits").to_string());
+            }
+            let mut regex = String::new();
+            for pair in digits.chunks(2) {
+                regex.push_str(&Self::byte_pattern_to_regex(pair[0], pair[1]).ok_or_else(
+                    || {
+                        t!(
+                            "errors.invalid_search_pattern",
+                            pattern = pair.iter().collect::<String>()
+                        )
+                        .to_string()
+                    },
+                )?);
+            }
+            regex
+        };
+        if regex.is_empty() {
+            return Err(t!("errors.empty_search_pattern").to_string());
+        }
+        Regex::new(&format!("(?s-u){regex}")).map_err(|e| e.to_string())
+    }
+
+    fn byte_pattern_to_regex(high: char, low: char) -> Option<String> {
+        match (high, low) {
+            ('?', '?') => Some(".".to_string()),
+            ('?', low) => {
+                let low = low.to_digit(16)?;
+                Some(format!(
+                    "[{}]",
+                    (0..16)
+                        .map(|high| format!("\\x{:02X}", high << 4 | low))
+                        .collect::<String>()
+                ))
+            }
+            (high, '?') => {
+                let high = high.to_digit(16)?;
+                Some(format!("[\\x{:02X}-\\x{:02X}]", high << 4, high << 4 | 0xF))
+            }
+            (high, low) => Some(format!(
+                "\\x{:02X}",
+                high.to_digit(16)? << 4 | low.to_digit(16)?
+            )),
+        }
+    }
+
+    /// Searches the whole file for the pattern, the results are kept to cycle through them.
+    pub(super) fn find_pattern(&mut self, pattern: &str) -> Vec<Range<usize>> {
+        let regex = match Self::parse_byte_pattern(pattern) {
+            Ok(regex) => regex,
+            Err(e) => {
+                self.log(NotificationLevel::Error, e);
+                return Vec::new();
+            }
+        };
+        // searching needs the whole file, even if it is read on demand
+        self.load_range(0..self.data.len());
+        self.search_results = regex
+            .find_iter(self.data.bytes())
+            .map(|m| m.range())
+            .filter(|range| !range.is_empty())
+            .collect();
+        if self.search_results.is_empty() {
+            self.log(
+                NotificationLevel::Warning,
+                t!("app.messages.pattern_not_found"),
+            );
+        }
+        self.search_results.clone()
+    }
+
+    pub(super) fn jump_to_search_result(&mut self, index: usize) {
+        if let Some(result) = self.search_results.get(index) {
+            let address = result.start;
+            self.log(
+                NotificationLevel::Info,
+                t!(
+                    "app.messages.jump_to_search_result",
+                    index = index + 1,
+                    count = self.search_results.len(),
+                    address = address : {:#X}
+                ),
+            );
+            self.jump_to(address, false);
+        }
+    }
+
+    /// Jumps to the next search result after the cursor if direction is positive,
+    /// or to the previous one before the cursor if it is negative, wrapping around.
+    pub(super) fn jump_to_near_search_result(&mut self, direction: isize) {
+        if self.search_results.is_empty() {
+            self.log(
+                NotificationLevel::Warning,
+                t!("app.messages.no_search_results"),
+            );
+            return;
+        }
+        let cursor = self.get_cursor_position().global_byte_index;
+        let index = if direction >= 0 {
+            let next = self
+                .search_results
+                .partition_point(|result| result.start <= cursor);
+            if next == self.search_results.len() {
+                0
+            } else {
+                next
+            }
+        } else {
+            let previous = self
+                .search_results
+                .partition_point(|result| result.start < cursor);
+            previous
+                .checked_sub(1)
+                .unwrap_or(self.search_results.len() - 1)
+        };
+        self.jump_to_search_result(index);
+    }
+
+    /// Compiles an assembly pattern like `mov rax, *; call *` into one regex per
+    /// instruction. Every `*` matches any text, and a pattern made of a mnemonic
+    /// alone matches the instruction whatever its operands are.
+    pub(super) fn parse_assembly_pattern(pattern: &str) -> Result<Vec<regex::Regex>, String> {
+        let mut ret = Vec::new();
+        for instruction in pattern.split(';') {
+            let instruction = Self::normalize_instruction(instruction);
+            if instruction.is_empty() {
+                continue;
+            }
+            let mut regex = String::from("(?i)^");
+            for (i, part) in instruction.split('*').enumerate() {
+                if i > 0 {
+                    regex.push_str(".*");
+                }
+                regex.push_str(&regex::escape(part));
+            }
+            if !instruction.contains(' ') {
+                regex.push_str("( .*)?");
+            }
+            regex.push('$');
+            ret.push(regex::Regex::new(&regex).map_err(|e| e.to_string())?);
+        }
+        if ret.is_empty() {
+            return Err(t!("errors.empty_search_pattern").to_string());
+        }
+        Ok(ret)
+    }
+
+    /// Collapses whitespace so that `mov rax,rbx` and `mov  rax, rbx` compare equal,
+    /// a space is kept only between two words.
+    fn normalize_instruction(instruction: &str) -> String {
+        let is_word_char = |c: char| c.is_alphanumeric() || matches!(c, '_' | '.' | '*');
+        let mut ret = String::new();
+        let mut pending_space = false;
+        for c in instruction.trim().chars() {
+            if c.is_whitespace() {
+                pending_space = true;
+                continue;
+            }
+            if pending_space && ret.chars().last().is_some_and(is_word_char) && is_word_char(c) {
+                ret.push(' ');
+            }
+            pending_space = false;
+            ret.push(c);
+        }
+        ret
+    }
+
+    /// Searches the disassembly of the whole file for sequences of consecutive
+    /// instructions matching the pattern, the results are kept to cycle through them.
+    pub(super) fn find_assembly(&mut self, pattern: &str) -> Vec<AssemblyMatch> {
+        let regexes = match Self::parse_assembly_pattern(pattern) {
+            Ok(regexes) => regexes,
+            Err(e) => {
+                self.log(NotificationLevel::Error, e);
+                return Vec::new();
+            }
+        };
+        // the assembly view only holds a window of the file, the search covers all of it
+        self.load_range(0..self.data.len());
+        let (_, lines, _) = Self::sections_from_bytes(
+            self.data.bytes(),
+            &self.header,
+            0..self.data.len(),
+            self.code_map.as_ref(),
+        );
+        let instructions = lines
+            .iter()
+            .map(|line| match line {
+                AssemblyLine::Instruction(instruction) => Some((
+                    instruction,
+                    Self::normalize_instruction(&instruction.instruction.to_string()),
+                )),
+                AssemblyLine::SectionTag(_) => None,
+            })
+            .collect::<Vec<_>>();
+        let mut ret = Vec::new();
+        for window in instructions.windows(regexes.len()) {
+            let matches = window.iter().zip(regexes.iter()).all(|(line, regex)| {
+                line.as_ref()
+                    .is_some_and(|(_, normalized)| regex.is_match(normalized))
+            });
+            if !matches {
+                continue;
+            }
+            let window = window.iter().flatten().collect::<Vec<_>>();
+            let (first, _) = window[0];
+            let (last, _) = window[window.len() - 1];
+            ret.push(AssemblyMatch {
+                virtual_address: first.instruction.ip(),
+                file_range: first.file_address as usize
+                    ..last.file_address as usize + last.instruction.len(),
+                text: window
+                    .iter()
+                    .map(|(instruction, _)| instruction.instruction.to_string().trim().to_string())
+                    .collect::<Vec<_>>()
+                    .join("; "),
+            });
+        }
+        self.set_assembly_search_results(&ret);
+        if ret.is_empty() {
+            self.log(
+                NotificationLevel::Warning,
+                t!("app.messages.pattern_not_found"),
+            );
+        }
+        ret
+    }
+
+    /// Moves the search results after old_len bytes at offset were replaced with new_len
+    /// bytes. The results overlapping the replaced bytes might not match anymore and
+    /// are dropped.
+    pub(super) fn rebase_search_results(&mut self, offset: usize, old_len: usize, new_len: usize) {
+        let old_end = offset + old_len;
+        self.search_results.retain_mut(|range| {
+            if range.end <= offset {
+                true
+            } else if range.start >= old_end {
+                *range = range.start - old_len + new_len..range.end - old_len + new_len;
+                true
+            } else {
+                false
+            }
+        });
+    }
+
+    fn set_assembly_search_results(&mut self, results: &[AssemblyMatch]) {
+        self.search_results = results
+            .iter()
+            .map(|result| result.file_range.clone())
+            .collect();
+        // sections are not always laid out in the file in order
+        self.search_results.sort_by_key(|range| range.start);
+    }
+
+    /// Jumps to the selected match, the next and previous matches are then
+    /// reachable like the results of a byte pattern search.
+    pub(super) fn jump_to_assembly_match(&mut self, results: &[AssemblyMatch], index: usize) {
+        if let Some(result) = results.get(index) {
+            self.set_assembly_search_results(results);
+            let index = self
+                .search_results
+                .partition_point(|range| range.start < result.file_range.start);
+            self.jump_to_search_result(index);
+        }
+    }
+
+    pub(in crate::app) fn request_popup_find_assembly(&mut self) {
+        self.popup = Some(PopupState::FindAssembly {
+            pattern: String::new(),
+            cursor: 0,
+            results: Vec::new(),
+            scroll: 0,
+        });
+    }
+
+    pub(in crate::app) fn request_popup_find_pattern(&mut self) {
+        self.popup = Some(PopupState::FindPattern {
+            pattern: String::new(),
+            cursor: 0,
+            results: Vec::new(),
+            scroll: 0,
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_byte_pattern() {
+        let data = [0x48, 0x8B, 0x05, 0x10, 0xE8, 0x48, 0x8B, 0xC0];
+        let regex = App::parse_byte_pattern("48 8B ?? ?? E8").unwrap();
+        assert_eq!(regex.find(&data).map(|m| m.range()), Some(0..5));
+        let regex = App::parse_byte_pattern("48 8b C?").unwrap();
+        assert_eq!(regex.find(&data).map(|m| m.range()), Some(5..8));
+        let regex = App::parse_byte_pattern("?0 E?").unwrap();
+        assert_eq!(regex.find(&data).map(|m| m.range()), Some(3..5));
+        let regex = App::parse_byte_pattern(r"/\x8B[\x00-\x0F]/").unwrap();
+        assert_eq!(regex.find(&data).map(|m| m.range()), Some(1..3));
+        // regexes match bytes that are not valid UTF-8
+        let regex = App::parse_byte_pattern(r"/\xE8./").unwrap();
+        assert_eq!(regex.find(&data).map(|m| m.range()), Some(4..6));
+
+        assert!(App::parse_byte_pattern("48 8").is_err());
+        assert!(App::parse_byte_pattern("4G").is_err());
+        assert!(App::parse_byte_pattern("").is_err());
+        assert!(App::parse_byte_pattern("/(/").is_err());
+    }
+
+    #[test]
+    fn test_search_results_cycle() {
+        let mut data = vec![0; 0x40];
+        data[0x08] = 0xCC;
+        data[0x10] = 0xCC;
+        data[0x30] = 0xCC;
+        let mut app = App::mockup(data);
+        app.resize_to_size(80, 24);
+        assert_eq!(
+            app.find_pattern("CC"),
+            vec![0x08..0x09, 0x10..0x11, 0x30..0x31]
+        );
+        app.jump_to_near_search_result(1);
+        assert_eq!(app.get_cursor_position().global_byte_index, 0x08);
+        app.jump_to_near_search_result(1);
+        assert_eq!(app.get_cursor_position().global_byte_index, 0x10);
+        app.jump_to_near_search_result(1);
+        app.jump_to_near_search_result(1);
+        assert_eq!(app.get_cursor_position().global_byte_index, 0x08);
+        app.jump_to_near_search_result(-1);
+        assert_eq!(app.get_cursor_position().global_byte_index, 0x30);
+        assert!(app.find_pattern("DD").is_empty());
+    }
+
+    #[test]
+    fn test_search_results_follow_resize() {
+        let mut data = vec![0; 0x40];
+        data[0x08] = 0xCC;
+        data[0x10..0x12].copy_from_slice(&[0xCC, 0xCC]);
+        data[0x30] = 0xCC;
+        let mut app = App::mockup(data);
+        app.resize_to_size(80, 24);
+        app.find_pattern("/\\xCC+/");
+        app.jump_to(0x11, false);
+        app.insert_bytes(&[0, 0]);
+        assert_eq!(app.search_results, vec![0x08..0x09, 0x32..0x33]);
+        app.jump_to(0, false);
+        app.delete_bytes(4);
+        assert_eq!(app.search_results, vec![0x04..0x05, 0x2E..0x2F]);
+        app.undo();
+        app.jump_to_near_search_result(1);
+        assert_eq!(app.get_cursor_position().global_byte_index, 0x08);
+    }
+
+    #[test]
+    fn test_parse_assembly_pattern() {
+        let regexes = App::parse_assembly_pattern("mov rax,*; call  *;ret;").unwrap();
+        assert_eq!(regexes.len(), 3);
+        assert!(regexes[0].is_match(&App::normalize_instruction("mov rax, rbx")));
+        assert!(regexes[0].is_match(&App::normalize_instruction("MOV RAX, qword ptr [rbp - 8]")));
+        assert!(!regexes[0].is_match(&App::normalize_instruction("mov rbx, rax")));
+        assert!(regexes[1].is_match(&App::normalize_instruction("call 0x1000")));
+        assert!(!regexes[1].is_match(&App::normalize_instruction("calls 0x1000")));
+        assert!(regexes[2].is_match(&App::normalize_instruction("ret ")));
+        // a lone mnemonic matches any operands
+        let regexes = App::parse_assembly_pattern("push").unwrap();
+        assert!(regexes[0].is_match(&App::normalize_instruction("push rbp")));
+        assert!(!regexes[0].is_match(&App::normalize_instruction("pushfq")));
+
+        assert!(App::parse_assembly_pattern(" ; ").is_err());
+    }
+
+    #[test]
+    fn test_find_assembly() {
+        // mov rax, rbx; mov rcx, rax; nop; mov rax, rax; ret
+        let data = vec![
+            0x48, 0x89, 0xd8, 0x48, 0x89, 0xc1, 0x90, 0x48, 0x89, 0xc0, 0xc3,
+        ];
+        let mut app = App::mockup(data);
+        app.resize_to_size(80, 24);
+        let results = app.find_assembly("mov rax, *");
+        assert_eq!(
+            results
+                .iter()
+                .map(|m| m.file_range.clone())
+                .collect::<Vec<_>>(),
+            vec![0..3, 7..10]
+        );
+        let results = app.find_assembly("mov *, rax; nop");
+        assert_eq!(
+            results,
+            vec![AssemblyMatch {
+                virtual_address: 3,
+                file_range: 3..7,
+                text: "mov rcx, rax; nop".to_string()
+            }]
+        );
+        assert_eq!(app.search_results, vec![3..7]);
+        assert!(app.find_assembly("nop; ret").is_empty());
+    }
+}
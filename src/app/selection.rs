@@ -0,0 +1,218 @@
+use std::{error::Error, ops::Range};
+
+use super::{files::path, log::NotificationLevel, popup::popup_state::PopupState, App};
+
+impl App {
+    pub(super) fn toggle_selection(&mut self) {
+        if self.selection_anchor.is_some() {
+            self.selection_anchor = None;
+        } else if !self.data.is_empty() {
+            self.selection_anchor = Some(self.get_cursor_position().global_byte_index);
+        }
+    }
+
+    /// Returns the range of bytes between the selection anchor and the cursor,
+    /// both included. None if there is no active selection.
+    pub(super) fn get_selection(&self) -> Option<Range<usize>> {
+        let anchor = self.selection_anchor?;
+        if self.data.is_empty() {
+            return None;
+        }
+        let cursor = self.get_cursor_position().global_byte_index;
+        let start = anchor.min(cursor).min(self.data.len() - 1);
+        let end = (anchor.max(cursor) + 1).min(self.data.len());
+        Some(start..end)
+    }
+
+    /// Returns the selected range, or the byte under the cursor if nothing is selected.
+    pub(super) fn get_selection_or_cursor(&self) -> Range<usize> {
+        self.get_selection().unwrap_or_else(|| {
+            let cursor = self.get_cursor_position().global_byte_index;
+            cursor..(cursor + 1).min(self.data.len())
+        })
+    }
+
+    pub(super) fn copy_selection(&mut self) {
+        if self.data.is_empty() {
+            return;
+        }
+        let range = self.get_selection_or_cursor();
+        self.load_range(range.clone());
+        self.clipboard = self.data.bytes()[range].to_vec();
+        self.selection_anchor = None;
+        self.log(
+            NotificationLevel::Info,
+            t!("app.messages.bytes_copied", count = self.clipboard.len()),
+        );
+    }
+
+    pub(super) fn paste(&mut self) {
+        if self.clipboard.is_empty() {
+            self.log(
+                NotificationLevel::Warning,
+                t!("app.messages.clipboard_empty"),
+            );
+            return;
+        }
+        if self.data.is_empty() {
+            return;
+        }
+        let range = self.get_selection_or_cursor();
+        self.selection_anchor = None;
+        self.jump_to(range.start, false);
+        let bytes = self.clipboard.clone();
+        self.patch_bytes(&bytes, false);
+    }
+
+    pub(super) fn fill_selection(&mut self, pattern: &str) {
+        if self.data.is_empty() {
+            return;
+        }
+        let pattern = match Self::parse_hex_bytes(pattern) {
+            Ok(pattern) if !pattern.is_empty() => pattern,
+            _ => {
+                self.log(
+                    NotificationLevel::Error,
+                    t!("errors.invalid_fill_pattern", pattern = pattern),
+                );
+                return;
+            }
+        };
+        let range = self.get_selection_or_cursor();
+        let bytes = pattern
+            .iter()
+            .cycle()
+            .take(range.len())
+            .copied()
+            .collect::<Vec<u8>>();
+        self.selection_anchor = None;
+        self.jump_to(range.start, false);
+        self.patch_bytes(&bytes, false);
+    }
+
+    pub(super) fn delete_selection(&mut self) {
+        if self.data.is_empty() {
+            return;
+        }
+        let range = self.get_selection_or_cursor();
+        self.selection_anchor = None;
+        self.jump_to(range.start, false);
+        self.delete_bytes(range.len());
+    }
+
+    pub(super) fn export_selection(&mut self, path: &str) -> Result<(), Box<dyn Error>> {
+        if self.data.is_empty() {
+            return Ok(());
+        }
+        let range = self.get_selection_or_cursor();
+        self.load_range(range.clone());
+        if let Some(parent) = path::parent(path) {
+            self.filesystem.mkdirs(parent)?;
+        };
+        self.filesystem.create(path)?;
+        self.filesystem
+            .write(path, &self.data.bytes()[range.clone()])?;
+        self.log(
+            NotificationLevel::Info,
+            t!(
+                "app.messages.bytes_exported",
+                count = range.len(),
+                path = path
+            ),
+        );
+        Ok(())
+    }
+
+    /// Parses a string of hex digits like "DEADBEEF" or "de ad be ef" into bytes.
+    pub(super) fn parse_hex_bytes(text: &str) -> Result<Vec<u8>, String> {
+        let digits = text
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .collect::<Vec<char>>();
+        if digits.len() % 2 != 0 {
+            return Err(t!("errors.odd_number_of_hex_digits").to_string());
+        }
+        digits
+            .chunks(2)
+            .map(|pair| {
+                let pair = pair.iter().collect::<String>();
+                u8::from_str_radix(&pair, 16).map_err(|e| e.to_string())
+            })
+            .collect()
+    }
+
+    pub(in crate::app) fn request_popup_fill(&mut self) {
+        self.popup = Some(PopupState::Fill {
+            pattern: String::new(),
+            cursor: 0,
+        });
+    }
+
+    pub(in crate::app) fn request_popup_export(&mut self) {
+        let path = self.filesystem.pwd().to_string() + ".bin";
+        let cursor = path.len();
+        self.popup = Some(PopupState::Export { path, cursor });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_selection_range() {
+        let mut app = App::mockup(vec![0x90; 0x100]);
+        app.resize_to_size(80, 24);
+        assert_eq!(app.get_selection(), None);
+        assert_eq!(app.get_selection_or_cursor(), 0..1);
+        app.jump_to(4, false);
+        app.toggle_selection();
+        app.jump_to(2, false);
+        assert_eq!(app.get_selection(), Some(2..5));
+        app.jump_to(6, false);
+        assert_eq!(app.get_selection(), Some(4..7));
+        app.toggle_selection();
+        assert_eq!(app.get_selection(), None);
+    }
+
+    #[test]
+    fn test_selection_edits() {
+        let mut app = App::mockup((0..0x10).collect());
+        app.resize_to_size(80, 24);
+        app.jump_to(2, false);
+        app.toggle_selection();
+        app.jump_to(5, false);
+        app.copy_selection();
+        assert_eq!(app.clipboard, vec![2, 3, 4, 5]);
+        assert_eq!(app.get_selection(), None);
+
+        app.jump_to(8, false);
+        app.paste();
+        assert_eq!(&app.data.bytes()[8..12], &[2, 3, 4, 5]);
+
+        app.jump_to(0, false);
+        app.toggle_selection();
+        app.jump_to(2, false);
+        app.fill_selection("AA BB");
+        assert_eq!(&app.data.bytes()[0..4], &[0xAA, 0xBB, 0xAA, 3]);
+
+        app.toggle_selection();
+        app.jump_to(1, false);
+        app.delete_selection();
+        assert_eq!(app.data.len(), 0x0E);
+        assert_eq!(&app.data.bytes()[0..3], &[0xAA, 3, 4]);
+        app.undo();
+        assert_eq!(app.data.len(), 0x10);
+    }
+
+    #[test]
+    fn test_parse_hex_bytes() {
+        assert_eq!(App::parse_hex_bytes("90"), Ok(vec![0x90]));
+        assert_eq!(
+            App::parse_hex_bytes("de ad BEEF"),
+            Ok(vec![0xDE, 0xAD, 0xBE, 0xEF])
+        );
+        assert!(App::parse_hex_bytes("9").is_err());
+        assert!(App::parse_hex_bytes("zz").is_err());
+    }
+}
@@ -11,6 +11,9 @@ pub struct AppSettings {
     pub log_level: Verbosity,
     pub theme: ThemePreference,
     pub locale: Locale,
+    pub large_file_threshold: usize,
+    pub disassembly_window: usize,
+    pub recursive_disassembly: bool,
 }
 
 impl AppSettings {
@@ -37,6 +40,45 @@ impl AppSettings {
                 Ok(())
             },
         );
+        mlua::UserDataFields::add_field_method_get(
+            data,
+            "app_large_file_threshold",
+            |_lua, settings| Ok(settings.app.large_file_threshold),
+        );
+        mlua::UserDataFields::add_field_method_set(
+            data,
+            "app_large_file_threshold",
+            |_lua, settings, value| {
+                settings.app.large_file_threshold = value;
+                Ok(())
+            },
+        );
+        mlua::UserDataFields::add_field_method_get(
+            data,
+            "app_disassembly_window",
+            |_lua, settings| Ok(settings.app.disassembly_window),
+        );
+        mlua::UserDataFields::add_field_method_set(
+            data,
+            "app_disassembly_window",
+            |_lua, settings, value| {
+                settings.app.disassembly_window = value;
+                Ok(())
+            },
+        );
+        mlua::UserDataFields::add_field_method_get(
+            data,
+            "app_recursive_disassembly",
+            |_lua, settings| Ok(settings.app.recursive_disassembly),
+        );
+        mlua::UserDataFields::add_field_method_set(
+            data,
+            "app_recursive_disassembly",
+            |_lua, settings, value| {
+                settings.app.recursive_disassembly = value;
+                Ok(())
+            },
+        );
     }
 }
 
@@ -48,6 +90,9 @@ impl Default for AppSettings {
             log_level: Verbosity::default(),
             theme: ThemePreference::default(),
             locale: Locale::default(),
+            large_file_threshold: 0x4000000,
+            disassembly_window: 0x100000,
+            recursive_disassembly: false,
         }
     }
 }
@@ -25,15 +25,19 @@ pub struct ColorSettings {
     pub hex_whitespace: Style,
     pub hex_current_instruction: Style,
     pub hex_current_section: Style,
+    pub hex_selection: Style,
+    pub hex_difference: Style,
     pub hex_default: Style,
 
     pub pane_selected: Style,
     pub pane: Style,
 
     pub text_selected: Style,
+    pub text_selection: Style,
 
     pub assembly_symbol: Style,
     pub assembly_selected: Style,
+    pub assembly_selection: Style,
     pub assembly_address: Style,
     pub assembly_virtual_address: Style,
     pub assembly_nop: Style,
@@ -111,14 +115,18 @@ impl ColorSettings {
             hex_whitespace: Style::default().fg(desaturated_dark_brown),
             hex_current_instruction: Style::default().fg(Color::White).bg(dark_orange),
             hex_current_section: Style::default().fg(Color::White).bg(dark_orange),
+            hex_selection: Style::default().fg(Color::White).bg(Color::DarkGray),
+            hex_difference: Style::default().fg(Color::White).bg(Color::Red),
             hex_default: Style::default(),
 
             text_selected: Style::default().fg(Color::White).bg(Color::Black),
+            text_selection: Style::default().fg(Color::White).bg(Color::DarkGray),
             pane_selected: Style::default().fg(Color::LightGreen),
             pane: Style::default().fg(Color::White),
 
             assembly_symbol: Style::default().fg(Color::Green),
             assembly_selected: Style::default().fg(Color::White).bg(Color::Black),
+            assembly_selection: Style::default().fg(Color::White).bg(Color::DarkGray),
             assembly_address: Style::default().fg(Color::Gray),
             assembly_virtual_address: Style::default().fg(Color::Gray).add_modifier(Modifier::DIM),
             assembly_nop: Style::default().fg(Color::Gray),
@@ -191,14 +199,18 @@ impl ColorSettings {
             hex_current_section: Style::default()
                 .fg(Color::Black)
                 .bg(Color::Rgb(215, 170, 92)),
+            hex_selection: Style::default().fg(Color::Black).bg(Color::Gray),
+            hex_difference: Style::default().fg(Color::Black).bg(Color::LightRed),
             hex_default: Style::default(),
 
             text_selected: Style::default().fg(Color::Black).bg(Color::White),
+            text_selection: Style::default().fg(Color::Black).bg(Color::Gray),
             pane_selected: Style::default().fg(Color::LightGreen),
             pane: Style::default().fg(Color::White),
 
             assembly_symbol: Style::default().fg(Color::LightGreen),
             assembly_selected: Style::default().fg(Color::Black).bg(Color::White),
+            assembly_selection: Style::default().fg(Color::Black).bg(Color::Gray),
             assembly_address: Style::default().fg(Color::DarkGray),
             assembly_virtual_address: Style::default()
                 .fg(Color::DarkGray)
@@ -51,6 +51,26 @@ pub struct KeySettings {
 
     pub undo: KeyEvent,
     pub redo: KeyEvent,
+
+    pub insert_byte: KeyEvent,
+    pub delete_byte: KeyEvent,
+
+    pub select: KeyEvent,
+    pub copy: KeyEvent,
+    pub paste: KeyEvent,
+
+    pub find_pattern: KeyEvent,
+    pub find_assembly: KeyEvent,
+    pub xrefs: KeyEvent,
+    pub rename_label: KeyEvent,
+    pub edit_symbol: KeyEvent,
+    pub inspector: KeyEvent,
+    pub apply_struct: KeyEvent,
+    pub edit_field: KeyEvent,
+    pub next_match: KeyEvent,
+    pub previous_match: KeyEvent,
+    pub next_difference: KeyEvent,
+    pub previous_difference: KeyEvent,
 }
 
 impl KeySettings {
@@ -249,6 +269,26 @@ impl Default for KeySettings {
 
             undo: KeyEvent::new(KeyCode::Char('z'), KeyModifiers::CONTROL),
             redo: KeyEvent::new(KeyCode::Char('y'), KeyModifiers::CONTROL),
+
+            insert_byte: KeyEvent::new(KeyCode::Insert, KeyModifiers::empty()),
+            delete_byte: KeyEvent::new(KeyCode::Delete, KeyModifiers::empty()),
+
+            select: KeyEvent::new(KeyCode::Char('m'), KeyModifiers::empty()),
+            copy: KeyEvent::new(KeyCode::Char('y'), KeyModifiers::empty()),
+            paste: KeyEvent::new(KeyCode::Char('w'), KeyModifiers::empty()),
+
+            find_pattern: KeyEvent::new(KeyCode::Char('x'), KeyModifiers::empty()),
+            find_assembly: KeyEvent::new(KeyCode::Char('X'), KeyModifiers::SHIFT),
+            xrefs: KeyEvent::new(KeyCode::Char('r'), KeyModifiers::empty()),
+            rename_label: KeyEvent::new(KeyCode::Char('R'), KeyModifiers::SHIFT),
+            edit_symbol: KeyEvent::new(KeyCode::Char('S'), KeyModifiers::SHIFT),
+            inspector: KeyEvent::new(KeyCode::Char('I'), KeyModifiers::SHIFT),
+            apply_struct: KeyEvent::new(KeyCode::Char('T'), KeyModifiers::SHIFT),
+            edit_field: KeyEvent::new(KeyCode::Char('i'), KeyModifiers::empty()),
+            next_match: KeyEvent::new(KeyCode::Char('n'), KeyModifiers::empty()),
+            previous_match: KeyEvent::new(KeyCode::Char('N'), KeyModifiers::SHIFT),
+            next_difference: KeyEvent::new(KeyCode::Char(']'), KeyModifiers::empty()),
+            previous_difference: KeyEvent::new(KeyCode::Char('['), KeyModifiers::empty()),
         }
     }
 }
@@ -0,0 +1,192 @@
+use std::path::{Path, PathBuf};
+
+/// The options of a host read from `~/.ssh/config`, only the first value of
+/// each option is used, as in OpenSSH.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HostConfig {
+    pub hostname: Option<String>,
+    pub user: Option<String>,
+    pub port: Option<u16>,
+    pub identity_files: Vec<PathBuf>,
+    pub proxy_jump: Option<String>,
+}
+
+impl HostConfig {
+    /// Reads the options for host from the config file of the user, if the file
+    /// can not be read no option is set.
+    pub fn load(host: &str) -> Self {
+        let Some(home_dir) = dirs::home_dir() else {
+            return Self::default();
+        };
+        match std::fs::read_to_string(home_dir.join(".ssh").join("config")) {
+            Ok(config) => Self::parse(&config, host, &home_dir),
+            Err(_) => Self::default(),
+        }
+    }
+
+    /// Only the `Host` sections are supported, the options inside a `Match`
+    /// section are ignored.
+    pub fn parse(config: &str, host: &str, home_dir: &Path) -> Self {
+        let mut ret = Self::default();
+        let mut active = true;
+        for line in config.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (keyword, value) = match line.find(|c: char| c.is_whitespace() || c == '=') {
+                Some(index) => (
+                    &line[..index],
+                    line[index..]
+                        .trim_start()
+                        .trim_start_matches('=')
+                        .trim_start(),
+                ),
+                None => (line, ""),
+            };
+            let value = value.trim_matches('"');
+            match keyword.to_lowercase().as_str() {
+                "host" => active = Self::host_matches(value, host),
+                "match" => active = false,
+                _ if !active => {}
+                "hostname" => {
+                    ret.hostname.get_or_insert_with(|| value.to_string());
+                }
+                "user" => {
+                    ret.user.get_or_insert_with(|| value.to_string());
+                }
+                "port" => {
+                    if ret.port.is_none() {
+                        ret.port = value.parse().ok();
+                    }
+                }
+                "identityfile" => {
+                    ret.identity_files.push(Self::expand_path(value, home_dir));
+                }
+                "proxyjump" => {
+                    ret.proxy_jump.get_or_insert_with(|| value.to_string());
+                }
+                _ => {}
+            }
+        }
+        ret
+    }
+
+    /// Checks a list of patterns separated by spaces, a negated pattern that
+    /// matches excludes the host even if another pattern matches it.
+    fn host_matches(patterns: &str, host: &str) -> bool {
+        let mut ret = false;
+        for pattern in patterns.split_whitespace() {
+            if let Some(pattern) = pattern.strip_prefix('!') {
+                if wildcard_match(pattern, host) {
+                    return false;
+                }
+            } else if wildcard_match(pattern, host) {
+                ret = true;
+            }
+        }
+        ret
+    }
+
+    fn expand_path(path: &str, home_dir: &Path) -> PathBuf {
+        if let Some(rest) = path.strip_prefix("~/").or(path.strip_prefix("%d/")) {
+            home_dir.join(rest)
+        } else {
+            PathBuf::from(path)
+        }
+    }
+}
+
+/// Matches text against a pattern where `*` stands for any sequence of
+/// characters and `?` for a single character.
+fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if let Some((star, matched)) = backtrack {
+            p = star + 1;
+            t = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_config() {
+        let config = "
+# the first value of each option wins
+Host bastion !*.internal
+    HostName 10.0.0.1
+    User admin
+    Port=2222
+    ProxyJump none
+
+Host web-?? *.example.com
+    HostName \"web.example.com\"
+    IdentityFile ~/.ssh/web_key
+    ProxyJump admin@bastion:2222,gateway
+
+Match user root
+    User nobody
+
+Host *
+    User default
+    IdentityFile /keys/id_ed25519
+    Port nan
+";
+        let home_dir = Path::new("/home/user");
+        let bastion = HostConfig::parse(config, "bastion", home_dir);
+        assert_eq!(bastion.hostname.as_deref(), Some("10.0.0.1"));
+        assert_eq!(bastion.user.as_deref(), Some("admin"));
+        assert_eq!(bastion.port, Some(2222));
+        assert_eq!(bastion.proxy_jump.as_deref(), Some("none"));
+        assert_eq!(
+            bastion.identity_files,
+            vec![PathBuf::from("/keys/id_ed25519")]
+        );
+
+        let web = HostConfig::parse(config, "web-01", home_dir);
+        assert_eq!(web.hostname.as_deref(), Some("web.example.com"));
+        assert_eq!(web.user.as_deref(), Some("default"));
+        assert_eq!(web.port, None);
+        assert_eq!(
+            web.proxy_jump.as_deref(),
+            Some("admin@bastion:2222,gateway")
+        );
+        assert_eq!(
+            web.identity_files,
+            vec![
+                PathBuf::from("/home/user/.ssh/web_key"),
+                PathBuf::from("/keys/id_ed25519")
+            ]
+        );
+
+        let other = HostConfig::parse(config, "web-001", home_dir);
+        assert_eq!(other.hostname, None);
+        assert_eq!(other.user.as_deref(), Some("default"));
+    }
+
+    #[test]
+    fn test_wildcard_match() {
+        assert!(wildcard_match("*", "anything"));
+        assert!(wildcard_match("*.example.com", "a.b.example.com"));
+        assert!(!wildcard_match("*.example.com", "example.com"));
+        assert!(wildcard_match("h?st*", "host1"));
+        assert!(!wildcard_match("h?st", "hst"));
+    }
+}
@@ -1,93 +1,334 @@
-use std::{error::Error, fmt::Display, path::PathBuf, sync::Arc};
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt::Display,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
 
 use russh::client::{self, AuthResult, Handler};
-use russh::keys::key::PrivateKeyWithHashAlg;
-use russh_sftp::client::SftpSession;
+#[cfg(unix)]
+use russh::keys::agent::client::AgentClient;
+use russh::keys::{key::PrivateKeyWithHashAlg, ssh_key::PublicKey};
+use russh_sftp::{client::SftpSession, protocol::OpenFlags};
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
 
 use crate::app::files::path;
 
-pub struct SSHClient;
+use super::{
+    config::HostConfig,
+    host_key::{check_host_key, known_hosts_path, RejectedKey},
+    passphrase::PassphraseRequired,
+};
+
+pub struct SSHClient {
+    host: String,
+    port: u16,
+    rejected: Arc<Mutex<Option<RejectedKey>>>,
+}
+
 impl Handler for SSHClient {
     type Error = russh::Error;
 
     async fn check_server_key(
         &mut self,
-        _server_public_key: &russh::keys::ssh_key::PublicKey,
+        server_public_key: &PublicKey,
     ) -> Result<bool, Self::Error> {
-        Ok(true)
+        let checked = known_hosts_path()
+            .map_err(RejectedKey::Error)
+            .and_then(|path| check_host_key(&self.host, self.port, server_public_key, &path));
+        let Err(rejected) = checked else {
+            return Ok(true);
+        };
+        *self.rejected.lock().unwrap() = Some(rejected);
+        Ok(false)
+    }
+}
+
+/// How to authenticate, the values not given on the command line are read from `~/.ssh/config`.
+#[derive(Debug, Clone, Default)]
+pub struct ConnectionOptions {
+    pub password: Option<String>,
+    pub identity: Option<PathBuf>,
+    /// The hosts to connect through, separated by commas, as in `ssh -J`.
+    pub jump: Option<String>,
+    /// The passphrases typed for the encrypted keys, an empty one skips the key.
+    pub passphrases: HashMap<PathBuf, String>,
+}
+
+/// A host to connect to, with the options read from `~/.ssh/config` applied.
+struct Target {
+    username: String,
+    hostname: String,
+    port: u16,
+    config: HostConfig,
+}
+
+impl Target {
+    fn resolve(connection_str: &str) -> Result<Self, Box<dyn Error>> {
+        let (username, host, port) = Connection::parse_connection_str(connection_str)?;
+        let config = HostConfig::load(host);
+        let username = match username.or(config.user.as_deref()) {
+            Some(username) => username.to_string(),
+            None => std::env::var("USER")
+                .or_else(|_| std::env::var("USERNAME"))
+                .map_err(|_| Box::<dyn Error>::from(t!("errors.invalid_connection_string")))?,
+        };
+        Ok(Self {
+            username,
+            hostname: config.hostname.as_deref().unwrap_or(host).to_string(),
+            port: port.or(config.port).unwrap_or(22),
+            config,
+        })
     }
 }
 
 pub struct Connection {
     runtime: tokio::runtime::Runtime,
     sftp: SftpSession,
+    /// The sessions with the jump hosts the connection goes through, kept open
+    /// while the connection is used.
+    _jump_sessions: Vec<client::Handle<SSHClient>>,
     connection_str: String,
 }
 
 impl Connection {
-    fn get_key_files() -> Result<(PathBuf, PathBuf), String> {
-        let home_dir = dirs::home_dir().ok_or_else(|| t!("errors.home_not_found").to_string())?;
+    /// Splits a connection string in the format `[<user>@]<host>[:<port>]`.
+    fn parse_connection_str(
+        connection_str: &str,
+    ) -> Result<(Option<&str>, &str, Option<u16>), Box<dyn Error>> {
+        let (username, host) = match connection_str.split_once('@') {
+            Some((username, host)) => (Some(username), host),
+            None => (None, connection_str),
+        };
+        let (host, port) = match host.split_once(':') {
+            Some((host, port)) => (
+                host,
+                Some(
+                    port.parse::<u16>()
+                        .map_err(|_| Box::<dyn Error>::from(t!("errors.invalid_port")))?,
+                ),
+            ),
+            None => (host, None),
+        };
+        if host.is_empty() || username.is_some_and(|username| username.is_empty()) {
+            return Err(t!("errors.invalid_connection_string").into());
+        }
+        Ok((username, host, port))
+    }
 
-        let ssh_dir = home_dir.join(".ssh");
-        if !ssh_dir.is_dir() {
-            return Err(t!("errors.ssh_dir_not_found").into());
+    /// The keys given with `--identity` or in the config are the only ones tried,
+    /// otherwise the default keys in `~/.ssh` are tried in order.
+    fn get_key_files(options: &ConnectionOptions, config: &HostConfig) -> Vec<PathBuf> {
+        if let Some(identity) = &options.identity {
+            return vec![identity.clone()];
         }
-        if ssh_dir.join("id_rsa").is_file() {
-            Ok((ssh_dir.join("id_rsa"), ssh_dir.join("id_rsa.pub")))
-        } else if ssh_dir.join("id_ed25519").is_file() {
-            Ok((ssh_dir.join("id_ed25519"), ssh_dir.join("id_ed25519.pub")))
-        } else if ssh_dir.join("id_ecdsa").is_file() {
-            Ok((ssh_dir.join("id_ecdsa"), ssh_dir.join("id_ecdsa.pub")))
-        } else if ssh_dir.join("id_dsa").is_file() {
-            Ok((ssh_dir.join("id_dsa"), ssh_dir.join("id_dsa.pub")))
-        } else {
-            Err(t!("errors.no_private_key").into())
+        if !config.identity_files.is_empty() {
+            // as in OpenSSH, the missing files in the config are skipped
+            return config
+                .identity_files
+                .iter()
+                .filter(|path| path.is_file())
+                .cloned()
+                .collect();
+        }
+        let Some(home_dir) = dirs::home_dir() else {
+            return Vec::new();
+        };
+        let ssh_dir = home_dir.join(".ssh");
+        ["id_rsa", "id_ed25519", "id_ecdsa", "id_dsa"]
+            .iter()
+            .map(|name| ssh_dir.join(name))
+            .filter(|path| path.is_file())
+            .collect()
+    }
+
+    /// Tries the keys held by the ssh-agent, if it is running.
+    #[cfg(unix)]
+    async fn authenticate_agent(session: &mut client::Handle<SSHClient>, username: &str) -> bool {
+        let Ok(mut agent) = AgentClient::connect_env().await else {
+            return false;
+        };
+        let Ok(identities) = agent.request_identities().await else {
+            return false;
+        };
+        for identity in identities {
+            let key = identity.public_key().into_owned();
+            if let Ok(AuthResult::Success) = session
+                .authenticate_publickey_with(username, key, None, &mut agent)
+                .await
+            {
+                return true;
+            }
         }
+        false
     }
 
-    pub fn new(connection_str: &str, password: Option<&str>) -> Result<Self, Box<dyn Error>> {
-        let runtime = tokio::runtime::Builder::new_current_thread()
-            .enable_all()
-            .build()?;
-        let (username, host) = connection_str
-            .split_once('@')
-            .ok_or_else(|| Box::<dyn Error>::from(t!("errors.invalid_connection_string")))?;
+    #[cfg(not(unix))]
+    async fn authenticate_agent(_session: &mut client::Handle<SSHClient>, _username: &str) -> bool {
+        false
+    }
 
-        let (hostname, port) =
-            host.split_once(':')
-                .map_or(Ok((host, 22)), |(hostname, port)| {
-                    port.parse::<u16>()
-                        .map(|port| (hostname, port))
-                        .map_err(|_| Box::<dyn Error>::from(t!("errors.invalid_port")))
-                })?;
+    async fn authenticate_key_files(
+        session: &mut client::Handle<SSHClient>,
+        username: &str,
+        key_files: Vec<PathBuf>,
+        options: &ConnectionOptions,
+    ) -> Result<bool, Box<dyn Error>> {
+        for path in key_files {
+            let passphrase = options.passphrases.get(&path);
+            if passphrase.is_some_and(|passphrase| passphrase.is_empty()) {
+                continue;
+            }
+            let keypair = match russh::keys::load_secret_key(&path, passphrase.map(|p| p.as_str()))
+            {
+                Ok(keypair) => keypair,
+                Err(russh::keys::Error::KeyIsEncrypted) => {
+                    return Err(Box::new(PassphraseRequired { path, wrong: false }));
+                }
+                Err(_) if passphrase.is_some() => {
+                    return Err(Box::new(PassphraseRequired { path, wrong: true }));
+                }
+                Err(e) => {
+                    return Err(t!(
+                        "errors.load_private_key",
+                        path = path.to_string_lossy(),
+                        e = e
+                    )
+                    .into())
+                }
+            };
+            let keypair = PrivateKeyWithHashAlg::new(Arc::new(keypair), None);
+            if let AuthResult::Success = session.authenticate_publickey(username, keypair).await? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Connects to target, through the session with the previous jump host if there is one,
+    /// and authenticates. The password is only used if it is given.
+    async fn connect_host(
+        target: &Target,
+        previous: Option<&client::Handle<SSHClient>>,
+        password: Option<&str>,
+        options: &ConnectionOptions,
+    ) -> Result<client::Handle<SSHClient>, Box<dyn Error>> {
+        let client_config = Arc::new(client::Config::default());
 
-        let config = client::Config::default();
+        let rejected = Arc::new(Mutex::new(None));
+        let client = SSHClient {
+            host: target.hostname.clone(),
+            port: target.port,
+            rejected: rejected.clone(),
+        };
+        let session = match previous {
+            Some(previous) => {
+                let channel = previous
+                    .channel_open_direct_tcpip(
+                        target.hostname.clone(),
+                        target.port as u32,
+                        "127.0.0.1",
+                        0,
+                    )
+                    .await
+                    .map_err(|e| {
+                        t!(
+                            "errors.jump_host_forward",
+                            host = target.hostname,
+                            port = target.port,
+                            e = e
+                        )
+                        .to_string()
+                    })?;
+                client::connect_stream(client_config, channel.into_stream(), client).await
+            }
+            None => {
+                client::connect(
+                    client_config,
+                    (target.hostname.as_str(), target.port),
+                    client,
+                )
+                .await
+            }
+        };
+        let rejected = rejected.lock().unwrap().take();
+        let mut session = match (session, rejected) {
+            (Ok(session), _) => session,
+            (Err(_), Some(rejected)) => return Err(rejected.into_error()),
+            (Err(e), None) => return Err(e.into()),
+        };
 
-        let mut session = runtime.block_on(client::connect(
-            config.into(),
-            (hostname, port),
-            SSHClient {},
-        ))?;
         if let Some(password) = password {
             if let AuthResult::Failure {
                 remaining_methods: _,
                 partial_success: _,
-            } = runtime.block_on(session.authenticate_password(username, password))?
+            } = session
+                .authenticate_password(&target.username, password)
+                .await?
             {
-                return Err(t!("errors.authentication_failed").into());
+                return Err(t!("errors.authentication_failed", host = target.hostname).into());
             }
         } else {
-            let (private_key, _public_key) = Self::get_key_files()?;
-            let keypair = russh::keys::load_secret_key(private_key, None)?;
-            let keypair = PrivateKeyWithHashAlg::new(Arc::new(keypair), None);
-            if let AuthResult::Failure {
-                remaining_methods: _,
-                partial_success: _,
-            } = runtime.block_on(session.authenticate_publickey(username, keypair))?
-            {
-                return Err(t!("errors.authentication_failed").into());
+            let key_files = Self::get_key_files(options, &target.config);
+            let no_key_files = key_files.is_empty();
+            let authenticated = Self::authenticate_agent(&mut session, &target.username).await
+                || Self::authenticate_key_files(&mut session, &target.username, key_files, options)
+                    .await?;
+            if !authenticated && no_key_files {
+                return Err(t!("errors.no_private_key").into());
+            } else if !authenticated {
+                return Err(t!("errors.authentication_failed", host = target.hostname).into());
             }
         }
+        Ok(session)
+    }
+
+    /// Returns the hosts to connect through, from `-J` if given, otherwise from the
+    /// `ProxyJump` of the config. A value of `none` connects directly.
+    fn jump_hosts<'a>(option: Option<&'a str>, config: &'a HostConfig) -> Vec<&'a str> {
+        match option.or(config.proxy_jump.as_deref()) {
+            Some(jumps) if !jumps.eq_ignore_ascii_case("none") => jumps
+                .split(',')
+                .map(|jump| jump.trim().trim_start_matches("ssh://"))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Returns the hosts in the order they are connected to, each with its password:
+    /// the password on the command line is for the last host.
+    fn hops<'a>(
+        jumps: &'a [Target],
+        target: &'a Target,
+        password: Option<&'a str>,
+    ) -> Vec<(&'a Target, Option<&'a str>)> {
+        jumps
+            .iter()
+            .map(|jump| (jump, None))
+            .chain([(target, password)])
+            .collect()
+    }
+
+    pub fn new(connection_str: &str, options: &ConnectionOptions) -> Result<Self, Box<dyn Error>> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        let target = Target::resolve(connection_str)?;
+        let jumps = Self::jump_hosts(options.jump.as_deref(), &target.config)
+            .into_iter()
+            .map(Target::resolve)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut sessions = runtime.block_on(async {
+            let mut sessions: Vec<client::Handle<SSHClient>> = Vec::new();
+            for (host, password) in Self::hops(&jumps, &target, options.password.as_deref()) {
+                let session = Self::connect_host(host, sessions.last(), password, options).await?;
+                sessions.push(session);
+            }
+            Ok::<_, Box<dyn Error>>(sessions)
+        })?;
+        let session = sessions.pop().unwrap();
 
         let channel = runtime.block_on(session.channel_open_session())?;
         runtime.block_on(channel.request_subsystem(true, "sftp"))?;
@@ -97,6 +338,7 @@ impl Connection {
         Ok(Self {
             runtime,
             sftp,
+            _jump_sessions: sessions,
             connection_str: connection_str.to_string(),
         })
     }
@@ -117,6 +359,36 @@ impl Connection {
         Ok(remote_file)
     }
 
+    pub fn file_size(&self, path: &str) -> Result<u64, Box<dyn Error>> {
+        let metadata = self.runtime.block_on(self.sftp.metadata(path))?;
+        Ok(metadata.len())
+    }
+
+    pub fn read_range(
+        &self,
+        path: &str,
+        offset: u64,
+        len: usize,
+    ) -> Result<Vec<u8>, Box<dyn Error>> {
+        self.runtime.block_on(async {
+            let mut file = self.sftp.open(path).await?;
+            file.seek(std::io::SeekFrom::Start(offset)).await?;
+            let mut data = vec![0; len];
+            file.read_exact(&mut data).await?;
+            Ok(data)
+        })
+    }
+
+    pub fn write_range(&self, path: &str, offset: u64, data: &[u8]) -> Result<(), Box<dyn Error>> {
+        self.runtime.block_on(async {
+            let mut file = self.sftp.open_with_flags(path, OpenFlags::WRITE).await?;
+            file.seek(std::io::SeekFrom::Start(offset)).await?;
+            file.write_all(data).await?;
+            file.shutdown().await?;
+            Ok(())
+        })
+    }
+
     pub fn mkdirs(&self, path: &str) -> Result<(), Box<dyn Error>> {
         self.runtime.block_on(async {
             let mut paths = vec![path];
@@ -172,3 +444,67 @@ impl Display for Connection {
         write!(f, "{}", self.connection_str)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_connection_str() {
+        assert_eq!(
+            Connection::parse_connection_str("user@host:2222").unwrap(),
+            (Some("user"), "host", Some(2222))
+        );
+        assert_eq!(
+            Connection::parse_connection_str("alias").unwrap(),
+            (None, "alias", None)
+        );
+        assert!(Connection::parse_connection_str("user@host:port").is_err());
+        assert!(Connection::parse_connection_str("@host").is_err());
+    }
+
+    #[test]
+    fn test_jump_hosts() {
+        let config = HostConfig {
+            proxy_jump: Some("ssh://bastion:2222".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            Connection::jump_hosts(Some("first, user@second:22"), &config),
+            vec!["first", "user@second:22"]
+        );
+        assert_eq!(Connection::jump_hosts(None, &config), vec!["bastion:2222"]);
+        assert!(Connection::jump_hosts(Some("none"), &config).is_empty());
+        let config = HostConfig {
+            proxy_jump: Some("None".to_string()),
+            ..Default::default()
+        };
+        assert!(Connection::jump_hosts(None, &config).is_empty());
+        assert!(Connection::jump_hosts(None, &HostConfig::default()).is_empty());
+    }
+
+    #[test]
+    fn test_hops() {
+        let host = |hostname: &str| Target {
+            username: "user".to_string(),
+            hostname: hostname.to_string(),
+            port: 22,
+            config: HostConfig::default(),
+        };
+        let jumps = vec![host("first"), host("second")];
+        let target = host("target");
+        let hops = Connection::hops(&jumps, &target, Some("secret"))
+            .into_iter()
+            .map(|(host, password)| (host.hostname.as_str(), password))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            hops,
+            vec![
+                ("first", None),
+                ("second", None),
+                ("target", Some("secret"))
+            ]
+        );
+        assert_eq!(Connection::hops(&[], &target, None).len(), 1);
+    }
+}
@@ -0,0 +1,199 @@
+use std::{
+    error::Error,
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+use russh::keys::{
+    check_known_hosts_path, known_hosts::learn_known_hosts_path, ssh_key::HashAlg,
+    ssh_key::PublicKey,
+};
+
+use crate::app::{log::NotificationLevel, App};
+
+/// The file where the keys of the known servers are kept, the same used by OpenSSH.
+pub fn known_hosts_path() -> Result<PathBuf, String> {
+    let home_dir = dirs::home_dir().ok_or_else(|| t!("errors.home_not_found").to_string())?;
+    Ok(home_dir.join(".ssh").join("known_hosts"))
+}
+
+/// Returned when connecting to a server whose key is not in the known hosts,
+/// the connection can be retried once the key is accepted.
+#[derive(Debug, Clone)]
+pub struct UnknownHostKey {
+    pub host: String,
+    pub port: u16,
+    pub key: PublicKey,
+}
+
+impl UnknownHostKey {
+    pub fn fingerprint(&self) -> String {
+        self.key.fingerprint(HashAlg::Sha256).to_string()
+    }
+
+    pub fn algorithm(&self) -> String {
+        self.key.algorithm().to_string()
+    }
+
+    /// Records the key in the known hosts, so the next connections trust it.
+    pub fn accept(&self) -> Result<(), Box<dyn Error>> {
+        self.accept_into(&known_hosts_path()?)
+    }
+
+    /// Records the key in the known hosts file at path, creating it if needed.
+    pub fn accept_into(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        learn_known_hosts_path(&self.host, self.port, &self.key, path)?;
+        Ok(())
+    }
+}
+
+impl Display for UnknownHostKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            t!(
+                "errors.unknown_host_key",
+                host = self.host,
+                port = self.port,
+                fingerprint = self.fingerprint()
+            )
+        )
+    }
+}
+
+impl Error for UnknownHostKey {}
+
+/// Why the key of a server was not trusted.
+#[derive(Debug)]
+pub enum RejectedKey {
+    Unknown(Box<UnknownHostKey>),
+    Changed {
+        host: String,
+        line: usize,
+        path: PathBuf,
+    },
+    Error(String),
+}
+
+impl RejectedKey {
+    /// The unknown key is returned as an UnknownHostKey, so it can be accepted.
+    pub fn into_error(self) -> Box<dyn Error> {
+        match self {
+            RejectedKey::Unknown(host_key) => host_key,
+            RejectedKey::Changed { host, line, path } => t!(
+                "errors.host_key_changed",
+                host = host,
+                line = line,
+                path = path.to_string_lossy()
+            )
+            .into(),
+            RejectedKey::Error(e) => t!("errors.check_host_key", e = e).into(),
+        }
+    }
+}
+
+/// Checks the key of a server against the known hosts file at known_hosts, the keys
+/// of the other algorithms do not count.
+pub fn check_host_key(
+    host: &str,
+    port: u16,
+    key: &PublicKey,
+    known_hosts: &Path,
+) -> Result<(), RejectedKey> {
+    match check_known_hosts_path(host, port, key, known_hosts) {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(RejectedKey::Unknown(Box::new(UnknownHostKey {
+            host: host.to_string(),
+            port,
+            key: key.clone(),
+        }))),
+        Err(russh::keys::Error::KeyChanged { line }) => Err(RejectedKey::Changed {
+            host: host.to_string(),
+            line,
+            path: known_hosts.to_path_buf(),
+        }),
+        Err(e) => Err(RejectedKey::Error(e.to_string())),
+    }
+}
+
+impl App {
+    pub(in crate::app) fn accept_host_key(&mut self, host_key: &UnknownHostKey) {
+        match host_key.accept() {
+            Ok(()) => self.log(
+                NotificationLevel::Info,
+                t!(
+                    "app.messages.host_key_accepted",
+                    host = host_key.host,
+                    fingerprint = host_key.fingerprint()
+                ),
+            ),
+            Err(e) => {
+                self.log(NotificationLevel::Error, t!("errors.save_host_key", e = e));
+                self.needs_to_exit = true;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use russh::keys::parse_public_key_base64;
+
+    use super::*;
+
+    const KEY: &str = "AAAAC3NzaC1lZDI1NTE5AAAAIJdD7y3aLq454yWBdwLWbieU1ebz9/cu7/QEXn9OIeZJ";
+    const OTHER_KEY: &str = "AAAAC3NzaC1lZDI1NTE5AAAAIA6rWI3G1sz07DnfFlrouTcysQlj2P+jpNSOEWD9OJ3X";
+
+    #[test]
+    fn test_check_host_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let known_hosts = dir.path().join("known_hosts");
+        fs::write(
+            &known_hosts,
+            format!(
+                "known.example ssh-ed25519 {KEY}\n\
+                 [changed.example]:2222 ssh-ed25519 {OTHER_KEY}\n\
+                 broken.example ssh-ed25519 not-a-key\n"
+            ),
+        )
+        .unwrap();
+        let key = parse_public_key_base64(KEY).unwrap();
+
+        assert!(check_host_key("known.example", 22, &key, &known_hosts).is_ok());
+        let Err(RejectedKey::Unknown(unknown)) =
+            check_host_key("missing.example", 22, &key, &known_hosts)
+        else {
+            panic!("the key of missing.example should be unknown");
+        };
+        assert_eq!(unknown.host, "missing.example");
+        assert_eq!(unknown.key, key);
+        assert!(matches!(
+            check_host_key("changed.example", 2222, &key, &known_hosts),
+            Err(RejectedKey::Changed { line: 2, .. })
+        ));
+        let changed = check_host_key("changed.example", 2222, &key, &known_hosts)
+            .unwrap_err()
+            .into_error();
+        assert!(changed.to_string().contains("changed.example"));
+        assert!(matches!(
+            check_host_key("broken.example", 22, &key, &known_hosts),
+            Err(RejectedKey::Error(_))
+        ));
+
+        // an accepted key is trusted by the next check, a missing file is created
+        let new_known_hosts = dir.path().join("ssh").join("known_hosts");
+        assert!(check_host_key("missing.example", 22, &key, &new_known_hosts).is_err());
+        unknown.accept_into(&new_known_hosts).unwrap();
+        assert!(check_host_key("missing.example", 22, &key, &new_known_hosts).is_ok());
+        let unknown = UnknownHostKey {
+            host: "missing.example".to_string(),
+            port: 2222,
+            key,
+        };
+        unknown.accept_into(&known_hosts).unwrap();
+        assert!(check_host_key("missing.example", 2222, &unknown.key, &known_hosts).is_ok());
+    }
+}
@@ -1 +1,5 @@
+pub mod config;
 pub mod connection;
+pub mod host_key;
+pub mod passphrase;
+pub mod prompt;
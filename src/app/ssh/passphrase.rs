@@ -0,0 +1,36 @@
+use std::{error::Error, fmt::Display, path::PathBuf};
+
+/// Returned when connecting with an encrypted private key whose passphrase is
+/// not known yet, the connection can be retried once it is typed.
+#[derive(Debug, Clone)]
+pub struct PassphraseRequired {
+    pub path: PathBuf,
+    /// The passphrase that was given could not decrypt the key.
+    pub wrong: bool,
+}
+
+impl Display for PassphraseRequired {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.wrong {
+            write!(
+                f,
+                "{}",
+                t!(
+                    "errors.wrong_passphrase",
+                    path = self.path.to_string_lossy()
+                )
+            )
+        } else {
+            write!(
+                f,
+                "{}",
+                t!(
+                    "errors.passphrase_required",
+                    path = self.path.to_string_lossy()
+                )
+            )
+        }
+    }
+}
+
+impl Error for PassphraseRequired {}
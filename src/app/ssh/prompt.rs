@@ -0,0 +1,84 @@
+use std::error::Error;
+
+use crossterm::event;
+use ratatui::backend::Backend;
+
+use crate::app::{
+    files::filesystem::FileSystem,
+    popup::{binary_choice::BinaryChoice, popup_state::PopupState},
+    App,
+};
+
+use super::{
+    connection::ConnectionOptions, host_key::UnknownHostKey, passphrase::PassphraseRequired,
+};
+
+impl App {
+    /// Connects to the remote host, asking the user to trust its key if it is unknown
+    /// and for the passphrases of the encrypted private keys.
+    pub(in crate::app) fn connect_remote<B: Backend>(
+        &mut self,
+        path: &str,
+        connection_str: &str,
+        mut options: ConnectionOptions,
+        terminal: &mut ratatui::Terminal<B>,
+    ) -> Result<FileSystem, Box<dyn Error>> {
+        loop {
+            let e = match FileSystem::new_remote(path, connection_str, &options) {
+                Ok(filesystem) => return Ok(filesystem),
+                Err(e) => e,
+            };
+            let e = match e.downcast::<UnknownHostKey>() {
+                Ok(host_key) => {
+                    self.popup = Some(PopupState::HostKey {
+                        host_key: *host_key,
+                        choice: BinaryChoice::No,
+                    });
+                    self.run_popup(terminal)?;
+                    if self.needs_to_exit {
+                        return Err(t!("errors.host_key_rejected").into());
+                    }
+                    continue;
+                }
+                Err(e) => e,
+            };
+            match e.downcast::<PassphraseRequired>() {
+                Ok(required) => {
+                    self.popup = Some(PopupState::Passphrase {
+                        path: required.path.to_string_lossy().to_string(),
+                        passphrase: String::new(),
+                        cursor: 0,
+                        wrong: required.wrong,
+                    });
+                    if let Some(PopupState::Passphrase { passphrase, .. }) =
+                        self.run_popup(terminal)?
+                    {
+                        options.passphrases.insert(required.path, passphrase);
+                    }
+                    if self.needs_to_exit {
+                        return Err(t!("errors.passphrase_cancelled").into());
+                    }
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Handles the events of the popup until it is closed, used before the app starts running.
+    /// Returns the state the popup had when it was closed.
+    fn run_popup<B: Backend>(
+        &mut self,
+        terminal: &mut ratatui::Terminal<B>,
+    ) -> Result<Option<PopupState>, Box<dyn Error>> {
+        let mut last_state = self.popup.clone();
+        while self.popup.is_some() && !self.needs_to_exit {
+            self.draw(terminal).map_err(|e| e.to_string())?;
+            if event::poll(self.poll_time)? {
+                last_state.clone_from(&self.popup);
+                let event = event::read()?;
+                self.handle_event(event, terminal)?;
+            }
+        }
+        Ok(last_state)
+    }
+}
@@ -0,0 +1,244 @@
+use std::{collections::BTreeSet, fs::File, ops::Range};
+
+use memmap2::{MmapMut, MmapOptions};
+
+/// Granularity used to track which parts of a lazily loaded file
+/// have been fetched or modified.
+pub const PAGE_SIZE: usize = 0x1000;
+
+/// The bytes of the opened file.
+///
+/// Small files are read entirely into memory. Big local files are mapped
+/// copy-on-write, so edits never touch the file until it is saved. Big remote
+/// files are fetched page by page when they are needed, into an anonymous mapping
+/// that does not reserve memory: the pages that were never fetched nor edited
+/// take no memory, while the bytes can still be viewed as a single slice.
+#[derive(Debug)]
+pub enum Storage {
+    Owned(Vec<u8>),
+    Mapped {
+        map: MmapMut,
+        dirty_pages: BTreeSet<usize>,
+    },
+    Paged {
+        map: MmapMut,
+        loaded_pages: BTreeSet<usize>,
+        dirty_pages: BTreeSet<usize>,
+    },
+}
+
+impl Storage {
+    pub fn map_file(path: &str) -> std::io::Result<Self> {
+        let file = File::open(path)?;
+        // SAFETY: the mapping is private, changes made by other processes to the
+        // file while it is open might still be visible, like with any other editor
+        // that does not lock the file.
+        let map = unsafe { MmapOptions::new().map_copy(&file)? };
+        Ok(Self::Mapped {
+            map,
+            dirty_pages: BTreeSet::new(),
+        })
+    }
+
+    /// Creates a storage of the given size where no page has been loaded yet.
+    pub fn paged(len: usize) -> std::io::Result<Self> {
+        let map = MmapOptions::new().len(len).no_reserve_swap().map_anon()?;
+        Ok(Self::Paged {
+            map,
+            loaded_pages: BTreeSet::new(),
+            dirty_pages: BTreeSet::new(),
+        })
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        match self {
+            Self::Owned(bytes) => bytes,
+            Self::Mapped { map, .. } | Self::Paged { map, .. } => map,
+        }
+    }
+
+    /// Returns a mutable view of the given range, marking the pages it spans as dirty.
+    pub fn as_mut_slice(&mut self, range: Range<usize>) -> &mut [u8] {
+        match self {
+            Self::Owned(bytes) => &mut bytes[range],
+            Self::Mapped { map, dirty_pages }
+            | Self::Paged {
+                map, dirty_pages, ..
+            } => {
+                dirty_pages.extend(Self::pages(&range));
+                &mut map[range]
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.as_slice().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.as_slice().is_empty()
+    }
+
+    /// Returns true if the whole file is not kept in memory.
+    pub fn is_lazy(&self) -> bool {
+        !matches!(self, Self::Owned(_))
+    }
+
+    /// Replaces the bytes in range with new, the length of the storage changes
+    /// if the two lengths differ. A lazy storage is converted to an owned one
+    /// before resizing, a paged storage must be fully loaded at that point.
+    pub fn splice(&mut self, range: Range<usize>, new: &[u8]) {
+        if range.len() == new.len() {
+            self.as_mut_slice(range).copy_from_slice(new);
+        } else {
+            self.make_owned().splice(range, new.iter().cloned());
+        }
+    }
+
+    fn make_owned(&mut self) -> &mut Vec<u8> {
+        if self.is_lazy() {
+            *self = Self::Owned(self.as_slice().to_vec());
+        }
+        match self {
+            Self::Owned(bytes) => bytes,
+            _ => unreachable!(),
+        }
+    }
+
+    fn pages(range: &Range<usize>) -> Range<usize> {
+        if range.is_empty() {
+            0..0
+        } else {
+            range.start / PAGE_SIZE..range.end.div_ceil(PAGE_SIZE)
+        }
+    }
+
+    /// Returns the page aligned byte ranges inside range that still need to be fetched.
+    pub fn missing_ranges(&self, range: Range<usize>) -> Vec<Range<usize>> {
+        let mut ret: Vec<Range<usize>> = Vec::new();
+        if let Self::Paged {
+            map, loaded_pages, ..
+        } = self
+        {
+            let range = range.start.min(map.len())..range.end.min(map.len());
+            for page in Self::pages(&range) {
+                if loaded_pages.contains(&page) {
+                    continue;
+                }
+                let start = page * PAGE_SIZE;
+                let end = (start + PAGE_SIZE).min(map.len());
+                match ret.last_mut() {
+                    Some(last) if last.end == start => last.end = end,
+                    _ => ret.push(start..end),
+                }
+            }
+        }
+        ret
+    }
+
+    /// Stores bytes fetched from offset, offset must be page aligned.
+    pub fn load(&mut self, offset: usize, new: &[u8]) {
+        if let Self::Paged {
+            map, loaded_pages, ..
+        } = self
+        {
+            let range = offset..offset + new.len();
+            loaded_pages.extend(Self::pages(&range));
+            map[range].copy_from_slice(new);
+        }
+    }
+
+    /// Returns the byte ranges modified since the last call to clear_dirty_pages.
+    /// None if the storage does not keep track of them and must be written entirely.
+    pub fn dirty_ranges(&self) -> Option<Vec<Range<usize>>> {
+        let (dirty_pages, len) = match self {
+            Self::Owned(_) => return None,
+            Self::Mapped { map, dirty_pages }
+            | Self::Paged {
+                map, dirty_pages, ..
+            } => (dirty_pages, map.len()),
+        };
+        let mut ret: Vec<Range<usize>> = Vec::new();
+        for page in dirty_pages {
+            let start = page * PAGE_SIZE;
+            let end = (start + PAGE_SIZE).min(len);
+            match ret.last_mut() {
+                Some(last) if last.end == start => last.end = end,
+                _ => ret.push(start..end),
+            }
+        }
+        Some(ret)
+    }
+
+    pub fn clear_dirty_pages(&mut self) {
+        match self {
+            Self::Owned(_) => {}
+            Self::Mapped { dirty_pages, .. } | Self::Paged { dirty_pages, .. } => {
+                dirty_pages.clear()
+            }
+        }
+    }
+}
+
+impl From<Vec<u8>> for Storage {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self::Owned(bytes)
+    }
+}
+
+impl Default for Storage {
+    fn default() -> Self {
+        Self::Owned(Vec::new())
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::single_range_in_vec_init)]
+mod test {
+    use std::io::Write;
+
+    use super::*;
+
+    #[test]
+    fn test_mapped_storage() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(&[0x90; PAGE_SIZE * 3]).unwrap();
+        let mut storage = Storage::map_file(&file.path().to_string_lossy()).unwrap();
+        assert!(storage.is_lazy());
+        assert_eq!(storage.len(), PAGE_SIZE * 3);
+        storage.splice(PAGE_SIZE + 1..PAGE_SIZE + 3, &[1, 2]);
+        assert_eq!(storage.dirty_ranges().unwrap(), [PAGE_SIZE..PAGE_SIZE * 2]);
+        // the mapping is copy-on-write
+        assert_eq!(
+            std::fs::read(file.path()).unwrap(),
+            vec![0x90; PAGE_SIZE * 3]
+        );
+
+        storage.splice(0..1, &[]);
+        assert!(!storage.is_lazy());
+        assert_eq!(storage.len(), PAGE_SIZE * 3 - 1);
+        assert_eq!(&storage.as_slice()[PAGE_SIZE..PAGE_SIZE + 2], &[1, 2]);
+    }
+
+    #[test]
+    fn test_paged_storage() {
+        let mut storage = Storage::paged(PAGE_SIZE * 4 + 1).unwrap();
+        assert_eq!(
+            storage.missing_ranges(PAGE_SIZE + 1..PAGE_SIZE * 2 + 1),
+            [PAGE_SIZE..PAGE_SIZE * 3]
+        );
+        storage.load(PAGE_SIZE * 2, &[1; PAGE_SIZE]);
+        assert_eq!(
+            storage.missing_ranges(0..usize::MAX),
+            vec![0..PAGE_SIZE * 2, PAGE_SIZE * 3..PAGE_SIZE * 4 + 1]
+        );
+        assert_eq!(storage.as_slice()[PAGE_SIZE * 2], 1);
+        storage.as_mut_slice(PAGE_SIZE * 4..PAGE_SIZE * 4 + 1)[0] = 2;
+        assert_eq!(
+            storage.dirty_ranges().unwrap(),
+            [PAGE_SIZE * 4..PAGE_SIZE * 4 + 1]
+        );
+        storage.clear_dirty_pages();
+        assert_eq!(storage.dirty_ranges(), Some(vec![]));
+    }
+}
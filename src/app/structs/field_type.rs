@@ -0,0 +1,356 @@
+use std::fmt::Display;
+
+use object::Endianness;
+
+/// The type of a field as written in a struct definition, like `u32le`, `char[16]`,
+/// `f64be[4]` or the name of another struct.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FieldType {
+    /// An integer, without an explicit endianness the one of the file is used.
+    Int {
+        size: usize,
+        signed: bool,
+        endianness: Option<Endianness>,
+    },
+    Float {
+        size: usize,
+        endianness: Option<Endianness>,
+    },
+    /// A fixed size string, terminated early by a null byte.
+    String(usize),
+    Array(Box<FieldType>, usize),
+    Struct(String),
+}
+
+/// A field that can be read and written, with the endianness already resolved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Primitive {
+    Int {
+        size: usize,
+        signed: bool,
+        endianness: Endianness,
+    },
+    Float {
+        size: usize,
+        endianness: Endianness,
+    },
+    String,
+    /// An array of bytes, shown as hex.
+    Bytes,
+}
+
+impl FieldType {
+    pub fn parse(name: &str) -> Option<Self> {
+        let name = name.trim();
+        if let Some(inner) = name.strip_suffix(']') {
+            let (element, count) = inner.rsplit_once('[')?;
+            let count = parse_count(count)?;
+            return match element.trim() {
+                "char" => Some(FieldType::String(count)),
+                element => Some(FieldType::Array(Box::new(Self::parse(element)?), count)),
+            };
+        }
+        let (base, endianness) = if let Some(base) = name.strip_suffix("le") {
+            (base, Some(Endianness::Little))
+        } else if let Some(base) = name.strip_suffix("be") {
+            (base, Some(Endianness::Big))
+        } else {
+            (name, None)
+        };
+        let field_type = match base {
+            "u8" | "i8" | "u16" | "i16" | "u32" | "i32" | "u64" | "i64" => FieldType::Int {
+                size: base[1..].parse::<usize>().unwrap() / 8,
+                signed: base.starts_with('i'),
+                endianness,
+            },
+            "f32" | "f64" => FieldType::Float {
+                size: base[1..].parse::<usize>().unwrap() / 8,
+                endianness,
+            },
+            "char" if endianness.is_none() => FieldType::String(1),
+            _ if is_identifier(name) => FieldType::Struct(name.to_string()),
+            _ => return None,
+        };
+        Some(field_type)
+    }
+}
+
+impl Display for FieldType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let suffix = |endianness: &Option<Endianness>| match endianness {
+            Some(Endianness::Little) => "le",
+            Some(Endianness::Big) => "be",
+            None => "",
+        };
+        match self {
+            FieldType::Int {
+                size,
+                signed,
+                endianness,
+            } => {
+                let sign = if *signed { 'i' } else { 'u' };
+                write!(f, "{sign}{}{}", size * 8, suffix(endianness))
+            }
+            FieldType::Float { size, endianness } => {
+                write!(f, "f{}{}", size * 8, suffix(endianness))
+            }
+            FieldType::String(size) => write!(f, "char[{size}]"),
+            FieldType::Array(element, count) => write!(f, "{element}[{count}]"),
+            FieldType::Struct(name) => write!(f, "{name}"),
+        }
+    }
+}
+
+impl Primitive {
+    /// Formats the value stored in bytes, that must be as long as the field.
+    pub fn format(&self, bytes: &[u8]) -> String {
+        match self {
+            Primitive::Int {
+                size,
+                signed,
+                endianness,
+            } => {
+                let value = read_uint(bytes, *endianness);
+                if *signed && *size < 8 && value >> (size * 8 - 1) & 1 == 1 {
+                    let value = (value | (u64::MAX << (size * 8))) as i64;
+                    format!(
+                        "{value} ({:#X})",
+                        value as u64 & (u64::MAX >> (64 - size * 8))
+                    )
+                } else if *signed {
+                    format!("{} ({value:#X})", value as i64)
+                } else {
+                    format!("{value} ({value:#X})")
+                }
+            }
+            Primitive::Float { size, endianness } => {
+                let value = read_uint(bytes, *endianness);
+                if *size == 4 {
+                    f32::from_bits(value as u32).to_string()
+                } else {
+                    f64::from_bits(value).to_string()
+                }
+            }
+            Primitive::String => {
+                let end = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+                format!("{:?}", String::from_utf8_lossy(&bytes[..end]))
+            }
+            Primitive::Bytes => bytes
+                .iter()
+                .map(|b| format!("{b:02X}"))
+                .collect::<Vec<_>>()
+                .join(" "),
+        }
+    }
+
+    /// Returns the value in the form accepted by encode.
+    pub fn editable_value(&self, bytes: &[u8]) -> String {
+        match self {
+            Primitive::Int { .. } => self
+                .format(bytes)
+                .split_once(' ')
+                .map(|(value, _)| value.to_string())
+                .unwrap_or_default(),
+            Primitive::String => {
+                let end = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+                String::from_utf8_lossy(&bytes[..end]).to_string()
+            }
+            Primitive::Float { .. } | Primitive::Bytes => self.format(bytes),
+        }
+    }
+
+    /// Encodes a value written by the user into size bytes.
+    pub fn encode(&self, value: &str, size: usize) -> Option<Vec<u8>> {
+        match self {
+            Primitive::Int {
+                signed, endianness, ..
+            } => {
+                let value = value.trim();
+                let (negative, digits) = match value.strip_prefix('-') {
+                    Some(digits) => (true, digits),
+                    None => (false, value),
+                };
+                let magnitude = match digits
+                    .strip_prefix("0x")
+                    .or_else(|| digits.strip_prefix("0X"))
+                {
+                    Some(hex) => u64::from_str_radix(hex, 16).ok()?,
+                    None => digits.parse::<u64>().ok()?,
+                };
+                let bits = size * 8;
+                let value = if negative {
+                    if !signed || magnitude > 1 << (bits - 1) {
+                        return None;
+                    }
+                    magnitude.wrapping_neg()
+                } else {
+                    if bits < 64 && magnitude >> bits != 0 {
+                        return None;
+                    }
+                    magnitude
+                };
+                Some(write_uint(value, size, *endianness))
+            }
+            Primitive::Float { endianness, .. } => {
+                let value = value.trim().parse::<f64>().ok()?;
+                let bits = if size == 4 {
+                    (value as f32).to_bits() as u64
+                } else {
+                    value.to_bits()
+                };
+                Some(write_uint(bits, size, *endianness))
+            }
+            Primitive::String => {
+                let mut bytes = value.as_bytes().to_vec();
+                if bytes.len() > size {
+                    return None;
+                }
+                bytes.resize(size, 0);
+                Some(bytes)
+            }
+            Primitive::Bytes => {
+                let digits = value
+                    .chars()
+                    .filter(|c| !c.is_whitespace())
+                    .collect::<String>();
+                if !digits.is_ascii() || digits.len() != size * 2 {
+                    return None;
+                }
+                (0..size)
+                    .map(|i| u8::from_str_radix(&digits[i * 2..i * 2 + 2], 16).ok())
+                    .collect()
+            }
+        }
+    }
+}
+
+fn parse_count(count: &str) -> Option<usize> {
+    let count = count.trim();
+    match count.strip_prefix("0x") {
+        Some(hex) => usize::from_str_radix(hex, 16).ok(),
+        None => count.parse().ok(),
+    }
+}
+
+pub(super) fn is_identifier(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn read_uint(bytes: &[u8], endianness: Endianness) -> u64 {
+    let mut value = 0u64;
+    match endianness {
+        Endianness::Little => {
+            for byte in bytes.iter().rev() {
+                value = value << 8 | *byte as u64;
+            }
+        }
+        Endianness::Big => {
+            for byte in bytes.iter() {
+                value = value << 8 | *byte as u64;
+            }
+        }
+    }
+    value
+}
+
+fn write_uint(value: u64, size: usize, endianness: Endianness) -> Vec<u8> {
+    let bytes = value.to_le_bytes()[..size].to_vec();
+    match endianness {
+        Endianness::Little => bytes,
+        Endianness::Big => bytes.into_iter().rev().collect(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_field_type() {
+        assert_eq!(
+            FieldType::parse("u32be"),
+            Some(FieldType::Int {
+                size: 4,
+                signed: false,
+                endianness: Some(Endianness::Big)
+            })
+        );
+        assert_eq!(
+            FieldType::parse("i16"),
+            Some(FieldType::Int {
+                size: 2,
+                signed: true,
+                endianness: None
+            })
+        );
+        assert_eq!(FieldType::parse("char[8]"), Some(FieldType::String(8)));
+        assert_eq!(
+            FieldType::parse("f64le[2][0x3]"),
+            Some(FieldType::Array(
+                Box::new(FieldType::Array(
+                    Box::new(FieldType::Float {
+                        size: 8,
+                        endianness: Some(Endianness::Little)
+                    }),
+                    2
+                )),
+                3
+            ))
+        );
+        assert_eq!(
+            FieldType::parse("elf_header"),
+            Some(FieldType::Struct("elf_header".to_string()))
+        );
+        assert_eq!(
+            FieldType::parse("u24"),
+            Some(FieldType::Struct("u24".to_string()))
+        );
+        assert_eq!(
+            FieldType::parse("table"),
+            Some(FieldType::Struct("table".to_string()))
+        );
+        assert_eq!(FieldType::parse("u8[x]"), None);
+        assert_eq!(
+            FieldType::parse("i16be[0x10]").unwrap().to_string(),
+            "i16be[16]"
+        );
+        assert_eq!(FieldType::parse("1abc"), None);
+    }
+
+    #[test]
+    fn test_primitive_format_and_encode() {
+        let i16be = Primitive::Int {
+            size: 2,
+            signed: true,
+            endianness: Endianness::Big,
+        };
+        assert_eq!(i16be.format(&[0xff, 0xfe]), "-2 (0xFFFE)");
+        assert_eq!(i16be.editable_value(&[0xff, 0xfe]), "-2");
+        assert_eq!(i16be.encode("-2", 2), Some(vec![0xff, 0xfe]));
+        assert_eq!(i16be.encode("0x1234", 2), Some(vec![0x12, 0x34]));
+        assert_eq!(i16be.encode("0x12345", 2), None);
+        let u32le = Primitive::Int {
+            size: 4,
+            signed: false,
+            endianness: Endianness::Little,
+        };
+        assert_eq!(
+            u32le.format(&[0x78, 0x56, 0x34, 0x12]),
+            "305419896 (0x12345678)"
+        );
+        assert_eq!(u32le.encode("-1", 4), None);
+        let f32le = Primitive::Float {
+            size: 4,
+            endianness: Endianness::Little,
+        };
+        assert_eq!(f32le.format(&1.5f32.to_le_bytes()), "1.5");
+        assert_eq!(f32le.encode("1.5", 4), Some(1.5f32.to_le_bytes().to_vec()));
+        assert_eq!(Primitive::String.format(b"ab\0c"), "\"ab\"");
+        assert_eq!(Primitive::String.encode("abc", 4), Some(b"abc\0".to_vec()));
+        assert_eq!(Primitive::String.encode("abcde", 4), None);
+        assert_eq!(Primitive::Bytes.format(&[0xde, 0xad]), "DE AD");
+        assert_eq!(Primitive::Bytes.encode("be ef", 2), Some(vec![0xbe, 0xef]));
+        assert_eq!(Primitive::Bytes.encode("be", 2), None);
+    }
+}
@@ -0,0 +1,3 @@
+pub mod field_type;
+pub mod overlay;
+pub mod struct_types;
@@ -0,0 +1,422 @@
+use object::Endianness;
+use ratatui::text::{Line, Span};
+
+use crate::app::{
+    data::Data, log::NotificationLevel, popup::popup_state::PopupState,
+    settings::color_settings::ColorSettings, App,
+};
+
+use super::{
+    field_type::{FieldType, Primitive},
+    struct_types::StructTypes,
+};
+
+/// Applying a struct with a huge array would make the pane unusable.
+const MAX_FIELDS: usize = 0x10000;
+
+/// A line of the struct pane, a value or the start of a nested struct or array.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OverlayField {
+    pub name: String,
+    pub type_name: String,
+    pub depth: usize,
+    pub offset: usize,
+    pub size: usize,
+    /// None for the nested structs and arrays, their fields follow.
+    pub primitive: Option<Primitive>,
+}
+
+/// A struct applied at a file address.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StructOverlay {
+    pub name: String,
+    pub address: usize,
+    pub size: usize,
+    pub fields: Vec<OverlayField>,
+}
+
+impl StructOverlay {
+    /// Lays out the fields of the struct, the integers and floats without an explicit
+    /// endianness use the one given.
+    pub fn new(
+        types: &StructTypes,
+        name: &str,
+        address: usize,
+        endianness: Endianness,
+    ) -> Result<Self, String> {
+        let field_type = FieldType::Struct(name.to_string());
+        let size = types.size_of(&field_type)?;
+        let mut fields = Vec::new();
+        let mut offset = address;
+        Self::push_fields(
+            types,
+            &types.get(name).unwrap().fields,
+            0,
+            &mut offset,
+            endianness,
+            &mut fields,
+        )?;
+        Ok(Self {
+            name: name.to_string(),
+            address,
+            size,
+            fields,
+        })
+    }
+
+    fn push_fields(
+        types: &StructTypes,
+        struct_fields: &[(String, FieldType)],
+        depth: usize,
+        offset: &mut usize,
+        endianness: Endianness,
+        fields: &mut Vec<OverlayField>,
+    ) -> Result<(), String> {
+        for (name, field_type) in struct_fields {
+            Self::push_field(types, name, field_type, depth, offset, endianness, fields)?;
+        }
+        Ok(())
+    }
+
+    fn push_field(
+        types: &StructTypes,
+        name: &str,
+        field_type: &FieldType,
+        depth: usize,
+        offset: &mut usize,
+        endianness: Endianness,
+        fields: &mut Vec<OverlayField>,
+    ) -> Result<(), String> {
+        if fields.len() >= MAX_FIELDS {
+            return Err(t!("errors.struct_too_big").to_string());
+        }
+        let size = types.size_of(field_type)?;
+        let primitive = match field_type {
+            FieldType::Int {
+                size,
+                signed,
+                endianness: field_endianness,
+            } => Some(Primitive::Int {
+                size: *size,
+                signed: *signed,
+                endianness: field_endianness.unwrap_or(endianness),
+            }),
+            FieldType::Float {
+                size,
+                endianness: field_endianness,
+            } => Some(Primitive::Float {
+                size: *size,
+                endianness: field_endianness.unwrap_or(endianness),
+            }),
+            FieldType::String(_) => Some(Primitive::String),
+            // a byte array is more readable on a single line
+            FieldType::Array(element, _) if matches!(**element, FieldType::Int { size: 1, .. }) => {
+                Some(Primitive::Bytes)
+            }
+            FieldType::Array(_, _) | FieldType::Struct(_) => None,
+        };
+        fields.push(OverlayField {
+            name: name.to_string(),
+            type_name: field_type.to_string(),
+            depth,
+            offset: *offset,
+            size,
+            primitive,
+        });
+        match field_type {
+            FieldType::Array(element, count) if primitive.is_none() => {
+                for i in 0..*count {
+                    Self::push_field(
+                        types,
+                        &format!("[{i}]"),
+                        element,
+                        depth + 1,
+                        offset,
+                        endianness,
+                        fields,
+                    )?;
+                }
+            }
+            FieldType::Struct(name) => {
+                // size_of already checked that the struct exists
+                let def = types.get(name).unwrap();
+                Self::push_fields(types, &def.fields, depth + 1, offset, endianness, fields)?;
+            }
+            _ => *offset += size,
+        }
+        Ok(())
+    }
+
+    /// Returns the index of the value containing the file address.
+    pub fn field_at(&self, address: usize) -> Option<usize> {
+        self.fields.iter().position(|field| {
+            field.primitive.is_some()
+                && (field.offset..field.offset + field.size).contains(&address)
+        })
+    }
+
+    /// Moves the struct after old_len bytes at offset were replaced with new_len bytes.
+    /// Returns false if the change is inside the struct, its fields are lost.
+    pub fn rebase(&mut self, offset: usize, old_len: usize, new_len: usize) -> bool {
+        if offset >= self.address + self.size {
+            return true;
+        }
+        if offset + old_len > self.address {
+            return false;
+        }
+        self.address = self.address - old_len + new_len;
+        for field in self.fields.iter_mut() {
+            field.offset = field.offset - old_len + new_len;
+        }
+        true
+    }
+
+    pub fn field_bytes<'a>(&self, field: &OverlayField, data: &'a Data) -> Option<&'a [u8]> {
+        data.bytes().get(field.offset..field.offset + field.size)
+    }
+
+    /// Returns height lines of the pane, scrolled to show the field under the cursor.
+    pub fn to_lines(
+        &self,
+        color_settings: &ColorSettings,
+        data: &Data,
+        cursor: usize,
+        height: usize,
+    ) -> Vec<Line<'static>> {
+        let selected = self.field_at(cursor);
+        let start = selected
+            .unwrap_or(0)
+            .saturating_sub(height / 2)
+            .min(self.fields.len().saturating_sub(height));
+        self.fields
+            .iter()
+            .enumerate()
+            .skip(start)
+            .take(height)
+            .map(|(i, field)| {
+                let mut line = Line::default();
+                line.spans.push(Span::raw("  ".repeat(field.depth)));
+                let name_style = if Some(i) == selected {
+                    color_settings.assembly_selected
+                } else {
+                    color_settings.assembly_symbol
+                };
+                line.spans
+                    .push(Span::styled(field.name.clone(), name_style));
+                line.spans.push(Span::raw(" "));
+                line.spans.push(Span::styled(
+                    field.type_name.clone(),
+                    color_settings.assembly_comment,
+                ));
+                if let Some(primitive) = &field.primitive {
+                    let value = self
+                        .field_bytes(field, data)
+                        .map(|bytes| primitive.format(bytes))
+                        .unwrap_or_else(|| "??".to_string());
+                    line.spans.push(Span::raw(": "));
+                    line.spans
+                        .push(Span::styled(value, color_settings.assembly_default));
+                }
+                line
+            })
+            .collect()
+    }
+}
+
+impl App {
+    /// Applies the struct at the cursor, an empty name removes the current one.
+    pub(in crate::app) fn apply_struct(&mut self, name: &str) {
+        let name = name.trim();
+        if name.is_empty() {
+            self.remove_struct();
+            return;
+        }
+        let address = self.get_cursor_position().global_byte_index;
+        let overlay =
+            match StructOverlay::new(&self.struct_types, name, address, self.header.endianness()) {
+                Ok(overlay) => overlay,
+                Err(e) => {
+                    self.log(NotificationLevel::Error, e);
+                    return;
+                }
+            };
+        if address + overlay.size > self.data.len() {
+            self.log(
+                NotificationLevel::Error,
+                t!("errors.struct_out_of_bounds", name = name, address = address : {:#X}),
+            );
+            return;
+        }
+        self.load_range(address..address + overlay.size);
+        self.log(
+            NotificationLevel::Info,
+            t!("app.messages.struct_applied", name = name, address = address : {:#X}),
+        );
+        self.struct_overlay = Some(overlay);
+    }
+
+    pub(in crate::app) fn remove_struct(&mut self) {
+        self.struct_overlay = None;
+    }
+
+    /// Writes a value to a field of the applied struct.
+    pub(in crate::app) fn edit_field(&mut self, index: usize, value: &str) {
+        let Some(field) = self
+            .struct_overlay
+            .as_ref()
+            .and_then(|overlay| overlay.fields.get(index))
+            .cloned()
+        else {
+            return;
+        };
+        let Some(primitive) = field.primitive else {
+            return;
+        };
+        match primitive.encode(value, field.size) {
+            Some(bytes) => {
+                self.jump_to(field.offset, false);
+                self.patch_bytes(&bytes, false);
+            }
+            None => {
+                self.log(
+                    NotificationLevel::Error,
+                    t!(
+                        "errors.invalid_field_value",
+                        field = field.name,
+                        value = value
+                    ),
+                );
+            }
+        }
+    }
+
+    pub(in crate::app) fn request_popup_apply_struct(&mut self) {
+        let name = self
+            .struct_overlay
+            .as_ref()
+            .map(|overlay| overlay.name.clone())
+            .unwrap_or_default();
+        self.popup = Some(PopupState::ApplyStruct {
+            cursor: name.len(),
+            name,
+        });
+    }
+
+    pub(in crate::app) fn request_popup_edit_field(&mut self) {
+        let address = self.get_cursor_position().global_byte_index;
+        let field = self.struct_overlay.as_ref().and_then(|overlay| {
+            let index = overlay.field_at(address)?;
+            let field = &overlay.fields[index];
+            let bytes = overlay.field_bytes(field, &self.data)?;
+            Some((index, field.primitive?.editable_value(bytes)))
+        });
+        match field {
+            Some((field, value)) => {
+                self.popup = Some(PopupState::EditField {
+                    field,
+                    cursor: value.len(),
+                    value,
+                });
+            }
+            None => {
+                self.log(
+                    NotificationLevel::Error,
+                    t!("errors.no_field_at", address = address : {:#X}),
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_struct_overlay() {
+        let mut types = StructTypes::new();
+        types
+            .add_definitions(
+                "struct point { i16 x; i16be y; }\n\
+                 struct header { char[4] magic; u8[2] pad; point[2] points; f32 scale; }",
+            )
+            .unwrap();
+        let overlay = StructOverlay::new(&types, "header", 2, Endianness::Little).unwrap();
+        assert_eq!(overlay.size, 18);
+        assert_eq!(
+            overlay
+                .fields
+                .iter()
+                .map(|f| (f.depth, f.name.as_str(), f.offset, f.primitive.is_some()))
+                .collect::<Vec<_>>(),
+            vec![
+                (0, "magic", 2, true),
+                (0, "pad", 6, true),
+                (0, "points", 8, false),
+                (1, "[0]", 8, false),
+                (2, "x", 8, true),
+                (2, "y", 10, true),
+                (1, "[1]", 12, false),
+                (2, "x", 12, true),
+                (2, "y", 14, true),
+                (0, "scale", 16, true),
+            ]
+        );
+        assert_eq!(overlay.field_at(13), Some(7));
+        assert_eq!(overlay.field_at(1), None);
+        assert_eq!(overlay.fields[2].type_name, "point[2]");
+
+        let mut moved = overlay.clone();
+        assert!(moved.rebase(20, 4, 0));
+        assert_eq!(moved, overlay);
+        assert!(moved.rebase(0, 1, 3));
+        assert_eq!(moved.address, 4);
+        assert_eq!(moved.field_at(15), Some(7));
+        assert!(!moved.rebase(10, 0, 1));
+    }
+
+    #[test]
+    fn test_apply_and_edit_struct() {
+        let mut data = vec![0; 0x20];
+        data[0..4].copy_from_slice(b"HPT\0");
+        data[4..8].copy_from_slice(&0x1234u32.to_le_bytes());
+        let mut app = App::mockup(data);
+        app.resize_to_size(80, 24);
+        app.struct_types
+            .add_definitions("struct header { char[4] magic; u32 size; u16be[2] values; }")
+            .unwrap();
+
+        app.apply_struct("missing");
+        assert!(app.struct_overlay.is_none());
+        app.jump_to(0x1e, false);
+        app.apply_struct("header");
+        assert!(app.struct_overlay.is_none());
+
+        app.jump_to(0, false);
+        app.apply_struct("header");
+        let overlay = app.struct_overlay.clone().unwrap();
+        let lines = overlay.to_lines(&app.settings.color, &app.data, 4, 10);
+        let text = lines.iter().map(|l| l.to_string()).collect::<Vec<_>>();
+        assert_eq!(text[0], "magic char[4]: \"HPT\"");
+        assert_eq!(text[1], "size u32: 4660 (0x1234)");
+        assert_eq!(text[3], "  [0] u16be: 0 (0x0)");
+
+        app.jump_to(5, false);
+        app.request_popup_edit_field();
+        assert!(matches!(
+            &app.popup,
+            Some(PopupState::EditField { field: 1, value, cursor: 4 }) if value == "4660"
+        ));
+        app.edit_field(1, "0xAABB");
+        assert_eq!(&app.data.bytes()[4..8], &[0xbb, 0xaa, 0, 0]);
+        app.edit_field(3, "258");
+        assert_eq!(&app.data.bytes()[8..10], &[0x01, 0x02]);
+        app.edit_field(0, "toolong");
+        assert_eq!(&app.data.bytes()[0..4], b"HPT\0");
+        app.edit_field(0, "AB");
+        assert_eq!(&app.data.bytes()[0..4], b"AB\0\0");
+
+        app.apply_struct("");
+        assert!(app.struct_overlay.is_none());
+    }
+}
@@ -0,0 +1,265 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use crate::app::log::{logger::Logger, NotificationLevel};
+
+use super::field_type::{is_identifier, FieldType};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StructDef {
+    pub name: String,
+    pub fields: Vec<(String, FieldType)>,
+}
+
+/// The structs that can be applied to the file, read from the types directory
+/// and from the plugins. A struct is written like:
+/// ```text
+/// struct header {
+///     char[4] magic;
+///     u16be version;
+///     u32 flags; // the endianness of the file
+///     entry[2] entries;
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct StructTypes {
+    structs: HashMap<String, StructDef>,
+}
+
+impl StructTypes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn get_default_types_path() -> Option<PathBuf> {
+        let config = dirs::config_dir()?;
+        Some(config.join("HexPatch").join("types"))
+    }
+
+    /// Reads every `.hpt` file in the directory, the files that can't be parsed are logged and skipped.
+    pub fn load(path: Option<&Path>, logger: &mut Logger) -> std::io::Result<Self> {
+        let mut types = Self::new();
+        let path = match path {
+            Some(path) => path.to_path_buf(),
+            None => Self::get_default_types_path()
+                .ok_or(std::io::Error::other(t!("errors.get_default_types_path")))?,
+        };
+        std::fs::create_dir_all(&path)?;
+        for entry in std::fs::read_dir(path)? {
+            let path = entry?.path();
+            if path.is_file() && path.extension().unwrap_or_default() == "hpt" {
+                let result = std::fs::read_to_string(&path)
+                    .map_err(|e| e.to_string())
+                    .and_then(|source| types.add_definitions(&source));
+                if let Err(e) = result {
+                    logger.log(
+                        NotificationLevel::Error,
+                        t!(
+                            "app.messages.types_load_error",
+                            path = path.to_string_lossy(),
+                            error = e
+                        ),
+                    );
+                }
+            }
+        }
+        Ok(types)
+    }
+
+    /// Parses the structs in source and adds them, replacing the ones with the same name.
+    /// Returns the names of the structs added.
+    pub fn add_definitions(&mut self, source: &str) -> Result<Vec<String>, String> {
+        let structs = Self::parse(source)?;
+        let names = structs.iter().map(|s| s.name.clone()).collect();
+        for s in structs {
+            self.structs.insert(s.name.clone(), s);
+        }
+        Ok(names)
+    }
+
+    fn parse(source: &str) -> Result<Vec<StructDef>, String> {
+        let mut tokens = Vec::new();
+        for (i, line) in source.lines().enumerate() {
+            let line = line.split("//").next().unwrap_or_default();
+            let line = line.split('#').next().unwrap_or_default();
+            let line = line
+                .replace('{', " { ")
+                .replace('}', " } ")
+                .replace(';', " ; ");
+            tokens.extend(
+                line.split_whitespace()
+                    .map(|token| (i + 1, token.to_string())),
+            );
+        }
+        let unexpected = |(line, token): (usize, String)| {
+            t!("errors.struct_unexpected_token", token = token, line = line).to_string()
+        };
+        let mut tokens = tokens.into_iter();
+        let mut next = || {
+            tokens
+                .next()
+                .ok_or_else(|| t!("errors.struct_unexpected_end").to_string())
+        };
+        let mut structs = Vec::new();
+        while let Ok(token) = next() {
+            if token.1 == ";" {
+                continue;
+            }
+            if token.1 != "struct" {
+                return Err(unexpected(token));
+            }
+            let name = next()?;
+            if !is_identifier(&name.1) {
+                return Err(unexpected(name));
+            }
+            let open = next()?;
+            if open.1 != "{" {
+                return Err(unexpected(open));
+            }
+            let mut fields = Vec::new();
+            loop {
+                let field_type = next()?;
+                if field_type.1 == "}" {
+                    break;
+                }
+                let field_name = next()?;
+                // the C style `u8 name[4]` is accepted too
+                let (name_part, array_part) = field_name
+                    .1
+                    .split_at(field_name.1.find('[').unwrap_or(field_name.1.len()));
+                if !is_identifier(name_part) {
+                    return Err(unexpected(field_name));
+                }
+                let Some(parsed) = FieldType::parse(&format!("{}{array_part}", field_type.1))
+                else {
+                    return Err(unexpected(field_type));
+                };
+                fields.push((name_part.to_string(), parsed));
+                let end = next()?;
+                if end.1 != ";" {
+                    return Err(unexpected(end));
+                }
+            }
+            structs.push(StructDef {
+                name: name.1,
+                fields,
+            });
+        }
+        Ok(structs)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&StructDef> {
+        self.structs.get(name)
+    }
+
+    /// Returns the names of the structs in alphabetical order.
+    pub fn names(&self) -> Vec<&str> {
+        let mut names = self.structs.keys().map(|s| s.as_str()).collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
+    pub fn len(&self) -> usize {
+        self.structs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.structs.is_empty()
+    }
+
+    /// Returns the size in bytes of a field, failing on unknown or recursive structs.
+    pub fn size_of(&self, field_type: &FieldType) -> Result<usize, String> {
+        self.size_of_nested(field_type, &mut Vec::new())
+    }
+
+    fn size_of_nested<'a>(
+        &'a self,
+        field_type: &'a FieldType,
+        parents: &mut Vec<&'a str>,
+    ) -> Result<usize, String> {
+        match field_type {
+            FieldType::Int { size, .. } | FieldType::Float { size, .. } => Ok(*size),
+            FieldType::String(size) => Ok(*size),
+            FieldType::Array(element, count) => self
+                .size_of_nested(element, parents)?
+                .checked_mul(*count)
+                .ok_or_else(|| t!("errors.struct_too_big").to_string()),
+            FieldType::Struct(name) => {
+                if parents.contains(&name.as_str()) {
+                    return Err(t!("errors.recursive_struct", name = name).to_string());
+                }
+                let def = self
+                    .get(name)
+                    .ok_or_else(|| t!("errors.unknown_struct", name = name).to_string())?;
+                parents.push(name);
+                let mut size = 0usize;
+                for (_, field_type) in def.fields.iter() {
+                    size = size
+                        .checked_add(self.size_of_nested(field_type, parents)?)
+                        .ok_or_else(|| t!("errors.struct_too_big").to_string())?;
+                }
+                parents.pop();
+                Ok(size)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use object::Endianness;
+
+    use super::*;
+
+    #[test]
+    fn test_struct_definitions() {
+        let mut types = StructTypes::new();
+        let names = types
+            .add_definitions(
+                "# an entry\n\
+                 struct entry { u16be id; char name[6]; };\n\
+                 struct header {\n\
+                     char[4] magic; // the signature\n\
+                     u32 count;\n\
+                     entry[2] entries;\n\
+                 }",
+            )
+            .unwrap();
+        assert_eq!(names, vec!["entry", "header"]);
+        assert_eq!(types.names(), vec!["entry", "header"]);
+        assert_eq!(
+            types.get("entry").unwrap().fields,
+            vec![
+                (
+                    "id".to_string(),
+                    FieldType::Int {
+                        size: 2,
+                        signed: false,
+                        endianness: Some(Endianness::Big)
+                    }
+                ),
+                ("name".to_string(), FieldType::String(6)),
+            ]
+        );
+        assert_eq!(
+            types.size_of(&FieldType::Struct("header".to_string())),
+            Ok(24)
+        );
+
+        assert!(types.add_definitions("struct broken { u32 a }").is_err());
+        assert!(types.add_definitions("struct broken { u32 a;").is_err());
+        assert!(types.add_definitions("strukt broken {}").is_err());
+        assert!(types.add_definitions("struct broken { u32 1a; }").is_err());
+        assert_eq!(types.len(), 2);
+
+        types
+            .add_definitions("struct a { b inner; } struct b { a inner; }")
+            .unwrap();
+        assert!(types.size_of(&FieldType::Struct("a".to_string())).is_err());
+        assert!(types
+            .size_of(&FieldType::Struct("missing".to_string()))
+            .is_err());
+    }
+}
@@ -1,3 +1,5 @@
+use std::ops::Range;
+
 use ratatui::text::{Line, Span, Text};
 
 use super::{log::NotificationLevel, settings::color_settings::ColorSettings, App};
@@ -9,6 +11,7 @@ impl App {
         block_size: usize,
         blocks_per_row: usize,
         selected_byte_offset: usize,
+        selection: Option<Range<usize>>,
     ) -> Text<'static> {
         let mut ret = Text::default();
         ret.lines
@@ -19,6 +22,8 @@ impl App {
         for (byte_index, b) in bytes.iter().enumerate() {
             let style = if byte_index == selected_byte_offset {
                 color_settings.text_selected
+            } else if selection.as_ref().is_some_and(|s| s.contains(&byte_index)) {
+                color_settings.text_selection
             } else {
                 Self::get_style_for_byte(color_settings, *b)
             };
@@ -84,6 +89,8 @@ impl App {
             self.block_size,
             self.blocks_per_row,
             selected_byte_offset,
+            self.get_selection()
+                .map(|s| s.start.saturating_sub(start_byte)..s.end.saturating_sub(start_byte)),
         )
     }
 
@@ -91,6 +98,8 @@ impl App {
         if text.is_empty() || self.data.is_empty() {
             return;
         }
+        // searching needs the whole file, even if it is read on demand
+        self.load_range(0..self.data.len());
         let already_searched = self.text_last_searched_string == text;
         if !already_searched {
             self.text_last_searched_string = text.to_string();
@@ -149,6 +158,7 @@ mod tests {
             block_size,
             blocks_per_row,
             selected_byte_offset,
+            None,
         );
         assert_eq!(text.lines.len(), 1);
         let mut char_index = 0;
@@ -0,0 +1,186 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::headers::Header;
+
+use super::{log::NotificationLevel, popup::popup_state::PopupState, App};
+
+/// Symbols created, renamed or deleted by the user, indexed by virtual address.
+/// A deleted symbol is kept as None so that it stays hidden when the header is parsed again.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct UserSymbols {
+    symbols: HashMap<u64, Option<String>>,
+    #[serde(skip)]
+    dirty: bool,
+}
+
+impl UserSymbols {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set(&mut self, address: u64, name: &str) {
+        self.symbols.insert(address, Some(name.to_string()));
+        self.dirty = true;
+    }
+
+    pub fn remove(&mut self, address: u64) {
+        self.symbols.insert(address, None);
+        self.dirty = true;
+    }
+
+    /// Merges the symbols into the ones read from the header.
+    pub fn apply(&self, header: &mut Header) {
+        for (address, name) in self.symbols.iter() {
+            match name {
+                Some(name) => header.set_symbol(*address, name),
+                None => header.remove_symbol(*address),
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.symbols.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    pub fn reset_dirty(&mut self) {
+        self.dirty = false;
+    }
+}
+
+impl App {
+    /// Returns the virtual address of the byte at the cursor, without sections it is the file offset.
+    pub(super) fn cursor_virtual_address(&self) -> u64 {
+        let cursor = self.get_cursor_position().global_byte_index as u64;
+        self.header
+            .physical_to_virtual_address(cursor)
+            .unwrap_or(cursor)
+    }
+
+    /// Creates or renames the symbol at the cursor, an empty name deletes it.
+    pub(super) fn edit_symbol(&mut self, name: &str) {
+        let name = name.trim();
+        if name.is_empty() {
+            self.delete_symbol();
+            return;
+        }
+        if name.contains(char::is_whitespace) {
+            self.log(
+                NotificationLevel::Error,
+                t!("errors.invalid_symbol", symbol = name),
+            );
+            return;
+        }
+        let address = self.cursor_virtual_address();
+        self.user_symbols.set(address, name);
+        self.header.set_symbol(address, name);
+        self.log(
+            NotificationLevel::Info,
+            t!("app.messages.symbol_set", symbol = name, address = address : {:#X}),
+        );
+        // the operands referring to the address are rewritten with the new name
+        self.disassemble();
+    }
+
+    pub(super) fn delete_symbol(&mut self) {
+        let address = self.cursor_virtual_address();
+        let Some(name) = self
+            .header
+            .get_symbols()
+            .and_then(|symbols| symbols.get(&address))
+            .cloned()
+        else {
+            self.log(
+                NotificationLevel::Warning,
+                t!("app.messages.no_symbol_at", address = address : {:#X}),
+            );
+            return;
+        };
+        self.user_symbols.remove(address);
+        self.header.remove_symbol(address);
+        self.log(
+            NotificationLevel::Info,
+            t!("app.messages.symbol_deleted", symbol = name, address = address : {:#X}),
+        );
+        self.disassemble();
+    }
+
+    pub(super) fn request_popup_edit_symbol(&mut self) {
+        let address = self.cursor_virtual_address();
+        let name = self
+            .header
+            .get_symbols()
+            .and_then(|symbols| symbols.get(&address))
+            .cloned()
+            .unwrap_or_default();
+        self.popup = Some(PopupState::EditSymbol {
+            cursor: name.len(),
+            name,
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::app::asm::assembly_line::AssemblyLine;
+
+    use super::*;
+
+    #[test]
+    fn test_user_symbols() {
+        // 0: call 0xa; 5: nop ...; a: ret
+        let data = vec![
+            0xe8, 0x05, 0x00, 0x00, 0x00, 0x90, 0x90, 0x90, 0x90, 0x90, 0xc3,
+        ];
+        let mut app = App::mockup(data);
+        app.resize_to_size(80, 24);
+        app.jump_to(0xa, false);
+        app.edit_symbol("helper");
+        assert_eq!(app.header.symbol_to_address("helper"), Some(0xa));
+        assert_eq!(
+            app.header.get_symbols().unwrap().get(&0xa),
+            Some(&"helper".to_string())
+        );
+        // the label is replaced by the symbol
+        assert_eq!(app.labels.get(0xa), None);
+        assert!(app.assembly_instructions.iter().any(|line| matches!(
+            line,
+            AssemblyLine::Instruction(i) if i.instruction.operands() == "helper"
+        )));
+        app.jump_to(0, false);
+        app.jump_to_symbol("helper");
+        assert_eq!(app.get_cursor_position().global_byte_index, 0xa);
+
+        app.edit_symbol("renamed");
+        assert_eq!(app.header.symbol_to_address("helper"), None);
+        assert_eq!(app.header.symbol_to_address("renamed"), Some(0xa));
+
+        let tmp = tempfile::NamedTempFile::new().unwrap();
+        let path = tmp.path().to_str().unwrap().to_string();
+        app.save_comments(Some(path.clone()));
+        app.user_symbols = UserSymbols::new();
+        app.header = Header::None;
+        app.load_comments(Some(path.clone()));
+        assert_eq!(app.header.symbol_to_address("renamed"), Some(0xa));
+        // the header holding the symbols does not make up an architecture
+        assert_eq!(app.header.architecture(), object::Architecture::Unknown);
+        assert!(app.header.is_none());
+
+        app.delete_symbol();
+        assert_eq!(app.header.symbol_to_address("renamed"), None);
+        app.save_comments(Some(path.clone()));
+        app.header.set_symbol(0xa, "renamed");
+        app.load_comments(Some(path));
+        assert_eq!(app.header.symbol_to_address("renamed"), None);
+    }
+}
@@ -1,12 +1,14 @@
 use std::path::PathBuf;
 
+use crate::{app::ssh::connection::ConnectionOptions, headers::raw_binary::RawBinary};
+
 #[derive(Debug, clap::Parser)]
 #[command(name = "hex-patch", about, version, author)]
 pub struct Args {
     #[arg(
         short,
         long,
-        help = "The connection string to the remote server, in the format <user>@<host>[:<port>]"
+        help = "The connection string to the remote server, in the format [<user>@]<host>[:<port>], the host can be an alias from the ssh config"
     )]
     pub ssh: Option<String>,
     #[arg(
@@ -16,14 +18,119 @@ pub struct Args {
         requires = "ssh"
     )]
     pub password: Option<String>,
+    #[arg(
+        short,
+        long,
+        help = "The private key to use for SSH connection, instead of the ones in the ssh config or in ~/.ssh",
+        requires = "ssh",
+        conflicts_with = "password"
+    )]
+    pub identity: Option<PathBuf>,
+    #[arg(
+        short = 'J',
+        long,
+        help = "The jump hosts to connect through, separated by commas, in the format [<user>@]<host>[:<port>], overrides ProxyJump in the ssh config",
+        requires = "ssh"
+    )]
+    pub jump: Option<String>,
     #[arg(short, long, help = "The configuration file to use")]
     pub config: Option<PathBuf>,
     #[arg(short, long, help = "The plugin directory to use")]
     pub plugins: Option<PathBuf>,
+    #[arg(short, long, help = "The directory of the struct definitions to use")]
+    pub types: Option<PathBuf>,
+    #[arg(
+        short,
+        long,
+        help = "A file to compare with the opened one, read from the same host"
+    )]
+    pub diff: Option<String>,
+    #[arg(
+        long,
+        help = "The architecture of a file without a header, like Arm or X86_64, the whole file is decoded as code"
+    )]
+    pub arch: Option<String>,
+    #[arg(
+        long,
+        help = "The bitness of a file without a header, 32 or 64, the default depends on the architecture",
+        requires = "arch"
+    )]
+    pub bitness: Option<u32>,
+    #[arg(
+        long,
+        help = "The endianness of a file without a header, little or big",
+        requires = "arch",
+        value_parser = ["little", "big"]
+    )]
+    pub endianness: Option<String>,
+    #[arg(
+        long,
+        help = "The address where a file without a header is loaded, 0 by default",
+        requires = "arch",
+        value_parser = RawBinary::parse_number
+    )]
+    pub load_address: Option<u64>,
+    #[arg(
+        long,
+        help = "The entry point of a file without a header, the load address by default",
+        requires = "arch",
+        value_parser = RawBinary::parse_number
+    )]
+    pub entry: Option<u64>,
     #[arg(
         index = 1,
         help = "The starting path of the editor",
         default_value = "./"
     )]
     pub path: String,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+impl Args {
+    /// Returns the description of a file without a header given by the raw binary flags.
+    pub fn raw_binary(&self) -> Result<Option<RawBinary>, String> {
+        match &self.arch {
+            Some(architecture) => RawBinary::new(
+                architecture,
+                self.bitness,
+                self.endianness.as_deref(),
+                self.load_address.unwrap_or(0),
+                self.entry,
+            )
+            .map(Some),
+            None => Ok(None),
+        }
+    }
+
+    pub fn connection_options(&self) -> ConnectionOptions {
+        ConnectionOptions {
+            password: self.password.clone(),
+            identity: self.identity.clone(),
+            jump: self.jump.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, clap::Subcommand)]
+pub enum Command {
+    #[command(about = "Apply a patch script to a file and save it, without opening the editor")]
+    Patch {
+        #[arg(help = "The file to patch")]
+        file: String,
+        #[arg(help = "The patch script to apply, - to read it from stdin")]
+        script: PathBuf,
+        #[arg(short, long, help = "Save the patched file to this path instead")]
+        output: Option<String>,
+    },
+    #[command(about = "Run the run function of a Lua script on a file, saving it if it changed")]
+    Script {
+        #[arg(help = "The file to open")]
+        file: String,
+        #[arg(help = "The Lua script to run, - to read it from stdin")]
+        script: PathBuf,
+        #[arg(short, long, help = "Save the file to this path instead")]
+        output: Option<String>,
+    },
 }
@@ -8,7 +8,7 @@ pub fn assemble(
     header: &Header,
 ) -> Result<Vec<u8>, Box<dyn Error>> {
     let encoder = header
-        .get_encoder()
+        .get_encoder_at(starting_virtual_address)
         .map_err(|e| t!("errors.create_encoder", e = e))?;
 
     let out = encoder
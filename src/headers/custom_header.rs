@@ -2,7 +2,7 @@ use std::collections::HashMap;
 
 use object::{Architecture, Endianness};
 
-use super::{bitness::Bitness, section::Section};
+use super::{bitness::Bitness, isa_mode::IsaModes, section::Section};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct CustomHeader {
@@ -13,4 +13,5 @@ pub struct CustomHeader {
     pub sections: Vec<Section>,
     pub symbols: HashMap<u64, String>,
     pub symbols_by_name: HashMap<String, u64>,
+    pub isa_modes: IsaModes,
 }
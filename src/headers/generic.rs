@@ -1,11 +1,15 @@
 use std::{collections::HashMap, io::Write};
 
-use object::{Object, ObjectSection, ObjectSymbol};
+use object::{Object, ObjectSection, ObjectSymbol, ReadRef, SymbolKind};
 use pdb::FallibleIterator;
 
 use crate::app::files::{filesystem::FileSystem, path};
 
-use super::{bitness::Bitness, section::Section};
+use super::{
+    bitness::Bitness,
+    isa_mode::{IsaMode, IsaModes},
+    section::Section,
+};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum FileType {
@@ -31,6 +35,7 @@ pub struct GenericHeader {
     pub(super) sections: Vec<Section>,
     pub(super) symbols: HashMap<u64, String>,
     pub(super) symbols_by_name: HashMap<String, u64>,
+    pub(super) isa_modes: IsaModes,
 }
 
 impl GenericHeader {
@@ -39,7 +44,11 @@ impl GenericHeader {
         name.to_string()
     }
 
-    pub fn parse_header(bytes: &[u8], file_path: &str, filesystem: &FileSystem) -> Option<Self> {
+    pub fn parse_header<'data, R: ReadRef<'data>>(
+        bytes: R,
+        file_path: &str,
+        filesystem: &FileSystem,
+    ) -> Option<Self> {
         let header = object::File::parse(bytes);
         if let Ok(header) = header {
             let file_type = match &header {
@@ -89,13 +98,30 @@ impl GenericHeader {
                 _ => {}
             }
 
+            // arm elf files mark thumb code with mapping symbols or with the lowest bit of
+            // the function addresses, the bit is not part of the address
+            let is_arm_elf = architecture == object::Architecture::Arm
+                && matches!(file_type, FileType::Elf32 | FileType::Elf64);
+            let isa_modes = if is_arm_elf {
+                let isa_modes = Self::arm_isa_modes(&header, &sections, entry);
+                entry &= !1;
+                isa_modes
+            } else {
+                IsaModes::new()
+            };
+
             let mut symbols: Vec<(u64, String)> = header
                 .symbols()
+                .filter(|symbol| {
+                    !is_arm_elf || !Self::is_mapping_symbol(symbol.name().unwrap_or_default())
+                })
                 .map(|symbol| {
-                    (
-                        symbol.address(),
-                        symbol.name().unwrap_or_default().to_string(),
-                    )
+                    let address = if is_arm_elf && symbol.kind() == SymbolKind::Text {
+                        symbol.address() & !1
+                    } else {
+                        symbol.address()
+                    };
+                    (address, symbol.name().unwrap_or_default().to_string())
                 })
                 .collect();
 
@@ -137,8 +163,12 @@ impl GenericHeader {
                         if ty == 2 {
                             let data_header_size = 24;
                             let path = String::from_utf8_lossy(
-                                &bytes[data_header_size + pointer_to_raw_data as usize
-                                    ..(pointer_to_raw_data + size_of_data) as usize],
+                                bytes
+                                    .read_bytes_at(
+                                        data_header_size + pointer_to_raw_data as u64,
+                                        (size_of_data as u64).saturating_sub(data_header_size),
+                                    )
+                                    .unwrap_or_default(),
                             )
                             .trim_end_matches('\0')
                             .to_string();
@@ -212,12 +242,85 @@ impl GenericHeader {
                 sections,
                 symbols,
                 symbols_by_name,
+                isa_modes,
             })
         } else {
             None
         }
     }
 
+    /// Checks if name is one of the arm mapping symbols, that mark the start of
+    /// arm code ($a), thumb code ($t) or data ($d).
+    fn is_mapping_symbol(name: &str) -> bool {
+        matches!(name.split('.').next(), Some("$a" | "$t" | "$d"))
+    }
+
+    /// Finds the thumb code of an arm elf file. The mapping symbols are used if there
+    /// are any, otherwise the functions with an odd address are thumb code, and if
+    /// there are no functions either the section of an odd entry point is.
+    fn arm_isa_modes<'data, R: ReadRef<'data>>(
+        header: &object::File<'data, R>,
+        sections: &[Section],
+        entry: u64,
+    ) -> IsaModes {
+        let section_end = |address: u64| {
+            sections
+                .iter()
+                .find(|section| {
+                    address >= section.virtual_address
+                        && address < section.virtual_address + section.size
+                })
+                .map(|section| section.virtual_address + section.size)
+                .unwrap_or(address)
+        };
+        let mut isa_modes = IsaModes::new();
+        let mut mapping_symbols: Vec<(u64, bool)> = header
+            .symbols()
+            .filter_map(|symbol| {
+                let name = symbol.name().ok()?;
+                Self::is_mapping_symbol(name).then(|| (symbol.address(), name.starts_with("$t")))
+            })
+            .collect();
+        mapping_symbols.sort();
+        if !mapping_symbols.is_empty() {
+            for (i, (address, thumb)) in mapping_symbols.iter().enumerate() {
+                if *thumb {
+                    let end = match mapping_symbols.get(i + 1) {
+                        Some((next, _)) => (*next).min(section_end(*address)),
+                        None => section_end(*address),
+                    };
+                    isa_modes.set(*address..end, IsaMode::Thumb);
+                }
+            }
+        } else {
+            for symbol in header.symbols() {
+                if symbol.kind() == SymbolKind::Text && symbol.address() & 1 == 1 {
+                    let start = symbol.address() & !1;
+                    let end = if symbol.size() > 0 {
+                        start + symbol.size()
+                    } else {
+                        section_end(start)
+                    };
+                    isa_modes.set(start..end, IsaMode::Thumb);
+                }
+            }
+            if isa_modes.is_empty() && entry & 1 == 1 {
+                let start = entry & !1;
+                if let Some(section) = sections.iter().find(|section| {
+                    start >= section.virtual_address
+                        && start < section.virtual_address + section.size
+                }) {
+                    isa_modes.set(
+                        section.virtual_address..section.virtual_address + section.size,
+                        IsaMode::Thumb,
+                    );
+                }
+            }
+        }
+        isa_modes.reset_dirty();
+        isa_modes
+    }
+
     pub fn file_type(&self) -> FileType {
         self.file_type
     }
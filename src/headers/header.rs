@@ -1,19 +1,55 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, ops::Range};
 
 use capstone::{
-    arch::{self, BuildsCapstone},
+    arch::{self, BuildsCapstone, BuildsCapstoneEndian, BuildsCapstoneExtraMode},
     Capstone, CsResult,
 };
 use hexpatch_keystone::{Arch, Error, Keystone, Mode};
 use mlua::UserData;
-use object::{Architecture, Endianness};
+use object::{Architecture, Endianness, ReadRef};
 
 use crate::app::files::filesystem::FileSystem;
 
 use super::{
-    bitness::Bitness, custom_header::CustomHeader, generic::GenericHeader, section::Section,
+    bitness::Bitness,
+    custom_header::CustomHeader,
+    generic::GenericHeader,
+    isa_mode::{IsaMode, IsaModes},
+    section::Section,
 };
 
+/// The names of the architectures, as shown by the header info and accepted by plugins.
+pub const ARCHITECTURES: &[(&str, Architecture)] = &[
+    ("Unknown", Architecture::Unknown),
+    ("Aarch64", Architecture::Aarch64),
+    ("Aarch64_Ilp32", Architecture::Aarch64_Ilp32),
+    ("Arm", Architecture::Arm),
+    ("Avr", Architecture::Avr),
+    ("Bpf", Architecture::Bpf),
+    ("Csky", Architecture::Csky),
+    ("I386", Architecture::I386),
+    ("X86_64", Architecture::X86_64),
+    ("X86_64_X32", Architecture::X86_64_X32),
+    ("Hexagon", Architecture::Hexagon),
+    ("LoongArch64", Architecture::LoongArch64),
+    ("Mips", Architecture::Mips),
+    ("Mips64", Architecture::Mips64),
+    ("Msp430", Architecture::Msp430),
+    ("PowerPc", Architecture::PowerPc),
+    ("PowerPc64", Architecture::PowerPc64),
+    ("Riscv32", Architecture::Riscv32),
+    ("Riscv64", Architecture::Riscv64),
+    ("S390x", Architecture::S390x),
+    ("Sbf", Architecture::Sbf),
+    ("Sharc", Architecture::Sharc),
+    ("Sparc", Architecture::Sparc),
+    ("Sparc32Plus", Architecture::Sparc32Plus),
+    ("Sparc64", Architecture::Sparc64),
+    ("Wasm32", Architecture::Wasm32),
+    ("Wasm64", Architecture::Wasm64),
+    ("Xtensa", Architecture::Xtensa),
+];
+
 #[derive(Debug, PartialEq, Eq, Clone, Default)]
 pub enum Header {
     GenericHeader(GenericHeader),
@@ -23,7 +59,11 @@ pub enum Header {
 }
 
 impl Header {
-    pub fn parse_header(bytes: &[u8], file_path: &str, filesystem: &FileSystem) -> Header {
+    pub fn parse_header<'data, R: ReadRef<'data>>(
+        bytes: R,
+        file_path: &str,
+        filesystem: &FileSystem,
+    ) -> Header {
         let header = GenericHeader::parse_header(bytes, file_path, filesystem);
         match header {
             Some(header) => Header::GenericHeader(header),
@@ -69,6 +109,13 @@ impl Header {
         }
     }
 
+    pub fn architecture_from_name(name: &str) -> Option<Architecture> {
+        ARCHITECTURES
+            .iter()
+            .find(|(architecture_name, _)| *architecture_name == name)
+            .map(|(_, architecture)| *architecture)
+    }
+
     pub fn get_sections(&self) -> Vec<Section> {
         match self {
             Header::GenericHeader(header) => header.sections.clone(),
@@ -115,6 +162,96 @@ impl Header {
         }
     }
 
+    /// Checks if the file has no header, the custom header made to hold the user
+    /// symbols or the instruction set modes of such a file does not count as one.
+    pub fn is_none(&self) -> bool {
+        match self {
+            Header::GenericHeader(_) => false,
+            Header::CustomHeader(header) => {
+                header.architecture == Architecture::Unknown && header.sections.is_empty()
+            }
+            Header::None => true,
+        }
+    }
+
+    fn make_custom_if_none(&mut self) {
+        if *self == Header::None {
+            // a file without header gets a custom one to hold the symbols and the
+            // instruction set modes, it describes the file as Header::None does
+            *self = Header::CustomHeader(CustomHeader {
+                bitness: Bitness::Bit64,
+                entry: 0,
+                endianness: Endianness::Little,
+                architecture: Architecture::Unknown,
+                sections: Vec::new(),
+                symbols: HashMap::new(),
+                symbols_by_name: HashMap::new(),
+                isa_modes: IsaModes::new(),
+            });
+        }
+    }
+
+    fn symbol_tables_mut(&mut self) -> (&mut HashMap<u64, String>, &mut HashMap<String, u64>) {
+        self.make_custom_if_none();
+        match self {
+            Header::GenericHeader(header) => (&mut header.symbols, &mut header.symbols_by_name),
+            Header::CustomHeader(header) => (&mut header.symbols, &mut header.symbols_by_name),
+            Header::None => unreachable!(),
+        }
+    }
+
+    /// Adds a symbol at address, replacing the one that was there.
+    pub fn set_symbol(&mut self, address: u64, name: &str) {
+        self.remove_symbol(address);
+        let (symbols, symbols_by_name) = self.symbol_tables_mut();
+        symbols.insert(address, name.to_string());
+        symbols_by_name.insert(name.to_string(), address);
+    }
+
+    pub fn remove_symbol(&mut self, address: u64) {
+        if *self == Header::None {
+            return;
+        }
+        let (symbols, symbols_by_name) = self.symbol_tables_mut();
+        if let Some(name) = symbols.remove(&address) {
+            if symbols_by_name.get(&name) == Some(&address) {
+                symbols_by_name.remove(&name);
+            }
+        }
+    }
+
+    fn isa_modes(&self) -> Option<&IsaModes> {
+        match self {
+            Header::GenericHeader(header) => Some(&header.isa_modes),
+            Header::CustomHeader(header) => Some(&header.isa_modes),
+            Header::None => None,
+        }
+    }
+
+    /// Makes the code in range use mode, replacing the modes that were there.
+    pub fn set_isa_mode(&mut self, range: Range<u64>, mode: IsaMode) {
+        self.make_custom_if_none();
+        match self {
+            Header::GenericHeader(header) => header.isa_modes.set(range, mode),
+            Header::CustomHeader(header) => header.isa_modes.set(range, mode),
+            Header::None => unreachable!(),
+        }
+    }
+
+    pub fn isa_mode_at(&self, virtual_address: u64) -> IsaMode {
+        self.isa_modes()
+            .map(|isa_modes| isa_modes.mode_at(virtual_address))
+            .unwrap_or(IsaMode::Default)
+    }
+
+    /// Splits range into the parts that use the same mode.
+    pub fn isa_runs(&self, range: Range<u64>) -> Vec<(Range<u64>, IsaMode)> {
+        match self.isa_modes() {
+            Some(isa_modes) => isa_modes.runs(range),
+            None => IsaModes::new().runs(range),
+        }
+    }
+
     pub fn virtual_to_physical_address(&self, virtual_address: u64) -> Option<u64> {
         self.get_sections()
             .iter()
@@ -133,17 +270,29 @@ impl Header {
             .map(|x| x.virtual_address + physical_address - x.file_offset)
     }
 
-    pub(super) fn get_decoder_for_arch(architecture: &Architecture) -> CsResult<Capstone> {
+    /// The endianness is the one of the code, the architectures that support a single
+    /// one ignore it.
+    pub(super) fn get_decoder_for_arch(
+        architecture: &Architecture,
+        endianness: Endianness,
+    ) -> CsResult<Capstone> {
+        let endian = Self::capstone_endian(endianness);
         match architecture {
             Architecture::Aarch64 => Capstone::new()
                 .arm64()
                 .mode(arch::arm64::ArchMode::Arm)
+                .endian(endian)
                 .build(),
             Architecture::Aarch64_Ilp32 => Capstone::new()
                 .arm64()
                 .mode(arch::arm64::ArchMode::Arm)
+                .endian(endian)
+                .build(),
+            Architecture::Arm => Capstone::new()
+                .arm()
+                .mode(arch::arm::ArchMode::Arm)
+                .endian(endian)
                 .build(),
-            Architecture::Arm => Capstone::new().arm().mode(arch::arm::ArchMode::Arm).build(),
             Architecture::I386 => Capstone::new()
                 .x86()
                 .mode(arch::x86::ArchMode::Mode32)
@@ -159,18 +308,22 @@ impl Header {
             Architecture::Mips => Capstone::new()
                 .mips()
                 .mode(arch::mips::ArchMode::Mips32)
+                .endian(endian)
                 .build(),
             Architecture::Mips64 => Capstone::new()
                 .mips()
                 .mode(arch::mips::ArchMode::Mips64)
+                .endian(endian)
                 .build(),
             Architecture::PowerPc => Capstone::new()
                 .ppc()
                 .mode(arch::ppc::ArchMode::Mode32)
+                .endian(endian)
                 .build(),
             Architecture::PowerPc64 => Capstone::new()
                 .ppc()
                 .mode(arch::ppc::ArchMode::Mode64)
+                .endian(endian)
                 .build(),
             Architecture::Riscv32 => Capstone::new()
                 .riscv()
@@ -184,6 +337,10 @@ impl Header {
                 .sysz()
                 .mode(arch::sysz::ArchMode::Default)
                 .build(),
+            Architecture::Sparc | Architecture::Sparc32Plus => Capstone::new()
+                .sparc()
+                .mode(arch::sparc::ArchMode::Default)
+                .build(),
             Architecture::Sparc64 => Capstone::new()
                 .sparc()
                 .mode(arch::sparc::ArchMode::V9)
@@ -195,47 +352,121 @@ impl Header {
         }
     }
 
-    pub(super) fn get_encoder_for_arch(architecture: &Architecture) -> Result<Keystone, Error> {
+    /// Keystone assembles Aarch64 only in little endian and 32 bit PowerPc only in big
+    /// endian, it fails to open for the other endianness.
+    pub(super) fn get_encoder_for_arch(
+        architecture: &Architecture,
+        endianness: Endianness,
+    ) -> Result<Keystone, Error> {
+        let endian = Self::keystone_endian(endianness);
         match architecture {
-            Architecture::Aarch64 => Keystone::new(Arch::ARM64, Mode::LITTLE_ENDIAN),
-            Architecture::Aarch64_Ilp32 => Keystone::new(Arch::ARM64, Mode::LITTLE_ENDIAN),
-            Architecture::Arm => Keystone::new(Arch::ARM, Mode::ARM),
+            Architecture::Aarch64 => Keystone::new(Arch::ARM64, endian),
+            Architecture::Aarch64_Ilp32 => Keystone::new(Arch::ARM64, endian),
+            Architecture::Arm => Keystone::new(Arch::ARM, Mode::ARM | endian),
             Architecture::I386 => Keystone::new(Arch::X86, Mode::MODE_32),
             Architecture::X86_64 => Keystone::new(Arch::X86, Mode::MODE_64),
             Architecture::X86_64_X32 => Keystone::new(Arch::X86, Mode::MODE_32),
             Architecture::Hexagon => Keystone::new(Arch::HEXAGON, Mode::MODE_32),
-            Architecture::Mips => Keystone::new(Arch::MIPS, Mode::MIPS32),
-            Architecture::Mips64 => Keystone::new(Arch::MIPS, Mode::MIPS64),
-            Architecture::PowerPc => Keystone::new(Arch::PPC, Mode::PPC32),
-            Architecture::PowerPc64 => Keystone::new(Arch::PPC, Mode::PPC64),
+            Architecture::Mips => Keystone::new(Arch::MIPS, Mode::MIPS32 | endian),
+            Architecture::Mips64 => Keystone::new(Arch::MIPS, Mode::MIPS64 | endian),
+            Architecture::PowerPc => Keystone::new(Arch::PPC, Mode::PPC32 | endian),
+            Architecture::PowerPc64 => Keystone::new(Arch::PPC, Mode::PPC64 | endian),
             Architecture::S390x => Keystone::new(Arch::SYSTEMZ, Mode::MODE_32),
-            Architecture::Sparc64 => Keystone::new(Arch::SPARC, Mode::SPARC64),
+            Architecture::Sparc | Architecture::Sparc32Plus => {
+                Keystone::new(Arch::SPARC, Mode::SPARC32 | endian)
+            }
+            Architecture::Sparc64 => Keystone::new(Arch::SPARC, Mode::SPARC64 | endian),
             _ => Keystone::new(Arch::X86, Mode::MODE_64),
         }
     }
 
+    fn capstone_endian(endianness: Endianness) -> capstone::Endian {
+        match endianness {
+            Endianness::Little => capstone::Endian::Little,
+            Endianness::Big => capstone::Endian::Big,
+        }
+    }
+
+    fn keystone_endian(endianness: Endianness) -> Mode {
+        match endianness {
+            Endianness::Little => Mode::LITTLE_ENDIAN,
+            Endianness::Big => Mode::BIG_ENDIAN,
+        }
+    }
+
+    fn setup_decoder(mut cs: Capstone) -> Capstone {
+        cs.set_skipdata(true).expect(&t!("errors.set_skipdata"));
+        // operand details are needed to find the cross references
+        cs.set_detail(true).expect(&t!("errors.set_detail"));
+        cs
+    }
+
     pub fn get_decoder(&self) -> CsResult<Capstone> {
         let ret = match self {
-            Header::GenericHeader(header) => Self::get_decoder_for_arch(&header.architecture),
-            Header::CustomHeader(header) => Self::get_decoder_for_arch(&header.architecture),
+            Header::GenericHeader(header) => {
+                Self::get_decoder_for_arch(&header.architecture, header.endianness)
+            }
+            Header::CustomHeader(header) => {
+                Self::get_decoder_for_arch(&header.architecture, header.endianness)
+            }
             Header::None => Capstone::new()
                 .x86()
                 .mode(capstone::arch::x86::ArchMode::Mode64)
                 .build(),
         };
-        ret.map(|mut cs| {
-            cs.set_skipdata(true).expect(&t!("errors.set_skipdata"));
-            cs
-        })
+        ret.map(Self::setup_decoder)
+    }
+
+    /// Returns the decoder of the code that uses mode.
+    pub fn get_decoder_for_mode(&self, mode: IsaMode) -> CsResult<Capstone> {
+        let ret = match mode {
+            IsaMode::Default => return self.get_decoder(),
+            IsaMode::Thumb => Capstone::new()
+                .arm()
+                .mode(arch::arm::ArchMode::Thumb)
+                .endian(Self::capstone_endian(self.endianness()))
+                .build(),
+            IsaMode::MicroMips => Capstone::new()
+                .mips()
+                .mode(arch::mips::ArchMode::Mips32)
+                .extra_mode([arch::mips::ArchExtraMode::Micro].iter().copied())
+                .endian(Self::capstone_endian(self.endianness()))
+                .build(),
+            IsaMode::X86_16 => Capstone::new()
+                .x86()
+                .mode(arch::x86::ArchMode::Mode16)
+                .build(),
+        };
+        ret.map(Self::setup_decoder)
     }
 
     pub fn get_encoder(&self) -> Result<Keystone, Error> {
         match self {
-            Header::GenericHeader(header) => Self::get_encoder_for_arch(&header.architecture),
-            Header::CustomHeader(header) => Self::get_encoder_for_arch(&header.architecture),
+            Header::GenericHeader(header) => {
+                Self::get_encoder_for_arch(&header.architecture, header.endianness)
+            }
+            Header::CustomHeader(header) => {
+                Self::get_encoder_for_arch(&header.architecture, header.endianness)
+            }
             Header::None => Keystone::new(Arch::X86, Mode::MODE_64),
         }
     }
+
+    /// Returns the encoder of the code at virtual_address, that depends on its mode.
+    pub fn get_encoder_at(&self, virtual_address: u64) -> Result<Keystone, Error> {
+        match self.isa_mode_at(virtual_address) {
+            IsaMode::Default => self.get_encoder(),
+            IsaMode::Thumb => Keystone::new(
+                Arch::ARM,
+                Mode::THUMB | Self::keystone_endian(self.endianness()),
+            ),
+            IsaMode::MicroMips => Keystone::new(
+                Arch::MIPS,
+                Mode::MIPS32 | Mode::MICRO | Self::keystone_endian(self.endianness()),
+            ),
+            IsaMode::X86_16 => Keystone::new(Arch::X86, Mode::MODE_16),
+        }
+    }
 }
 
 impl UserData for Header {
@@ -273,7 +504,11 @@ mod test {
     #[test]
     fn test_parse_elf() {
         let data = include_bytes!("../../test/elf.bin");
-        let header = Header::parse_header(data, "./elf.bin", &FileSystem::new_local(".").unwrap());
+        let header = Header::parse_header(
+            data.as_slice(),
+            "./elf.bin",
+            &FileSystem::new_local(".").unwrap(),
+        );
         if let Header::GenericHeader(header) = &header {
             assert_eq!(header.file_type, FileType::Elf64);
         } else {
@@ -287,7 +522,11 @@ mod test {
     #[test]
     fn test_parse_pe() {
         let data = include_bytes!("../../test/pe.bin");
-        let header = Header::parse_header(data, "./pe.bin", &FileSystem::new_local(".").unwrap());
+        let header = Header::parse_header(
+            data.as_slice(),
+            "./pe.bin",
+            &FileSystem::new_local(".").unwrap(),
+        );
         if let Header::GenericHeader(header) = &header {
             assert_eq!(header.file_type, FileType::Pe64);
         } else {
@@ -301,8 +540,11 @@ mod test {
     #[test]
     fn test_parse_macho() {
         let data = include_bytes!("../../test/macho.bin");
-        let header =
-            Header::parse_header(data, "./macho.bin", &FileSystem::new_local(".").unwrap());
+        let header = Header::parse_header(
+            data.as_slice(),
+            "./macho.bin",
+            &FileSystem::new_local(".").unwrap(),
+        );
         if let Header::GenericHeader(header) = &header {
             assert_eq!(header.file_type, FileType::MachO64);
         } else {
@@ -312,4 +554,23 @@ mod test {
         assert_eq!(header.bitness(), 64);
         assert_eq!(header.endianness(), Endianness::Little);
     }
+
+    #[test]
+    fn test_isa_mode() {
+        let mut header = Header::None;
+        header.set_isa_mode(0x100..0x200, IsaMode::Thumb);
+        assert_eq!(header.architecture(), Architecture::Unknown);
+        assert_eq!(header.bitness(), 64);
+        assert_eq!(header.isa_mode_at(0xff), IsaMode::Default);
+        assert_eq!(header.isa_mode_at(0x100), IsaMode::Thumb);
+        let decoder = header.get_decoder_for_mode(IsaMode::Thumb).unwrap();
+        // bx lr
+        let decoded = decoder.disasm_all(&[0x70, 0x47], 0x100).unwrap();
+        assert_eq!(decoded.iter().next().unwrap().mnemonic(), Some("bx"));
+        let encoded = header
+            .get_encoder_at(0x100)
+            .unwrap()
+            .asm("bx lr".to_string(), 0x100);
+        assert_eq!(encoded.unwrap().bytes, vec![0x70, 0x47]);
+    }
 }
@@ -0,0 +1,232 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt::Display,
+    ops::Range,
+};
+
+use capstone::Capstone;
+use object::Architecture;
+use serde::{Deserialize, Serialize};
+
+use super::Header;
+
+/// The instruction set used to decode and encode a part of the code,
+/// the default one is the usual instruction set of the architecture.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IsaMode {
+    Default,
+    /// The 16/32-bit instruction set of ARM.
+    Thumb,
+    /// The 16/32-bit instruction set of MIPS32.
+    MicroMips,
+    /// The 16-bit real mode of x86.
+    X86_16,
+}
+
+impl IsaMode {
+    /// Returns the modes that can be used with architecture, the default one first.
+    pub fn available(architecture: Architecture) -> Vec<IsaMode> {
+        match architecture {
+            Architecture::Arm => vec![IsaMode::Default, IsaMode::Thumb],
+            Architecture::Mips | Architecture::Mips64 => {
+                vec![IsaMode::Default, IsaMode::MicroMips]
+            }
+            Architecture::I386 | Architecture::X86_64 | Architecture::X86_64_X32 => {
+                vec![IsaMode::Default, IsaMode::X86_16]
+            }
+            _ => vec![IsaMode::Default],
+        }
+    }
+}
+
+impl Display for IsaMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            IsaMode::Default => "default",
+            IsaMode::Thumb => "thumb",
+            IsaMode::MicroMips => "micromips",
+            IsaMode::X86_16 => "x86_16",
+        };
+        write!(f, "{name}")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IsaRange {
+    pub end: u64,
+    pub mode: IsaMode,
+}
+
+/// The ranges of virtual addresses that use another instruction set, indexed by
+/// their start. The ranges never overlap, a new range replaces the part of the
+/// older ones that it covers.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct IsaModes {
+    ranges: BTreeMap<u64, IsaRange>,
+    #[serde(skip)]
+    dirty: bool,
+}
+
+impl IsaModes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set(&mut self, range: Range<u64>, mode: IsaMode) {
+        if range.is_empty() {
+            return;
+        }
+        let overlapping = self
+            .ranges
+            .range(..range.end)
+            .filter(|(_, old)| old.end > range.start)
+            .map(|(start, old)| (*start, *old))
+            .collect::<Vec<_>>();
+        for (start, old) in overlapping {
+            self.ranges.remove(&start);
+            if start < range.start {
+                self.ranges.insert(
+                    start,
+                    IsaRange {
+                        end: range.start,
+                        mode: old.mode,
+                    },
+                );
+            }
+            if old.end > range.end {
+                self.ranges.insert(range.end, old);
+            }
+        }
+        self.ranges.insert(
+            range.start,
+            IsaRange {
+                end: range.end,
+                mode,
+            },
+        );
+        self.dirty = true;
+    }
+
+    pub fn mode_at(&self, virtual_address: u64) -> IsaMode {
+        self.ranges
+            .range(..=virtual_address)
+            .next_back()
+            .filter(|(_, range)| virtual_address < range.end)
+            .map(|(_, range)| range.mode)
+            .unwrap_or(IsaMode::Default)
+    }
+
+    /// Splits range into the parts that use the same mode.
+    pub fn runs(&self, range: Range<u64>) -> Vec<(Range<u64>, IsaMode)> {
+        let mut bounds = BTreeSet::from([range.start, range.end]);
+        for (start, isa_range) in self.ranges.range(..range.end) {
+            bounds.extend(
+                [*start, isa_range.end]
+                    .into_iter()
+                    .filter(|bound| range.contains(bound)),
+            );
+        }
+        let bounds = bounds.into_iter().collect::<Vec<_>>();
+        let mut ret: Vec<(Range<u64>, IsaMode)> = Vec::new();
+        for window in bounds.windows(2) {
+            let mode = self.mode_at(window[0]);
+            match ret.last_mut() {
+                Some((last, last_mode)) if *last_mode == mode => last.end = window[1],
+                _ => ret.push((window[0]..window[1], mode)),
+            }
+        }
+        ret
+    }
+
+    /// Merges the ranges into the ones of the header, the newer ones win.
+    pub fn apply(&self, header: &mut Header) {
+        for (start, range) in self.ranges.iter() {
+            header.set_isa_mode(*start..range.end, range.mode);
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Range<u64>, IsaMode)> + '_ {
+        self.ranges
+            .iter()
+            .map(|(start, range)| (*start..range.end, range.mode))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    pub fn reset_dirty(&mut self) {
+        self.dirty = false;
+    }
+}
+
+/// Builds the decoder of each mode the first time it is needed.
+pub struct Decoders<'a> {
+    header: &'a Header,
+    decoders: HashMap<IsaMode, Option<Capstone>>,
+}
+
+impl<'a> Decoders<'a> {
+    pub fn new(header: &'a Header) -> Self {
+        Self {
+            header,
+            decoders: HashMap::new(),
+        }
+    }
+
+    /// Returns the decoder for the instruction at virtual_address.
+    pub fn at(&mut self, virtual_address: u64) -> Option<&Capstone> {
+        let mode = self.header.isa_mode_at(virtual_address);
+        self.decoders
+            .entry(mode)
+            .or_insert_with(|| self.header.get_decoder_for_mode(mode).ok())
+            .as_ref()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_isa_modes() {
+        let mut modes = IsaModes::new();
+        modes.set(0x100..0x200, IsaMode::Thumb);
+        modes.set(0x180..0x280, IsaMode::Default);
+        modes.set(0x140..0x150, IsaMode::Default);
+        assert_eq!(
+            modes.iter().collect::<Vec<_>>(),
+            vec![
+                (0x100..0x140, IsaMode::Thumb),
+                (0x140..0x150, IsaMode::Default),
+                (0x150..0x180, IsaMode::Thumb),
+                (0x180..0x280, IsaMode::Default),
+            ]
+        );
+        assert_eq!(modes.mode_at(0xff), IsaMode::Default);
+        assert_eq!(modes.mode_at(0x100), IsaMode::Thumb);
+        assert_eq!(modes.mode_at(0x17f), IsaMode::Thumb);
+        assert_eq!(modes.mode_at(0x180), IsaMode::Default);
+        assert_eq!(
+            modes.runs(0x0..0x300),
+            vec![
+                (0x0..0x100, IsaMode::Default),
+                (0x100..0x140, IsaMode::Thumb),
+                (0x140..0x150, IsaMode::Default),
+                (0x150..0x180, IsaMode::Thumb),
+                (0x180..0x300, IsaMode::Default),
+            ]
+        );
+        assert_eq!(
+            modes.runs(0x160..0x170),
+            vec![(0x160..0x170, IsaMode::Thumb)]
+        );
+        assert!(modes.is_dirty());
+    }
+}
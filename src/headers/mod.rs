@@ -4,4 +4,6 @@ pub use header::*;
 pub mod bitness;
 pub mod custom_header;
 pub mod generic;
+pub mod isa_mode;
+pub mod raw_binary;
 pub mod section;
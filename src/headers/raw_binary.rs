@@ -0,0 +1,274 @@
+use std::{collections::HashMap, fmt::Display, str::FromStr};
+
+use object::{AddressSize, Architecture, Endianness};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    bitness::Bitness, custom_header::CustomHeader, isa_mode::IsaModes, section::Section, Header,
+    ARCHITECTURES,
+};
+
+/// The architectures with a 32 bit and a 64 bit variant, the bitness of a raw
+/// binary picks one of them.
+const BITNESS_VARIANTS: &[(Architecture, Architecture)] = &[
+    (Architecture::I386, Architecture::X86_64),
+    (Architecture::Arm, Architecture::Aarch64),
+    (Architecture::Mips, Architecture::Mips64),
+    (Architecture::PowerPc, Architecture::PowerPc64),
+    (Architecture::Riscv32, Architecture::Riscv64),
+    (Architecture::Sparc, Architecture::Sparc64),
+    (Architecture::Wasm32, Architecture::Wasm64),
+];
+
+/// The description of a file without a header, like a firmware image, chosen by the user.
+/// The whole file is a code section loaded at load_address.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RawBinary {
+    pub architecture: String,
+    pub bitness: u32,
+    pub endianness: String,
+    pub load_address: u64,
+    pub entry: u64,
+}
+
+impl RawBinary {
+    /// The bitness defaults to the one of the architecture, the endianness to little
+    /// and the entry point to the load address.
+    pub fn new(
+        architecture: &str,
+        bitness: Option<u32>,
+        endianness: Option<&str>,
+        load_address: u64,
+        entry: Option<u64>,
+    ) -> Result<Self, String> {
+        let Some((name, architecture)) = ARCHITECTURES
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(architecture))
+        else {
+            return Err(t!("errors.invalid_architecture", architecture = architecture).into());
+        };
+        let bitness = bitness.unwrap_or(match architecture.address_size() {
+            Some(object::AddressSize::U64) => 64,
+            _ => 32,
+        });
+        let endianness = endianness.unwrap_or("little").to_lowercase();
+        let ret = Self {
+            architecture: name.to_string(),
+            bitness,
+            endianness,
+            load_address,
+            entry: entry.unwrap_or(load_address),
+        };
+        // the fields are checked by building a header
+        ret.to_header(0)?;
+        Ok(ret)
+    }
+
+    /// Parses a number in decimal or in hexadecimal with the 0x prefix.
+    pub fn parse_number(text: &str) -> Result<u64, String> {
+        match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+            Some(hex) => u64::from_str_radix(hex, 16),
+            None => text.parse(),
+        }
+        .map_err(|_| t!("errors.invalid_address", address = text).to_string())
+    }
+
+    pub fn to_header(&self, len: usize) -> Result<Header, String> {
+        let Some(architecture) = Header::architecture_from_name(&self.architecture) else {
+            return Err(t!(
+                "errors.invalid_architecture",
+                architecture = self.architecture
+            )
+            .into());
+        };
+        let bitness = match self.bitness {
+            32 => Bitness::Bit32,
+            64 => Bitness::Bit64,
+            _ => return Err(t!("errors.invalid_bitness", bitness = self.bitness).into()),
+        };
+        let endianness = match self.endianness.as_str() {
+            "little" => Endianness::Little,
+            "big" => Endianness::Big,
+            _ => return Err(t!("errors.invalid_endianness", endianness = self.endianness).into()),
+        };
+        let architecture = Self::with_bitness(architecture, bitness).ok_or_else(|| {
+            t!(
+                "errors.unsupported_bitness",
+                architecture = self.architecture,
+                bitness = self.bitness
+            )
+            .to_string()
+        })?;
+        if !Self::supports_endianness(architecture, endianness) {
+            return Err(t!(
+                "errors.unsupported_endianness",
+                architecture = self.architecture,
+                endianness = self.endianness
+            )
+            .into());
+        }
+        Ok(Header::CustomHeader(CustomHeader {
+            bitness,
+            entry: self.entry,
+            endianness,
+            architecture,
+            sections: vec![Section {
+                name: ".text".to_string(),
+                virtual_address: self.load_address,
+                file_offset: 0,
+                size: len as u64,
+            }],
+            symbols: HashMap::new(),
+            symbols_by_name: HashMap::new(),
+            isa_modes: IsaModes::new(),
+        }))
+    }
+
+    /// Returns the variant of architecture with bitness, None if the architecture has
+    /// a single one with another bitness. The bitness of the architectures that are
+    /// neither 32 nor 64 bit is ignored.
+    fn with_bitness(architecture: Architecture, bitness: Bitness) -> Option<Architecture> {
+        let variant = BITNESS_VARIANTS
+            .iter()
+            .find(|(bit32, bit64)| *bit32 == architecture || *bit64 == architecture);
+        match (variant, bitness) {
+            (Some((bit32, _)), Bitness::Bit32) => Some(*bit32),
+            (Some((_, bit64)), Bitness::Bit64) => Some(*bit64),
+            (None, _) => match (architecture.address_size(), bitness) {
+                (Some(AddressSize::U32), Bitness::Bit64)
+                | (Some(AddressSize::U64), Bitness::Bit32) => None,
+                _ => Some(architecture),
+            },
+        }
+    }
+
+    /// Checks that the code of architecture can be disassembled with endianness.
+    fn supports_endianness(architecture: Architecture, endianness: Endianness) -> bool {
+        match architecture {
+            Architecture::I386
+            | Architecture::X86_64
+            | Architecture::X86_64_X32
+            | Architecture::Riscv32
+            | Architecture::Riscv64 => endianness == Endianness::Little,
+            Architecture::Sparc
+            | Architecture::Sparc32Plus
+            | Architecture::Sparc64
+            | Architecture::S390x => endianness == Endianness::Big,
+            _ => true,
+        }
+    }
+}
+
+/// Parses "architecture bitness endianness load_address [entry]".
+impl FromStr for RawBinary {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let words = s.split_whitespace().collect::<Vec<_>>();
+        let [architecture, bitness, endianness, load_address, entry @ ..] = words.as_slice() else {
+            return Err(t!("errors.invalid_raw_binary", text = s).into());
+        };
+        if entry.len() > 1 {
+            return Err(t!("errors.invalid_raw_binary", text = s).into());
+        }
+        let bitness = bitness
+            .parse()
+            .map_err(|_| t!("errors.invalid_bitness", bitness = bitness).to_string())?;
+        let entry = match entry.first() {
+            Some(entry) => Some(Self::parse_number(entry)?),
+            None => None,
+        };
+        Self::new(
+            architecture,
+            Some(bitness),
+            Some(endianness),
+            Self::parse_number(load_address)?,
+            entry,
+        )
+    }
+}
+
+impl Display for RawBinary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} {} {:#X} {:#X}",
+            self.architecture, self.bitness, self.endianness, self.load_address, self.entry
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_raw_binary() {
+        let raw = "arm 32 big 0x8000000".parse::<RawBinary>().unwrap();
+        assert_eq!(
+            raw,
+            RawBinary {
+                architecture: "Arm".to_string(),
+                bitness: 32,
+                endianness: "big".to_string(),
+                load_address: 0x8000000,
+                entry: 0x8000000,
+            }
+        );
+        assert_eq!(raw.to_string().parse::<RawBinary>(), Ok(raw.clone()));
+        let header = raw.to_header(0x100).unwrap();
+        assert_eq!(header.architecture(), Architecture::Arm);
+        assert_eq!(header.endianness(), Endianness::Big);
+        assert_eq!(header.virtual_to_physical_address(0x8000010), Some(0x10));
+
+        let raw = RawBinary::new("x86_64", None, None, 0x1000, Some(0x1004)).unwrap();
+        assert_eq!(raw.bitness, 64);
+        assert_eq!(raw.endianness, "little");
+        assert_eq!(raw.entry, 0x1004);
+
+        assert!("z80 8 little 0".parse::<RawBinary>().is_err());
+        assert!("arm 16 little 0".parse::<RawBinary>().is_err());
+        assert!("arm 32 middle 0".parse::<RawBinary>().is_err());
+        assert!("arm 32 little".parse::<RawBinary>().is_err());
+        assert!("arm 32 little 0 0 0".parse::<RawBinary>().is_err());
+        assert!("x86_64 64 big 0".parse::<RawBinary>().is_err());
+        assert!("s390x 32 big 0".parse::<RawBinary>().is_err());
+    }
+
+    fn disassemble(raw_binary: &str, bytes: &[u8]) -> Vec<String> {
+        let raw = raw_binary.parse::<RawBinary>().unwrap();
+        let header = raw.to_header(bytes.len()).unwrap();
+        let decoder = header.get_decoder().unwrap();
+        let instructions = decoder.disasm_all(bytes, raw.load_address).unwrap();
+        instructions
+            .iter()
+            .map(|i| format!("{} {}", i.mnemonic().unwrap(), i.op_str().unwrap()))
+            .map(|text| text.trim().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_raw_binary_mode() {
+        // jr $ra; nop
+        let bytes = [0x03, 0xE0, 0x00, 0x08, 0, 0, 0, 0];
+        assert_eq!(disassemble("mips 32 big 0", &bytes), vec!["jr $ra", "nop"]);
+        // bx lr
+        let bytes = [0xE1, 0x2F, 0xFF, 0x1E];
+        assert_eq!(disassemble("arm 32 big 0", &bytes), vec!["bx lr"]);
+        // dec eax; ret, 0x48 is a REX prefix in 64 bit mode
+        let bytes = [0x48, 0xC3];
+        assert_eq!(
+            disassemble("x86_64 32 little 0", &bytes),
+            vec!["dec eax", "ret"]
+        );
+        assert_eq!(disassemble("x86_64 64 little 0", &bytes), vec!["ret"]);
+
+        let header = "mips 64 big 0".parse::<RawBinary>().unwrap().to_header(0);
+        assert_eq!(header.unwrap().architecture(), Architecture::Mips64);
+        let header = "x86_64 32 little 0"
+            .parse::<RawBinary>()
+            .unwrap()
+            .to_header(0);
+        assert_eq!(header.unwrap().architecture(), Architecture::I386);
+    }
+}
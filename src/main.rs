@@ -6,7 +6,10 @@ use crossterm::{
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
-use hex_patch::{app::App, args};
+use hex_patch::{
+    app::{batch::run_headless, App},
+    args,
+};
 use ratatui::backend::CrosstermBackend;
 #[macro_use]
 extern crate rust_i18n;
@@ -15,6 +18,13 @@ i18n!();
 
 fn main() {
     let args = args::Args::parse();
+    if args.command.is_some() {
+        if let Err(e) = run_headless(args) {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+        return;
+    }
     let theme = termbg::theme(Duration::from_secs(2));
 
     enable_raw_mode().expect(&t!("errors.enable_raw_mode"));